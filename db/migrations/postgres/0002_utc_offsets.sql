@@ -0,0 +1,5 @@
+ALTER TABLE beepee.measurements ADD COLUMN IF NOT EXISTS utc_offset_minutes integer NULL DEFAULT NULL;
+ALTER TABLE beepee.mass_measurements ADD COLUMN IF NOT EXISTS utc_offset_minutes integer NULL DEFAULT NULL;
+ALTER TABLE beepee.body_temperature_measurements ADD COLUMN IF NOT EXISTS utc_offset_minutes integer NULL DEFAULT NULL;
+ALTER TABLE beepee.blood_sugar_measurements ADD COLUMN IF NOT EXISTS utc_offset_minutes integer NULL DEFAULT NULL;
+ALTER TABLE beepee.long_term_blood_sugar_measurements ADD COLUMN IF NOT EXISTS utc_offset_minutes integer NULL DEFAULT NULL;
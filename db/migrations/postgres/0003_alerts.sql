@@ -0,0 +1,14 @@
+CREATE SEQUENCE IF NOT EXISTS beepee.alerts_id_seq AS bigint START WITH 1;
+
+CREATE TABLE IF NOT EXISTS beepee.alerts
+( id bigint NOT NULL DEFAULT nextval('beepee.alerts_id_seq')
+, "timestamp" timestamp with time zone NOT NULL
+, utc_offset_minutes integer NULL DEFAULT NULL
+, rule_name varchar(256) NOT NULL
+, measurement_kind varchar(32) NOT NULL
+, field varchar(64) NOT NULL
+, "value" double precision NOT NULL
+, threshold double precision NOT NULL
+, message text NOT NULL
+, CONSTRAINT alerts_pkey PRIMARY KEY (id)
+);
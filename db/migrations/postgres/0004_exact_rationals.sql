@@ -0,0 +1,9 @@
+ALTER TABLE beepee.mass_measurements
+    ALTER COLUMN mass_kg TYPE numeric(20, 10),
+    ALTER COLUMN waist_circum_cm TYPE numeric(20, 10);
+ALTER TABLE beepee.body_temperature_measurements
+    ALTER COLUMN temperature_celsius TYPE numeric(20, 10);
+ALTER TABLE beepee.blood_sugar_measurements
+    ALTER COLUMN sugar_mmol_per_l TYPE numeric(20, 10);
+ALTER TABLE beepee.long_term_blood_sugar_measurements
+    ALTER COLUMN hba1c_mmol_per_mol TYPE numeric(20, 10);
@@ -0,0 +1,4 @@
+ALTER TABLE beepee.body_temperature_locations
+    ADD COLUMN IF NOT EXISTS core_offset_celsius numeric(20, 10) NULL DEFAULT NULL,
+    ADD COLUMN IF NOT EXISTS normal_min_celsius numeric(20, 10) NULL DEFAULT NULL,
+    ADD COLUMN IF NOT EXISTS normal_max_celsius numeric(20, 10) NULL DEFAULT NULL;
@@ -0,0 +1,6 @@
+ALTER TABLE beepee.mass_measurements
+    ADD COLUMN IF NOT EXISTS hip_circum_cm numeric(20, 10) NULL DEFAULT NULL,
+    ADD COLUMN IF NOT EXISTS body_fat_percent numeric(20, 10) NULL DEFAULT NULL,
+    ADD COLUMN IF NOT EXISTS muscle_mass_kg numeric(20, 10) NULL DEFAULT NULL,
+    ADD COLUMN IF NOT EXISTS visceral_fat_rating numeric(20, 10) NULL DEFAULT NULL,
+    ADD COLUMN IF NOT EXISTS body_water_percent numeric(20, 10) NULL DEFAULT NULL;
@@ -0,0 +1,10 @@
+CREATE SEQUENCE IF NOT EXISTS beepee.height_measurements_id_seq AS bigint START WITH 1;
+
+CREATE TABLE IF NOT EXISTS beepee.height_measurements
+( id bigint NOT NULL DEFAULT nextval('beepee.height_measurements_id_seq')
+, "timestamp" timestamp with time zone NOT NULL
+, utc_offset_minutes integer NULL DEFAULT NULL
+, height_cm numeric(20, 10) NOT NULL
+, CONSTRAINT height_measurements_pkey PRIMARY KEY (id)
+, CONSTRAINT height_measurements_check CHECK (height_cm > 0)
+);
@@ -0,0 +1,34 @@
+CREATE SEQUENCE IF NOT EXISTS beepee.peak_flow_measurements_id_seq AS bigint START WITH 1;
+
+CREATE TABLE IF NOT EXISTS beepee.peak_flow_measurements
+( id bigint NOT NULL DEFAULT nextval('beepee.peak_flow_measurements_id_seq')
+, "timestamp" timestamp with time zone NOT NULL
+, utc_offset_minutes integer NULL DEFAULT NULL
+, peak_flow_l_per_min integer NOT NULL
+, CONSTRAINT peak_flow_measurements_pkey PRIMARY KEY (id)
+, CONSTRAINT peak_flow_measurements_check CHECK (peak_flow_l_per_min >= 0)
+);
+
+CREATE SEQUENCE IF NOT EXISTS beepee.ketone_measurements_id_seq AS bigint START WITH 1;
+
+CREATE TABLE IF NOT EXISTS beepee.ketone_measurements
+( id bigint NOT NULL DEFAULT nextval('beepee.ketone_measurements_id_seq')
+, "timestamp" timestamp with time zone NOT NULL
+, utc_offset_minutes integer NULL DEFAULT NULL
+, ketones_mmol_per_l numeric(20, 10) NOT NULL
+, CONSTRAINT ketone_measurements_pkey PRIMARY KEY (id)
+, CONSTRAINT ketone_measurements_check CHECK (ketones_mmol_per_l >= 0)
+);
+
+CREATE SEQUENCE IF NOT EXISTS beepee.lipid_panel_measurements_id_seq AS bigint START WITH 1;
+
+CREATE TABLE IF NOT EXISTS beepee.lipid_panel_measurements
+( id bigint NOT NULL DEFAULT nextval('beepee.lipid_panel_measurements_id_seq')
+, "timestamp" timestamp with time zone NOT NULL
+, utc_offset_minutes integer NULL DEFAULT NULL
+, total_cholesterol_mmol_per_l numeric(20, 10) NULL DEFAULT NULL
+, ldl_cholesterol_mmol_per_l numeric(20, 10) NULL DEFAULT NULL
+, hdl_cholesterol_mmol_per_l numeric(20, 10) NULL DEFAULT NULL
+, triglycerides_mmol_per_l numeric(20, 10) NULL DEFAULT NULL
+, CONSTRAINT lipid_panel_measurements_pkey PRIMARY KEY (id)
+);
@@ -0,0 +1,13 @@
+CREATE SEQUENCE IF NOT EXISTS beepee.custom_measurements_id_seq AS bigint START WITH 1;
+
+CREATE TABLE IF NOT EXISTS beepee.custom_measurements
+( id bigint NOT NULL DEFAULT nextval('beepee.custom_measurements_id_seq')
+, metric varchar(64) NOT NULL
+, "timestamp" timestamp with time zone NOT NULL
+, utc_offset_minutes integer NULL DEFAULT NULL
+, "values" jsonb NOT NULL DEFAULT '{}'
+, categories jsonb NOT NULL DEFAULT '{}'
+, CONSTRAINT custom_measurements_pkey PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS custom_measurements_metric_timestamp_idx ON beepee.custom_measurements (metric, "timestamp");
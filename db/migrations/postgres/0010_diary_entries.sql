@@ -0,0 +1,18 @@
+CREATE SEQUENCE IF NOT EXISTS beepee.diary_entries_id_seq AS bigint START WITH 1;
+
+CREATE TABLE IF NOT EXISTS beepee.diary_entries
+( id bigint NOT NULL DEFAULT nextval('beepee.diary_entries_id_seq')
+, "timestamp" timestamp with time zone NOT NULL
+, utc_offset_minutes integer NULL DEFAULT NULL
+, headache boolean NOT NULL DEFAULT FALSE
+, dizziness boolean NOT NULL DEFAULT FALSE
+, palpitations boolean NOT NULL DEFAULT FALSE
+, chest_pain boolean NOT NULL DEFAULT FALSE
+, shortness_of_breath boolean NOT NULL DEFAULT FALSE
+, wellbeing_score integer NULL DEFAULT NULL
+, notes text NOT NULL DEFAULT ''
+, CONSTRAINT diary_entries_pkey PRIMARY KEY (id)
+, CONSTRAINT diary_entries_wellbeing_score_check CHECK (wellbeing_score BETWEEN 1 AND 10)
+);
+
+CREATE INDEX IF NOT EXISTS diary_entries_timestamp_idx ON beepee.diary_entries ("timestamp");
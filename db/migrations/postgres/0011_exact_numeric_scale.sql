@@ -0,0 +1,27 @@
+ALTER TABLE beepee.mass_measurements
+    ALTER COLUMN mass_kg TYPE numeric(30, 20),
+    ALTER COLUMN waist_circum_cm TYPE numeric(30, 20),
+    ALTER COLUMN hip_circum_cm TYPE numeric(30, 20),
+    ALTER COLUMN body_fat_percent TYPE numeric(30, 20),
+    ALTER COLUMN muscle_mass_kg TYPE numeric(30, 20),
+    ALTER COLUMN visceral_fat_rating TYPE numeric(30, 20),
+    ALTER COLUMN body_water_percent TYPE numeric(30, 20);
+ALTER TABLE beepee.height_measurements
+    ALTER COLUMN height_cm TYPE numeric(30, 20);
+ALTER TABLE beepee.body_temperature_locations
+    ALTER COLUMN core_offset_celsius TYPE numeric(30, 20),
+    ALTER COLUMN normal_min_celsius TYPE numeric(30, 20),
+    ALTER COLUMN normal_max_celsius TYPE numeric(30, 20);
+ALTER TABLE beepee.body_temperature_measurements
+    ALTER COLUMN temperature_celsius TYPE numeric(30, 20);
+ALTER TABLE beepee.blood_sugar_measurements
+    ALTER COLUMN sugar_mmol_per_l TYPE numeric(30, 20);
+ALTER TABLE beepee.long_term_blood_sugar_measurements
+    ALTER COLUMN hba1c_mmol_per_mol TYPE numeric(30, 20);
+ALTER TABLE beepee.ketone_measurements
+    ALTER COLUMN ketones_mmol_per_l TYPE numeric(30, 20);
+ALTER TABLE beepee.lipid_panel_measurements
+    ALTER COLUMN total_cholesterol_mmol_per_l TYPE numeric(30, 20),
+    ALTER COLUMN ldl_cholesterol_mmol_per_l TYPE numeric(30, 20),
+    ALTER COLUMN hdl_cholesterol_mmol_per_l TYPE numeric(30, 20),
+    ALTER COLUMN triglycerides_mmol_per_l TYPE numeric(30, 20);
@@ -0,0 +1,50 @@
+-- timestamps are stored as UTC in RFC 3339 format with nine fractional digits, so that they sort correctly as text
+-- rational numbers are stored as text in the form "numerator/denominator" so that they round-trip exactly
+
+CREATE TABLE IF NOT EXISTS measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, systolic_mmhg INTEGER NOT NULL
+, diastolic_mmhg INTEGER NOT NULL
+, pulse_bpm INTEGER NOT NULL
+, spo2_percent INTEGER NULL DEFAULT NULL
+, CONSTRAINT measurements_check CHECK (systolic_mmhg >= 0 AND diastolic_mmhg >= 0 AND pulse_bpm >= 0 AND (spo2_percent IS NULL OR spo2_percent BETWEEN 0 AND 100))
+);
+
+CREATE TABLE IF NOT EXISTS mass_measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, mass_kg TEXT NOT NULL
+, waist_circum_cm TEXT NULL DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS body_temperature_locations
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "name" TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS body_temperature_measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, location_id INTEGER NOT NULL
+, temperature_celsius TEXT NOT NULL
+, CONSTRAINT body_temperature_measurements_location_id_fkey FOREIGN KEY (location_id) REFERENCES body_temperature_locations (id)
+);
+
+CREATE TABLE IF NOT EXISTS blood_sugar_measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, sugar_mmol_per_l TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS long_term_blood_sugar_measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, hba1c_mmol_per_mol TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS measurements_timestamp_idx ON measurements ("timestamp");
+CREATE INDEX IF NOT EXISTS mass_measurements_timestamp_idx ON mass_measurements ("timestamp");
+CREATE INDEX IF NOT EXISTS body_temperature_measurements_timestamp_idx ON body_temperature_measurements ("timestamp");
+CREATE INDEX IF NOT EXISTS blood_sugar_measurements_timestamp_idx ON blood_sugar_measurements ("timestamp");
+CREATE INDEX IF NOT EXISTS long_term_blood_sugar_measurements_timestamp_idx ON long_term_blood_sugar_measurements ("timestamp");
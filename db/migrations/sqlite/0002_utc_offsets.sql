@@ -0,0 +1,5 @@
+ALTER TABLE measurements ADD COLUMN utc_offset_minutes INTEGER NULL DEFAULT NULL;
+ALTER TABLE mass_measurements ADD COLUMN utc_offset_minutes INTEGER NULL DEFAULT NULL;
+ALTER TABLE body_temperature_measurements ADD COLUMN utc_offset_minutes INTEGER NULL DEFAULT NULL;
+ALTER TABLE blood_sugar_measurements ADD COLUMN utc_offset_minutes INTEGER NULL DEFAULT NULL;
+ALTER TABLE long_term_blood_sugar_measurements ADD COLUMN utc_offset_minutes INTEGER NULL DEFAULT NULL;
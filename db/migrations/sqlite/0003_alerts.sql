@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS alerts
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, utc_offset_minutes INTEGER NULL DEFAULT NULL
+, rule_name TEXT NOT NULL
+, measurement_kind TEXT NOT NULL
+, field TEXT NOT NULL
+, "value" REAL NOT NULL
+, threshold REAL NOT NULL
+, message TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS alerts_timestamp_idx ON alerts ("timestamp");
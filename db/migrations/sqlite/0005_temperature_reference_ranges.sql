@@ -0,0 +1,3 @@
+ALTER TABLE body_temperature_locations ADD COLUMN core_offset_celsius TEXT NULL DEFAULT NULL;
+ALTER TABLE body_temperature_locations ADD COLUMN normal_min_celsius TEXT NULL DEFAULT NULL;
+ALTER TABLE body_temperature_locations ADD COLUMN normal_max_celsius TEXT NULL DEFAULT NULL;
@@ -0,0 +1,5 @@
+ALTER TABLE mass_measurements ADD COLUMN hip_circum_cm TEXT NULL DEFAULT NULL;
+ALTER TABLE mass_measurements ADD COLUMN body_fat_percent TEXT NULL DEFAULT NULL;
+ALTER TABLE mass_measurements ADD COLUMN muscle_mass_kg TEXT NULL DEFAULT NULL;
+ALTER TABLE mass_measurements ADD COLUMN visceral_fat_rating TEXT NULL DEFAULT NULL;
+ALTER TABLE mass_measurements ADD COLUMN body_water_percent TEXT NULL DEFAULT NULL;
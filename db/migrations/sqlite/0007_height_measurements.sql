@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS height_measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, utc_offset_minutes INTEGER NULL DEFAULT NULL
+, height_cm TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS height_measurements_timestamp_idx ON height_measurements ("timestamp");
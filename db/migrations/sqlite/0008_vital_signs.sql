@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS peak_flow_measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, utc_offset_minutes INTEGER NULL DEFAULT NULL
+, peak_flow_l_per_min INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS ketone_measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, utc_offset_minutes INTEGER NULL DEFAULT NULL
+, ketones_mmol_per_l TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS lipid_panel_measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, utc_offset_minutes INTEGER NULL DEFAULT NULL
+, total_cholesterol_mmol_per_l TEXT NULL DEFAULT NULL
+, ldl_cholesterol_mmol_per_l TEXT NULL DEFAULT NULL
+, hdl_cholesterol_mmol_per_l TEXT NULL DEFAULT NULL
+, triglycerides_mmol_per_l TEXT NULL DEFAULT NULL
+);
+
+CREATE INDEX IF NOT EXISTS peak_flow_measurements_timestamp_idx ON peak_flow_measurements ("timestamp");
+CREATE INDEX IF NOT EXISTS ketone_measurements_timestamp_idx ON ketone_measurements ("timestamp");
+CREATE INDEX IF NOT EXISTS lipid_panel_measurements_timestamp_idx ON lipid_panel_measurements ("timestamp");
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS custom_measurements
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, metric TEXT NOT NULL
+, "timestamp" TEXT NOT NULL
+, utc_offset_minutes INTEGER NULL DEFAULT NULL
+, "values" TEXT NOT NULL DEFAULT '{}'
+, categories TEXT NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX IF NOT EXISTS custom_measurements_metric_timestamp_idx ON custom_measurements (metric, "timestamp");
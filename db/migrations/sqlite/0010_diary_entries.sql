@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS diary_entries
+( id INTEGER PRIMARY KEY AUTOINCREMENT
+, "timestamp" TEXT NOT NULL
+, utc_offset_minutes INTEGER NULL DEFAULT NULL
+, headache INTEGER NOT NULL DEFAULT 0
+, dizziness INTEGER NOT NULL DEFAULT 0
+, palpitations INTEGER NOT NULL DEFAULT 0
+, chest_pain INTEGER NOT NULL DEFAULT 0
+, shortness_of_breath INTEGER NOT NULL DEFAULT 0
+, wellbeing_score INTEGER NULL DEFAULT NULL CHECK (wellbeing_score BETWEEN 1 AND 10)
+, notes TEXT NOT NULL DEFAULT ''
+);
+
+CREATE INDEX IF NOT EXISTS diary_entries_timestamp_idx ON diary_entries ("timestamp");
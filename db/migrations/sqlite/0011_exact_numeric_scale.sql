@@ -0,0 +1 @@
+-- rational numbers have been stored exactly as "numerator/denominator" text from the start
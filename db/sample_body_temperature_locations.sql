@@ -1,6 +1,6 @@
-INSERT INTO beepee.body_temperature_locations (id, name) VALUES
-(DEFAULT, 'rectum'),
-(DEFAULT, 'mouth'),
-(DEFAULT, 'armpit'),
-(DEFAULT, 'ear'),
-(DEFAULT, 'forehead');
+INSERT INTO beepee.body_temperature_locations (id, name, core_offset_celsius, normal_min_celsius, normal_max_celsius) VALUES
+(DEFAULT, 'rectum', -0.5, 36.6, 38.0),
+(DEFAULT, 'mouth', 0.0, 35.5, 37.5),
+(DEFAULT, 'armpit', 0.5, 34.7, 37.3),
+(DEFAULT, 'ear', 0.0, 35.8, 38.0),
+(DEFAULT, 'forehead', 0.0, 35.8, 37.5);
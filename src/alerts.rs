@@ -0,0 +1,246 @@
+use std::error::Error;
+use std::fmt;
+
+use chrono::{DateTime, Duration, FixedOffset};
+use tracing::error;
+
+use crate::config::{AlertRule, CONFIG};
+use crate::database::{StoredMeasurement, add_alert, get_recent_alerts};
+use crate::model::{Alert, Measurement};
+use crate::notify::{Notification, deliver};
+
+
+#[derive(Debug)]
+pub(crate) enum AlertError {
+    UnknownField(String),
+}
+impl fmt::Display for AlertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertError::UnknownField(key)
+                => write!(f, "unknown field: {}", key),
+        }
+    }
+}
+impl Error for AlertError {
+}
+
+
+impl Notification for Alert {
+    fn subject(&self) -> String {
+        self.rule_name.clone()
+    }
+
+    fn text(&self) -> String {
+        format!("{}\n\nmeasured at {}\n", self.message, self.timestamp.to_rfc3339())
+    }
+
+    fn variables(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("BEEPEE_ALERT_TIMESTAMP", self.timestamp.to_rfc3339()),
+            ("BEEPEE_ALERT_RULE", self.rule_name.clone()),
+            ("BEEPEE_ALERT_MEASUREMENT", self.measurement_kind.clone()),
+            ("BEEPEE_ALERT_FIELD", self.field.clone()),
+            ("BEEPEE_ALERT_VALUE", self.value.to_string()),
+            ("BEEPEE_ALERT_THRESHOLD", self.threshold.to_string()),
+            ("BEEPEE_ALERT_MESSAGE", self.message.clone()),
+        ]
+    }
+}
+
+
+/// Checks whether the latest readings in `measurements` trigger the given rule.
+///
+/// `measurements` must be sorted by timestamp. Readings that have no value for the rule's field are
+/// skipped. The rule only triggers if the reading with the ID `new_measurement_id` is one of the
+/// matching readings, so that readings without a value for the field and backfilled older readings
+/// do not raise the same alert again. If the rule triggers, the returned alert refers to the latest
+/// reading.
+pub(crate) fn check_rule<T: Measurement>(
+    rule: &AlertRule,
+    measurements: &[T],
+    new_measurement_id: i64,
+) -> Result<Option<Alert>, AlertError> {
+    let field = T::STATISTICS_FIELDS.iter()
+        .find(|f| f.key == rule.field)
+        .ok_or_else(|| AlertError::UnknownField(rule.field.clone()))?;
+
+    let required_count = rule.consecutive_readings.max(1);
+    let latest_readings: Vec<(i64, DateTime<FixedOffset>, f64)> = measurements.iter()
+        .rev()
+        .filter_map(|m| (field.extract)(m).map(|v| (m.id(), m.timestamp(), v)))
+        .take(required_count)
+        .collect();
+    if latest_readings.len() < required_count {
+        return Ok(None);
+    }
+    if !latest_readings.iter().any(|(id, _, _)| *id == new_measurement_id) {
+        return Ok(None);
+    }
+    if !latest_readings.iter().all(|(_, _, v)| rule.comparison.matches(*v, rule.threshold)) {
+        return Ok(None);
+    }
+
+    let (_, latest_timestamp, latest_value) = latest_readings[0];
+    let mut message = format!(
+        "{}: {} {:.*} {} {:.*}",
+        rule.name,
+        field.label,
+        field.digits, latest_value,
+        rule.comparison.as_str(),
+        field.digits, rule.threshold,
+    );
+    if required_count > 1 {
+        message.push_str(&format!(" in {} consecutive readings", required_count));
+    }
+
+    Ok(Some(Alert {
+        id: -1,
+        timestamp: latest_timestamp,
+        rule_name: rule.name.clone(),
+        measurement_kind: rule.measurement.as_str().to_owned(),
+        field: rule.field.clone(),
+        value: latest_value,
+        threshold: rule.threshold,
+        message,
+    }))
+}
+
+
+/// Evaluates the configured alert rules for the given kind of measurement, stores the resulting
+/// alerts and delivers them to the configured sinks.
+///
+/// Called after the measurement with the ID `new_measurement_id` has been stored. Alerts that have
+/// already been stored for the same rule and reading are not stored or delivered again. Errors are
+/// logged, not returned.
+pub(crate) async fn check_alerts<T: StoredMeasurement>(new_measurement_id: i64) {
+    let (rules, sinks) = {
+        let config_guard = CONFIG
+            .get().unwrap()
+            .read().await;
+        let rules: Vec<AlertRule> = config_guard.alert_rules
+            .iter()
+            .filter(|r| r.measurement == T::KIND)
+            .cloned()
+            .collect();
+        (rules, config_guard.alert_sinks.clone())
+    };
+    if rules.is_empty() {
+        return;
+    }
+
+    let mut measurements = match T::get_recent(Duration::days(T::ALERT_WINDOW_DAYS)).await {
+        Ok(m) => m,
+        Err(e) => {
+            error!("error obtaining measurements for alert rules: {}", e);
+            return;
+        },
+    };
+    measurements.sort_by_key(|m| m.timestamp());
+
+    let existing_alerts = match get_recent_alerts(Duration::days(T::ALERT_WINDOW_DAYS)).await {
+        Ok(a) => a,
+        Err(e) => {
+            error!("error obtaining stored alerts: {}", e);
+            return;
+        },
+    };
+
+    for rule in &rules {
+        let mut alert = match check_rule(rule, &measurements, new_measurement_id) {
+            Ok(Some(a)) => a,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("error checking alert rule {:?}: {}", rule.name, e);
+                continue;
+            },
+        };
+        if existing_alerts.iter().any(|a| a.rule_name == alert.rule_name && a.timestamp == alert.timestamp) {
+            continue;
+        }
+
+        match add_alert(&alert).await {
+            Ok(id) => {
+                alert.id = id;
+            },
+            Err(e) => {
+                error!("error storing alert: {}", e);
+            },
+        }
+
+        for sink in &sinks {
+            if let Err(e) = deliver(sink, &alert).await {
+                error!("error delivering alert {:?} to {:?}: {}", alert.message, sink, e);
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    use crate::config::AlertComparison;
+    use crate::model::{BloodPressureMeasurement, MeasurementKind};
+
+    fn bp(day: u32, systolic_mmhg: i32) -> BloodPressureMeasurement {
+        let timestamp = FixedOffset::east_opt(3600).unwrap()
+            .with_ymd_and_hms(2024, 1, day, 8, 0, 0).unwrap();
+        BloodPressureMeasurement::new(day.into(), timestamp, systolic_mmhg, 80, 60, None)
+    }
+
+    fn rule(consecutive_readings: usize) -> AlertRule {
+        AlertRule {
+            name: "high systolic".to_owned(),
+            measurement: MeasurementKind::Bp,
+            field: "systolic_mmhg".to_owned(),
+            comparison: AlertComparison::Above,
+            threshold: 180.0,
+            consecutive_readings,
+        }
+    }
+
+    #[test]
+    fn consecutive_readings() {
+        let measurements = [bp(1, 185), bp(2, 170), bp(3, 185), bp(4, 190)];
+
+        let alert = check_rule(&rule(2), &measurements, 4).unwrap().unwrap();
+        assert_eq!(alert.value, 190.0);
+        assert_eq!(alert.timestamp, measurements[3].timestamp);
+        assert_eq!(alert.message, "high systolic: systolic BP 190.0 above 180.0 in 2 consecutive readings");
+
+        assert_eq!(check_rule(&rule(3), &measurements, 4).unwrap(), None);
+        assert_eq!(check_rule(&rule(5), &measurements, 4).unwrap(), None);
+
+        let mut unknown = rule(1);
+        unknown.field = "glucose".to_owned();
+        assert!(check_rule(&unknown, &measurements, 4).is_err());
+    }
+
+    #[test]
+    fn only_new_matching_readings() {
+        let mut spo2_rule = rule(1);
+        spo2_rule.field = "spo2_percent".to_owned();
+        spo2_rule.comparison = AlertComparison::Below;
+        spo2_rule.threshold = 92.0;
+
+        let mut low = bp(2, 120);
+        low.spo2_percent = Some(88);
+        let mut measurements = vec![low];
+        let alert = check_rule(&spo2_rule, &measurements, low.id).unwrap().unwrap();
+        assert_eq!(alert.timestamp, low.timestamp);
+
+        // a later reading without SpO₂ does not raise the alert again
+        let without_spo2 = bp(3, 125);
+        measurements.push(without_spo2);
+        assert_eq!(check_rule(&spo2_rule, &measurements, without_spo2.id).unwrap(), None);
+
+        // neither does a backfilled older reading
+        let mut backfilled = bp(1, 118);
+        backfilled.spo2_percent = Some(97);
+        measurements.insert(0, backfilled);
+        assert_eq!(check_rule(&spo2_rule, &measurements, backfilled.id).unwrap(), None);
+    }
+}
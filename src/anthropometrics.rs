@@ -0,0 +1,190 @@
+use chrono::{DateTime, FixedOffset};
+use num_rational::Rational32;
+use num_traits::{CheckedDiv, CheckedMul, Zero};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{BodyMassMeasurement, HeightMeasurement};
+
+
+/// Waist-to-height ratios from this value upward indicate an increased cardiometabolic risk
+/// (Ashwell et al., 2012).
+const WAIST_TO_HEIGHT_INCREASED: Rational32 = Rational32::new_raw(1, 2);
+
+/// Waist-to-height ratios from this value upward indicate a high cardiometabolic risk.
+const WAIST_TO_HEIGHT_HIGH: Rational32 = Rational32::new_raw(3, 5);
+
+
+/// The sex of the person being measured, which influences the interpretation of some values.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Sex {
+    Female,
+    Male,
+}
+impl Sex {
+    /// The waist-to-hip ratios from which the risk is increased and high, after the WHO expert
+    /// consultation on waist circumference and waist-hip ratio (2008).
+    fn waist_to_hip_limits(&self) -> (Rational32, Rational32) {
+        match self {
+            Self::Female => (Rational32::new(4, 5), Rational32::new(17, 20)),
+            Self::Male => (Rational32::new(9, 10), Rational32::new(1, 1)),
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RiskCategory {
+    Low,
+    Increased,
+    High,
+}
+impl RiskCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Increased => "increased",
+            Self::High => "high",
+        }
+    }
+
+    fn classify(value: Rational32, increased_from: Rational32, high_from: Rational32) -> Self {
+        if value >= high_from {
+            Self::High
+        } else if value >= increased_from {
+            Self::Increased
+        } else {
+            Self::Low
+        }
+    }
+
+    pub fn for_waist_to_height_ratio(ratio: Rational32) -> Self {
+        Self::classify(ratio, WAIST_TO_HEIGHT_INCREASED, WAIST_TO_HEIGHT_HIGH)
+    }
+
+    pub fn for_waist_to_hip_ratio(ratio: Rational32, sex: Sex) -> Self {
+        let (increased_from, high_from) = sex.waist_to_hip_limits();
+        Self::classify(ratio, increased_from, high_from)
+    }
+}
+
+
+/// Returns the height in effect at the given time: the latest one measured at or before it or, if
+/// there is none, the fallback. The heights must be sorted by ascending timestamp.
+pub(crate) fn height_at(heights: &[HeightMeasurement], timestamp: DateTime<FixedOffset>, fallback_height_cm: Option<i32>) -> Option<Rational32> {
+    let in_effect = heights.partition_point(|h| h.timestamp <= timestamp);
+    if in_effect > 0 {
+        Some(heights[in_effect - 1].height_cm)
+    } else {
+        fallback_height_cm.map(Rational32::from_integer)
+    }
+}
+
+
+/// Fills in the BMI, the waist ratios and their risk categories of each measurement.
+///
+/// Values that depend on the height are only calculated if it is known (see [`height_at`]), and
+/// the waist-to-hip risk category only if the sex is known. Values that cannot be represented as a
+/// 32-bit rational number are left empty.
+pub(crate) fn normalize_measurements(measurements: &mut [BodyMassMeasurement], heights: &[HeightMeasurement], fallback_height_cm: Option<i32>, sex: Option<Sex>) {
+    for measurement in measurements {
+        let height_cm = height_at(heights, measurement.timestamp, fallback_height_cm)
+            .filter(|h| *h > Rational32::zero());
+        let square_height_m2 = height_cm
+            .and_then(|h| h.checked_div(&Rational32::from_integer(100)))
+            .and_then(|h| h.checked_mul(&h));
+
+        measurement.bmi = square_height_m2
+            .and_then(|sqh| measurement.mass_kg.checked_div(&sqh));
+
+        measurement.waist_to_height_ratio = height_cm
+            .and_then(|h| measurement.waist_circum_cm.and_then(|wc| wc.checked_div(&h)));
+        measurement.waist_to_height_risk = measurement.waist_to_height_ratio
+            .map(RiskCategory::for_waist_to_height_ratio);
+
+        measurement.waist_to_hip_ratio = match (measurement.waist_circum_cm, measurement.hip_circum_cm) {
+            (Some(waist), Some(hip)) if !hip.is_zero() => waist.checked_div(&hip),
+            _ => None,
+        };
+        measurement.waist_to_hip_risk = measurement.waist_to_hip_ratio
+            .and_then(|r| sex.map(|s| RiskCategory::for_waist_to_hip_ratio(r, s)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::BodyComposition;
+
+    #[test]
+    fn ratios_and_risk_categories() {
+        let timestamp = DateTime::parse_from_rfc3339("2024-03-01T07:00:00+01:00").unwrap();
+        let mut measurements = [
+            BodyMassMeasurement::new(1, timestamp, Rational32::new(81, 1), Some(Rational32::new(88, 1)), Some(Rational32::new(100, 1)), BodyComposition::default(), None),
+            BodyMassMeasurement::new(2, timestamp, Rational32::new(81, 1), Some(Rational32::new(110, 1)), None, BodyComposition::default(), None),
+            BodyMassMeasurement::new(3, timestamp, Rational32::new(81, 1), None, Some(Rational32::new(100, 1)), BodyComposition::default(), None),
+        ];
+
+        normalize_measurements(&mut measurements, &[], Some(180), Some(Sex::Female));
+        assert_eq!(measurements[0].bmi, Some(Rational32::new(2500, 100)));
+        assert_eq!(measurements[0].waist_to_height_ratio, Some(Rational32::new(22, 45)));
+        assert_eq!(measurements[0].waist_to_height_risk, Some(RiskCategory::Low));
+        assert_eq!(measurements[0].waist_to_hip_ratio, Some(Rational32::new(22, 25)));
+        assert_eq!(measurements[0].waist_to_hip_risk, Some(RiskCategory::High));
+        assert_eq!(measurements[1].waist_to_height_risk, Some(RiskCategory::High));
+        assert_eq!(measurements[1].waist_to_hip_ratio, None);
+        assert_eq!(measurements[2].waist_to_height_ratio, None);
+        assert_eq!(measurements[2].waist_to_hip_ratio, None);
+
+        normalize_measurements(&mut measurements, &[], None, Some(Sex::Male));
+        assert_eq!(measurements[0].bmi, None);
+        assert_eq!(measurements[0].waist_to_height_ratio, None);
+        assert_eq!(measurements[0].waist_to_hip_risk, Some(RiskCategory::Low));
+
+        normalize_measurements(&mut measurements, &[], None, None);
+        assert_eq!(measurements[0].waist_to_hip_ratio, Some(Rational32::new(22, 25)));
+        assert_eq!(measurements[0].waist_to_hip_risk, None);
+    }
+
+    #[test]
+    fn unrepresentable_ratios() {
+        let timestamp = DateTime::parse_from_rfc3339("2024-03-01T07:00:00+01:00").unwrap();
+        let waist = Rational32::new(1_000_000_001, 100_000_000);
+        let mut measurements = [
+            BodyMassMeasurement::new(1, timestamp, Rational32::new(80, 1), Some(waist), Some(Rational32::new(1003, 10)), BodyComposition::default(), None),
+            BodyMassMeasurement::new(2, timestamp, Rational32::new(2_147_483_647, 1_000), None, None, BodyComposition::default(), None),
+        ];
+
+        normalize_measurements(&mut measurements, &[], Some(183), Some(Sex::Male));
+        assert_eq!(measurements[0].bmi, Some(Rational32::new(800_000, 33_489)));
+        assert_eq!(measurements[0].waist_to_height_ratio, None);
+        assert_eq!(measurements[0].waist_to_height_risk, None);
+        assert_eq!(measurements[0].waist_to_hip_ratio, None);
+        assert_eq!(measurements[0].waist_to_hip_risk, None);
+        assert_eq!(measurements[1].bmi, None);
+    }
+
+    #[test]
+    fn height_in_effect() {
+        let at = |day: u32| DateTime::parse_from_rfc3339(&format!("2024-03-{:02}T07:00:00+01:00", day)).unwrap();
+        let heights = [
+            HeightMeasurement::new(1, at(10), Rational32::new(150, 1)),
+            HeightMeasurement::new(2, at(20), Rational32::new(152, 1)),
+        ];
+        assert_eq!(height_at(&heights, at(5), Some(180)), Some(Rational32::new(180, 1)));
+        assert_eq!(height_at(&heights, at(5), None), None);
+        assert_eq!(height_at(&heights, at(10), None), Some(Rational32::new(150, 1)));
+        assert_eq!(height_at(&heights, at(19), Some(180)), Some(Rational32::new(150, 1)));
+        assert_eq!(height_at(&heights, at(25), Some(180)), Some(Rational32::new(152, 1)));
+
+        let mut measurements = [
+            BodyMassMeasurement::new(1, at(15), Rational32::new(45, 1), None, None, BodyComposition::default(), None),
+            BodyMassMeasurement::new(2, at(25), Rational32::new(45, 1), None, None, BodyComposition::default(), None),
+        ];
+        normalize_measurements(&mut measurements, &heights, Some(180), None);
+        assert_eq!(measurements[0].bmi, Some(Rational32::new(20, 1)));
+        assert_eq!(measurements[1].bmi, Some(Rational32::new(45 * 10_000, 152 * 152)));
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use chrono::Duration;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::{FormMeasurement, ServerError};
+use crate::alerts::check_alerts;
+use crate::database::{
+    add_alert, add_blood_pressure_measurement, add_blood_sugar_measurement, add_custom_measurement, add_diary_entry,
+    add_height_measurement, add_ketone_measurement, add_lipid_panel_measurement, add_long_term_blood_sugar_measurement,
+    add_mass_measurement, add_peak_flow_measurement, add_temperature_location, add_temperature_measurement,
+    get_recent_alerts, get_recent_blood_pressure_measurements, get_recent_custom_measurements, get_height_measurements,
+    get_recent_blood_sugar_measurements, get_recent_ketone_measurements, get_recent_lipid_panel_measurements,
+    get_recent_diary_entries, get_recent_long_term_blood_sugar_measurements, get_recent_mass_measurements,
+    get_recent_peak_flow_measurements, get_recent_temperature_measurements, get_temperature_locations,
+};
+use crate::model::{
+    Alert, BloodPressureMeasurement, BloodSugarMeasurement, BodyMassMeasurement,
+    BodyTemperatureLocation, BodyTemperatureMeasurement, CustomMeasurement, DiaryEntry, HeightMeasurement,
+    KetoneMeasurement, LipidPanelMeasurement, LongTermBloodSugarMeasurement, MeasurementKind, PeakFlowMeasurement,
+    with_measurement_type,
+};
+
+
+/// How far back `export` looks by default; in effect, everything.
+const EXPORT_ALL_DAYS: i64 = 100 * 366;
+
+/// The number of random bytes in a token generated by `gen-token`.
+const TOKEN_BYTES: usize = 24;
+
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Keeps track of blood pressure and other body measurements.")]
+pub(crate) struct Cli {
+    /// The configuration file.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// The format of log messages.
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// The address and port on which to listen, instead of `http_listen` from the configuration.
+    #[arg(long, global = true, value_name = "ADDRESS")]
+    pub listen: Option<String>,
+
+    /// The configuration file, as passed by older versions of the service files.
+    #[arg(hide = true, value_name = "CONFIG")]
+    pub legacy_config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+impl Cli {
+    /// Returns the path to the configuration file.
+    pub fn config_path(&self) -> PathBuf {
+        self.config.clone()
+            .or_else(|| self.legacy_config.clone())
+            .unwrap_or_else(|| PathBuf::from("config.toml"))
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum LogFormat {
+    Text,
+    Json,
+}
+
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Runs the web server (the default).
+    Serve,
+
+    /// Applies pending database migrations.
+    Migrate {
+        /// Only lists the migrations that would be applied.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Checks the configuration, including the values that refer to the database.
+    CheckConfig,
+
+    /// Adds the measurements and temperature locations from a file written by `export`.
+    ///
+    /// Measurements receive new IDs; temperature locations are matched by name. Importing the
+    /// same file twice adds its measurements twice.
+    Import {
+        /// The file to import, or `-` for standard input.
+        file: PathBuf,
+    },
+
+    /// Writes measurements, temperature locations and alerts as JSON.
+    Export {
+        /// The file to write to instead of standard output.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Only exports measurements from the given number of most recent days.
+        #[arg(long)]
+        days: Option<i64>,
+    },
+
+    /// Records a measurement
+    ///
+    /// Takes the same fields as the web forms:
+    /// bp: systolic_mmhg, diastolic_mmhg, pulse_bpm, [spo2_percent];
+    /// mass: mass_kg, [waist_circum_cm], [hip_circum_cm], [body_fat_percent], [muscle_mass_kg],
+    ///       [visceral_fat_rating], [body_water_percent];
+    /// temperature: location, temperature_celsius;
+    /// sugar: sugar_unit_key (mmol-per-l or mg-per-dl), sugar_value;
+    /// long-term-sugar: hba1c_unit_key (mmol-per-mol or dcct-percent), hba1c_value;
+    /// peak-flow: peak_flow_l_per_min;
+    /// ketones: ketones_mmol_per_l;
+    /// lipids: at least one of total_cholesterol_mmol_per_l, ldl_cholesterol_mmol_per_l,
+    ///         hdl_cholesterol_mmol_per_l, triglycerides_mmol_per_l.
+    /// Every kind also accepts utc_offset_minutes.
+    #[command(verbatim_doc_comment)]
+    Add {
+        /// The kind of measurement.
+        #[arg(value_parser = parse_measurement_kind)]
+        kind: MeasurementKind,
+
+        /// The values of the measurement, as KEY=VALUE.
+        #[arg(value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        fields: Vec<(String, String)>,
+    },
+
+    /// Generates a random token to add to `auth_tokens`.
+    GenToken {
+        /// Allows the token to record measurements.
+        #[arg(long)]
+        write: bool,
+    },
+}
+
+
+fn parse_measurement_kind(s: &str) -> Result<MeasurementKind, String> {
+    MeasurementKind::by_name(s)
+        .ok_or_else(|| {
+            let names: Vec<&str> = MeasurementKind::ALL.iter().map(|k| k.as_str()).collect();
+            format!("expected one of: {}", names.join(", "))
+        })
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .ok_or_else(|| "expected KEY=VALUE".to_owned())
+}
+
+
+/// The contents of a file written by `export` and read by `import`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub(crate) struct ExportData {
+    #[serde(default)] pub temperature_locations: Vec<BodyTemperatureLocation>,
+    #[serde(default)] pub bp: Vec<BloodPressureMeasurement>,
+    #[serde(default)] pub mass: Vec<BodyMassMeasurement>,
+    #[serde(default)] pub heights: Vec<HeightMeasurement>,
+    #[serde(default)] pub temperature: Vec<BodyTemperatureMeasurement>,
+    #[serde(default)] pub sugar: Vec<BloodSugarMeasurement>,
+    #[serde(default)] pub long_term_sugar: Vec<LongTermBloodSugarMeasurement>,
+    #[serde(default)] pub peak_flow: Vec<PeakFlowMeasurement>,
+    #[serde(default)] pub ketones: Vec<KetoneMeasurement>,
+    #[serde(default)] pub lipids: Vec<LipidPanelMeasurement>,
+    #[serde(default)] pub custom: Vec<CustomMeasurement>,
+    #[serde(default)] pub diary: Vec<DiaryEntry>,
+    #[serde(default)] pub alerts: Vec<Alert>,
+}
+
+
+pub(crate) async fn export(output: Option<PathBuf>, days: Option<i64>) -> Result<(), ServerError> {
+    let ago = Duration::days(days.unwrap_or(EXPORT_ALL_DAYS));
+    let data = ExportData {
+        temperature_locations: get_temperature_locations().await
+            .map_err(ServerError::Storing)?,
+        bp: get_recent_blood_pressure_measurements(ago).await
+            .map_err(ServerError::Storing)?,
+        mass: get_recent_mass_measurements(ago).await
+            .map_err(ServerError::Storing)?,
+        heights: get_height_measurements().await
+            .map_err(ServerError::Storing)?,
+        temperature: get_recent_temperature_measurements(ago).await
+            .map_err(ServerError::Storing)?,
+        sugar: get_recent_blood_sugar_measurements(ago).await
+            .map_err(ServerError::Storing)?,
+        long_term_sugar: get_recent_long_term_blood_sugar_measurements(ago).await
+            .map_err(ServerError::Storing)?,
+        peak_flow: get_recent_peak_flow_measurements(ago).await
+            .map_err(ServerError::Storing)?,
+        ketones: get_recent_ketone_measurements(ago).await
+            .map_err(ServerError::Storing)?,
+        lipids: get_recent_lipid_panel_measurements(ago).await
+            .map_err(ServerError::Storing)?,
+        custom: get_recent_custom_measurements(None, ago).await
+            .map_err(ServerError::Storing)?,
+        diary: get_recent_diary_entries(ago).await
+            .map_err(ServerError::Storing)?,
+        alerts: get_recent_alerts(ago).await
+            .map_err(ServerError::Storing)?,
+    };
+
+    let mut json = serde_json::to_string_pretty(&data)
+        .expect("failed to serialize export");
+    json.push('\n');
+    match output {
+        Some(path) => {
+            std::fs::write(path, json)
+                .map_err(ServerError::WritingExport)?;
+        },
+        None => {
+            std::io::stdout().write_all(json.as_bytes())
+                .map_err(ServerError::WritingExport)?;
+        },
+    }
+    Ok(())
+}
+
+
+pub(crate) async fn import(file: PathBuf) -> Result<(), ServerError> {
+    let mut json = String::new();
+    if file.as_os_str() == "-" {
+        std::io::stdin().read_to_string(&mut json)
+            .map_err(ServerError::ReadingImport)?;
+    } else {
+        File::open(&file)
+            .and_then(|mut f| f.read_to_string(&mut json))
+            .map_err(ServerError::ReadingImport)?;
+    }
+    let data: ExportData = serde_json::from_str(&json)
+        .map_err(ServerError::ParsingImport)?;
+
+    // temperature locations are matched by name
+    let existing_locations = get_temperature_locations().await
+        .map_err(ServerError::Storing)?;
+    let mut location_ids: HashMap<i64, i64> = HashMap::new();
+    for location in &data.temperature_locations {
+        let new_id = match existing_locations.iter().find(|l| l.name == location.name) {
+            Some(existing) => existing.id,
+            None => add_temperature_location(location).await
+                .map_err(ServerError::Storing)?,
+        };
+        location_ids.insert(location.id, new_id);
+    }
+
+    for measurement in &data.bp {
+        add_blood_pressure_measurement(measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for measurement in &data.mass {
+        add_mass_measurement(measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for measurement in &data.heights {
+        add_height_measurement(measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for measurement in &data.temperature {
+        let mut measurement = *measurement;
+        measurement.location_id = *location_ids.get(&measurement.location_id)
+            .ok_or(ServerError::UnknownImportedLocation(measurement.location_id))?;
+        add_temperature_measurement(&measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for measurement in &data.sugar {
+        add_blood_sugar_measurement(measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for measurement in &data.long_term_sugar {
+        add_long_term_blood_sugar_measurement(measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for measurement in &data.peak_flow {
+        add_peak_flow_measurement(measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for measurement in &data.ketones {
+        add_ketone_measurement(measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for measurement in &data.lipids {
+        add_lipid_panel_measurement(measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for measurement in &data.custom {
+        add_custom_measurement(measurement).await
+            .map_err(ServerError::Storing)?;
+    }
+    for entry in &data.diary {
+        add_diary_entry(entry).await
+            .map_err(ServerError::Storing)?;
+    }
+    for alert in &data.alerts {
+        add_alert(alert).await
+            .map_err(ServerError::Storing)?;
+    }
+
+    println!(
+        "imported {} blood pressure, {} mass, {} height, {} temperature, {} blood sugar, {} long-term blood sugar, {} peak flow, {} ketone, {} lipid panel and {} custom measurements, {} diary entries and {} alerts",
+        data.bp.len(), data.mass.len(), data.heights.len(), data.temperature.len(), data.sugar.len(), data.long_term_sugar.len(),
+        data.peak_flow.len(), data.ketones.len(), data.lipids.len(), data.custom.len(), data.diary.len(),
+        data.alerts.len(),
+    );
+    Ok(())
+}
+
+
+/// Records a measurement given as form fields, then checks the alert rules like the web form does.
+pub(crate) async fn add(kind: MeasurementKind, fields: Vec<(String, String)>) -> Result<(), ServerError> {
+    let req_kv: HashMap<String, String> = fields.into_iter().collect();
+
+    let id = with_measurement_type!(kind, T => add_from_form::<T>(&req_kv).await?);
+
+    println!("added {} measurement {}", kind.as_str(), id);
+    Ok(())
+}
+
+
+async fn add_from_form<T: FormMeasurement>(req_kv: &HashMap<String, String>) -> Result<i64, ServerError> {
+    let measurement = T::from_form(req_kv).await
+        .map_err(ServerError::InvalidMeasurement)?;
+    let id = measurement.add().await
+        .map_err(ServerError::Storing)?;
+    check_alerts::<T>(id).await;
+    Ok(id)
+}
+
+
+/// Prints a new random token as an entry for `auth_tokens`.
+pub(crate) fn gen_token(write: bool) -> Result<(), ServerError> {
+    let mut random_bytes = [0u8; TOKEN_BYTES];
+    File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut random_bytes))
+        .map_err(ServerError::GeneratingToken)?;
+    let token: String = random_bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    println!("{{ token = \"{}\", write = {} }}", token, write);
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn arguments() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::parse_from(["beepee", "--config", "/etc/beepee.toml", "add", "sugar", "sugar_unit_key=mg-per-dl", "sugar_value=100"]);
+        assert_eq!(cli.config_path(), PathBuf::from("/etc/beepee.toml"));
+        match cli.command {
+            Some(Command::Add { kind, fields }) => {
+                assert_eq!(kind, MeasurementKind::Sugar);
+                assert_eq!(fields[1], ("sugar_value".to_owned(), "100".to_owned()));
+            },
+            other => panic!("unexpected command {:?}", other),
+        }
+
+        // older versions took the configuration file as the only argument
+        let cli = Cli::parse_from(["beepee", "other.toml"]);
+        assert_eq!(cli.config_path(), PathBuf::from("other.toml"));
+        assert!(cli.command.is_none());
+
+        let cli = Cli::parse_from(["beepee", "migrate", "--dry-run", "--log-format", "json"]);
+        assert!(matches!(cli.command, Some(Command::Migrate { dry_run: true })));
+        assert_eq!(cli.log_format, LogFormat::Json);
+
+        assert!(Cli::try_parse_from(["beepee", "add", "pressure"]).is_err());
+        assert!(Cli::try_parse_from(["beepee", "add", "bp", "systolic_mmhg"]).is_err());
+    }
+}
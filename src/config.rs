@@ -1,18 +1,33 @@
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+use chrono_tz::Tz;
 use once_cell::sync::OnceCell;
 use serde::{Serialize, Deserialize};
+use tokio::signal::unix::{SignalKind, signal};
 use tokio::sync::RwLock;
 use toml;
+use tracing::{error, info, warn};
+use url::Url;
 
 use crate::ServerError;
+use crate::anthropometrics::Sex;
+use crate::custom_metrics::CustomMetric;
+use crate::database::{DatabaseError, get_temperature_locations};
+use crate::model::MeasurementKind;
+use crate::units::UnitPreferences;
 
 
 pub(crate) static CONFIG_PATH: OnceCell<PathBuf> = OnceCell::new();
 pub(crate) static CONFIG: OnceCell<RwLock<Config>> = OnceCell::new();
 
+/// Overrides from command-line options, in the same form as the `BEEPEE_*` environment variables.
+pub(crate) static CLI_OVERRIDES: OnceCell<Vec<(String, String)>> = OnceCell::new();
+
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub(crate) struct Hours {
@@ -24,22 +39,642 @@ pub(crate) struct Hours {
 }
 
 
+impl Hours {
+    /// Returns the part of the day into which a measurement taken at the given hour falls.
+    ///
+    /// Measurements taken before the start of the morning count towards the (previous) evening. If
+    /// parts of the day overlap, the earlier one wins.
+    pub fn day_part(&self, hour: u32) -> DayPart {
+        if hour < self.morning_start {
+            DayPart::Evening
+        } else if hour < self.morning_end {
+            DayPart::Morning
+        } else if hour >= self.midday_start && hour < self.midday_end {
+            DayPart::Midday
+        } else if hour >= self.evening_start {
+            DayPart::Evening
+        } else {
+            DayPart::Other
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum DayPart {
+    Morning,
+    Midday,
+    Evening,
+    Other,
+}
+impl DayPart {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Morning => "morning",
+            Self::Midday => "midday",
+            Self::Evening => "evening",
+            Self::Other => "other",
+        }
+    }
+}
+
+
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub(crate) struct AuthToken {
     pub token: String,
     pub write: bool,
+
+    /// The units in which values are shown and entered by users of this token. If unset, the global
+    /// `units` are used.
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub units: Option<UnitPreferences>,
+}
+impl AuthToken {
+    pub fn units(&self) -> UnitPreferences {
+        self.units.unwrap_or_default()
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AlertComparison {
+    Above,
+    Below,
+}
+impl AlertComparison {
+    pub fn matches(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Above => value > threshold,
+            Self::Below => value < threshold,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Above => "above",
+            Self::Below => "below",
+        }
+    }
+}
+
+
+fn default_consecutive_readings() -> usize { 1 }
+
+
+/// A rule that raises an alert if the latest readings of a field cross a threshold.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct AlertRule {
+    pub name: String,
+    pub measurement: MeasurementKind,
+
+    /// The key of the field, as used in the statistics API.
+    pub field: String,
+
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+
+    /// The number of most recent readings that must all cross the threshold.
+    #[serde(default = "default_consecutive_readings")] pub consecutive_readings: usize,
+}
+
+
+fn default_smtp_port() -> u16 { 25 }
+
+
+/// A destination to which alerts and reminders are delivered.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum NotificationSink {
+    /// POSTs the notification as JSON to a plain-HTTP URL.
+    Webhook {
+        url: String,
+    },
+
+    /// Sends the notification as an e-mail through an SMTP relay that requires neither TLS nor
+    /// authentication, such as a local mail transfer agent.
+    Smtp {
+        host: String,
+        #[serde(default = "default_smtp_port")] port: u16,
+        from: String,
+        to: Vec<String>,
+    },
+
+    /// Runs a local command; the notification is passed in environment variables and as JSON on
+    /// stdin.
+    Command {
+        program: String,
+        #[serde(default)] args: Vec<String>,
+    },
 }
 
 
+fn default_check_interval_minutes() -> u64 { 5 }
+fn default_true() -> bool { true }
+
+
+/// Reminders for blood pressure readings that have not been taken by a given hour.
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct Reminders {
+    /// The hour of the day (in the display time zone) by which the morning reading should have been
+    /// taken; no reminder is sent if unset.
+    pub morning_deadline: Option<u32>,
+
+    pub midday_deadline: Option<u32>,
+
+    /// The hour by which the evening reading should have been taken. Values below
+    /// `hours.morning_start` refer to the early hours of the following day.
+    pub evening_deadline: Option<u32>,
+
+    #[serde(default = "default_check_interval_minutes")] pub check_interval_minutes: u64,
+    #[serde(default)] pub sinks: Vec<NotificationSink>,
+}
+
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DbBackend {
+    Memory,
+    Postgres,
+    Sqlite,
+}
+
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub(crate) struct Config {
+    /// The PostgreSQL connection string or the path to the SQLite database file.
+    ///
+    /// For the in-memory backend, `memory:demo` fills the storage with fake measurements.
     pub db_conn_string: String,
+
+    /// The storage backend. If not set, it is derived from `db_conn_string`.
+    pub db_backend: Option<DbBackend>,
+
     pub http_listen: String,
     pub auth_tokens: Vec<AuthToken>,
     pub base_url: String,
     pub hours: Hours,
+
+    /// The height used for body mass measurements taken before the first recorded height.
     pub height_cm: Option<i32>,
+
+    /// Used to classify the waist-to-hip ratio, whose risk thresholds differ between the sexes.
+    pub sex: Option<Sex>,
+
     pub default_temperature_location_id: i64,
+    pub display_timezone: Option<Tz>,
+
+    /// The units in which values are shown and entered, unless overridden for a token. Values are
+    /// always stored in the canonical units (kg, cm, °C, mmol/l and mmol/mol).
+    #[serde(default)] pub units: UnitPreferences,
+
+    #[serde(default)] pub alert_rules: Vec<AlertRule>,
+    #[serde(default)] pub alert_sinks: Vec<NotificationSink>,
+    pub reminders: Option<Reminders>,
+
+    /// Additional kinds of measurement, each with its own page and API.
+    #[serde(default)] pub custom_metrics: Vec<CustomMetric>,
+
+    /// Whether pending database migrations are applied when the server starts or another command
+    /// that uses the database runs. If not, such commands refuse to run while migrations are pending.
+    #[serde(default = "default_true")] pub migrate_on_startup: bool,
+
+    /// How often to check whether the configuration file has changed and reload it; it is only
+    /// reloaded on SIGHUP if unset.
+    pub config_watch_interval_seconds: Option<u64>,
+}
+
+
+impl Config {
+    /// Returns the storage backend to use.
+    ///
+    /// Connection strings starting with `memory:` select the in-memory backend. Connection strings
+    /// starting with `sqlite:` or naming a file with a typical SQLite extension select the SQLite
+    /// backend. Everything else is passed to PostgreSQL.
+    pub fn db_backend(&self) -> DbBackend {
+        if let Some(backend) = self.db_backend {
+            return backend;
+        }
+        if self.db_conn_string.starts_with("memory:") {
+            return DbBackend::Memory;
+        }
+        let is_sqlite =
+            self.db_conn_string.starts_with("sqlite:")
+            || [".db", ".sqlite", ".sqlite3"].iter().any(|ext| self.db_conn_string.ends_with(ext));
+        if is_sqlite {
+            DbBackend::Sqlite
+        } else {
+            DbBackend::Postgres
+        }
+    }
+
+    /// Returns the path to the SQLite database file.
+    pub fn sqlite_path(&self) -> &str {
+        self.db_conn_string
+            .strip_prefix("sqlite:")
+            .unwrap_or(&self.db_conn_string)
+    }
+
+    /// Returns all problems with this configuration that can be found without consulting the
+    /// database.
+    pub fn problems(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+
+        if self.http_listen.parse::<SocketAddr>().is_err() {
+            problems.push(ConfigProblem::new("http_listen", "not an IP address and port"));
+        }
+
+        if self.auth_tokens.is_empty() {
+            problems.push(ConfigProblem::new("auth_tokens", "no tokens; nobody would be able to log in"));
+        }
+        for (i, token) in self.auth_tokens.iter().enumerate() {
+            if token.token.is_empty() {
+                problems.push(ConfigProblem::new(format!("auth_tokens[{}].token", i), "empty"));
+            } else if let Some(first) = self.auth_tokens[..i].iter().position(|t| t.token == token.token) {
+                problems.push(ConfigProblem::new(
+                    format!("auth_tokens[{}].token", i),
+                    format!("duplicate of auth_tokens[{}].token", first),
+                ));
+            }
+        }
+
+        self.hours.add_problems("hours", &mut problems);
+
+        if let Some(height_cm) = self.height_cm {
+            if height_cm <= 0 {
+                problems.push(ConfigProblem::new("height_cm", format!("{} is not positive", height_cm)));
+            }
+        }
+
+        for (i, rule) in self.alert_rules.iter().enumerate() {
+            let path = format!("alert_rules[{}]", i);
+            if rule.name.is_empty() {
+                problems.push(ConfigProblem::new(format!("{}.name", path), "empty"));
+            }
+            let field_keys = rule.measurement.statistics_field_keys();
+            if !field_keys.contains(&rule.field.as_str()) {
+                problems.push(ConfigProblem::new(
+                    format!("{}.field", path),
+                    format!(
+                        "{:?} is not a field of {} measurements; expected one of: {}",
+                        rule.field, rule.measurement.as_str(), field_keys.join(", "),
+                    ),
+                ));
+            }
+            if !rule.threshold.is_finite() {
+                problems.push(ConfigProblem::new(format!("{}.threshold", path), "not a finite number"));
+            }
+            if rule.consecutive_readings == 0 {
+                problems.push(ConfigProblem::new(format!("{}.consecutive_readings", path), "must be at least 1"));
+            }
+        }
+
+        for (i, sink) in self.alert_sinks.iter().enumerate() {
+            sink.add_problems(&format!("alert_sinks[{}]", i), &mut problems);
+        }
+
+        if let Some(reminders) = &self.reminders {
+            let deadlines = [
+                ("morning_deadline", reminders.morning_deadline),
+                ("midday_deadline", reminders.midday_deadline),
+                ("evening_deadline", reminders.evening_deadline),
+            ];
+            for (name, deadline) in deadlines {
+                if let Some(hour) = deadline {
+                    if hour >= 24 {
+                        problems.push(ConfigProblem::new(format!("reminders.{}", name), format!("{} is not an hour of the day", hour)));
+                    }
+                }
+            }
+            if reminders.check_interval_minutes == 0 {
+                problems.push(ConfigProblem::new("reminders.check_interval_minutes", "must be at least 1"));
+            }
+            for (i, sink) in reminders.sinks.iter().enumerate() {
+                sink.add_problems(&format!("reminders.sinks[{}]", i), &mut problems);
+            }
+        }
+
+        for (i, metric) in self.custom_metrics.iter().enumerate() {
+            let path = format!("custom_metrics[{}]", i);
+            metric.add_problems(&path, &mut problems);
+            if let Some(first) = self.custom_metrics[..i].iter().position(|m| m.key == metric.key) {
+                problems.push(ConfigProblem::new(
+                    format!("{}.key", path),
+                    format!("duplicate of custom_metrics[{}].key", first),
+                ));
+            }
+        }
+
+        if self.config_watch_interval_seconds == Some(0) {
+            problems.push(ConfigProblem::new("config_watch_interval_seconds", "must be at least 1"));
+        }
+
+        problems
+    }
+
+    /// Returns the problems with this configuration that can only be found by consulting the
+    /// database.
+    ///
+    /// The default temperature location is only checked once any location exists: locations are
+    /// added through the running server, so a fresh database could never pass the check.
+    pub async fn storage_problems(&self) -> Result<Vec<ConfigProblem>, DatabaseError> {
+        let mut problems = Vec::new();
+
+        let locations = get_temperature_locations().await?;
+        if !locations.is_empty() && !locations.iter().any(|l| l.id == self.default_temperature_location_id) {
+            let known: Vec<String> = locations
+                .iter()
+                .map(|l| format!("{} ({})", l.id, l.name))
+                .collect();
+            let known_str = known.join(", ");
+            problems.push(ConfigProblem::new(
+                "default_temperature_location_id",
+                format!(
+                    "no temperature location with ID {}; known locations: {}",
+                    self.default_temperature_location_id, known_str,
+                ),
+            ));
+        }
+
+        Ok(problems)
+    }
+
+    /// Fails with the problems found by [`Config::storage_problems`], if there are any.
+    pub async fn check_storage(&self) -> Result<(), ServerError> {
+        let problems = self.storage_problems().await
+            .map_err(ServerError::CheckingConfig)?;
+        if !problems.is_empty() {
+            return Err(ServerError::InvalidConfig(problems));
+        }
+        Ok(())
+    }
+}
+
+
+/// A semantic problem with a configuration value.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct ConfigProblem {
+    /// The path to the offending value, such as `hours.morning_end` or `auth_tokens[1].token`.
+    pub path: String,
+    pub message: String,
+}
+impl ConfigProblem {
+    pub fn new<P: Into<String>, M: Into<String>>(path: P, message: M) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+
+impl Hours {
+    fn add_problems(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        let hours = [
+            ("morning_start", self.morning_start),
+            ("morning_end", self.morning_end),
+            ("midday_start", self.midday_start),
+            ("midday_end", self.midday_end),
+            ("evening_start", self.evening_start),
+        ];
+        for (name, hour) in hours {
+            if hour >= 24 {
+                problems.push(ConfigProblem::new(format!("{}.{}", path, name), format!("{} is not an hour of the day", hour)));
+            }
+        }
+
+        let ranges = [
+            ("morning", self.morning_start, self.morning_end),
+            ("midday", self.midday_start, self.midday_end),
+        ];
+        for (name, start, end) in ranges {
+            if end < start {
+                problems.push(ConfigProblem::new(
+                    format!("{}.{}_end", path, name),
+                    format!("{} is before {}.{}_start ({})", end, path, name, start),
+                ));
+            }
+        }
+    }
+}
+
+
+impl NotificationSink {
+    fn add_problems(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        match self {
+            Self::Webhook { url } => {
+                match Url::parse(url) {
+                    Ok(u) if u.scheme() != "http" => {
+                        problems.push(ConfigProblem::new(format!("{}.url", path), format!("unsupported URL scheme {:?}; only \"http\" is supported", u.scheme())));
+                    },
+                    Ok(_) => {},
+                    Err(e) => {
+                        problems.push(ConfigProblem::new(format!("{}.url", path), format!("invalid URL: {}", e)));
+                    },
+                }
+            },
+            Self::Smtp { host, from, to, .. } => {
+                if host.is_empty() {
+                    problems.push(ConfigProblem::new(format!("{}.host", path), "empty"));
+                }
+                if from.is_empty() {
+                    problems.push(ConfigProblem::new(format!("{}.from", path), "empty"));
+                }
+                if to.is_empty() {
+                    problems.push(ConfigProblem::new(format!("{}.to", path), "no recipients"));
+                }
+            },
+            Self::Command { program, .. } => {
+                if program.is_empty() {
+                    problems.push(ConfigProblem::new(format!("{}.program", path), "empty"));
+                }
+            },
+        }
+    }
+}
+
+
+/// Explains where configuration values are taken from; appended to errors that might be caused by
+/// an unexpected source.
+pub(crate) const CONFIG_PRECEDENCE_NOTE: &str = "\
+configuration values are taken from the first of these sources that sets them:
+  1. command-line options such as --listen
+  2. an environment variable named BEEPEE_ followed by the key in upper case, with nested keys and array indexes separated by a double underscore (e.g. BEEPEE_DB_CONN_STRING, BEEPEE_HOURS__MORNING_START, BEEPEE_AUTH_TOKENS__0__TOKEN)
+  3. the same environment variable with a _FILE suffix, naming a file containing the value (e.g. BEEPEE_DB_CONN_STRING_FILE)
+  4. the key in the configuration file
+  5. the key with a _file suffix in the configuration file, naming a file containing the value (e.g. db_conn_string_file)
+setting a value both directly and via a file in the same source is an error";
+
+const ENV_PREFIX: &str = "BEEPEE_";
+const ENV_FILE_SUFFIX: &str = "_FILE";
+const FILE_SUFFIX: &str = "_file";
+
+
+/// Parses the value of an environment variable.
+///
+/// Values that are valid TOML (numbers, booleans, arrays, inline tables) are taken as such unless
+/// they replace a string; everything else is taken as a string.
+fn parse_env_value(raw: &str, replaced: Option<&toml::Value>) -> toml::Value {
+    if let Some(toml::Value::String(_)) = replaced {
+        return toml::Value::String(raw.to_owned());
+    }
+    let parsed: Result<toml::Table, _> = toml::from_str(&format!("value = {}", raw));
+    match parsed.ok().and_then(|mut t| t.remove("value")) {
+        Some(value) => value,
+        None => toml::Value::String(raw.to_owned()),
+    }
+}
+
+
+/// Walks the path of an environment variable, descending into tables (creating them as needed)
+/// and, by numeric segments, into arrays of tables. Returns the table that holds the final key.
+fn env_target_table<'a>(root: &'a mut toml::Table, path: &[String], var_name: &str) -> Result<&'a mut toml::Table, ServerError> {
+    let invalid = |reason: String| ServerError::InvalidConfigOverride(var_name.to_owned(), reason);
+
+    let mut table = root;
+    let mut i = 0;
+    while i < path.len() {
+        let segment = &path[i];
+        let child = table
+            .entry(segment.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        table = match child {
+            toml::Value::Table(t) => {
+                i += 1;
+                t
+            },
+            toml::Value::Array(array) => {
+                let index_str = path.get(i + 1)
+                    .ok_or_else(|| invalid(format!("{:?} is an array; only its entries can have nested keys", segment)))?;
+                let index: usize = index_str.parse()
+                    .map_err(|_| invalid(format!("{:?} is an array, but {:?} is not an index", segment, index_str)))?;
+                let length = array.len();
+                i += 2;
+                match array.get_mut(index) {
+                    Some(toml::Value::Table(t)) => t,
+                    Some(_) => return Err(invalid(format!("entry {} of {:?} is not a table", index, segment))),
+                    None => return Err(invalid(format!("{:?} only has {} entries", segment, length))),
+                }
+            },
+            _ => return Err(invalid(format!("{:?} is not a table", segment))),
+        };
+    }
+    Ok(table)
+}
+
+
+/// Applies `BEEPEE_*` environment variables to the parsed configuration file.
+fn apply_env_overrides<I: IntoIterator<Item = (String, String)>>(root: &mut toml::Table, vars: I) -> Result<(), ServerError> {
+    let mut overrides: Vec<(String, String)> = vars
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect();
+    // apply outer keys first so that replacing a table does not undo overrides of its keys
+    overrides.sort_by_key(|(name, _)| (name.matches("__").count(), name.clone()));
+
+    for (name, raw_value) in &overrides {
+        let (key_name, from_file) = match name.strip_suffix(ENV_FILE_SUFFIX) {
+            Some(k) => (k, true),
+            None => (name.as_str(), false),
+        };
+        if from_file && overrides.iter().any(|(n, _)| n == key_name) {
+            return Err(ServerError::ConflictingConfigSources(key_name.to_owned(), name.clone()));
+        }
+
+        let mut path: Vec<String> = key_name[ENV_PREFIX.len()..]
+            .split("__")
+            .map(|s| s.to_lowercase())
+            .collect();
+        if path.iter().any(|s| s.is_empty()) {
+            return Err(ServerError::InvalidConfigOverride(name.clone(), "empty key".to_owned()));
+        }
+        let key = path.pop().unwrap();
+        let file_key = format!("{}{}", key, FILE_SUFFIX);
+
+        let table = env_target_table(root, &path, name)?;
+        if from_file {
+            table.remove(&key);
+            table.insert(file_key, toml::Value::String(raw_value.clone()));
+        } else {
+            table.remove(&file_key);
+            let value = parse_env_value(raw_value, table.get(&key));
+            table.insert(key, value);
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Replaces each `*_file` key in the configuration with the contents of the file it names.
+fn resolve_secret_files(table: &mut toml::Table, path: &str) -> Result<(), ServerError> {
+    let file_keys: Vec<String> = table.keys()
+        .filter(|k| k.ends_with(FILE_SUFFIX))
+        .cloned()
+        .collect();
+    for file_key in file_keys {
+        let key = file_key[..file_key.len() - FILE_SUFFIX.len()].to_owned();
+        let full_key = format!("{}{}", path, key);
+        let full_file_key = format!("{}{}", path, file_key);
+        if table.contains_key(&key) {
+            return Err(ServerError::ConflictingConfigSources(full_key, full_file_key));
+        }
+
+        let file_path = match table.remove(&file_key) {
+            Some(toml::Value::String(p)) => PathBuf::from(p),
+            _ => return Err(ServerError::InvalidConfigOverride(full_file_key, "not a string".to_owned())),
+        };
+        let contents = std::fs::read_to_string(&file_path)
+            .map_err(|e| ServerError::ReadingSecretFile(full_file_key, file_path, e))?;
+        // files written by editors and `echo` end with a newline that is not part of the secret
+        let secret = contents.trim_end_matches(['\r', '\n']);
+        table.insert(key, toml::Value::String(secret.to_owned()));
+    }
+
+    for (key, value) in table.iter_mut() {
+        match value {
+            toml::Value::Table(t) => resolve_secret_files(t, &format!("{}{}.", path, key))?,
+            toml::Value::Array(a) => {
+                for (i, entry) in a.iter_mut().enumerate() {
+                    if let toml::Value::Table(t) = entry {
+                        resolve_secret_files(t, &format!("{}{}[{}].", path, key, i))?;
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    Ok(())
+}
+
+
+fn read_config_file(path: &Path) -> Result<Config, ServerError> {
+    let mut config_file = File::open(path)
+        .map_err(ServerError::OpeningConfigFile)?;
+    let mut config_str = String::new();
+    config_file.read_to_string(&mut config_str)
+        .map_err(ServerError::ReadingConfigFile)?;
+    let mut table: toml::Table = toml::from_str(&config_str)
+        .map_err(ServerError::ParsingConfigFile)?;
+
+    // later overrides of the same key win
+    let cli_overrides = CLI_OVERRIDES.get().cloned().unwrap_or_default();
+    apply_env_overrides(&mut table, std::env::vars().chain(cli_overrides))?;
+    resolve_secret_files(&mut table, "")?;
+    let config: Config = toml::Value::Table(table).try_into()
+        .map_err(ServerError::ParsingConfigFile)?;
+
+    let problems = config.problems();
+    if !problems.is_empty() {
+        return Err(ServerError::InvalidConfig(problems));
+    }
+
+    Ok(config)
 }
 
 
@@ -47,15 +682,7 @@ pub(crate) async fn load_config() -> Result<(), ServerError> {
     let path = CONFIG_PATH
         .get().expect("configuration path missing");
 
-    let config: Config = {
-        let mut config_file = File::open(path)
-            .map_err(|e| ServerError::OpeningConfigFile(e))?;
-        let mut config_str = String::new();
-        config_file.read_to_string(&mut config_str)
-            .map_err(|e| ServerError::ReadingConfigFile(e))?;
-        toml::from_str(&config_str)
-            .map_err(|e| ServerError::ParsingConfigFile(e))?
-    };
+    let config = read_config_file(path)?;
 
     match CONFIG.get() {
         Some(cg) => {
@@ -71,3 +698,240 @@ pub(crate) async fn load_config() -> Result<(), ServerError> {
 
     Ok(())
 }
+
+
+/// Copies the settings that are only evaluated at startup from the running configuration into the
+/// new one, returning the names of those that the new configuration tried to change.
+fn retain_startup_settings(running: &Config, new: &mut Config) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    if new.db_conn_string != running.db_conn_string {
+        new.db_conn_string = running.db_conn_string.clone();
+        changed.push("db_conn_string");
+    }
+    if new.db_backend != running.db_backend {
+        new.db_backend = running.db_backend;
+        changed.push("db_backend");
+    }
+    if new.http_listen != running.http_listen {
+        new.http_listen = running.http_listen.clone();
+        changed.push("http_listen");
+    }
+    if new.migrate_on_startup != running.migrate_on_startup {
+        new.migrate_on_startup = running.migrate_on_startup;
+        changed.push("migrate_on_startup");
+    }
+    changed
+}
+
+
+/// Reads the configuration file again and replaces the running configuration with it.
+///
+/// If the file cannot be read or parsed, or if the new configuration does not match the stored
+/// data, the running configuration is kept. Settings that are only evaluated at startup keep their
+/// running values until the server is restarted.
+pub(crate) async fn reload_config() -> Result<(), ServerError> {
+    let path = CONFIG_PATH
+        .get().expect("configuration path missing");
+
+    let mut config = read_config_file(path)?;
+    config.check_storage().await?;
+
+    let mut config_guard = CONFIG
+        .get().expect("no config lock")
+        .write().await;
+    for setting in retain_startup_settings(&config_guard, &mut config) {
+        warn!("changing {} requires a restart; keeping the running value", setting);
+    }
+    *config_guard = config;
+
+    Ok(())
+}
+
+
+fn config_modified_time() -> Option<SystemTime> {
+    let path = CONFIG_PATH
+        .get().expect("configuration path missing");
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+
+/// Reloads the configuration on SIGHUP and, if `config_watch_interval_seconds` is set, whenever the
+/// modification time of the configuration file changes. Runs forever.
+pub(crate) async fn watch_config() {
+    let mut hangups = signal(SignalKind::hangup())
+        .expect("failed to install SIGHUP handler");
+    let mut last_modified = config_modified_time();
+
+    loop {
+        let watch_interval = {
+            CONFIG
+                .get().expect("no config lock")
+                .read().await
+                .config_watch_interval_seconds
+        };
+
+        let reason = match watch_interval {
+            Some(seconds) => tokio::select! {
+                _ = hangups.recv() => "SIGHUP",
+                _ = tokio::time::sleep(std::time::Duration::from_secs(seconds.max(1))) => {
+                    if config_modified_time() == last_modified {
+                        continue;
+                    }
+                    "file change"
+                },
+            },
+            None => {
+                hangups.recv().await;
+                "SIGHUP"
+            },
+        };
+        last_modified = config_modified_time();
+
+        match reload_config().await {
+            Ok(()) => info!("configuration reloaded after {}", reason),
+            Err(e) => error!("failed to reload configuration after {}, keeping the previous one: {}", reason, e),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG_TOML: &str = r#"
+base_url = "http://127.0.0.1/"
+db_conn_string = "memory:"
+http_listen = "127.0.0.1:8000"
+auth_tokens = [{ token = "old", write = true }]
+default_temperature_location_id = 1
+
+[hours]
+morning_start = 5
+morning_end = 13
+midday_start = 11
+midday_end = 20
+evening_start = 17
+"#;
+
+    #[test]
+    fn startup_settings_retained() {
+        let running: Config = toml::from_str(CONFIG_TOML).unwrap();
+
+        let mut unchanged = running.clone();
+        assert!(retain_startup_settings(&running, &mut unchanged).is_empty());
+
+        let mut new = running.clone();
+        new.db_conn_string = "other.sqlite".to_owned();
+        new.http_listen = "[::]:8000".to_owned();
+        new.auth_tokens[0].token = "new".to_owned();
+        new.height_cm = Some(170);
+        assert_eq!(retain_startup_settings(&running, &mut new), vec!["db_conn_string", "http_listen"]);
+        assert_eq!(new.db_conn_string, "memory:");
+        assert_eq!(new.http_listen, "127.0.0.1:8000");
+        assert_eq!(new.auth_tokens[0].token, "new");
+        assert_eq!(new.height_cm, Some(170));
+    }
+
+    #[test]
+    fn problems_reported_with_paths() {
+        let valid: Config = toml::from_str(CONFIG_TOML).unwrap();
+        assert_eq!(valid.problems(), Vec::new());
+
+        let mut config = valid.clone();
+        config.http_listen = "localhost".to_owned();
+        config.auth_tokens.push(AuthToken { token: "old".to_owned(), write: false, units: None });
+        config.hours.morning_end = 4;
+        config.hours.evening_start = 24;
+        config.alert_rules.push(AlertRule {
+            name: "fever".to_owned(),
+            measurement: MeasurementKind::Temperature,
+            field: "temperature_fahrenheit".to_owned(),
+            comparison: AlertComparison::Above,
+            threshold: 38.0,
+            consecutive_readings: 0,
+        });
+        let paths: Vec<String> = config.problems()
+            .into_iter()
+            .map(|p| p.path)
+            .collect();
+        assert_eq!(paths, vec![
+            "http_listen",
+            "auth_tokens[1].token",
+            "hours.evening_start",
+            "hours.morning_end",
+            "alert_rules[0].field",
+            "alert_rules[0].consecutive_readings",
+        ]);
+
+        config.auth_tokens.clear();
+        assert!(config.problems().iter().any(|p| p.path == "auth_tokens"));
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn env_overrides() {
+        let mut table: toml::Table = toml::from_str(CONFIG_TOML).unwrap();
+        apply_env_overrides(&mut table, vars(&[
+            ("BEEPEE_HOURS__MORNING_START", "6"),
+            ("BEEPEE_HEIGHT_CM", "172"),
+            ("BEEPEE_HTTP_LISTEN", "8000"),
+            ("BEEPEE_AUTH_TOKENS__0__WRITE", "false"),
+            ("BEEPEE_DISPLAY_TIMEZONE", "Europe/Vienna"),
+            ("PATH", "/usr/bin"),
+        ])).unwrap();
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.hours.morning_start, 6);
+        assert_eq!(config.height_cm, Some(172));
+        // replaces a string, so it stays a string
+        assert_eq!(config.http_listen, "8000");
+        assert!(!config.auth_tokens[0].write);
+        assert_eq!(config.display_timezone, Some(chrono_tz::Europe::Vienna));
+
+        let mut table: toml::Table = toml::from_str(CONFIG_TOML).unwrap();
+        let both = vars(&[("BEEPEE_DB_CONN_STRING", "a"), ("BEEPEE_DB_CONN_STRING_FILE", "/b")]);
+        assert!(matches!(apply_env_overrides(&mut table, both), Err(ServerError::ConflictingConfigSources(_, _))));
+        let out_of_range = vars(&[("BEEPEE_AUTH_TOKENS__3__TOKEN", "x")]);
+        assert!(matches!(apply_env_overrides(&mut table, out_of_range), Err(ServerError::InvalidConfigOverride(_, _))));
+    }
+
+    #[test]
+    fn secret_files() {
+        let dir = std::env::temp_dir().join(format!("beepee-secret-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_secret = dir.join("db");
+        std::fs::write(&db_secret, "host=db password=hunter2\n").unwrap();
+        let token_secret = dir.join("token");
+        std::fs::write(&token_secret, "s3cret").unwrap();
+
+        // the environment takes precedence over the file, and the file key is dropped
+        let mut table: toml::Table = toml::from_str(CONFIG_TOML).unwrap();
+        apply_env_overrides(&mut table, vec![
+            ("BEEPEE_DB_CONN_STRING_FILE".to_owned(), db_secret.display().to_string()),
+            ("BEEPEE_AUTH_TOKENS__0__TOKEN_FILE".to_owned(), token_secret.display().to_string()),
+        ]).unwrap();
+        resolve_secret_files(&mut table, "").unwrap();
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.db_conn_string, "host=db password=hunter2");
+        assert_eq!(config.auth_tokens[0].token, "s3cret");
+
+        // both the key and the file key in the same source
+        let mut table: toml::Table = toml::from_str(CONFIG_TOML).unwrap();
+        table.insert("db_conn_string_file".to_owned(), toml::Value::String(db_secret.display().to_string()));
+        assert!(matches!(resolve_secret_files(&mut table, ""), Err(ServerError::ConflictingConfigSources(_, _))));
+
+        let mut table: toml::Table = toml::from_str(CONFIG_TOML).unwrap();
+        table.remove("db_conn_string");
+        table.insert("db_conn_string_file".to_owned(), toml::Value::String(dir.join("missing").display().to_string()));
+        assert!(matches!(resolve_secret_files(&mut table, ""), Err(ServerError::ReadingSecretFile(_, _, _))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
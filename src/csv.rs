@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value;
+
+use crate::numerism::r32_to_f64;
+use crate::ser_de::string_to_rat32;
+
+
+/// Columns that are output first, in this order, if present.
+const LEADING_COLUMNS: [&str; 2] = ["id", "timestamp"];
+
+
+/// Renders a serialized API response as CSV with a header line.
+///
+/// Each element of an array becomes a row; any other value becomes a single row. Nested objects are
+/// flattened into columns whose names are joined with a dot (e.g. `peak.temperature_celsius`) and
+/// nested arrays are output as JSON. Rationals (`"numerator/denominator"`) are output as decimals.
+pub(crate) fn json_to_csv(value: &Value) -> String {
+    let rows: Vec<BTreeMap<String, String>> = match value {
+        Value::Array(items) => items.iter().map(flatten_row).collect(),
+        other => vec![flatten_row(other)],
+    };
+
+    let all_columns: BTreeSet<&String> = rows.iter()
+        .flat_map(|r| r.keys())
+        .collect();
+    let mut columns: Vec<&String> = Vec::with_capacity(all_columns.len());
+    for leading in LEADING_COLUMNS {
+        columns.extend(all_columns.iter().filter(|c| c.as_str() == leading));
+    }
+    columns.extend(all_columns.iter().filter(|c| !LEADING_COLUMNS.contains(&c.as_str())));
+
+    let mut csv = String::new();
+    push_line(&mut csv, columns.iter().map(|c| c.as_str()));
+    for row in &rows {
+        push_line(&mut csv, columns.iter().map(|c| row.get(*c).map(|v| v.as_str()).unwrap_or("")));
+    }
+    csv
+}
+
+fn flatten_row(value: &Value) -> BTreeMap<String, String> {
+    let mut row = BTreeMap::new();
+    match value {
+        Value::Object(_) => flatten_into(&mut row, None, value),
+        other => flatten_into(&mut row, Some("value"), other),
+    }
+    row
+}
+
+fn flatten_into(row: &mut BTreeMap<String, String>, prefix: Option<&str>, value: &Value) {
+    let cell = match value {
+        Value::Object(map) => {
+            for (key, entry) in map {
+                let column = match prefix {
+                    Some(p) => format!("{}.{}", p, key),
+                    None => key.clone(),
+                };
+                flatten_into(row, Some(&column), entry);
+            }
+            return;
+        },
+        Value::Null => String::new(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => {
+            match string_to_rat32(s) {
+                Ok(r) if s.contains('/') => r32_to_f64(&r).to_string(),
+                _ => s.clone(),
+            }
+        },
+        Value::Array(_) => value.to_string(),
+    };
+    row.insert(prefix.unwrap_or("value").to_owned(), cell);
+}
+
+fn push_line<'a, I: Iterator<Item = &'a str>>(csv: &mut String, cells: I) {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            csv.push(',');
+        }
+        if cell.contains([',', '"', '\n', '\r']) {
+            csv.push('"');
+            csv.push_str(&cell.replace('"', "\"\""));
+            csv.push('"');
+        } else {
+            csv.push_str(cell);
+        }
+    }
+    csv.push_str("\r\n");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn rows_and_columns() {
+        let value = json!([
+            { "timestamp": "2024-03-01T08:00:00+01:00", "mass_kg": "817/10", "id": 2, "note": "a, \"b\"" },
+            { "timestamp": "2024-03-02T08:00:00+01:00", "mass_kg": "81/1", "id": 3, "peak": { "value": 1.5 } },
+        ]);
+        assert_eq!(
+            json_to_csv(&value),
+            concat!(
+                "id,timestamp,mass_kg,note,peak.value\r\n",
+                "2,2024-03-01T08:00:00+01:00,81.7,\"a, \"\"b\"\"\",\r\n",
+                "3,2024-03-02T08:00:00+01:00,81,,1.5\r\n",
+            ),
+        );
+    }
+}
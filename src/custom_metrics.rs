@@ -0,0 +1,240 @@
+use std::collections::BTreeSet;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigProblem, Hours};
+use crate::model::CustomMeasurement;
+use crate::numerism::r32_to_f64;
+use crate::statistics::{FieldStatistics, MeasurementStatistics, StatisticsSet};
+use crate::trends::{FieldTrend, TrendPoint};
+
+
+/// Keys of metrics and their fields must be usable in URLs, form field names and JSON keys.
+static KEY_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("^[a-z0-9_-]+$").expect("invalid key regex"));
+
+
+fn default_digits() -> usize { 1 }
+
+
+/// A kind of measurement defined in the configuration instead of in code.
+///
+/// Its page, form, statistics and API are generated from the definition. Measurements of all custom
+/// metrics share a single table; removing a definition hides its measurements but keeps them
+/// stored (and exported).
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct CustomMetric {
+    /// Identifies the metric in URLs and in stored measurements; must not change once measurements
+    /// have been recorded.
+    pub key: String,
+
+    pub name: String,
+
+    /// The unit of the fields that do not specify their own.
+    #[serde(default)] pub unit: String,
+
+    pub fields: Vec<CustomMetricField>,
+    #[serde(default)] pub categories: Vec<CustomMetricCategory>,
+}
+
+/// A numeric field of a custom metric.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct CustomMetricField {
+    pub key: String,
+    pub label: String,
+    pub unit: Option<String>,
+
+    /// The lowest value that is accepted.
+    pub min: Option<f64>,
+
+    /// The highest value that is accepted.
+    pub max: Option<f64>,
+
+    /// The number of fractional digits with which values are shown.
+    #[serde(default = "default_digits")] pub digits: usize,
+
+    #[serde(default)] pub required: bool,
+}
+
+/// A field of a custom metric that takes one of a fixed set of values, such as the arm on which a
+/// measurement was taken.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct CustomMetricCategory {
+    pub key: String,
+    pub label: String,
+    pub options: Vec<String>,
+    #[serde(default)] pub required: bool,
+}
+
+
+impl CustomMetric {
+    /// Returns the unit of the given field.
+    pub fn field_unit<'a>(&'a self, field: &'a CustomMetricField) -> &'a str {
+        field.unit.as_deref().unwrap_or(&self.unit)
+    }
+
+    /// Calculates the statistics of each numeric field, overall and by part of the day.
+    pub fn statistics(&self, measurements: &[CustomMeasurement], hours: &Hours) -> MeasurementStatistics {
+        MeasurementStatistics::calculate_with(
+            measurements,
+            hours,
+            |m| m.timestamp,
+            |ms| {
+                let fields = self.fields.iter()
+                    .filter_map(|field| {
+                        let values: Vec<f64> = ms.iter()
+                            .filter_map(|m| m.values.get(&field.key).map(r32_to_f64))
+                            .collect();
+                        FieldStatistics::from_values(&field.key, &field.label, self.field_unit(field), field.digits, &values)
+                    })
+                    .collect();
+                StatisticsSet {
+                    count: ms.len(),
+                    fields,
+                }
+            },
+        )
+    }
+
+    /// Calculates the trend of each numeric field that has at least one value.
+    ///
+    /// `measurements` must be sorted by timestamp.
+    pub fn trends(&self, measurements: &[CustomMeasurement]) -> Vec<FieldTrend> {
+        self.fields.iter()
+            .filter_map(|field| {
+                let values: Vec<TrendPoint> = measurements.iter()
+                    .filter_map(|m| m.values.get(&field.key).map(|v| TrendPoint { timestamp: m.timestamp, value: r32_to_f64(v) }))
+                    .collect();
+                FieldTrend::from_points(&field.key, &field.label, self.field_unit(field), field.digits, values)
+            })
+            .collect()
+    }
+
+    pub fn add_problems(&self, path: &str, problems: &mut Vec<ConfigProblem>) {
+        if !KEY_REGEX.is_match(&self.key) {
+            problems.push(ConfigProblem::new(
+                format!("{}.key", path),
+                format!("{:?} may only contain lowercase letters, digits, underscores and hyphens", self.key),
+            ));
+        }
+        if self.name.trim().is_empty() {
+            problems.push(ConfigProblem::new(format!("{}.name", path), "empty"));
+        }
+        if self.fields.is_empty() {
+            problems.push(ConfigProblem::new(format!("{}.fields", path), "no fields"));
+        }
+
+        // fields and categories share the namespace of form field names
+        let mut seen_keys = BTreeSet::new();
+        for (i, field) in self.fields.iter().enumerate() {
+            let field_path = format!("{}.fields[{}]", path, i);
+            check_field_key(&field.key, &field_path, &mut seen_keys, problems);
+            if let (Some(min), Some(max)) = (field.min, field.max) {
+                if min > max {
+                    problems.push(ConfigProblem::new(format!("{}.max", field_path), format!("{} is less than min {}", max, min)));
+                }
+            }
+            for (name, bound) in [("min", field.min), ("max", field.max)] {
+                if bound.is_some_and(|b| !b.is_finite()) {
+                    problems.push(ConfigProblem::new(format!("{}.{}", field_path, name), "not a finite number"));
+                }
+            }
+        }
+        for (i, category) in self.categories.iter().enumerate() {
+            let category_path = format!("{}.categories[{}]", path, i);
+            check_field_key(&category.key, &category_path, &mut seen_keys, problems);
+            if category.options.is_empty() {
+                problems.push(ConfigProblem::new(format!("{}.options", category_path), "no options"));
+            }
+        }
+    }
+}
+
+fn check_field_key(key: &str, path: &str, seen_keys: &mut BTreeSet<String>, problems: &mut Vec<ConfigProblem>) {
+    if !KEY_REGEX.is_match(key) {
+        problems.push(ConfigProblem::new(
+            format!("{}.key", path),
+            format!("{:?} may only contain lowercase letters, digits, underscores and hyphens", key),
+        ));
+    } else if key == "utc_offset_minutes" || key == "token" || key == "metric" {
+        problems.push(ConfigProblem::new(format!("{}.key", path), format!("{:?} is reserved", key)));
+    } else if !seen_keys.insert(key.to_owned()) {
+        problems.push(ConfigProblem::new(format!("{}.key", path), format!("{:?} is used more than once", key)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use chrono::{FixedOffset, TimeZone};
+    use num_rational::Rational32;
+
+    fn fev1() -> CustomMetric {
+        toml::from_str(r#"
+            key = "spirometry"
+            name = "Spirometry"
+            unit = "l"
+            fields = [
+                { key = "fev1", label = "FEV₁", min = 0, max = 10, digits = 2, required = true },
+                { key = "fvc", label = "FVC", digits = 2 },
+                { key = "effort", label = "effort", unit = "" },
+            ]
+            categories = [{ key = "position", label = "position", options = ["sitting", "standing"] }]
+        "#).unwrap()
+    }
+
+    #[test]
+    fn statistics_and_trends() {
+        let metric = fev1();
+        let measurements: Vec<CustomMeasurement> = [(8, 3.2), (9, 3.4), (20, 3.0)].iter()
+            .map(|(hour, fev1)| {
+                let timestamp = FixedOffset::east_opt(3600).unwrap().with_ymd_and_hms(2024, 3, 1, *hour, 0, 0).unwrap();
+                let values = BTreeMap::from([("fev1".to_owned(), Rational32::approximate_float(*fev1).unwrap())]);
+                CustomMeasurement::new(-1, timestamp, "spirometry".to_owned(), values, BTreeMap::new())
+            })
+            .collect();
+        let hours = Hours { morning_start: 5, morning_end: 13, midday_start: 11, midday_end: 20, evening_start: 17 };
+
+        let statistics = metric.statistics(&measurements, &hours);
+        assert_eq!(statistics.overall.count, 3);
+        // fields without values are left out
+        assert_eq!(statistics.overall.fields.len(), 1);
+        assert_eq!(statistics.overall.fields[0].unit, "l");
+        assert_eq!(statistics.overall.fields[0].maximum, 3.4);
+        assert_eq!(statistics.morning.count, 2);
+        assert_eq!(statistics.evening.fields[0].median, 3.0);
+
+        let trends = metric.trends(&measurements);
+        assert_eq!(trends.len(), 1);
+        assert_eq!(trends[0].key, "fev1");
+        assert_eq!(trends[0].values.len(), 3);
+    }
+
+    #[test]
+    fn definition_problems() {
+        let mut problems = Vec::new();
+        fev1().add_problems("custom_metrics[0]", &mut problems);
+        assert_eq!(problems, Vec::new());
+
+        let mut metric = fev1();
+        metric.key = "Spirometry!".to_owned();
+        metric.fields[1].key = "fev1".to_owned();
+        metric.fields[2].min = Some(5.0);
+        metric.fields[2].max = Some(1.0);
+        metric.categories[0].key = "utc_offset_minutes".to_owned();
+        metric.categories[0].options.clear();
+        let mut problems = Vec::new();
+        metric.add_problems("custom_metrics[0]", &mut problems);
+        let paths: Vec<String> = problems.into_iter().map(|p| p.path).collect();
+        assert_eq!(paths, vec![
+            "custom_metrics[0].key",
+            "custom_metrics[0].fields[1].key",
+            "custom_metrics[0].fields[2].max",
+            "custom_metrics[0].categories[0].key",
+            "custom_metrics[0].categories[0].options",
+        ]);
+    }
+}
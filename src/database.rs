@@ -1,490 +1,718 @@
-use chrono::{Duration, Local};
+mod memory;
+mod postgres;
+mod sqlite;
+#[cfg(test)]
+pub(crate) mod testing;
+
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
 use num_rational::Rational32;
-use tokio;
-use tokio_postgres::{self, Client, NoTls};
-use tracing::error;
+use once_cell::sync::OnceCell;
 
-use crate::config::CONFIG;
+use crate::anthropometrics;
+use crate::config::{CONFIG, DbBackend};
+use crate::fever::normalize_measurements;
+use crate::migrations::Migration;
 use crate::model::{
-    BloodPressureMeasurement, BloodSugarMeasurement, BodyMassMeasurement, BodyTemperatureLocation,
-    BodyTemperatureMeasurement, LongTermBloodSugarMeasurement,
+    Alert, BloodPressureMeasurement, BloodSugarMeasurement, BodyMassMeasurement, BodyTemperatureLocation,
+    BodyTemperatureMeasurement, CustomMeasurement, DiaryEntry, HeightMeasurement, KetoneMeasurement,
+    LipidPanelMeasurement, LongTermBloodSugarMeasurement, Measurement, PeakFlowMeasurement,
 };
-use crate::numerism::r32_from_decimal;
+use crate::ser_de::{rat32_to_string, string_to_rat32};
+use crate::timezone::{get_display_timezone, in_display_timezone};
 
+pub(crate) use self::memory::MemoryStorage;
+pub(crate) use self::postgres::PostgresStorage;
+pub(crate) use self::sqlite::SqliteStorage;
 
-async fn get_conn_string() -> String {
-    CONFIG
-        .get().expect("config not set")
-        .read().await
-        .db_conn_string
-        .clone()
-}
 
-async fn connect() -> Result<Client, tokio_postgres::Error> {
-    let conn_string = get_conn_string()
-        .await;
+static STORAGE: OnceCell<Backend> = OnceCell::new();
 
-    let (client, connection) = tokio_postgres::connect(&conn_string, NoTls)
-        .await?;
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            error!("connection error: {}", e);
+
+#[derive(Debug)]
+pub(crate) enum DatabaseError {
+    Postgres(tokio_postgres::Error),
+    Sqlite(rusqlite::Error),
+    InvalidValue(&'static str, String),
+    BackgroundTask(tokio::task::JoinError),
+
+    /// The row cannot be removed because other rows still refer to it; contains the kind and ID of
+    /// the row.
+    StillReferenced(&'static str, i64),
+}
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatabaseError::Postgres(e)
+                => write!(f, "PostgreSQL error: {}", e),
+            DatabaseError::Sqlite(e)
+                => write!(f, "SQLite error: {}", e),
+            DatabaseError::InvalidValue(column, message)
+                => write!(f, "invalid value in column {:?}: {}", column, message),
+            DatabaseError::BackgroundTask(e)
+                => write!(f, "database task failed: {}", e),
+            DatabaseError::StillReferenced(kind, id)
+                => write!(f, "{} {} is still in use", kind, id),
         }
-    });
+    }
+}
+impl Error for DatabaseError {
+}
+impl From<tokio_postgres::Error> for DatabaseError {
+    fn from(value: tokio_postgres::Error) -> Self {
+        DatabaseError::Postgres(value)
+    }
+}
+impl From<rusqlite::Error> for DatabaseError {
+    fn from(value: rusqlite::Error) -> Self {
+        DatabaseError::Sqlite(value)
+    }
+}
+
 
-    Ok(client)
+/// A place where measurements are stored.
+///
+/// Timestamps are returned in the UTC offset at which the measurement was taken or, if that is not
+/// known, in the given display time zone. Rational values must round-trip without loss. Body mass
+/// measurements are returned without BMI and waist ratios and body temperature measurements without
+/// core temperature and status; these are derived by the caller.
+pub(crate) trait Storage {
+    async fn get_applied_migration_versions(&self) -> Result<Vec<i32>, DatabaseError>;
+
+    /// Applies the migration and records it. Returns `false` if it had already been applied.
+    async fn apply_migration(&self, migration: &Migration) -> Result<bool, DatabaseError>;
+
+    async fn add_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_blood_pressure_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    async fn update_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<(), DatabaseError>;
+    async fn get_blood_pressure_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BloodPressureMeasurement>, DatabaseError>;
+
+    async fn add_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_mass_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    async fn update_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<(), DatabaseError>;
+    async fn get_mass_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BodyMassMeasurement>, DatabaseError>;
+
+    async fn add_height_measurement(&self, measurement: &HeightMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_height_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    /// Returns all height measurements, since every one of them may still apply to a recent body
+    /// mass measurement.
+    async fn get_height_measurements(&self, display_timezone: Option<Tz>) -> Result<Vec<HeightMeasurement>, DatabaseError>;
+
+    async fn add_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<i64, DatabaseError>;
+    async fn remove_temperature_location(&self, loc_id: i64) -> Result<(), DatabaseError>;
+    async fn update_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<(), DatabaseError>;
+    async fn get_temperature_locations(&self) -> Result<Vec<BodyTemperatureLocation>, DatabaseError>;
+
+    async fn add_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_temperature_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    async fn update_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<(), DatabaseError>;
+    async fn get_temperature_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BodyTemperatureMeasurement>, DatabaseError>;
+
+    async fn add_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    async fn update_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<(), DatabaseError>;
+    async fn get_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BloodSugarMeasurement>, DatabaseError>;
+
+    async fn add_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_long_term_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    async fn update_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<(), DatabaseError>;
+    async fn get_long_term_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<LongTermBloodSugarMeasurement>, DatabaseError>;
+
+    async fn add_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_peak_flow_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    async fn update_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<(), DatabaseError>;
+    async fn get_peak_flow_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<PeakFlowMeasurement>, DatabaseError>;
+
+    async fn add_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_ketone_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    async fn update_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<(), DatabaseError>;
+    async fn get_ketone_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<KetoneMeasurement>, DatabaseError>;
+
+    async fn add_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_lipid_panel_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    async fn update_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<(), DatabaseError>;
+    async fn get_lipid_panel_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<LipidPanelMeasurement>, DatabaseError>;
+
+    async fn add_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<i64, DatabaseError>;
+    async fn remove_custom_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError>;
+    async fn update_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<(), DatabaseError>;
+    /// Returns the measurements of the given custom metric, or of all custom metrics if `metric` is
+    /// `None`.
+    async fn get_custom_measurements_since(&self, metric: Option<&str>, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<CustomMeasurement>, DatabaseError>;
+
+    async fn add_diary_entry(&self, entry: &DiaryEntry) -> Result<i64, DatabaseError>;
+    async fn remove_diary_entry(&self, entry_id: i64) -> Result<(), DatabaseError>;
+    async fn update_diary_entry(&self, entry: &DiaryEntry) -> Result<(), DatabaseError>;
+    async fn get_diary_entries_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<DiaryEntry>, DatabaseError>;
+
+    async fn add_alert(&self, alert: &Alert) -> Result<i64, DatabaseError>;
+    async fn get_alerts_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<Alert>, DatabaseError>;
 }
 
-pub(crate) async fn add_blood_pressure_measurement(measurement: &BloodPressureMeasurement) -> Result<i64, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
 
-    let row = client
-        .query_one(
-            "INSERT INTO beepee.measurements (\"timestamp\", systolic_mmhg, diastolic_mmhg, pulse_bpm, spo2_percent) VALUES ($1, $2, $3, $4, $5) RETURNING id",
-            &[&measurement.timestamp, &measurement.systolic_mmhg, &measurement.diastolic_mmhg, &measurement.pulse_bpm, &measurement.spo2_percent],
-        )
-        .await?;
-    let measurement_id: i64 = row.get(0);
+/// The storage backend selected in the configuration.
+pub(crate) enum Backend {
+    Memory(Box<MemoryStorage>),
+    Postgres(PostgresStorage),
+    Sqlite(SqliteStorage),
+}
 
-    Ok(measurement_id)
+macro_rules! dispatch {
+    ($self:expr, $method:ident($($arg:expr),*)) => {
+        match $self {
+            Backend::Memory(s) => s.$method($($arg),*).await,
+            Backend::Postgres(s) => s.$method($($arg),*).await,
+            Backend::Sqlite(s) => s.$method($($arg),*).await,
+        }
+    };
 }
 
-pub(crate) async fn remove_blood_pressure_measurement(measurement_id: i64) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+impl Storage for Backend {
+    async fn get_applied_migration_versions(&self) -> Result<Vec<i32>, DatabaseError> {
+        dispatch!(self, get_applied_migration_versions())
+    }
 
-    client
-        .execute(
-            "DELETE FROM beepee.measurements WHERE id = $1",
-            &[&measurement_id],
-        )
-        .await?;
+    async fn apply_migration(&self, migration: &Migration) -> Result<bool, DatabaseError> {
+        dispatch!(self, apply_migration(migration))
+    }
 
-    Ok(())
-}
+    async fn add_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_blood_pressure_measurement(measurement))
+    }
 
-pub(crate) async fn update_blood_pressure_measurement(measurement: &BloodPressureMeasurement) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+    async fn remove_blood_pressure_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_blood_pressure_measurement(measurement_id))
+    }
 
-    client
-        .execute(
-            "UPDATE beepee.measurements SET \"timestamp\"=$1, systolic_mmhg=$2, diastolic_mmhg=$3, pulse_bpm=$4, spo2_percent=$5 WHERE id=$6",
-            &[&measurement.timestamp, &measurement.systolic_mmhg, &measurement.diastolic_mmhg, &measurement.pulse_bpm, &measurement.spo2_percent, &measurement.id],
-        )
-        .await?;
+    async fn update_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<(), DatabaseError> {
+        dispatch!(self, update_blood_pressure_measurement(measurement))
+    }
 
-    Ok(())
-}
+    async fn get_blood_pressure_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BloodPressureMeasurement>, DatabaseError> {
+        dispatch!(self, get_blood_pressure_measurements_since(start_time, display_timezone))
+    }
 
-pub(crate) async fn get_recent_blood_pressure_measurements(ago: Duration) -> Result<Vec<BloodPressureMeasurement>, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+    async fn add_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_mass_measurement(measurement))
+    }
 
-    let start_time = Local::now() - ago;
+    async fn remove_mass_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_mass_measurement(measurement_id))
+    }
 
-    let rows = client
-        .query(
-            "SELECT id, \"timestamp\", systolic_mmhg, diastolic_mmhg, pulse_bpm, spo2_percent FROM beepee.measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
-            &[&start_time],
-        )
-        .await?;
-    let mut ret = Vec::new();
-    for row in rows {
-        ret.push(BloodPressureMeasurement::new(
-            row.get(0),
-            row.get(1),
-            row.get(2),
-            row.get(3),
-            row.get(4),
-            row.get(5),
-        ));
+    async fn update_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<(), DatabaseError> {
+        dispatch!(self, update_mass_measurement(measurement))
     }
 
-    Ok(ret)
-}
+    async fn get_mass_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BodyMassMeasurement>, DatabaseError> {
+        dispatch!(self, get_mass_measurements_since(start_time, display_timezone))
+    }
 
-pub(crate) async fn add_mass_measurement(measurement: &BodyMassMeasurement) -> Result<i64, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+    async fn add_height_measurement(&self, measurement: &HeightMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_height_measurement(measurement))
+    }
 
-    let row = if let Some(circum) = &measurement.waist_circum_cm {
-        client
-            .query_one(
-                "INSERT INTO beepee.mass_measurements (\"timestamp\", mass_kg, waist_circum_cm) VALUES ($1, (CAST(CAST($2 AS int) AS numeric(6, 2)) / CAST(CAST($3 AS int) AS numeric(6, 2))), (CAST(CAST($4 AS int) AS numeric(6, 2)) / CAST(CAST($5 AS int) AS numeric(6, 2)))) RETURNING id",
-                &[&measurement.timestamp, &measurement.mass_kg.numer(), &measurement.mass_kg.denom(), &circum.numer(), &circum.denom()],
-            )
-            .await?
-    } else {
-        client
-            .query_one(
-                "INSERT INTO beepee.mass_measurements (\"timestamp\", mass_kg, waist_circum_cm) VALUES ($1, (CAST(CAST($2 AS int) AS numeric(6, 2)) / CAST(CAST($3 AS int) AS numeric(6, 2))), NULL) RETURNING id",
-                &[&measurement.timestamp, &measurement.mass_kg.numer(), &measurement.mass_kg.denom()],
-            )
-            .await?
-    };
-    let measurement_id: i64 = row.get(0);
+    async fn remove_height_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_height_measurement(measurement_id))
+    }
 
-    Ok(measurement_id)
-}
+    async fn get_height_measurements(&self, display_timezone: Option<Tz>) -> Result<Vec<HeightMeasurement>, DatabaseError> {
+        dispatch!(self, get_height_measurements(display_timezone))
+    }
 
-pub(crate) async fn remove_mass_measurement(measurement_id: i64) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+    async fn add_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_temperature_location(loc))
+    }
 
-    client
-        .execute(
-            "DELETE FROM beepee.mass_measurements WHERE id = $1",
-            &[&measurement_id],
-        )
-        .await?;
+    async fn remove_temperature_location(&self, loc_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_temperature_location(loc_id))
+    }
 
-    Ok(())
-}
+    async fn update_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<(), DatabaseError> {
+        dispatch!(self, update_temperature_location(loc))
+    }
 
-pub(crate) async fn update_mass_measurement(measurement: &BodyMassMeasurement) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+    async fn get_temperature_locations(&self) -> Result<Vec<BodyTemperatureLocation>, DatabaseError> {
+        dispatch!(self, get_temperature_locations())
+    }
 
-    if let Some(circum) = &measurement.waist_circum_cm {
-        client
-            .execute(
-                "UPDATE beepee.mass_measurements SET \"timestamp\"=$1, mass_kg=(CAST(CAST($2 AS int) AS numeric(6, 2)) / CAST(CAST($3 AS int) AS numeric(6, 2))), waist_circum_cm=(CAST(CAST($4 AS int) AS numeric(6, 2)) / CAST(CAST($5 AS int) AS numeric(6, 2))) WHERE id=$6",
-                &[&measurement.timestamp, &measurement.mass_kg.numer(), &measurement.mass_kg.denom(), &circum.numer(), &circum.denom(), &measurement.id],
-            )
-            .await?
-    } else {
-        client
-            .execute(
-                "UPDATE beepee.mass_measurements SET \"timestamp\"=$1, mass_kg=(CAST(CAST($2 AS int) AS numeric(6, 2)) / CAST(CAST($3 AS int) AS numeric(6, 2))), waist_circum_cm=NULL WHERE id=$4",
-                &[&measurement.timestamp, &measurement.mass_kg.numer(), &measurement.mass_kg.denom(), &measurement.id],
-            )
-            .await?
-    };
+    async fn add_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_temperature_measurement(measurement))
+    }
 
-    Ok(())
+    async fn remove_temperature_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_temperature_measurement(measurement_id))
+    }
+
+    async fn update_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<(), DatabaseError> {
+        dispatch!(self, update_temperature_measurement(measurement))
+    }
+
+    async fn get_temperature_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BodyTemperatureMeasurement>, DatabaseError> {
+        dispatch!(self, get_temperature_measurements_since(start_time, display_timezone))
+    }
+
+    async fn add_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_blood_sugar_measurement(measurement))
+    }
+
+    async fn remove_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_blood_sugar_measurement(measurement_id))
+    }
+
+    async fn update_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<(), DatabaseError> {
+        dispatch!(self, update_blood_sugar_measurement(measurement))
+    }
+
+    async fn get_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BloodSugarMeasurement>, DatabaseError> {
+        dispatch!(self, get_blood_sugar_measurements_since(start_time, display_timezone))
+    }
+
+    async fn add_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_long_term_blood_sugar_measurement(measurement))
+    }
+
+    async fn remove_long_term_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_long_term_blood_sugar_measurement(measurement_id))
+    }
+
+    async fn update_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<(), DatabaseError> {
+        dispatch!(self, update_long_term_blood_sugar_measurement(measurement))
+    }
+
+    async fn get_long_term_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<LongTermBloodSugarMeasurement>, DatabaseError> {
+        dispatch!(self, get_long_term_blood_sugar_measurements_since(start_time, display_timezone))
+    }
+
+    async fn add_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_peak_flow_measurement(measurement))
+    }
+
+    async fn remove_peak_flow_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_peak_flow_measurement(measurement_id))
+    }
+
+    async fn update_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<(), DatabaseError> {
+        dispatch!(self, update_peak_flow_measurement(measurement))
+    }
+
+    async fn get_peak_flow_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<PeakFlowMeasurement>, DatabaseError> {
+        dispatch!(self, get_peak_flow_measurements_since(start_time, display_timezone))
+    }
+
+    async fn add_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_ketone_measurement(measurement))
+    }
+
+    async fn remove_ketone_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_ketone_measurement(measurement_id))
+    }
+
+    async fn update_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<(), DatabaseError> {
+        dispatch!(self, update_ketone_measurement(measurement))
+    }
+
+    async fn get_ketone_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<KetoneMeasurement>, DatabaseError> {
+        dispatch!(self, get_ketone_measurements_since(start_time, display_timezone))
+    }
+
+    async fn add_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_lipid_panel_measurement(measurement))
+    }
+
+    async fn remove_lipid_panel_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_lipid_panel_measurement(measurement_id))
+    }
+
+    async fn update_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<(), DatabaseError> {
+        dispatch!(self, update_lipid_panel_measurement(measurement))
+    }
+
+    async fn get_lipid_panel_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<LipidPanelMeasurement>, DatabaseError> {
+        dispatch!(self, get_lipid_panel_measurements_since(start_time, display_timezone))
+    }
+
+    async fn add_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_custom_measurement(measurement))
+    }
+
+    async fn remove_custom_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_custom_measurement(measurement_id))
+    }
+
+    async fn update_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<(), DatabaseError> {
+        dispatch!(self, update_custom_measurement(measurement))
+    }
+
+    async fn get_custom_measurements_since(&self, metric: Option<&str>, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<CustomMeasurement>, DatabaseError> {
+        dispatch!(self, get_custom_measurements_since(metric, start_time, display_timezone))
+    }
+
+    async fn add_diary_entry(&self, entry: &DiaryEntry) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_diary_entry(entry))
+    }
+
+    async fn remove_diary_entry(&self, entry_id: i64) -> Result<(), DatabaseError> {
+        dispatch!(self, remove_diary_entry(entry_id))
+    }
+
+    async fn update_diary_entry(&self, entry: &DiaryEntry) -> Result<(), DatabaseError> {
+        dispatch!(self, update_diary_entry(entry))
+    }
+
+    async fn get_diary_entries_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<DiaryEntry>, DatabaseError> {
+        dispatch!(self, get_diary_entries_since(start_time, display_timezone))
+    }
+
+    async fn add_alert(&self, alert: &Alert) -> Result<i64, DatabaseError> {
+        dispatch!(self, add_alert(alert))
+    }
+
+    async fn get_alerts_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<Alert>, DatabaseError> {
+        dispatch!(self, get_alerts_since(start_time, display_timezone))
+    }
 }
 
-pub(crate) async fn get_recent_mass_measurements(ago: Duration) -> Result<Vec<BodyMassMeasurement>, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
 
-    let height_cm: Option<i32> = {
+/// Sets up the storage backend selected in the configuration.
+///
+/// The backend is only chosen once; changing it requires a restart.
+pub(crate) async fn init_storage() -> Result<(), DatabaseError> {
+    if STORAGE.get().is_some() {
+        return Ok(());
+    }
+
+    let backend = {
         let config_guard = CONFIG
-            .get().expect("initial config not set")
+            .get().expect("config not set")
             .read().await;
-        config_guard.height_cm
+        match config_guard.db_backend() {
+            DbBackend::Memory => {
+                if config_guard.db_conn_string == "memory:demo" {
+                    let now = in_display_timezone(&Utc::now(), config_guard.display_timezone);
+                    Backend::Memory(Box::new(MemoryStorage::with_demo_data(now)))
+                } else {
+                    Backend::Memory(Box::new(MemoryStorage::new()))
+                }
+            },
+            DbBackend::Postgres => Backend::Postgres(PostgresStorage::new(config_guard.db_conn_string.clone())),
+            DbBackend::Sqlite => Backend::Sqlite(SqliteStorage::open(config_guard.sqlite_path())?),
+        }
     };
-    let height_m = height_cm
-        .map(|h| Rational32::new(h, 100));
-    let square_height_m2 = height_m
-        .map(|h| h * h);
-
-    let start_time = Local::now() - ago;
-
-    let rows = client
-        .query(
-            "SELECT id, \"timestamp\", CAST(mass_kg AS character varying(128)) mass_kg, CAST(waist_circum_cm AS character varying(128)) waist_circum_cm FROM beepee.mass_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
-            &[&start_time],
-        )
-        .await?;
-    let mut ret = Vec::new();
-    for row in rows {
-        let mass_string: String = row.get(2);
-        let mass_kg: Rational32 = r32_from_decimal(&mass_string)
-            .expect("parsing mass failed");
-        let circum_string: Option<String> = row.get(3);
-        let circum_cm: Option<Rational32> = circum_string.map(|s|
-            r32_from_decimal(&s)
-                .expect("parsing circumference failed")
-        );
-        let bmi: Option<Rational32> = square_height_m2.map(|sqh|
-            mass_kg / sqh
-        );
-        ret.push(BodyMassMeasurement::new(
-            row.get(0),
-            row.get(1),
-            mass_kg,
-            circum_cm,
-            bmi,
-        ));
-    }
-
-    Ok(ret)
+    // if another task was quicker, its backend is kept
+    let _ = STORAGE.set(backend);
+    Ok(())
 }
 
-pub(crate) async fn add_temperature_location(loc: &BodyTemperatureLocation) -> Result<i64, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
-
-    let row = client
-        .query_one(
-            "INSERT INTO beepee.body_temperature_locations (\"name\") VALUES ($1) RETURNING id",
-            &[&loc.name],
-        )
-        .await?;
-    let loc_id: i64 = row.get(0);
+pub(crate) fn storage() -> &'static Backend {
+    STORAGE
+        .get().expect("storage not initialized")
+}
 
-    Ok(loc_id)
+fn start_time(ago: Duration) -> DateTime<Utc> {
+    Utc::now() - ago
 }
 
-pub(crate) async fn remove_temperature_location(loc_id: i64) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+/// The numeric and the categorical fields of a custom measurement.
+pub(crate) type CustomFields = (BTreeMap<String, Rational32>, BTreeMap<String, String>);
+
+/// Encodes the numeric and the categorical fields of a custom measurement as JSON objects, in which
+/// form the SQL backends store them.
+pub(crate) fn encode_custom_fields(measurement: &CustomMeasurement) -> (String, String) {
+    let values: BTreeMap<&String, String> = measurement.values.iter()
+        .map(|(k, v)| (k, rat32_to_string(v)))
+        .collect();
+    (
+        serde_json::to_string(&values).expect("failed to serialize custom values"),
+        serde_json::to_string(&measurement.categories).expect("failed to serialize custom categories"),
+    )
+}
 
-    client
-        .execute(
-            "DELETE FROM beepee.body_temperature_locations WHERE id = $1",
-            &[&loc_id],
-        )
-        .await?;
+/// Decodes the numeric and the categorical fields of a custom measurement from the JSON objects
+/// written by [`encode_custom_fields`].
+pub(crate) fn decode_custom_fields(values_json: &str, categories_json: &str) -> Result<CustomFields, DatabaseError> {
+    let value_strings: BTreeMap<String, String> = serde_json::from_str(values_json)
+        .map_err(|e| DatabaseError::InvalidValue("values", e.to_string()))?;
+    let mut values = BTreeMap::new();
+    for (key, value_string) in value_strings {
+        let value = string_to_rat32(&value_string)
+            .map_err(|e| DatabaseError::InvalidValue("values", e))?;
+        values.insert(key, value);
+    }
+    let categories = serde_json::from_str(categories_json)
+        .map_err(|e| DatabaseError::InvalidValue("categories", e.to_string()))?;
+    Ok((values, categories))
+}
 
-    Ok(())
+pub(crate) async fn add_blood_pressure_measurement(measurement: &BloodPressureMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_blood_pressure_measurement(measurement)
+        .await
 }
 
-pub(crate) async fn update_temperature_location(loc: &BodyTemperatureLocation) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn remove_blood_pressure_measurement(measurement_id: i64) -> Result<(), DatabaseError> {
+    storage().remove_blood_pressure_measurement(measurement_id)
+        .await
+}
 
-    client
-        .execute(
-            "UPDATE beepee.body_temperature_locations SET \"name\"=$1 WHERE id=$2",
-            &[&loc.name, &loc.id],
-        )
-        .await?;
+pub(crate) async fn update_blood_pressure_measurement(measurement: &BloodPressureMeasurement) -> Result<(), DatabaseError> {
+    storage().update_blood_pressure_measurement(measurement)
+        .await
+}
 
-    Ok(())
+pub(crate) async fn get_recent_blood_pressure_measurements(ago: Duration) -> Result<Vec<BloodPressureMeasurement>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_blood_pressure_measurements_since(start_time(ago), display_timezone)
+        .await
 }
 
-pub(crate) async fn get_temperature_locations() -> Result<Vec<BodyTemperatureLocation>, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn add_mass_measurement(measurement: &BodyMassMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_mass_measurement(measurement)
+        .await
+}
 
-    let rows = client
-        .query(
-            "SELECT id, \"name\" FROM beepee.body_temperature_locations ORDER BY \"name\"",
-            &[],
-        )
-        .await?;
-    let mut ret = Vec::new();
-    for row in rows {
-        ret.push(BodyTemperatureLocation::new(
-            row.get(0),
-            row.get(1),
-        ));
-    }
+pub(crate) async fn remove_mass_measurement(measurement_id: i64) -> Result<(), DatabaseError> {
+    storage().remove_mass_measurement(measurement_id)
+        .await
+}
 
-    Ok(ret)
+pub(crate) async fn update_mass_measurement(measurement: &BodyMassMeasurement) -> Result<(), DatabaseError> {
+    storage().update_mass_measurement(measurement)
+        .await
 }
 
-pub(crate) async fn add_temperature_measurement(measurement: &BodyTemperatureMeasurement) -> Result<i64, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn get_recent_mass_measurements(ago: Duration) -> Result<Vec<BodyMassMeasurement>, DatabaseError> {
+    let (fallback_height_cm, sex) = {
+        let config_guard = CONFIG
+            .get().expect("initial config not set")
+            .read().await;
+        (config_guard.height_cm, config_guard.sex)
+    };
 
-    let row = client
-        .query_one(
-            "INSERT INTO beepee.body_temperature_measurements (\"timestamp\", location_id, temperature_celsius) VALUES ($1, $2, (CAST(CAST($3 AS int) AS numeric(6, 2)) / CAST(CAST($4 AS int) AS numeric(6, 2)))) RETURNING id",
-            &[&measurement.timestamp, &measurement.location_id, &measurement.temperature_celsius.numer(), &measurement.temperature_celsius.denom()],
-        )
+    let display_timezone = get_display_timezone()
+        .await;
+    let heights = storage().get_height_measurements(display_timezone)
+        .await?;
+    let mut ret = storage().get_mass_measurements_since(start_time(ago), display_timezone)
         .await?;
-    let measurement_id: i64 = row.get(0);
+    anthropometrics::normalize_measurements(&mut ret, &heights, fallback_height_cm, sex);
 
-    Ok(measurement_id)
+    Ok(ret)
 }
 
-pub(crate) async fn remove_temperature_measurement(measurement_id: i64) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn add_height_measurement(measurement: &HeightMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_height_measurement(measurement)
+        .await
+}
 
-    client
-        .execute(
-            "DELETE FROM beepee.body_temperature_measurements WHERE id = $1",
-            &[&measurement_id],
-        )
-        .await?;
+pub(crate) async fn remove_height_measurement(measurement_id: i64) -> Result<(), DatabaseError> {
+    storage().remove_height_measurement(measurement_id)
+        .await
+}
 
-    Ok(())
+pub(crate) async fn get_height_measurements() -> Result<Vec<HeightMeasurement>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_height_measurements(display_timezone)
+        .await
 }
 
-pub(crate) async fn update_temperature_measurement(measurement: &BodyTemperatureMeasurement) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn add_temperature_location(loc: &BodyTemperatureLocation) -> Result<i64, DatabaseError> {
+    storage().add_temperature_location(loc)
+        .await
+}
 
-    client
-        .execute(
-            "UPDATE beepee.body_temperature_measurements SET \"timestamp\"=$1, location_id=$2, temperature_celsius=(CAST(CAST($3 AS int) AS numeric(6, 2)) / CAST(CAST($4 AS int) AS numeric(6, 2))) WHERE id=$5",
-            &[&measurement.timestamp, &measurement.location_id, &measurement.temperature_celsius.numer(), &measurement.temperature_celsius.denom(), &measurement.id],
-        )
-        .await?;
+pub(crate) async fn remove_temperature_location(loc_id: i64) -> Result<(), DatabaseError> {
+    storage().remove_temperature_location(loc_id)
+        .await
+}
 
-    Ok(())
+pub(crate) async fn update_temperature_location(loc: &BodyTemperatureLocation) -> Result<(), DatabaseError> {
+    storage().update_temperature_location(loc)
+        .await
 }
 
-pub(crate) async fn get_recent_temperature_measurements(ago: Duration) -> Result<Vec<BodyTemperatureMeasurement>, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn get_temperature_locations() -> Result<Vec<BodyTemperatureLocation>, DatabaseError> {
+    storage().get_temperature_locations()
+        .await
+}
 
-    let start_time = Local::now() - ago;
+pub(crate) async fn add_temperature_measurement(measurement: &BodyTemperatureMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_temperature_measurement(measurement)
+        .await
+}
 
-    let rows = client
-        .query(
-            "SELECT id, \"timestamp\", location_id, CAST(temperature_celsius AS character varying(128)) temperature_celsius FROM beepee.body_temperature_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
-            &[&start_time],
-        )
-        .await?;
-    let mut ret = Vec::new();
-    for row in rows {
-        let temperature_string: String = row.get(3);
-        let temperature_celsius: Rational32 = r32_from_decimal(&temperature_string)
-            .expect("parsing temperature failed");
-        ret.push(BodyTemperatureMeasurement::new(
-            row.get(0),
-            row.get(1),
-            row.get(2),
-            temperature_celsius,
-        ));
-    }
+pub(crate) async fn remove_temperature_measurement(measurement_id: i64) -> Result<(), DatabaseError> {
+    storage().remove_temperature_measurement(measurement_id)
+        .await
+}
 
-    Ok(ret)
+pub(crate) async fn update_temperature_measurement(measurement: &BodyTemperatureMeasurement) -> Result<(), DatabaseError> {
+    storage().update_temperature_measurement(measurement)
+        .await
 }
 
-pub(crate) async fn add_blood_sugar_measurement(measurement: &BloodSugarMeasurement) -> Result<i64, tokio_postgres::Error> {
-    let client = connect()
+pub(crate) async fn get_recent_temperature_measurements(ago: Duration) -> Result<Vec<BodyTemperatureMeasurement>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    let locations = storage().get_temperature_locations()
         .await?;
-
-    let row = client
-        .query_one(
-            "INSERT INTO beepee.blood_sugar_measurements (\"timestamp\", sugar_mmol_per_l) VALUES ($1, (CAST(CAST($2 AS int) AS numeric(6, 2)) / CAST(CAST($3 AS int) AS numeric(6, 2)))) RETURNING id",
-            &[&measurement.timestamp, &measurement.sugar_mmol_per_l.numer(), &measurement.sugar_mmol_per_l.denom()],
-        )
+    let mut ret = storage().get_temperature_measurements_since(start_time(ago), display_timezone)
         .await?;
-    let measurement_id: i64 = row.get(0);
+    normalize_measurements(&mut ret, &locations);
+    Ok(ret)
+}
 
-    Ok(measurement_id)
+pub(crate) async fn add_blood_sugar_measurement(measurement: &BloodSugarMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_blood_sugar_measurement(measurement)
+        .await
 }
 
-pub(crate) async fn remove_blood_sugar_measurement(measurement_id: i64) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn remove_blood_sugar_measurement(measurement_id: i64) -> Result<(), DatabaseError> {
+    storage().remove_blood_sugar_measurement(measurement_id)
+        .await
+}
 
-    client
-        .execute(
-            "DELETE FROM beepee.blood_sugar_measurements WHERE id = $1",
-            &[&measurement_id],
-        )
-        .await?;
+pub(crate) async fn update_blood_sugar_measurement(measurement: &BloodSugarMeasurement) -> Result<(), DatabaseError> {
+    storage().update_blood_sugar_measurement(measurement)
+        .await
+}
 
-    Ok(())
+pub(crate) async fn get_recent_blood_sugar_measurements(ago: Duration) -> Result<Vec<BloodSugarMeasurement>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_blood_sugar_measurements_since(start_time(ago), display_timezone)
+        .await
 }
 
-pub(crate) async fn update_blood_sugar_measurement(measurement: &BloodSugarMeasurement) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn add_long_term_blood_sugar_measurement(measurement: &LongTermBloodSugarMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_long_term_blood_sugar_measurement(measurement)
+        .await
+}
 
-    client
-        .execute(
-            "UPDATE beepee.blood_sugar_measurements SET \"timestamp\"=$1, sugar_mmol_per_l=(CAST(CAST($2 AS int) AS numeric(6, 2)) / CAST(CAST($3 AS int) AS numeric(6, 2))) WHERE id=$4",
-            &[&measurement.timestamp, &measurement.sugar_mmol_per_l.numer(), &measurement.sugar_mmol_per_l.denom(), &measurement.id],
-        )
-        .await?;
+pub(crate) async fn remove_long_term_blood_sugar_measurement(measurement_id: i64) -> Result<(), DatabaseError> {
+    storage().remove_long_term_blood_sugar_measurement(measurement_id)
+        .await
+}
 
-    Ok(())
+pub(crate) async fn update_long_term_blood_sugar_measurement(measurement: &LongTermBloodSugarMeasurement) -> Result<(), DatabaseError> {
+    storage().update_long_term_blood_sugar_measurement(measurement)
+        .await
 }
 
-pub(crate) async fn get_recent_blood_sugar_measurements(ago: Duration) -> Result<Vec<BloodSugarMeasurement>, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn get_recent_long_term_blood_sugar_measurements(ago: Duration) -> Result<Vec<LongTermBloodSugarMeasurement>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_long_term_blood_sugar_measurements_since(start_time(ago), display_timezone)
+        .await
+}
 
-    let start_time = Local::now() - ago;
+pub(crate) async fn add_peak_flow_measurement(measurement: &PeakFlowMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_peak_flow_measurement(measurement)
+        .await
+}
 
-    let rows = client
-        .query(
-            "SELECT id, \"timestamp\", CAST(sugar_mmol_per_l AS character varying(128)) sugar_mmol_per_l FROM beepee.blood_sugar_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
-            &[&start_time],
-        )
-        .await?;
-    let mut ret = Vec::new();
-    for row in rows {
-        let temperature_string: String = row.get(2);
-        let temperature_celsius: Rational32 = r32_from_decimal(&temperature_string)
-            .expect("parsing temperature failed");
-        ret.push(BloodSugarMeasurement::new(
-            row.get(0),
-            row.get(1),
-            temperature_celsius,
-        ));
-    }
+pub(crate) async fn get_recent_peak_flow_measurements(ago: Duration) -> Result<Vec<PeakFlowMeasurement>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_peak_flow_measurements_since(start_time(ago), display_timezone)
+        .await
+}
 
-    Ok(ret)
+pub(crate) async fn add_ketone_measurement(measurement: &KetoneMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_ketone_measurement(measurement)
+        .await
 }
 
-pub(crate) async fn add_long_term_blood_sugar_measurement(measurement: &LongTermBloodSugarMeasurement) -> Result<i64, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn get_recent_ketone_measurements(ago: Duration) -> Result<Vec<KetoneMeasurement>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_ketone_measurements_since(start_time(ago), display_timezone)
+        .await
+}
 
-    let row = client
-        .query_one(
-            "INSERT INTO beepee.long_term_blood_sugar_measurements (\"timestamp\", hba1c_mmol_per_mol) VALUES ($1, (CAST(CAST($2 AS int) AS numeric(6, 2)) / CAST(CAST($3 AS int) AS numeric(6, 2)))) RETURNING id",
-            &[&measurement.timestamp, &measurement.hba1c_mmol_per_mol.numer(), &measurement.hba1c_mmol_per_mol.denom()],
-        )
-        .await?;
-    let measurement_id: i64 = row.get(0);
+pub(crate) async fn add_lipid_panel_measurement(measurement: &LipidPanelMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_lipid_panel_measurement(measurement)
+        .await
+}
 
-    Ok(measurement_id)
+pub(crate) async fn get_recent_lipid_panel_measurements(ago: Duration) -> Result<Vec<LipidPanelMeasurement>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_lipid_panel_measurements_since(start_time(ago), display_timezone)
+        .await
 }
 
-pub(crate) async fn remove_long_term_blood_sugar_measurement(measurement_id: i64) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn add_custom_measurement(measurement: &CustomMeasurement) -> Result<i64, DatabaseError> {
+    storage().add_custom_measurement(measurement)
+        .await
+}
 
-    client
-        .execute(
-            "DELETE FROM beepee.long_term_blood_sugar_measurements WHERE id = $1",
-            &[&measurement_id],
-        )
-        .await?;
+pub(crate) async fn get_recent_custom_measurements(metric: Option<&str>, ago: Duration) -> Result<Vec<CustomMeasurement>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_custom_measurements_since(metric, start_time(ago), display_timezone)
+        .await
+}
 
-    Ok(())
+pub(crate) async fn add_diary_entry(entry: &DiaryEntry) -> Result<i64, DatabaseError> {
+    storage().add_diary_entry(entry)
+        .await
 }
 
-pub(crate) async fn update_long_term_blood_sugar_measurement(measurement: &LongTermBloodSugarMeasurement) -> Result<(), tokio_postgres::Error> {
-    let client = connect()
-        .await?;
+pub(crate) async fn get_recent_diary_entries(ago: Duration) -> Result<Vec<DiaryEntry>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_diary_entries_since(start_time(ago), display_timezone)
+        .await
+}
 
-    client
-        .execute(
-            "UPDATE beepee.long_term_blood_sugar_measurements SET \"timestamp\"=$1, hba1c_mmol_per_mol=(CAST(CAST($2 AS int) AS numeric(6, 2)) / CAST(CAST($3 AS int) AS numeric(6, 2))) WHERE id=$4",
-            &[&measurement.timestamp, &measurement.hba1c_mmol_per_mol.numer(), &measurement.hba1c_mmol_per_mol.denom(), &measurement.id],
-        )
-        .await?;
+pub(crate) async fn add_alert(alert: &Alert) -> Result<i64, DatabaseError> {
+    storage().add_alert(alert)
+        .await
+}
 
-    Ok(())
+pub(crate) async fn get_recent_alerts(ago: Duration) -> Result<Vec<Alert>, DatabaseError> {
+    let display_timezone = get_display_timezone()
+        .await;
+    storage().get_alerts_since(start_time(ago), display_timezone)
+        .await
 }
 
-pub(crate) async fn get_recent_long_term_blood_sugar_measurements(ago: Duration) -> Result<Vec<LongTermBloodSugarMeasurement>, tokio_postgres::Error> {
-    let client = connect()
-        .await?;
 
-    let start_time = Local::now() - ago;
+/// A kind of measurement that can be stored and retrieved through the functions above.
+///
+/// The futures are `Send` so that generic callers can spawn them.
+pub(crate) trait StoredMeasurement: Measurement + Send + Sync + 'static {
+    fn add(&self) -> impl Future<Output = Result<i64, DatabaseError>> + Send;
+    fn get_recent(ago: Duration) -> impl Future<Output = Result<Vec<Self>, DatabaseError>> + Send;
+}
 
-    let rows = client
-        .query(
-            "SELECT id, \"timestamp\", CAST(hba1c_mmol_per_mol AS character varying(128)) hba1c_mmol_per_mol FROM beepee.long_term_blood_sugar_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
-            &[&start_time],
-        )
-        .await?;
-    let mut ret = Vec::new();
-    for row in rows {
-        let hba1c_mmol_per_mol_string: String = row.get(2);
-        let hba1c_mmol_per_mol: Rational32 = r32_from_decimal(&hba1c_mmol_per_mol_string)
-            .expect("parsing temperature failed");
-        ret.push(LongTermBloodSugarMeasurement::new(
-            row.get(0),
-            row.get(1),
-            hba1c_mmol_per_mol,
-        ));
-    }
+macro_rules! impl_stored_measurement {
+    ($type:ty, $add:ident, $get_recent:ident) => {
+        impl StoredMeasurement for $type {
+            async fn add(&self) -> Result<i64, DatabaseError> {
+                $add(self).await
+            }
 
-    Ok(ret)
+            async fn get_recent(ago: Duration) -> Result<Vec<Self>, DatabaseError> {
+                $get_recent(ago).await
+            }
+        }
+    };
 }
+
+impl_stored_measurement!(BloodPressureMeasurement, add_blood_pressure_measurement, get_recent_blood_pressure_measurements);
+impl_stored_measurement!(BodyMassMeasurement, add_mass_measurement, get_recent_mass_measurements);
+impl_stored_measurement!(BodyTemperatureMeasurement, add_temperature_measurement, get_recent_temperature_measurements);
+impl_stored_measurement!(BloodSugarMeasurement, add_blood_sugar_measurement, get_recent_blood_sugar_measurements);
+impl_stored_measurement!(LongTermBloodSugarMeasurement, add_long_term_blood_sugar_measurement, get_recent_long_term_blood_sugar_measurements);
+impl_stored_measurement!(PeakFlowMeasurement, add_peak_flow_measurement, get_recent_peak_flow_measurements);
+impl_stored_measurement!(KetoneMeasurement, add_ketone_measurement, get_recent_ketone_measurements);
+impl_stored_measurement!(LipidPanelMeasurement, add_lipid_panel_measurement, get_recent_lipid_panel_measurements);
@@ -0,0 +1,631 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, Utc};
+use chrono_tz::Tz;
+use num_rational::Rational32;
+
+use crate::database::{DatabaseError, Storage};
+use crate::migrations::{MIGRATIONS, Migration};
+use crate::model::{
+    Alert, BloodPressureMeasurement, BloodSugarMeasurement, BodyComposition, BodyMassMeasurement, BodyTemperatureLocation,
+    BodyTemperatureMeasurement, CustomMeasurement, DiaryEntry, DiarySymptoms, HeightMeasurement, KetoneMeasurement,
+    LipidPanelMeasurement, LongTermBloodSugarMeasurement, PeakFlowMeasurement,
+};
+
+
+/// A value that is stored in a table of the in-memory storage.
+trait Row: Clone {
+    fn id(&self) -> i64;
+    fn set_id(&mut self, id: i64);
+    fn timestamp(&self) -> DateTime<FixedOffset>;
+}
+macro_rules! impl_row {
+    ($type:ty) => {
+        impl Row for $type {
+            fn id(&self) -> i64 { self.id }
+            fn set_id(&mut self, id: i64) { self.id = id; }
+            fn timestamp(&self) -> DateTime<FixedOffset> { self.timestamp }
+        }
+    };
+}
+impl_row!(BloodPressureMeasurement);
+impl_row!(BodyMassMeasurement);
+impl_row!(HeightMeasurement);
+impl_row!(BodyTemperatureMeasurement);
+impl_row!(BloodSugarMeasurement);
+impl_row!(LongTermBloodSugarMeasurement);
+impl_row!(PeakFlowMeasurement);
+impl_row!(KetoneMeasurement);
+impl_row!(LipidPanelMeasurement);
+impl_row!(CustomMeasurement);
+impl_row!(DiaryEntry);
+impl_row!(Alert);
+
+
+struct Table<T: Row> {
+    last_id: i64,
+    rows: Vec<T>,
+}
+impl<T: Row> Table<T> {
+    fn insert(&mut self, row: &T) -> i64 {
+        self.last_id += 1;
+        let mut row = row.clone();
+        row.set_id(self.last_id);
+        self.rows.push(row);
+        self.last_id
+    }
+
+    fn remove(&mut self, id: i64) {
+        self.rows.retain(|r| r.id() != id);
+    }
+
+    fn update(&mut self, row: &T) {
+        if let Some(stored) = self.rows.iter_mut().find(|r| r.id() == row.id()) {
+            *stored = row.clone();
+        }
+    }
+
+    fn since(&self, start_time: DateTime<Utc>) -> Vec<T> {
+        let mut ret: Vec<T> = self.rows.iter()
+            .filter(|r| r.timestamp() >= start_time)
+            .cloned()
+            .collect();
+        ret.sort_by_key(|r| r.timestamp());
+        ret
+    }
+}
+impl<T: Row> Default for Table<T> {
+    fn default() -> Self {
+        Self {
+            last_id: 0,
+            rows: Vec::new(),
+        }
+    }
+}
+
+
+#[derive(Default)]
+struct Tables {
+    blood_pressure: Table<BloodPressureMeasurement>,
+    mass: Table<BodyMassMeasurement>,
+    heights: Table<HeightMeasurement>,
+    temperature_locations: Vec<BodyTemperatureLocation>,
+    last_temperature_location_id: i64,
+    temperature: Table<BodyTemperatureMeasurement>,
+    blood_sugar: Table<BloodSugarMeasurement>,
+    long_term_blood_sugar: Table<LongTermBloodSugarMeasurement>,
+    peak_flow: Table<PeakFlowMeasurement>,
+    ketones: Table<KetoneMeasurement>,
+    lipid_panels: Table<LipidPanelMeasurement>,
+    custom: Table<CustomMeasurement>,
+    diary: Table<DiaryEntry>,
+    alerts: Table<Alert>,
+}
+impl Tables {
+    fn location_exists(&self, loc_id: i64) -> Result<(), DatabaseError> {
+        if self.temperature_locations.iter().any(|l| l.id == loc_id) {
+            Ok(())
+        } else {
+            Err(DatabaseError::InvalidValue("location_id", format!("no temperature location with ID {}", loc_id)))
+        }
+    }
+}
+
+
+/// Keeps measurements in memory. Everything is lost when the process exits.
+///
+/// Meant for tests and demonstrations; timestamps always keep the UTC offset at which they were
+/// recorded and the schema is always up to date.
+#[derive(Default)]
+pub(crate) struct MemoryStorage {
+    tables: Mutex<Tables>,
+}
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a storage filled with 90 days of plausible-looking fake measurements up to `now`.
+    pub fn with_demo_data(now: DateTime<FixedOffset>) -> Self {
+        let storage = Self::new();
+        {
+            let mut tables = storage.lock();
+            let mut random = DemoRandom(0x5eed_beef);
+            let offset = *now.offset();
+            let at = |days_ago: i64, hour: u32, minute: u32| -> Option<DateTime<FixedOffset>> {
+                let date = (now - Duration::days(days_ago)).date_naive();
+                let time = NaiveTime::from_hms_opt(hour, minute, 0)?;
+                let timestamp = date.and_time(time).and_local_timezone(offset).single()?;
+                (timestamp <= now).then_some(timestamp)
+            };
+
+            // offsets and normal ranges in tenths of a degree, as in db/sample_body_temperature_locations.sql
+            let locations = [
+                ("rectum", -5, 366, 380),
+                ("mouth", 0, 355, 375),
+                ("armpit", 5, 347, 373),
+                ("ear", 0, 358, 380),
+                ("forehead", 0, 358, 375),
+            ];
+            for (name, core_offset, normal_min, normal_max) in locations {
+                tables.last_temperature_location_id += 1;
+                let id = tables.last_temperature_location_id;
+                tables.temperature_locations.push(BodyTemperatureLocation::new(
+                    id,
+                    name.to_owned(),
+                    Some(Rational32::new(core_offset, 10)),
+                    Some(Rational32::new(normal_min, 10)),
+                    Some(Rational32::new(normal_max, 10)),
+                ));
+            }
+
+            for days_ago in (0..90).rev() {
+                for (hour, minute) in [(7, 30), (21, 15)] {
+                    if let Some(timestamp) = at(days_ago, hour, minute) {
+                        let spo2_percent = (days_ago % 3 == 0).then(|| random.around(97, 1).min(100));
+                        tables.blood_pressure.insert(&BloodPressureMeasurement::new(
+                            -1,
+                            timestamp,
+                            random.around(128 + (days_ago / 15) as i32, 9),
+                            random.around(82, 6),
+                            random.around(66, 8),
+                            spo2_percent,
+                        ));
+                    }
+                }
+
+                if let Some(timestamp) = at(days_ago, 7, 0) {
+                    if days_ago % 3 == 0 {
+                        let waist_circum_cm = (days_ago % 9 == 0).then(|| Rational32::new(random.around(950, 10), 10));
+                        let hip_circum_cm = (days_ago % 9 == 0).then(|| Rational32::new(random.around(1020, 10), 10));
+                        // as if weighed on a body composition scale
+                        let composition = BodyComposition {
+                            body_fat_percent: Some(Rational32::new(random.around(240, 6), 10)),
+                            muscle_mass_kg: Some(Rational32::new(random.around(590, 5), 10)),
+                            visceral_fat_rating: Some(Rational32::new(random.around(10, 1), 1)),
+                            body_water_percent: Some(Rational32::new(random.around(550, 6), 10)),
+                        };
+                        tables.mass.insert(&BodyMassMeasurement::new(
+                            -1,
+                            timestamp,
+                            Rational32::new(random.around(820 + (days_ago / 10) as i32, 4), 10),
+                            waist_circum_cm,
+                            hip_circum_cm,
+                            composition,
+                            None,
+                        ));
+                    }
+
+                    // a short fever episode three weeks ago
+                    let fever = (19..=23).contains(&days_ago);
+                    let temperature_tenths = if fever { random.around(386, 4) } else { random.around(366, 2) };
+                    tables.temperature.insert(&BodyTemperatureMeasurement::new(
+                        -1,
+                        timestamp,
+                        2,
+                        Rational32::new(temperature_tenths, 10),
+                        None,
+                        None,
+                    ));
+
+                    tables.blood_sugar.insert(&BloodSugarMeasurement::new(
+                        -1,
+                        timestamp,
+                        Rational32::new(random.around(54, 6), 10),
+                    ));
+                }
+
+                if days_ago % 30 == 0 {
+                    if let Some(timestamp) = at(days_ago, 9, 0) {
+                        tables.long_term_blood_sugar.insert(&LongTermBloodSugarMeasurement::new(
+                            -1,
+                            timestamp,
+                            Rational32::new(random.around(390, 20), 10),
+                        ));
+                    }
+                }
+
+                if let Some(timestamp) = at(days_ago, 8, 0) {
+                    if days_ago % 2 == 0 {
+                        tables.peak_flow.insert(&PeakFlowMeasurement::new(
+                            -1,
+                            timestamp,
+                            random.around(520, 30),
+                        ));
+                    }
+
+                    if days_ago % 7 == 0 {
+                        tables.ketones.insert(&KetoneMeasurement::new(
+                            -1,
+                            timestamp,
+                            Rational32::new(random.around(3, 2), 10),
+                        ));
+                    }
+                }
+
+                if days_ago % 45 == 0 {
+                    if let Some(timestamp) = at(days_ago, 9, 0) {
+                        tables.lipid_panels.insert(&LipidPanelMeasurement::new(
+                            -1,
+                            timestamp,
+                            Some(Rational32::new(random.around(520, 20), 100)),
+                            Some(Rational32::new(random.around(330, 20), 100)),
+                            Some(Rational32::new(random.around(130, 10), 100)),
+                            Some(Rational32::new(random.around(150, 20), 100)),
+                        ));
+                    }
+                }
+
+                // a weekly diary entry, and a daily one during the fever episode
+                let fever = (19..=23).contains(&days_ago);
+                if fever || days_ago % 7 == 3 {
+                    if let Some(timestamp) = at(days_ago, 7, 45) {
+                        let symptoms = DiarySymptoms {
+                            headache: fever,
+                            dizziness: days_ago == 21,
+                            ..DiarySymptoms::default()
+                        };
+                        let notes = if fever { "feverish, stayed in bed" } else { "" };
+                        tables.diary.insert(&DiaryEntry::new(
+                            -1,
+                            timestamp,
+                            symptoms,
+                            Some(if fever { 3 } else { 7 }),
+                            notes.to_owned(),
+                        ));
+                    }
+                }
+            }
+        }
+        storage
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Tables> {
+        self.tables
+            .lock().expect("in-memory storage lock poisoned")
+    }
+}
+
+
+/// A small deterministic pseudo-random number generator (xorshift), so that demo data is the same on
+/// every start.
+struct DemoRandom(u64);
+impl DemoRandom {
+    /// Returns a value that is at most `spread` away from `center`.
+    fn around(&mut self, center: i32, spread: i32) -> i32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        let width = u64::try_from(2 * spread + 1).expect("negative spread");
+        let offset = i32::try_from(self.0 % width).expect("offset too large");
+        center - spread + offset
+    }
+}
+
+
+impl Storage for MemoryStorage {
+    async fn get_applied_migration_versions(&self) -> Result<Vec<i32>, DatabaseError> {
+        Ok(MIGRATIONS.iter().map(|m| m.version).collect())
+    }
+
+    async fn apply_migration(&self, _migration: &Migration) -> Result<bool, DatabaseError> {
+        Ok(false)
+    }
+
+    async fn add_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<i64, DatabaseError> {
+        Ok(self.lock().blood_pressure.insert(measurement))
+    }
+
+    async fn remove_blood_pressure_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().blood_pressure.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn update_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<(), DatabaseError> {
+        self.lock().blood_pressure.update(measurement);
+        Ok(())
+    }
+
+    async fn get_blood_pressure_measurements_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<BloodPressureMeasurement>, DatabaseError> {
+        Ok(self.lock().blood_pressure.since(start_time))
+    }
+
+    async fn add_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<i64, DatabaseError> {
+        let mut measurement = *measurement;
+        measurement.bmi = None;
+        measurement.waist_to_hip_ratio = None;
+        measurement.waist_to_hip_risk = None;
+        measurement.waist_to_height_ratio = None;
+        measurement.waist_to_height_risk = None;
+        Ok(self.lock().mass.insert(&measurement))
+    }
+
+    async fn remove_mass_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().mass.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn update_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<(), DatabaseError> {
+        let mut measurement = *measurement;
+        measurement.bmi = None;
+        measurement.waist_to_hip_ratio = None;
+        measurement.waist_to_hip_risk = None;
+        measurement.waist_to_height_ratio = None;
+        measurement.waist_to_height_risk = None;
+        self.lock().mass.update(&measurement);
+        Ok(())
+    }
+
+    async fn get_mass_measurements_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<BodyMassMeasurement>, DatabaseError> {
+        Ok(self.lock().mass.since(start_time))
+    }
+
+    async fn add_height_measurement(&self, measurement: &HeightMeasurement) -> Result<i64, DatabaseError> {
+        Ok(self.lock().heights.insert(measurement))
+    }
+
+    async fn remove_height_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().heights.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn get_height_measurements(&self, _display_timezone: Option<Tz>) -> Result<Vec<HeightMeasurement>, DatabaseError> {
+        Ok(self.lock().heights.since(DateTime::<Utc>::MIN_UTC))
+    }
+
+    async fn add_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<i64, DatabaseError> {
+        let mut tables = self.lock();
+        tables.last_temperature_location_id += 1;
+        let loc_id = tables.last_temperature_location_id;
+        let mut loc = loc.clone();
+        loc.id = loc_id;
+        tables.temperature_locations.push(loc);
+        Ok(loc_id)
+    }
+
+    async fn remove_temperature_location(&self, loc_id: i64) -> Result<(), DatabaseError> {
+        let mut tables = self.lock();
+        if tables.temperature.rows.iter().any(|m| m.location_id == loc_id) {
+            return Err(DatabaseError::StillReferenced("temperature location", loc_id));
+        }
+        tables.temperature_locations.retain(|l| l.id != loc_id);
+        Ok(())
+    }
+
+    async fn update_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<(), DatabaseError> {
+        let mut tables = self.lock();
+        if let Some(stored) = tables.temperature_locations.iter_mut().find(|l| l.id == loc.id) {
+            *stored = loc.clone();
+        }
+        Ok(())
+    }
+
+    async fn get_temperature_locations(&self) -> Result<Vec<BodyTemperatureLocation>, DatabaseError> {
+        let mut locations = self.lock().temperature_locations.clone();
+        locations.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(locations)
+    }
+
+    async fn add_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<i64, DatabaseError> {
+        let mut measurement = *measurement;
+        measurement.core_temperature_celsius = None;
+        measurement.status = None;
+        let mut tables = self.lock();
+        tables.location_exists(measurement.location_id)?;
+        Ok(tables.temperature.insert(&measurement))
+    }
+
+    async fn remove_temperature_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().temperature.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn update_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<(), DatabaseError> {
+        let mut measurement = *measurement;
+        measurement.core_temperature_celsius = None;
+        measurement.status = None;
+        let mut tables = self.lock();
+        tables.location_exists(measurement.location_id)?;
+        tables.temperature.update(&measurement);
+        Ok(())
+    }
+
+    async fn get_temperature_measurements_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<BodyTemperatureMeasurement>, DatabaseError> {
+        Ok(self.lock().temperature.since(start_time))
+    }
+
+    async fn add_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<i64, DatabaseError> {
+        Ok(self.lock().blood_sugar.insert(measurement))
+    }
+
+    async fn remove_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().blood_sugar.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn update_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<(), DatabaseError> {
+        self.lock().blood_sugar.update(measurement);
+        Ok(())
+    }
+
+    async fn get_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<BloodSugarMeasurement>, DatabaseError> {
+        Ok(self.lock().blood_sugar.since(start_time))
+    }
+
+    async fn add_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<i64, DatabaseError> {
+        Ok(self.lock().long_term_blood_sugar.insert(measurement))
+    }
+
+    async fn remove_long_term_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().long_term_blood_sugar.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn update_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<(), DatabaseError> {
+        self.lock().long_term_blood_sugar.update(measurement);
+        Ok(())
+    }
+
+    async fn get_long_term_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<LongTermBloodSugarMeasurement>, DatabaseError> {
+        Ok(self.lock().long_term_blood_sugar.since(start_time))
+    }
+
+    async fn add_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<i64, DatabaseError> {
+        Ok(self.lock().peak_flow.insert(measurement))
+    }
+
+    async fn remove_peak_flow_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().peak_flow.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn update_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<(), DatabaseError> {
+        self.lock().peak_flow.update(measurement);
+        Ok(())
+    }
+
+    async fn get_peak_flow_measurements_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<PeakFlowMeasurement>, DatabaseError> {
+        Ok(self.lock().peak_flow.since(start_time))
+    }
+
+    async fn add_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<i64, DatabaseError> {
+        Ok(self.lock().ketones.insert(measurement))
+    }
+
+    async fn remove_ketone_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().ketones.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn update_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<(), DatabaseError> {
+        self.lock().ketones.update(measurement);
+        Ok(())
+    }
+
+    async fn get_ketone_measurements_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<KetoneMeasurement>, DatabaseError> {
+        Ok(self.lock().ketones.since(start_time))
+    }
+
+    async fn add_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<i64, DatabaseError> {
+        Ok(self.lock().lipid_panels.insert(measurement))
+    }
+
+    async fn remove_lipid_panel_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().lipid_panels.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn update_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<(), DatabaseError> {
+        self.lock().lipid_panels.update(measurement);
+        Ok(())
+    }
+
+    async fn get_lipid_panel_measurements_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<LipidPanelMeasurement>, DatabaseError> {
+        Ok(self.lock().lipid_panels.since(start_time))
+    }
+
+    async fn add_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<i64, DatabaseError> {
+        Ok(self.lock().custom.insert(measurement))
+    }
+
+    async fn remove_custom_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.lock().custom.remove(measurement_id);
+        Ok(())
+    }
+
+    async fn update_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<(), DatabaseError> {
+        self.lock().custom.update(measurement);
+        Ok(())
+    }
+
+    async fn get_custom_measurements_since(&self, metric: Option<&str>, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<CustomMeasurement>, DatabaseError> {
+        let mut ret = self.lock().custom.since(start_time);
+        if let Some(metric) = metric {
+            ret.retain(|m| m.metric == metric);
+        }
+        Ok(ret)
+    }
+
+    async fn add_diary_entry(&self, entry: &DiaryEntry) -> Result<i64, DatabaseError> {
+        Ok(self.lock().diary.insert(entry))
+    }
+
+    async fn remove_diary_entry(&self, entry_id: i64) -> Result<(), DatabaseError> {
+        self.lock().diary.remove(entry_id);
+        Ok(())
+    }
+
+    async fn update_diary_entry(&self, entry: &DiaryEntry) -> Result<(), DatabaseError> {
+        self.lock().diary.update(entry);
+        Ok(())
+    }
+
+    async fn get_diary_entries_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<DiaryEntry>, DatabaseError> {
+        Ok(self.lock().diary.since(start_time))
+    }
+
+    async fn add_alert(&self, alert: &Alert) -> Result<i64, DatabaseError> {
+        Ok(self.lock().alerts.insert(alert))
+    }
+
+    async fn get_alerts_since(&self, start_time: DateTime<Utc>, _display_timezone: Option<Tz>) -> Result<Vec<Alert>, DatabaseError> {
+        Ok(self.lock().alerts.since(start_time))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn demo_data() {
+        let now = FixedOffset::east_opt(3600).unwrap()
+            .with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let storage = MemoryStorage::with_demo_data(now);
+        let start_time = (now - Duration::days(100)).with_timezone(&Utc);
+
+        let bp = storage.get_blood_pressure_measurements_since(start_time, None).await.unwrap();
+        // two readings a day, but the evening reading of today is still in the future
+        assert_eq!(bp.len(), 2 * 90 - 1);
+        assert!(bp.iter().all(|m| m.timestamp <= now));
+        assert!(bp.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+
+        let other = MemoryStorage::with_demo_data(now);
+        assert_eq!(other.get_blood_pressure_measurements_since(start_time, None).await.unwrap(), bp);
+
+        let locations = storage.get_temperature_locations().await.unwrap();
+        assert_eq!(locations.len(), 5);
+        assert!(storage.remove_temperature_location(2).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn ids_and_updates() {
+        let storage = MemoryStorage::new();
+        let timestamp = FixedOffset::west_opt(5 * 3600).unwrap()
+            .with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap();
+        let start_time = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        let mut sugar = BloodSugarMeasurement::new(-1, timestamp, Rational32::new(1, 18));
+        sugar.id = storage.add_blood_sugar_measurement(&sugar).await.unwrap();
+        let second_id = storage.add_blood_sugar_measurement(&sugar).await.unwrap();
+        assert_eq!((sugar.id, second_id), (1, 2));
+
+        storage.remove_blood_sugar_measurement(second_id).await.unwrap();
+        sugar.sugar_mmol_per_l = Rational32::new(100, 18);
+        storage.update_blood_sugar_measurement(&sugar).await.unwrap();
+        assert_eq!(storage.get_blood_sugar_measurements_since(start_time, None).await.unwrap(), vec![sugar]);
+
+        // IDs are not reused
+        assert_eq!(storage.add_blood_sugar_measurement(&sugar).await.unwrap(), 3);
+
+        let temperature = BodyTemperatureMeasurement::new(-1, timestamp, 1, Rational32::new(37, 1), None, None);
+        assert!(storage.add_temperature_measurement(&temperature).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn conformance() {
+        crate::database::testing::exercise_storage(&MemoryStorage::new()).await;
+    }
+}
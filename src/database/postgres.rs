@@ -0,0 +1,1238 @@
+use std::error::Error;
+
+use bytes::{BufMut, BytesMut};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use num_rational::Rational32;
+use tokio_postgres::{Client, NoTls};
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::{FromSql, IsNull, ToSql, Type, accepts, to_sql_checked};
+use tracing::error;
+
+use crate::database::{DatabaseError, Storage, decode_custom_fields, encode_custom_fields};
+use crate::migrations::Migration;
+use crate::model::{
+    Alert, BloodPressureMeasurement, BloodSugarMeasurement, BodyComposition, BodyMassMeasurement, BodyTemperatureLocation,
+    BodyTemperatureMeasurement, CustomMeasurement, DiaryEntry, DiarySymptoms, HeightMeasurement, KetoneMeasurement,
+    LipidPanelMeasurement, LongTermBloodSugarMeasurement, PeakFlowMeasurement,
+};
+use crate::numerism::{DECIMAL_STORAGE_SCALE, r32_from_scaled_decimal, r32_to_scaled_decimal};
+use crate::timezone::{localize_timestamp, utc_offset_minutes};
+
+
+const CREATE_SCHEMA_VERSION_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS beepee.schema_version
+( version integer NOT NULL
+, name varchar(256) NOT NULL
+, applied_at timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP
+, CONSTRAINT schema_version_pkey PRIMARY KEY (version)
+);
+";
+
+
+const NUMERIC_POSITIVE: u16 = 0x0000;
+const NUMERIC_NEGATIVE: u16 = 0x4000;
+const NUMERIC_BASE: i128 = 10_000;
+const NUMERIC_DIGITS_PER_BASE_DIGIT: u32 = 4;
+
+
+/// The number of fractional digits with which older versions stored rational numbers.
+const LEGACY_DECIMAL_STORAGE_SCALE: u32 = 10;
+
+
+/// A rational number stored in a `numeric` column.
+///
+/// Values are written with `DECIMAL_STORAGE_SCALE` fractional digits, rounded half away from zero.
+/// When read, the simplest fraction that rounds to the stored decimal is returned, which is exactly
+/// the value that was written; see `r32_from_scaled_decimal`. Values that were rounded to
+/// `LEGACY_DECIMAL_STORAGE_SCALE` fractional digits by older versions are not representable as
+/// written, so the simplest fraction that rounds to them at that scale is returned instead, as
+/// those versions did.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct NumericRational(Rational32);
+
+/// Encodes a decimal, given multiplied by `10^scale`, in the binary format of `numeric`.
+fn scaled_decimal_to_numeric(scaled: i128, scale: u32, out: &mut BytesMut) {
+    // pad the fractional part to a whole number of base-10000 digits
+    let fraction_digits = scale.div_ceil(NUMERIC_DIGITS_PER_BASE_DIGIT);
+    let mut rest = scaled.abs() * 10i128.pow(fraction_digits * NUMERIC_DIGITS_PER_BASE_DIGIT - scale);
+
+    let mut digits: Vec<i16> = Vec::new();
+    while rest > 0 {
+        digits.push(i16::try_from(rest % NUMERIC_BASE).expect("base-10000 digit out of range"));
+        rest /= NUMERIC_BASE;
+    }
+    let weight = i32::try_from(digits.len()).expect("too many digits") - 1 - i32::try_from(fraction_digits).expect("scale too large");
+    // trailing zeroes are implied by the weight
+    let leading_zeroes = digits.iter().take_while(|d| **d == 0).count();
+    digits.drain(..leading_zeroes);
+    digits.reverse();
+
+    out.put_i16(i16::try_from(digits.len()).expect("too many digits"));
+    out.put_i16(if digits.is_empty() { 0 } else { i16::try_from(weight).expect("weight out of range") });
+    out.put_u16(if scaled < 0 { NUMERIC_NEGATIVE } else { NUMERIC_POSITIVE });
+    out.put_u16(u16::try_from(scale).expect("scale out of range"));
+    for digit in digits {
+        out.put_i16(digit);
+    }
+}
+
+/// Decodes a value in the binary format of `numeric` into a decimal multiplied by `10^scale`.
+///
+/// Values with more fractional digits than `scale` are rejected.
+fn numeric_to_scaled_decimal(raw: &[u8], scale: u32) -> Result<i128, String> {
+    let read_u16 = |index: usize| -> Result<u16, String> {
+        raw.get(2 * index..2 * index + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(|| "numeric value too short".to_owned())
+    };
+    let digit_count = usize::from(read_u16(0)?);
+    let weight = i64::from(read_u16(1)? as i16);
+    let sign = read_u16(2)?;
+    let value_scale = u32::from(read_u16(3)?);
+    if sign != NUMERIC_POSITIVE && sign != NUMERIC_NEGATIVE {
+        return Err("numeric value is not a finite number".to_owned());
+    }
+    if value_scale > scale {
+        return Err(format!("numeric value has {} fractional digits, at most {} are supported", value_scale, scale));
+    }
+
+    let out_of_range = || "numeric value out of range".to_owned();
+    let mut scaled: i128 = 0;
+    for i in 0..digit_count {
+        let digit = i128::from(read_u16(4 + i)? as i16);
+        if !(0..NUMERIC_BASE).contains(&digit) {
+            return Err(format!("invalid numeric digit {}", digit));
+        }
+        // the digit is worth 10000^(weight - i); the result is worth 10^(-scale)
+        let exponent = (weight - i64::try_from(i).map_err(|_| out_of_range())?) * i64::from(NUMERIC_DIGITS_PER_BASE_DIGIT) + i64::from(scale);
+        let contribution = if exponent >= 0 {
+            let power = 10i128.checked_pow(u32::try_from(exponent).map_err(|_| out_of_range())?)
+                .ok_or_else(out_of_range)?;
+            digit.checked_mul(power)
+                .ok_or_else(out_of_range)?
+        } else {
+            // only zeroes can be lost here, since the value has at most `scale` fractional digits
+            let power = 10i128.checked_pow(u32::try_from(-exponent).map_err(|_| out_of_range())?)
+                .ok_or_else(out_of_range)?;
+            digit / power
+        };
+        scaled = scaled.checked_add(contribution)
+            .ok_or_else(out_of_range)?;
+    }
+
+    if sign == NUMERIC_NEGATIVE {
+        Ok(-scaled)
+    } else {
+        Ok(scaled)
+    }
+}
+
+impl ToSql for NumericRational {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let scaled = r32_to_scaled_decimal(&self.0, DECIMAL_STORAGE_SCALE);
+        scaled_decimal_to_numeric(scaled, DECIMAL_STORAGE_SCALE, out);
+        Ok(IsNull::No)
+    }
+
+    accepts!(NUMERIC);
+    to_sql_checked!();
+}
+
+impl<'a> FromSql<'a> for NumericRational {
+    fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let scaled = numeric_to_scaled_decimal(raw, DECIMAL_STORAGE_SCALE)?;
+        let value = r32_from_scaled_decimal(scaled, DECIMAL_STORAGE_SCALE)
+            .or_else(|e| {
+                let legacy_factor = 10i128.pow(DECIMAL_STORAGE_SCALE - LEGACY_DECIMAL_STORAGE_SCALE);
+                if scaled % legacy_factor == 0 {
+                    r32_from_scaled_decimal(scaled / legacy_factor, LEGACY_DECIMAL_STORAGE_SCALE)
+                } else {
+                    Err(e)
+                }
+            })?;
+        Ok(Self(value))
+    }
+
+    accepts!(NUMERIC);
+}
+
+
+/// Stores measurements in the `beepee` schema of a PostgreSQL database.
+///
+/// A new connection is opened for each operation.
+pub(crate) struct PostgresStorage {
+    conn_string: String,
+}
+impl PostgresStorage {
+    pub fn new(conn_string: String) -> Self {
+        Self {
+            conn_string,
+        }
+    }
+
+    async fn connect(&self) -> Result<Client, DatabaseError> {
+        let (client, connection) = tokio_postgres::connect(&self.conn_string, NoTls)
+            .await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                error!("connection error: {}", e);
+            }
+        });
+
+        Ok(client)
+    }
+}
+impl Storage for PostgresStorage {
+    async fn get_applied_migration_versions(&self) -> Result<Vec<i32>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let table_exists: bool = client
+            .query_one("SELECT to_regclass('beepee.schema_version') IS NOT NULL", &[])
+            .await?
+            .get(0);
+        if !table_exists {
+            return Ok(Vec::new());
+        }
+
+        let rows = client
+            .query("SELECT version FROM beepee.schema_version ORDER BY version", &[])
+            .await?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
+    }
+
+    async fn apply_migration(&self, migration: &Migration) -> Result<bool, DatabaseError> {
+        let mut client = self.connect()
+            .await?;
+
+        client
+            .batch_execute(CREATE_SCHEMA_VERSION_TABLE)
+            .await?;
+
+        let transaction = client
+            .transaction()
+            .await?;
+
+        // another instance might be migrating at the same time
+        transaction
+            .batch_execute("LOCK TABLE beepee.schema_version IN EXCLUSIVE MODE")
+            .await?;
+        let already_applied = transaction
+            .query_opt("SELECT 1 FROM beepee.schema_version WHERE version = $1", &[&migration.version])
+            .await?
+            .is_some();
+        if already_applied {
+            transaction
+                .rollback()
+                .await?;
+            return Ok(false);
+        }
+
+        transaction
+            .batch_execute(migration.postgres_sql)
+            .await?;
+        transaction
+            .execute(
+                "INSERT INTO beepee.schema_version (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )
+            .await?;
+        transaction
+            .commit()
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn add_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.measurements (\"timestamp\", utc_offset_minutes, systolic_mmhg, diastolic_mmhg, pulse_bpm, spo2_percent) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &measurement.systolic_mmhg, &measurement.diastolic_mmhg, &measurement.pulse_bpm, &measurement.spo2_percent],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_blood_pressure_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "UPDATE beepee.measurements SET \"timestamp\"=$1, utc_offset_minutes=$2, systolic_mmhg=$3, diastolic_mmhg=$4, pulse_bpm=$5, spo2_percent=$6 WHERE id=$7",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &measurement.systolic_mmhg, &measurement.diastolic_mmhg, &measurement.pulse_bpm, &measurement.spo2_percent, &measurement.id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_blood_pressure_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BloodPressureMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, systolic_mmhg, diastolic_mmhg, pulse_bpm, spo2_percent FROM beepee.measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            ret.push(BloodPressureMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                row.try_get(3)?,
+                row.try_get(4)?,
+                row.try_get(5)?,
+                row.try_get(6)?,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.mass_measurements (\"timestamp\", utc_offset_minutes, mass_kg, waist_circum_cm, hip_circum_cm, body_fat_percent, muscle_mass_kg, visceral_fat_rating, body_water_percent) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id",
+                &[
+                    &measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &NumericRational(measurement.mass_kg),
+                    &measurement.waist_circum_cm.map(NumericRational), &measurement.hip_circum_cm.map(NumericRational),
+                    &measurement.composition.body_fat_percent.map(NumericRational), &measurement.composition.muscle_mass_kg.map(NumericRational),
+                    &measurement.composition.visceral_fat_rating.map(NumericRational), &measurement.composition.body_water_percent.map(NumericRational),
+                ],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_mass_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.mass_measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "UPDATE beepee.mass_measurements SET \"timestamp\"=$1, utc_offset_minutes=$2, mass_kg=$3, waist_circum_cm=$4, hip_circum_cm=$5, body_fat_percent=$6, muscle_mass_kg=$7, visceral_fat_rating=$8, body_water_percent=$9 WHERE id=$10",
+                &[
+                    &measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &NumericRational(measurement.mass_kg),
+                    &measurement.waist_circum_cm.map(NumericRational), &measurement.hip_circum_cm.map(NumericRational),
+                    &measurement.composition.body_fat_percent.map(NumericRational), &measurement.composition.muscle_mass_kg.map(NumericRational),
+                    &measurement.composition.visceral_fat_rating.map(NumericRational), &measurement.composition.body_water_percent.map(NumericRational),
+                    &measurement.id,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_mass_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BodyMassMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, mass_kg, waist_circum_cm, hip_circum_cm, body_fat_percent, muscle_mass_kg, visceral_fat_rating, body_water_percent FROM beepee.mass_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let mass_kg: NumericRational = row.try_get(3)?;
+            let optional_rat32 = |index: usize| -> Result<Option<Rational32>, tokio_postgres::Error> {
+                let value: Option<NumericRational> = row.try_get(index)?;
+                Ok(value.map(|v| v.0))
+            };
+            let composition = BodyComposition {
+                body_fat_percent: optional_rat32(6)?,
+                muscle_mass_kg: optional_rat32(7)?,
+                visceral_fat_rating: optional_rat32(8)?,
+                body_water_percent: optional_rat32(9)?,
+            };
+            ret.push(BodyMassMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                mass_kg.0,
+                optional_rat32(4)?,
+                optional_rat32(5)?,
+                composition,
+                None,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_height_measurement(&self, measurement: &HeightMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.height_measurements (\"timestamp\", utc_offset_minutes, height_cm) VALUES ($1, $2, $3) RETURNING id",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &NumericRational(measurement.height_cm)],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_height_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.height_measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_height_measurements(&self, display_timezone: Option<Tz>) -> Result<Vec<HeightMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, height_cm FROM beepee.height_measurements ORDER BY \"timestamp\"",
+                &[],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let height_cm: NumericRational = row.try_get(3)?;
+            ret.push(HeightMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                height_cm.0,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.body_temperature_locations (\"name\", core_offset_celsius, normal_min_celsius, normal_max_celsius) VALUES ($1, $2, $3, $4) RETURNING id",
+                &[&loc.name, &loc.core_offset_celsius.map(NumericRational), &loc.normal_min_celsius.map(NumericRational), &loc.normal_max_celsius.map(NumericRational)],
+            )
+            .await?;
+        let loc_id: i64 = row.try_get(0)?;
+
+        Ok(loc_id)
+    }
+
+    async fn remove_temperature_location(&self, loc_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let result = client
+            .execute(
+                "DELETE FROM beepee.body_temperature_locations WHERE id = $1",
+                &[&loc_id],
+            )
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) if e.code() == Some(&SqlState::FOREIGN_KEY_VIOLATION)
+                => Err(DatabaseError::StillReferenced("temperature location", loc_id)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn update_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "UPDATE beepee.body_temperature_locations SET \"name\"=$1, core_offset_celsius=$2, normal_min_celsius=$3, normal_max_celsius=$4 WHERE id=$5",
+                &[&loc.name, &loc.core_offset_celsius.map(NumericRational), &loc.normal_min_celsius.map(NumericRational), &loc.normal_max_celsius.map(NumericRational), &loc.id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_temperature_locations(&self) -> Result<Vec<BodyTemperatureLocation>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"name\", core_offset_celsius, normal_min_celsius, normal_max_celsius FROM beepee.body_temperature_locations ORDER BY \"name\"",
+                &[],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let core_offset_celsius: Option<NumericRational> = row.try_get(2)?;
+            let normal_min_celsius: Option<NumericRational> = row.try_get(3)?;
+            let normal_max_celsius: Option<NumericRational> = row.try_get(4)?;
+            ret.push(BodyTemperatureLocation::new(
+                row.try_get(0)?,
+                row.try_get(1)?,
+                core_offset_celsius.map(|o| o.0),
+                normal_min_celsius.map(|m| m.0),
+                normal_max_celsius.map(|m| m.0),
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.body_temperature_measurements (\"timestamp\", utc_offset_minutes, location_id, temperature_celsius) VALUES ($1, $2, $3, $4) RETURNING id",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &measurement.location_id, &NumericRational(measurement.temperature_celsius)],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_temperature_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.body_temperature_measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "UPDATE beepee.body_temperature_measurements SET \"timestamp\"=$1, utc_offset_minutes=$2, location_id=$3, temperature_celsius=$4 WHERE id=$5",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &measurement.location_id, &NumericRational(measurement.temperature_celsius), &measurement.id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_temperature_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BodyTemperatureMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, location_id, temperature_celsius FROM beepee.body_temperature_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let temperature_celsius: NumericRational = row.try_get(4)?;
+            ret.push(BodyTemperatureMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                row.try_get(3)?,
+                temperature_celsius.0,
+                None,
+                None,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.blood_sugar_measurements (\"timestamp\", utc_offset_minutes, sugar_mmol_per_l) VALUES ($1, $2, $3) RETURNING id",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &NumericRational(measurement.sugar_mmol_per_l)],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.blood_sugar_measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "UPDATE beepee.blood_sugar_measurements SET \"timestamp\"=$1, utc_offset_minutes=$2, sugar_mmol_per_l=$3 WHERE id=$4",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &NumericRational(measurement.sugar_mmol_per_l), &measurement.id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BloodSugarMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, sugar_mmol_per_l FROM beepee.blood_sugar_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let sugar_mmol_per_l: NumericRational = row.try_get(3)?;
+            ret.push(BloodSugarMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                sugar_mmol_per_l.0,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.long_term_blood_sugar_measurements (\"timestamp\", utc_offset_minutes, hba1c_mmol_per_mol) VALUES ($1, $2, $3) RETURNING id",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &NumericRational(measurement.hba1c_mmol_per_mol)],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_long_term_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.long_term_blood_sugar_measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "UPDATE beepee.long_term_blood_sugar_measurements SET \"timestamp\"=$1, utc_offset_minutes=$2, hba1c_mmol_per_mol=$3 WHERE id=$4",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &NumericRational(measurement.hba1c_mmol_per_mol), &measurement.id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_long_term_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<LongTermBloodSugarMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, hba1c_mmol_per_mol FROM beepee.long_term_blood_sugar_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let hba1c_mmol_per_mol: NumericRational = row.try_get(3)?;
+            ret.push(LongTermBloodSugarMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                hba1c_mmol_per_mol.0,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.peak_flow_measurements (\"timestamp\", utc_offset_minutes, peak_flow_l_per_min) VALUES ($1, $2, $3) RETURNING id",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &measurement.peak_flow_l_per_min],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_peak_flow_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.peak_flow_measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "UPDATE beepee.peak_flow_measurements SET \"timestamp\"=$1, utc_offset_minutes=$2, peak_flow_l_per_min=$3 WHERE id=$4",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &measurement.peak_flow_l_per_min, &measurement.id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_peak_flow_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<PeakFlowMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, peak_flow_l_per_min FROM beepee.peak_flow_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let peak_flow_l_per_min: i32 = row.try_get(3)?;
+            ret.push(PeakFlowMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                peak_flow_l_per_min,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.ketone_measurements (\"timestamp\", utc_offset_minutes, ketones_mmol_per_l) VALUES ($1, $2, $3) RETURNING id",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &NumericRational(measurement.ketones_mmol_per_l)],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_ketone_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.ketone_measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "UPDATE beepee.ketone_measurements SET \"timestamp\"=$1, utc_offset_minutes=$2, ketones_mmol_per_l=$3 WHERE id=$4",
+                &[&measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &NumericRational(measurement.ketones_mmol_per_l), &measurement.id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_ketone_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<KetoneMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, ketones_mmol_per_l FROM beepee.ketone_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let ketones_mmol_per_l: NumericRational = row.try_get(3)?;
+            ret.push(KetoneMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                ketones_mmol_per_l.0,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.lipid_panel_measurements (\"timestamp\", utc_offset_minutes, total_cholesterol_mmol_per_l, ldl_cholesterol_mmol_per_l, hdl_cholesterol_mmol_per_l, triglycerides_mmol_per_l) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+                &[
+                    &measurement.timestamp, &utc_offset_minutes(&measurement.timestamp),
+                    &measurement.total_cholesterol_mmol_per_l.map(NumericRational), &measurement.ldl_cholesterol_mmol_per_l.map(NumericRational),
+                    &measurement.hdl_cholesterol_mmol_per_l.map(NumericRational), &measurement.triglycerides_mmol_per_l.map(NumericRational),
+                ],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_lipid_panel_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.lipid_panel_measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "UPDATE beepee.lipid_panel_measurements SET \"timestamp\"=$1, utc_offset_minutes=$2, total_cholesterol_mmol_per_l=$3, ldl_cholesterol_mmol_per_l=$4, hdl_cholesterol_mmol_per_l=$5, triglycerides_mmol_per_l=$6 WHERE id=$7",
+                &[
+                    &measurement.timestamp, &utc_offset_minutes(&measurement.timestamp),
+                    &measurement.total_cholesterol_mmol_per_l.map(NumericRational), &measurement.ldl_cholesterol_mmol_per_l.map(NumericRational),
+                    &measurement.hdl_cholesterol_mmol_per_l.map(NumericRational), &measurement.triglycerides_mmol_per_l.map(NumericRational),
+                    &measurement.id,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_lipid_panel_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<LipidPanelMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, total_cholesterol_mmol_per_l, ldl_cholesterol_mmol_per_l, hdl_cholesterol_mmol_per_l, triglycerides_mmol_per_l FROM beepee.lipid_panel_measurements WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let optional_rat32 = |index: usize| -> Result<Option<Rational32>, tokio_postgres::Error> {
+                let value: Option<NumericRational> = row.try_get(index)?;
+                Ok(value.map(|v| v.0))
+            };
+            ret.push(LipidPanelMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                optional_rat32(3)?,
+                optional_rat32(4)?,
+                optional_rat32(5)?,
+                optional_rat32(6)?,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        // the JSON is passed as text since the jsonb type is not enabled in the client
+        let (values_json, categories_json) = encode_custom_fields(measurement);
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.custom_measurements (metric, \"timestamp\", utc_offset_minutes, \"values\", categories) VALUES ($1, $2, $3, CAST($4 AS text)::jsonb, CAST($5 AS text)::jsonb) RETURNING id",
+                &[&measurement.metric, &measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &values_json, &categories_json],
+            )
+            .await?;
+        let measurement_id: i64 = row.try_get(0)?;
+
+        Ok(measurement_id)
+    }
+
+    async fn remove_custom_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.custom_measurements WHERE id = $1",
+                &[&measurement_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let (values_json, categories_json) = encode_custom_fields(measurement);
+        client
+            .execute(
+                "UPDATE beepee.custom_measurements SET metric=$1, \"timestamp\"=$2, utc_offset_minutes=$3, \"values\"=CAST($4 AS text)::jsonb, categories=CAST($5 AS text)::jsonb WHERE id=$6",
+                &[&measurement.metric, &measurement.timestamp, &utc_offset_minutes(&measurement.timestamp), &values_json, &categories_json, &measurement.id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_custom_measurements_since(&self, metric: Option<&str>, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<CustomMeasurement>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, metric, CAST(\"values\" AS text), CAST(categories AS text) FROM beepee.custom_measurements WHERE \"timestamp\" >= $1 AND (CAST($2 AS varchar) IS NULL OR metric = $2) ORDER BY \"timestamp\"",
+                &[&start_time, &metric],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let values_json: String = row.try_get(4)?;
+            let categories_json: String = row.try_get(5)?;
+            let (values, categories) = decode_custom_fields(&values_json, &categories_json)?;
+            ret.push(CustomMeasurement::new(
+                row.try_get(0)?,
+                timestamp,
+                row.try_get(3)?,
+                values,
+                categories,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_diary_entry(&self, entry: &DiaryEntry) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let symptoms = &entry.symptoms;
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.diary_entries (\"timestamp\", utc_offset_minutes, headache, dizziness, palpitations, chest_pain, shortness_of_breath, wellbeing_score, notes) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id",
+                &[&entry.timestamp, &utc_offset_minutes(&entry.timestamp), &symptoms.headache, &symptoms.dizziness, &symptoms.palpitations, &symptoms.chest_pain, &symptoms.shortness_of_breath, &entry.wellbeing_score, &entry.notes],
+            )
+            .await?;
+        let entry_id: i64 = row.try_get(0)?;
+
+        Ok(entry_id)
+    }
+
+    async fn remove_diary_entry(&self, entry_id: i64) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        client
+            .execute(
+                "DELETE FROM beepee.diary_entries WHERE id = $1",
+                &[&entry_id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_diary_entry(&self, entry: &DiaryEntry) -> Result<(), DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let symptoms = &entry.symptoms;
+        client
+            .execute(
+                "UPDATE beepee.diary_entries SET \"timestamp\"=$1, utc_offset_minutes=$2, headache=$3, dizziness=$4, palpitations=$5, chest_pain=$6, shortness_of_breath=$7, wellbeing_score=$8, notes=$9 WHERE id=$10",
+                &[&entry.timestamp, &utc_offset_minutes(&entry.timestamp), &symptoms.headache, &symptoms.dizziness, &symptoms.palpitations, &symptoms.chest_pain, &symptoms.shortness_of_breath, &entry.wellbeing_score, &entry.notes, &entry.id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_diary_entries_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<DiaryEntry>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, headache, dizziness, palpitations, chest_pain, shortness_of_breath, wellbeing_score, notes FROM beepee.diary_entries WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            let symptoms = DiarySymptoms {
+                headache: row.try_get(3)?,
+                dizziness: row.try_get(4)?,
+                palpitations: row.try_get(5)?,
+                chest_pain: row.try_get(6)?,
+                shortness_of_breath: row.try_get(7)?,
+            };
+            ret.push(DiaryEntry::new(
+                row.try_get(0)?,
+                timestamp,
+                symptoms,
+                row.try_get(8)?,
+                row.try_get(9)?,
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    async fn add_alert(&self, alert: &Alert) -> Result<i64, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO beepee.alerts (\"timestamp\", utc_offset_minutes, rule_name, measurement_kind, field, \"value\", threshold, message) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+                &[&alert.timestamp, &utc_offset_minutes(&alert.timestamp), &alert.rule_name, &alert.measurement_kind, &alert.field, &alert.value, &alert.threshold, &alert.message],
+            )
+            .await?;
+        let alert_id: i64 = row.try_get(0)?;
+
+        Ok(alert_id)
+    }
+
+    async fn get_alerts_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<Alert>, DatabaseError> {
+        let client = self.connect()
+            .await?;
+
+        let rows = client
+            .query(
+                "SELECT id, \"timestamp\", utc_offset_minutes, rule_name, measurement_kind, field, \"value\", threshold, message FROM beepee.alerts WHERE \"timestamp\" >= $1 ORDER BY \"timestamp\"",
+                &[&start_time],
+            )
+            .await?;
+        let mut ret = Vec::new();
+        for row in rows {
+            let timestamp_utc: DateTime<Utc> = row.try_get(1)?;
+            let timestamp = localize_timestamp(&timestamp_utc, row.try_get(2)?, display_timezone);
+            ret.push(Alert {
+                id: row.try_get(0)?,
+                timestamp,
+                rule_name: row.try_get(3)?,
+                measurement_kind: row.try_get(4)?,
+                field: row.try_get(5)?,
+                value: row.try_get(6)?,
+                threshold: row.try_get(7)?,
+                message: row.try_get(8)?,
+            });
+        }
+
+        Ok(ret)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::database::testing::{create_test_database, exercise_storage};
+    use crate::migrations::migrate_storage;
+
+    fn encode(scaled: i128) -> Vec<u8> {
+        let mut out = BytesMut::new();
+        scaled_decimal_to_numeric(scaled, DECIMAL_STORAGE_SCALE, &mut out);
+        out.to_vec()
+    }
+
+    #[test]
+    fn numeric_encoding() {
+        // 80.33: two base-10000 digits, 80 and 3300, the first one worth 10000^0
+        assert_eq!(encode(8_033_000_000_000_000_000_000), vec![0, 2, 0, 0, 0x00, 0x00, 0, 20, 0, 80, 0x0C, 0xE4]);
+        // -0.05555555555555555556: weight -1, since the first digit is worth 10000^-1
+        assert_eq!(encode(-5_555_555_555_555_555_556), vec![0, 5, 0xFF, 0xFF, 0x40, 0x00, 0, 20, 0x02, 0x2B, 0x15, 0xB3, 0x15, 0xB3, 0x15, 0xB3, 0x15, 0xB4]);
+        assert_eq!(encode(0), vec![0, 0, 0, 0, 0, 0, 0, 20]);
+    }
+
+    #[test]
+    fn numeric_round_trip() {
+        let max = i128::from(i32::MAX) * 100_000_000_000_000_000_000;
+        for scaled in [0, 1, -1, 8_033_000_000_000_000_000_000, -5_555_555_555_555_555_556, 100_000_000_000_000_000_000, max] {
+            assert_eq!(numeric_to_scaled_decimal(&encode(scaled), DECIMAL_STORAGE_SCALE).unwrap(), scaled);
+        }
+
+        // numeric(6, 2) as written by older versions: 36.60
+        let legacy = [0, 2, 0, 0, 0, 0, 0, 2, 0, 36, 0x17, 0x70];
+        assert_eq!(numeric_to_scaled_decimal(&legacy, DECIMAL_STORAGE_SCALE).unwrap(), 3_660_000_000_000_000_000_000);
+
+        // NaN
+        assert!(numeric_to_scaled_decimal(&[0, 0, 0, 0, 0xC0, 0x00, 0, 0], DECIMAL_STORAGE_SCALE).is_err());
+        // too many fractional digits
+        assert!(numeric_to_scaled_decimal(&[0, 1, 0xFF, 0xFA, 0, 0, 0, 24, 0, 1], DECIMAL_STORAGE_SCALE).is_err());
+    }
+
+    #[test]
+    fn legacy_numeric_values() {
+        // 1/18 as rounded to 10 fractional digits by older versions, padded to the current scale
+        let legacy = [0, 3, 0xFF, 0xFF, 0x00, 0x00, 0, 20, 0x02, 0x2B, 0x15, 0xB3, 0x15, 0xE0];
+        assert_eq!(NumericRational::from_sql(&Type::NUMERIC, &legacy).unwrap().0, Rational32::new(1, 18));
+        // values that are representable are returned exactly
+        let exact = encode(r32_to_scaled_decimal(&Rational32::new(9_694_023, 200_000), DECIMAL_STORAGE_SCALE));
+        assert_eq!(NumericRational::from_sql(&Type::NUMERIC, &exact).unwrap().0, Rational32::new(9_694_023, 200_000));
+    }
+
+    #[tokio::test]
+    async fn conformance() {
+        let Some(conn_string) = create_test_database().await else { return };
+        exercise_storage(&PostgresStorage::new(conn_string)).await;
+    }
+
+    #[tokio::test]
+    async fn rationals_survive_round_trip() {
+        let Some(conn_string) = create_test_database().await else { return };
+        let storage = PostgresStorage::new(conn_string);
+        migrate_storage(&storage, false).await.unwrap();
+        let start_time = Utc::now() - chrono::Duration::days(1);
+
+        // more fractional digits than the old numeric(6, 2) columns could hold
+        let timestamp = Utc::now().fixed_offset();
+        let mass = BodyMassMeasurement::new(-1, timestamp, Rational32::new(80_333, 1000), None, None, BodyComposition::default(), None);
+        storage.add_mass_measurement(&mass).await.unwrap();
+        let sugar = BloodSugarMeasurement::new(-1, timestamp, Rational32::new(100, 18));
+        storage.add_blood_sugar_measurement(&sugar).await.unwrap();
+
+        let masses = storage.get_mass_measurements_since(start_time, None).await.unwrap();
+        assert_eq!(masses[0].mass_kg, Rational32::new(80_333, 1000));
+        let sugars = storage.get_blood_sugar_measurements_since(start_time, None).await.unwrap();
+        assert_eq!(sugars[0].sugar_mmol_per_l, Rational32::new(50, 9));
+    }
+}
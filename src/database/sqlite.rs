@@ -0,0 +1,966 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, FixedOffset, Utc};
+use chrono_tz::Tz;
+use num_rational::Rational32;
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior, params};
+
+use crate::database::{DatabaseError, Storage, decode_custom_fields, encode_custom_fields};
+use crate::migrations::Migration;
+use crate::model::{
+    Alert, BloodPressureMeasurement, BloodSugarMeasurement, BodyComposition, BodyMassMeasurement, BodyTemperatureLocation,
+    BodyTemperatureMeasurement, CustomMeasurement, DiaryEntry, DiarySymptoms, HeightMeasurement, KetoneMeasurement,
+    LipidPanelMeasurement, LongTermBloodSugarMeasurement, PeakFlowMeasurement,
+};
+use crate::ser_de::{rat32_to_string, string_to_rat32};
+use crate::timezone::{localize_timestamp, utc_offset_minutes};
+
+
+const CREATE_SCHEMA_VERSION_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS schema_version
+( version INTEGER NOT NULL PRIMARY KEY
+, name TEXT NOT NULL
+, applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+";
+
+/// The format in which timestamps are stored.
+///
+/// All timestamps are stored in UTC with a fixed number of fractional digits, so comparing them as
+/// text gives the same result as comparing them as points in time.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.9fZ";
+
+
+/// Stores measurements in an embedded SQLite database file.
+///
+/// SQLite calls block, so they are run on the blocking thread pool, one at a time.
+pub(crate) struct SqliteStorage {
+    connection: Arc<Mutex<Connection>>,
+}
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self, DatabaseError> {
+        let connection = Connection::open(path)?;
+        Self::from_connection(connection)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, DatabaseError> {
+        let connection = Connection::open_in_memory()?;
+        Self::from_connection(connection)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self, DatabaseError> {
+        connection.execute_batch("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;")?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn run<T, F>(&self, operation: F) -> Result<T, DatabaseError>
+        where
+            T: Send + 'static,
+            F: FnOnce(&mut Connection) -> Result<T, DatabaseError> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        tokio::task::spawn_blocking(move || {
+            let mut connection_guard = connection
+                .lock().expect("SQLite connection lock poisoned");
+            operation(&mut connection_guard)
+        })
+            .await
+            .map_err(DatabaseError::BackgroundTask)?
+    }
+}
+
+
+fn timestamp_to_text(timestamp: &DateTime<FixedOffset>) -> String {
+    timestamp
+        .with_timezone(&Utc)
+        .format(TIMESTAMP_FORMAT)
+        .to_string()
+}
+
+fn text_to_timestamp(
+    text: &str,
+    utc_offset_minutes: Option<i32>,
+    display_timezone: Option<Tz>,
+) -> Result<DateTime<FixedOffset>, DatabaseError> {
+    let timestamp_utc = DateTime::parse_from_rfc3339(text)
+        .map_err(|e| DatabaseError::InvalidValue("timestamp", e.to_string()))?
+        .with_timezone(&Utc);
+    Ok(localize_timestamp(&timestamp_utc, utc_offset_minutes, display_timezone))
+}
+
+fn text_to_rat32(column: &'static str, text: &str) -> Result<Rational32, DatabaseError> {
+    string_to_rat32(text)
+        .map_err(|e| DatabaseError::InvalidValue(column, e))
+}
+
+
+impl Storage for SqliteStorage {
+    async fn get_applied_migration_versions(&self) -> Result<Vec<i32>, DatabaseError> {
+        self.run(|conn| {
+            let table_exists: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE \"type\" = 'table' AND \"name\" = 'schema_version')",
+                [],
+                |row| row.get(0),
+            )?;
+            if !table_exists {
+                return Ok(Vec::new());
+            }
+
+            let mut statement = conn.prepare("SELECT version FROM schema_version ORDER BY version")?;
+            let versions = statement
+                .query_map([], |row| row.get(0))?
+                .collect::<Result<Vec<i32>, rusqlite::Error>>()?;
+            Ok(versions)
+        }).await
+    }
+
+    async fn apply_migration(&self, migration: &Migration) -> Result<bool, DatabaseError> {
+        let version = migration.version;
+        let name = migration.name;
+        let sql = migration.sqlite_sql;
+        self.run(move |conn| {
+            conn.execute_batch(CREATE_SCHEMA_VERSION_TABLE)?;
+
+            // another instance might be migrating at the same time; an immediate transaction takes the
+            // write lock right away
+            let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let already_applied = transaction
+                .query_row("SELECT 1 FROM schema_version WHERE version = ?1", params![version], |_| Ok(()))
+                .optional()?
+                .is_some();
+            if already_applied {
+                transaction.rollback()?;
+                return Ok(false);
+            }
+
+            transaction.execute_batch(sql)?;
+            transaction.execute(
+                "INSERT INTO schema_version (version, name) VALUES (?1, ?2)",
+                params![version, name],
+            )?;
+            transaction.commit()?;
+
+            Ok(true)
+        }).await
+    }
+
+    async fn add_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            let measurement_id = conn.query_row(
+                "INSERT INTO measurements (\"timestamp\", utc_offset_minutes, systolic_mmhg, diastolic_mmhg, pulse_bpm, spo2_percent) VALUES (?1, ?2, ?3, ?4, ?5, ?6) RETURNING id",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), measurement.systolic_mmhg, measurement.diastolic_mmhg, measurement.pulse_bpm, measurement.spo2_percent],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_blood_pressure_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_blood_pressure_measurement(&self, measurement: &BloodPressureMeasurement) -> Result<(), DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE measurements SET \"timestamp\"=?1, utc_offset_minutes=?2, systolic_mmhg=?3, diastolic_mmhg=?4, pulse_bpm=?5, spo2_percent=?6 WHERE id=?7",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), measurement.systolic_mmhg, measurement.diastolic_mmhg, measurement.pulse_bpm, measurement.spo2_percent, measurement.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_blood_pressure_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BloodPressureMeasurement>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, systolic_mmhg, diastolic_mmhg, pulse_bpm, spo2_percent FROM measurements WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                ret.push(BloodPressureMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            let measurement_id = conn.query_row(
+                "INSERT INTO mass_measurements (\"timestamp\", utc_offset_minutes, mass_kg, waist_circum_cm, hip_circum_cm, body_fat_percent, muscle_mass_kg, visceral_fat_rating, body_water_percent) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) RETURNING id",
+                params![
+                    timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), rat32_to_string(&measurement.mass_kg),
+                    measurement.waist_circum_cm.as_ref().map(rat32_to_string), measurement.hip_circum_cm.as_ref().map(rat32_to_string),
+                    measurement.composition.body_fat_percent.as_ref().map(rat32_to_string), measurement.composition.muscle_mass_kg.as_ref().map(rat32_to_string),
+                    measurement.composition.visceral_fat_rating.as_ref().map(rat32_to_string), measurement.composition.body_water_percent.as_ref().map(rat32_to_string),
+                ],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_mass_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM mass_measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_mass_measurement(&self, measurement: &BodyMassMeasurement) -> Result<(), DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE mass_measurements SET \"timestamp\"=?1, utc_offset_minutes=?2, mass_kg=?3, waist_circum_cm=?4, hip_circum_cm=?5, body_fat_percent=?6, muscle_mass_kg=?7, visceral_fat_rating=?8, body_water_percent=?9 WHERE id=?10",
+                params![
+                    timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), rat32_to_string(&measurement.mass_kg),
+                    measurement.waist_circum_cm.as_ref().map(rat32_to_string), measurement.hip_circum_cm.as_ref().map(rat32_to_string),
+                    measurement.composition.body_fat_percent.as_ref().map(rat32_to_string), measurement.composition.muscle_mass_kg.as_ref().map(rat32_to_string),
+                    measurement.composition.visceral_fat_rating.as_ref().map(rat32_to_string), measurement.composition.body_water_percent.as_ref().map(rat32_to_string),
+                    measurement.id,
+                ],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_mass_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BodyMassMeasurement>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, mass_kg, waist_circum_cm, hip_circum_cm, body_fat_percent, muscle_mass_kg, visceral_fat_rating, body_water_percent FROM mass_measurements WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                let mass_text: String = row.get(3)?;
+                let mass_kg = text_to_rat32("mass_kg", &mass_text)?;
+                let optional_rat32 = |index: usize, name: &'static str| -> Result<Option<Rational32>, DatabaseError> {
+                    let text: Option<String> = row.get(index)?;
+                    text
+                        .map(|t| text_to_rat32(name, &t))
+                        .transpose()
+                };
+                let composition = BodyComposition {
+                    body_fat_percent: optional_rat32(6, "body_fat_percent")?,
+                    muscle_mass_kg: optional_rat32(7, "muscle_mass_kg")?,
+                    visceral_fat_rating: optional_rat32(8, "visceral_fat_rating")?,
+                    body_water_percent: optional_rat32(9, "body_water_percent")?,
+                };
+                ret.push(BodyMassMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    mass_kg,
+                    optional_rat32(4, "waist_circum_cm")?,
+                    optional_rat32(5, "hip_circum_cm")?,
+                    composition,
+                    None,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_height_measurement(&self, measurement: &HeightMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            let measurement_id = conn.query_row(
+                "INSERT INTO height_measurements (\"timestamp\", utc_offset_minutes, height_cm) VALUES (?1, ?2, ?3) RETURNING id",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), rat32_to_string(&measurement.height_cm)],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_height_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM height_measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_height_measurements(&self, display_timezone: Option<Tz>) -> Result<Vec<HeightMeasurement>, DatabaseError> {
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, height_cm FROM height_measurements ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query([])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                let height_text: String = row.get(3)?;
+                ret.push(HeightMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    text_to_rat32("height_cm", &height_text)?,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<i64, DatabaseError> {
+        let loc = loc.clone();
+        self.run(move |conn| {
+            let loc_id = conn.query_row(
+                "INSERT INTO body_temperature_locations (\"name\", core_offset_celsius, normal_min_celsius, normal_max_celsius) VALUES (?1, ?2, ?3, ?4) RETURNING id",
+                params![loc.name, loc.core_offset_celsius.as_ref().map(rat32_to_string), loc.normal_min_celsius.as_ref().map(rat32_to_string), loc.normal_max_celsius.as_ref().map(rat32_to_string)],
+                |row| row.get(0),
+            )?;
+            Ok(loc_id)
+        }).await
+    }
+
+    async fn remove_temperature_location(&self, loc_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            match conn.execute("DELETE FROM body_temperature_locations WHERE id = ?1", params![loc_id]) {
+                Ok(_) => Ok(()),
+                Err(rusqlite::Error::SqliteFailure(e, _)) if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY
+                    => Err(DatabaseError::StillReferenced("temperature location", loc_id)),
+                Err(e) => Err(e.into()),
+            }
+        }).await
+    }
+
+    async fn update_temperature_location(&self, loc: &BodyTemperatureLocation) -> Result<(), DatabaseError> {
+        let loc = loc.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE body_temperature_locations SET \"name\"=?1, core_offset_celsius=?2, normal_min_celsius=?3, normal_max_celsius=?4 WHERE id=?5",
+                params![loc.name, loc.core_offset_celsius.as_ref().map(rat32_to_string), loc.normal_min_celsius.as_ref().map(rat32_to_string), loc.normal_max_celsius.as_ref().map(rat32_to_string), loc.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_temperature_locations(&self) -> Result<Vec<BodyTemperatureLocation>, DatabaseError> {
+        self.run(|conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"name\", core_offset_celsius, normal_min_celsius, normal_max_celsius FROM body_temperature_locations ORDER BY \"name\"",
+            )?;
+            let mut rows = statement.query([])?;
+            let mut locations = Vec::new();
+            while let Some(row) = rows.next()? {
+                let core_offset_text: Option<String> = row.get(2)?;
+                let normal_min_text: Option<String> = row.get(3)?;
+                let normal_max_text: Option<String> = row.get(4)?;
+                locations.push(BodyTemperatureLocation::new(
+                    row.get(0)?,
+                    row.get(1)?,
+                    core_offset_text
+                        .map(|t| text_to_rat32("core_offset_celsius", &t))
+                        .transpose()?,
+                    normal_min_text
+                        .map(|t| text_to_rat32("normal_min_celsius", &t))
+                        .transpose()?,
+                    normal_max_text
+                        .map(|t| text_to_rat32("normal_max_celsius", &t))
+                        .transpose()?,
+                ));
+            }
+            Ok(locations)
+        }).await
+    }
+
+    async fn add_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            let measurement_id = conn.query_row(
+                "INSERT INTO body_temperature_measurements (\"timestamp\", utc_offset_minutes, location_id, temperature_celsius) VALUES (?1, ?2, ?3, ?4) RETURNING id",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), measurement.location_id, rat32_to_string(&measurement.temperature_celsius)],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_temperature_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM body_temperature_measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_temperature_measurement(&self, measurement: &BodyTemperatureMeasurement) -> Result<(), DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE body_temperature_measurements SET \"timestamp\"=?1, utc_offset_minutes=?2, location_id=?3, temperature_celsius=?4 WHERE id=?5",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), measurement.location_id, rat32_to_string(&measurement.temperature_celsius), measurement.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_temperature_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BodyTemperatureMeasurement>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, location_id, temperature_celsius FROM body_temperature_measurements WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                let temperature_text: String = row.get(4)?;
+                ret.push(BodyTemperatureMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    row.get(3)?,
+                    text_to_rat32("temperature_celsius", &temperature_text)?,
+                    None,
+                    None,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            let measurement_id = conn.query_row(
+                "INSERT INTO blood_sugar_measurements (\"timestamp\", utc_offset_minutes, sugar_mmol_per_l) VALUES (?1, ?2, ?3) RETURNING id",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), rat32_to_string(&measurement.sugar_mmol_per_l)],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM blood_sugar_measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_blood_sugar_measurement(&self, measurement: &BloodSugarMeasurement) -> Result<(), DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE blood_sugar_measurements SET \"timestamp\"=?1, utc_offset_minutes=?2, sugar_mmol_per_l=?3 WHERE id=?4",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), rat32_to_string(&measurement.sugar_mmol_per_l), measurement.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<BloodSugarMeasurement>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, sugar_mmol_per_l FROM blood_sugar_measurements WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                let sugar_text: String = row.get(3)?;
+                ret.push(BloodSugarMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    text_to_rat32("sugar_mmol_per_l", &sugar_text)?,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            let measurement_id = conn.query_row(
+                "INSERT INTO long_term_blood_sugar_measurements (\"timestamp\", utc_offset_minutes, hba1c_mmol_per_mol) VALUES (?1, ?2, ?3) RETURNING id",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), rat32_to_string(&measurement.hba1c_mmol_per_mol)],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_long_term_blood_sugar_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM long_term_blood_sugar_measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_long_term_blood_sugar_measurement(&self, measurement: &LongTermBloodSugarMeasurement) -> Result<(), DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE long_term_blood_sugar_measurements SET \"timestamp\"=?1, utc_offset_minutes=?2, hba1c_mmol_per_mol=?3 WHERE id=?4",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), rat32_to_string(&measurement.hba1c_mmol_per_mol), measurement.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_long_term_blood_sugar_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<LongTermBloodSugarMeasurement>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, hba1c_mmol_per_mol FROM long_term_blood_sugar_measurements WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                let hba1c_text: String = row.get(3)?;
+                ret.push(LongTermBloodSugarMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    text_to_rat32("hba1c_mmol_per_mol", &hba1c_text)?,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            let measurement_id = conn.query_row(
+                "INSERT INTO peak_flow_measurements (\"timestamp\", utc_offset_minutes, peak_flow_l_per_min) VALUES (?1, ?2, ?3) RETURNING id",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), measurement.peak_flow_l_per_min],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_peak_flow_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM peak_flow_measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_peak_flow_measurement(&self, measurement: &PeakFlowMeasurement) -> Result<(), DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE peak_flow_measurements SET \"timestamp\"=?1, utc_offset_minutes=?2, peak_flow_l_per_min=?3 WHERE id=?4",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), measurement.peak_flow_l_per_min, measurement.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_peak_flow_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<PeakFlowMeasurement>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, peak_flow_l_per_min FROM peak_flow_measurements WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                ret.push(PeakFlowMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    row.get(3)?,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            let measurement_id = conn.query_row(
+                "INSERT INTO ketone_measurements (\"timestamp\", utc_offset_minutes, ketones_mmol_per_l) VALUES (?1, ?2, ?3) RETURNING id",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), rat32_to_string(&measurement.ketones_mmol_per_l)],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_ketone_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM ketone_measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_ketone_measurement(&self, measurement: &KetoneMeasurement) -> Result<(), DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE ketone_measurements SET \"timestamp\"=?1, utc_offset_minutes=?2, ketones_mmol_per_l=?3 WHERE id=?4",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), rat32_to_string(&measurement.ketones_mmol_per_l), measurement.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_ketone_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<KetoneMeasurement>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, ketones_mmol_per_l FROM ketone_measurements WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                let ketones_text: String = row.get(3)?;
+                ret.push(KetoneMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    text_to_rat32("ketones_mmol_per_l", &ketones_text)?,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            let measurement_id = conn.query_row(
+                "INSERT INTO lipid_panel_measurements (\"timestamp\", utc_offset_minutes, total_cholesterol_mmol_per_l, ldl_cholesterol_mmol_per_l, hdl_cholesterol_mmol_per_l, triglycerides_mmol_per_l) VALUES (?1, ?2, ?3, ?4, ?5, ?6) RETURNING id",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), measurement.total_cholesterol_mmol_per_l.as_ref().map(rat32_to_string), measurement.ldl_cholesterol_mmol_per_l.as_ref().map(rat32_to_string), measurement.hdl_cholesterol_mmol_per_l.as_ref().map(rat32_to_string), measurement.triglycerides_mmol_per_l.as_ref().map(rat32_to_string)],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_lipid_panel_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM lipid_panel_measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_lipid_panel_measurement(&self, measurement: &LipidPanelMeasurement) -> Result<(), DatabaseError> {
+        let measurement = *measurement;
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE lipid_panel_measurements SET \"timestamp\"=?1, utc_offset_minutes=?2, total_cholesterol_mmol_per_l=?3, ldl_cholesterol_mmol_per_l=?4, hdl_cholesterol_mmol_per_l=?5, triglycerides_mmol_per_l=?6 WHERE id=?7",
+                params![timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), measurement.total_cholesterol_mmol_per_l.as_ref().map(rat32_to_string), measurement.ldl_cholesterol_mmol_per_l.as_ref().map(rat32_to_string), measurement.hdl_cholesterol_mmol_per_l.as_ref().map(rat32_to_string), measurement.triglycerides_mmol_per_l.as_ref().map(rat32_to_string), measurement.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_lipid_panel_measurements_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<LipidPanelMeasurement>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, total_cholesterol_mmol_per_l, ldl_cholesterol_mmol_per_l, hdl_cholesterol_mmol_per_l, triglycerides_mmol_per_l FROM lipid_panel_measurements WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                let total_text: Option<String> = row.get(3)?;
+                let ldl_text: Option<String> = row.get(4)?;
+                let hdl_text: Option<String> = row.get(5)?;
+                let triglycerides_text: Option<String> = row.get(6)?;
+                ret.push(LipidPanelMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    total_text
+                        .map(|t| text_to_rat32("total_cholesterol_mmol_per_l", &t))
+                        .transpose()?,
+                    ldl_text
+                        .map(|t| text_to_rat32("ldl_cholesterol_mmol_per_l", &t))
+                        .transpose()?,
+                    hdl_text
+                        .map(|t| text_to_rat32("hdl_cholesterol_mmol_per_l", &t))
+                        .transpose()?,
+                    triglycerides_text
+                        .map(|t| text_to_rat32("triglycerides_mmol_per_l", &t))
+                        .transpose()?,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<i64, DatabaseError> {
+        let measurement = measurement.clone();
+        self.run(move |conn| {
+            let (values_json, categories_json) = encode_custom_fields(&measurement);
+            let measurement_id = conn.query_row(
+                "INSERT INTO custom_measurements (metric, \"timestamp\", utc_offset_minutes, \"values\", categories) VALUES (?1, ?2, ?3, ?4, ?5) RETURNING id",
+                params![measurement.metric, timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), values_json, categories_json],
+                |row| row.get(0),
+            )?;
+            Ok(measurement_id)
+        }).await
+    }
+
+    async fn remove_custom_measurement(&self, measurement_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM custom_measurements WHERE id = ?1", params![measurement_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_custom_measurement(&self, measurement: &CustomMeasurement) -> Result<(), DatabaseError> {
+        let measurement = measurement.clone();
+        self.run(move |conn| {
+            let (values_json, categories_json) = encode_custom_fields(&measurement);
+            conn.execute(
+                "UPDATE custom_measurements SET metric=?1, \"timestamp\"=?2, utc_offset_minutes=?3, \"values\"=?4, categories=?5 WHERE id=?6",
+                params![measurement.metric, timestamp_to_text(&measurement.timestamp), utc_offset_minutes(&measurement.timestamp), values_json, categories_json, measurement.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_custom_measurements_since(&self, metric: Option<&str>, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<CustomMeasurement>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        let metric = metric.map(|m| m.to_owned());
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, metric, \"values\", categories FROM custom_measurements WHERE \"timestamp\" >= ?1 AND (?2 IS NULL OR metric = ?2) ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text, metric])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                let values_json: String = row.get(4)?;
+                let categories_json: String = row.get(5)?;
+                let (values, categories) = decode_custom_fields(&values_json, &categories_json)?;
+                ret.push(CustomMeasurement::new(
+                    row.get(0)?,
+                    timestamp,
+                    row.get(3)?,
+                    values,
+                    categories,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_diary_entry(&self, entry: &DiaryEntry) -> Result<i64, DatabaseError> {
+        let entry = entry.clone();
+        self.run(move |conn| {
+            let symptoms = entry.symptoms;
+            let entry_id = conn.query_row(
+                "INSERT INTO diary_entries (\"timestamp\", utc_offset_minutes, headache, dizziness, palpitations, chest_pain, shortness_of_breath, wellbeing_score, notes) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) RETURNING id",
+                params![timestamp_to_text(&entry.timestamp), utc_offset_minutes(&entry.timestamp), symptoms.headache, symptoms.dizziness, symptoms.palpitations, symptoms.chest_pain, symptoms.shortness_of_breath, entry.wellbeing_score, entry.notes],
+                |row| row.get(0),
+            )?;
+            Ok(entry_id)
+        }).await
+    }
+
+    async fn remove_diary_entry(&self, entry_id: i64) -> Result<(), DatabaseError> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM diary_entries WHERE id = ?1", params![entry_id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn update_diary_entry(&self, entry: &DiaryEntry) -> Result<(), DatabaseError> {
+        let entry = entry.clone();
+        self.run(move |conn| {
+            let symptoms = entry.symptoms;
+            conn.execute(
+                "UPDATE diary_entries SET \"timestamp\"=?1, utc_offset_minutes=?2, headache=?3, dizziness=?4, palpitations=?5, chest_pain=?6, shortness_of_breath=?7, wellbeing_score=?8, notes=?9 WHERE id=?10",
+                params![timestamp_to_text(&entry.timestamp), utc_offset_minutes(&entry.timestamp), symptoms.headache, symptoms.dizziness, symptoms.palpitations, symptoms.chest_pain, symptoms.shortness_of_breath, entry.wellbeing_score, entry.notes, entry.id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_diary_entries_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<DiaryEntry>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, headache, dizziness, palpitations, chest_pain, shortness_of_breath, wellbeing_score, notes FROM diary_entries WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                let symptoms = DiarySymptoms {
+                    headache: row.get(3)?,
+                    dizziness: row.get(4)?,
+                    palpitations: row.get(5)?,
+                    chest_pain: row.get(6)?,
+                    shortness_of_breath: row.get(7)?,
+                };
+                ret.push(DiaryEntry::new(
+                    row.get(0)?,
+                    timestamp,
+                    symptoms,
+                    row.get(8)?,
+                    row.get(9)?,
+                ));
+            }
+            Ok(ret)
+        }).await
+    }
+
+    async fn add_alert(&self, alert: &Alert) -> Result<i64, DatabaseError> {
+        let alert = alert.clone();
+        self.run(move |conn| {
+            let alert_id = conn.query_row(
+                "INSERT INTO alerts (\"timestamp\", utc_offset_minutes, rule_name, measurement_kind, field, \"value\", threshold, message) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) RETURNING id",
+                params![timestamp_to_text(&alert.timestamp), utc_offset_minutes(&alert.timestamp), alert.rule_name, alert.measurement_kind, alert.field, alert.value, alert.threshold, alert.message],
+                |row| row.get(0),
+            )?;
+            Ok(alert_id)
+        }).await
+    }
+
+    async fn get_alerts_since(&self, start_time: DateTime<Utc>, display_timezone: Option<Tz>) -> Result<Vec<Alert>, DatabaseError> {
+        let start_text = start_time.format(TIMESTAMP_FORMAT).to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT id, \"timestamp\", utc_offset_minutes, rule_name, measurement_kind, field, \"value\", threshold, message FROM alerts WHERE \"timestamp\" >= ?1 ORDER BY \"timestamp\"",
+            )?;
+            let mut rows = statement.query(params![start_text])?;
+            let mut ret = Vec::new();
+            while let Some(row) = rows.next()? {
+                let timestamp_text: String = row.get(1)?;
+                let timestamp = text_to_timestamp(&timestamp_text, row.get(2)?, display_timezone)?;
+                ret.push(Alert {
+                    id: row.get(0)?,
+                    timestamp,
+                    rule_name: row.get(3)?,
+                    measurement_kind: row.get(4)?,
+                    field: row.get(5)?,
+                    value: row.get(6)?,
+                    threshold: row.get(7)?,
+                    message: row.get(8)?,
+                });
+            }
+            Ok(ret)
+        }).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    use crate::migrations::{MIGRATIONS, migrate_storage};
+
+    async fn migrated_storage() -> SqliteStorage {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let applied = migrate_storage(&storage, false).await.unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        storage
+    }
+
+    fn timestamp() -> DateTime<FixedOffset> {
+        // an unusual offset and sub-microsecond precision
+        FixedOffset::east_opt(5 * 3600 + 45 * 60).unwrap()
+            .with_ymd_and_hms(2024, 2, 29, 23, 59, 58).unwrap()
+            + Duration::nanoseconds(123_456_789)
+    }
+
+    #[tokio::test]
+    async fn migrations_idempotent() {
+        let storage = migrated_storage().await;
+        assert_eq!(storage.get_applied_migration_versions().await.unwrap(), vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+        assert!(migrate_storage(&storage, false).await.unwrap().is_empty());
+        assert!(!storage.apply_migration(&MIGRATIONS[0]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let storage = migrated_storage().await;
+        let start_time = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        let mut bp = BloodPressureMeasurement::new(-1, timestamp(), 120, 80, 60, Some(98));
+        bp.id = storage.add_blood_pressure_measurement(&bp).await.unwrap();
+        assert_eq!(storage.get_blood_pressure_measurements_since(start_time, None).await.unwrap(), vec![bp]);
+        bp.spo2_percent = None;
+        storage.update_blood_pressure_measurement(&bp).await.unwrap();
+        assert_eq!(storage.get_blood_pressure_measurements_since(start_time, None).await.unwrap(), vec![bp]);
+
+        let mut mass = BodyMassMeasurement::new(-1, timestamp(), Rational32::new(2401, 30), Some(Rational32::new(-1, 3)), None, BodyComposition::default(), None);
+        mass.id = storage.add_mass_measurement(&mass).await.unwrap();
+        assert_eq!(storage.get_mass_measurements_since(start_time, None).await.unwrap(), vec![mass]);
+
+        let mut loc = BodyTemperatureLocation::new(-1, "rectal".to_owned(), None, None, None);
+        loc.id = storage.add_temperature_location(&loc).await.unwrap();
+        loc.name = "oral".to_owned();
+        storage.update_temperature_location(&loc).await.unwrap();
+        assert_eq!(storage.get_temperature_locations().await.unwrap(), vec![loc.clone()]);
+
+        let mut temperature = BodyTemperatureMeasurement::new(-1, timestamp(), loc.id, Rational32::new(1117, 30), None, None);
+        temperature.id = storage.add_temperature_measurement(&temperature).await.unwrap();
+        assert_eq!(storage.get_temperature_measurements_since(start_time, None).await.unwrap(), vec![temperature]);
+        // the location is still referenced
+        assert!(storage.remove_temperature_location(loc.id).await.is_err());
+
+        let mut sugar = BloodSugarMeasurement::new(-1, timestamp(), Rational32::new(100, 18));
+        sugar.id = storage.add_blood_sugar_measurement(&sugar).await.unwrap();
+        assert_eq!(storage.get_blood_sugar_measurements_since(start_time, None).await.unwrap(), vec![sugar]);
+
+        let mut long_term_sugar = LongTermBloodSugarMeasurement::new(-1, timestamp(), Rational32::new(4279, 100));
+        long_term_sugar.id = storage.add_long_term_blood_sugar_measurement(&long_term_sugar).await.unwrap();
+        assert_eq!(storage.get_long_term_blood_sugar_measurements_since(start_time, None).await.unwrap(), vec![long_term_sugar]);
+        storage.remove_long_term_blood_sugar_measurement(long_term_sugar.id).await.unwrap();
+        assert!(storage.get_long_term_blood_sugar_measurements_since(start_time, None).await.unwrap().is_empty());
+
+        let mut alert = Alert {
+            id: -1,
+            timestamp: timestamp(),
+            rule_name: "low sugar".to_owned(),
+            measurement_kind: "sugar".to_owned(),
+            field: "sugar_mmol_per_l".to_owned(),
+            value: 100.0 / 18.0,
+            threshold: 3.9,
+            message: "low sugar: blood sugar 5.56 below 3.9".to_owned(),
+        };
+        alert.id = storage.add_alert(&alert).await.unwrap();
+        assert_eq!(storage.get_alerts_since(start_time, None).await.unwrap(), vec![alert]);
+
+        // the start time is compared as a point in time, not as text in the original offset
+        let after = timestamp().with_timezone(&Utc) + Duration::nanoseconds(1);
+        assert!(storage.get_blood_pressure_measurements_since(after, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn conformance() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        crate::database::testing::exercise_storage(&storage).await;
+    }
+}
@@ -0,0 +1,410 @@
+//! Test support: a disposable PostgreSQL cluster and a conformance test shared by all storage
+//! backends.
+//!
+//! The cluster is created with `initdb` in a temporary directory on first use and only listens
+//! on a Unix socket inside that directory. A watchdog shell process shuts it down and deletes
+//! the directory once the test process has exited. Setting `BEEPEE_TEST_POSTGRES` to a
+//! connection string uses an existing server instead; its `beepee_test_*` databases are left
+//! behind. If neither works (e.g. because `initdb` refuses to run as root), the PostgreSQL tests
+//! fail unless `BEEPEE_SKIP_POSTGRES_TESTS=1` is set, in which case they are skipped. Tests that
+//! can run on another backend use [`create_test_database_if_available`] and fall back to it.
+
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, FixedOffset, TimeZone, Utc};
+use num_rational::Rational32;
+use once_cell::sync::Lazy;
+use tokio_postgres::NoTls;
+
+use crate::database::{DatabaseError, Storage};
+use crate::migrations::{MIGRATIONS, migrate_storage};
+use crate::model::{
+    Alert, BloodPressureMeasurement, BloodSugarMeasurement, BodyComposition, BodyMassMeasurement,
+    BodyTemperatureLocation, BodyTemperatureMeasurement, CustomMeasurement, DiaryEntry, DiarySymptoms, HeightMeasurement,
+    KetoneMeasurement, LipidPanelMeasurement, LongTermBloodSugarMeasurement, PeakFlowMeasurement,
+};
+
+
+const CLUSTER_PORT: u16 = 5432;
+const CLUSTER_USER: &str = "beepee";
+
+/// Starts the cluster, then waits for the postgres process to go away or for the test process
+/// (`$3`) to exit, whichever happens first.
+const WATCHDOG_SCRIPT: &str = r#"
+"$1" -D "$2/data" -k "$2" -p "$4" -c listen_addresses= -c fsync=off > "$2/postgres.log" 2>&1 &
+pg=$!
+while kill -0 "$3" 2> /dev/null && kill -0 "$pg" 2> /dev/null; do sleep 1; done
+kill -INT "$pg" 2> /dev/null
+wait "$pg"
+rm -rf "$2"
+"#;
+
+/// The connection string of the server to test against, `None` if the PostgreSQL tests are
+/// skipped, or why no server is available.
+static BASE_CONN_STRING: Lazy<Result<Option<String>, String>> = Lazy::new(|| {
+    if env::var("BEEPEE_SKIP_POSTGRES_TESTS").is_ok_and(|v| v == "1") {
+        eprintln!("skipping PostgreSQL tests as BEEPEE_SKIP_POSTGRES_TESTS=1");
+        return Ok(None);
+    }
+    if let Ok(conn_string) = env::var("BEEPEE_TEST_POSTGRES") {
+        return Ok(Some(conn_string));
+    }
+    start_cluster()
+        .map(|dir| Some(format!(
+            "host={} port={} user={} dbname=postgres",
+            dir.display(), CLUSTER_PORT, CLUSTER_USER,
+        )))
+});
+static DATABASE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+
+fn find_pg_binary(name: &str) -> Option<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Ok(output) = Command::new("pg_config").arg("--bindir").output() {
+        if output.status.success() {
+            dirs.push(PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()));
+        }
+    }
+    if let Some(path) = env::var_os("PATH") {
+        dirs.extend(env::split_paths(&path));
+    }
+    if let Ok(entries) = fs::read_dir("/usr/lib/postgresql") {
+        let mut versions: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().join("bin"))
+            .collect();
+        versions.sort();
+        versions.reverse();
+        dirs.extend(versions);
+    }
+
+    dirs.into_iter()
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn start_cluster() -> Result<PathBuf, String> {
+    let initdb = find_pg_binary("initdb")
+        .ok_or("initdb not found")?;
+    let postgres = find_pg_binary("postgres")
+        .ok_or("postgres not found")?;
+
+    let dir = env::temp_dir().join(format!("beepee-test-{}", std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)
+            .map_err(|e| format!("failed to clean up {}: {}", dir.display(), e))?;
+    }
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+
+    let output = Command::new(&initdb)
+        .arg("--pgdata").arg(dir.join("data"))
+        .arg("--username").arg(CLUSTER_USER)
+        .args(["--auth", "trust", "--encoding", "UTF8", "--no-sync"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("failed to run initdb: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(format!("initdb failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    Command::new("sh")
+        .arg("-c").arg(WATCHDOG_SCRIPT)
+        .arg("beepee-test-watchdog")
+        .arg(&postgres)
+        .arg(&dir)
+        .arg(std::process::id().to_string())
+        .arg(CLUSTER_PORT.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to start postgres: {}", e))?;
+
+    wait_for_socket(&dir)?;
+    Ok(dir)
+}
+
+fn wait_for_socket(dir: &Path) -> Result<(), String> {
+    let socket = dir.join(format!(".s.PGSQL.{}", CLUSTER_PORT));
+    for _ in 0..300 {
+        if socket.exists() {
+            return Ok(());
+        }
+        thread::sleep(StdDuration::from_millis(100));
+    }
+    let log = fs::read_to_string(dir.join("postgres.log"))
+        .unwrap_or_default();
+    Err(format!("postgres did not start: {}", log.trim()))
+}
+
+/// Returns the connection string of the server to test against, `None` if the PostgreSQL tests
+/// are skipped, or why no server is available.
+async fn base_conn_string() -> Result<Option<String>, String> {
+    tokio::task::spawn_blocking(|| BASE_CONN_STRING.clone())
+        .await
+        .unwrap()
+}
+
+/// Creates a new, empty database containing the `beepee` schema and returns its connection
+/// string, or `None` if the PostgreSQL tests are skipped.
+///
+/// Panics if no PostgreSQL server is available.
+pub(crate) async fn create_test_database() -> Option<String> {
+    let base_conn_string = base_conn_string()
+        .await
+        .unwrap_or_else(|reason| panic!(
+            "no PostgreSQL server for the tests ({}); set BEEPEE_TEST_POSTGRES to a connection string, or BEEPEE_SKIP_POSTGRES_TESTS=1 to skip these tests",
+            reason,
+        ))?;
+    Some(create_database_on(&base_conn_string).await)
+}
+
+/// Like [`create_test_database`], but returns `None` instead of panicking if no PostgreSQL server
+/// is available, for tests that can fall back to another backend.
+pub(crate) async fn create_test_database_if_available() -> Option<String> {
+    let base_conn_string = base_conn_string()
+        .await
+        .ok()
+        .flatten()?;
+    Some(create_database_on(&base_conn_string).await)
+}
+
+async fn create_database_on(base_conn_string: &str) -> String {
+    let database_name = format!(
+        "beepee_test_{}_{}",
+        std::process::id(), DATABASE_COUNTER.fetch_add(1, Ordering::SeqCst),
+    );
+
+    let (client, connection) = tokio_postgres::connect(base_conn_string, NoTls)
+        .await.unwrap();
+    let connection_task = tokio::spawn(connection);
+    client.batch_execute(&format!("DROP DATABASE IF EXISTS {}", database_name))
+        .await.unwrap();
+    client.batch_execute(&format!("CREATE DATABASE {}", database_name))
+        .await.unwrap();
+    drop(client);
+    connection_task.await.unwrap().unwrap();
+
+    // later keys override earlier ones
+    let conn_string = format!("{} dbname={}", base_conn_string, database_name);
+    let (client, connection) = tokio_postgres::connect(&conn_string, NoTls)
+        .await.unwrap();
+    let connection_task = tokio::spawn(connection);
+    client.batch_execute("CREATE SCHEMA beepee")
+        .await.unwrap();
+    drop(client);
+    connection_task.await.unwrap().unwrap();
+
+    conn_string
+}
+
+
+fn timestamp(day: u32) -> DateTime<FixedOffset> {
+    // microsecond precision is the most that every backend keeps
+    FixedOffset::east_opt(5 * 3600 + 45 * 60).unwrap()
+        .with_ymd_and_hms(2024, 2, day, 23, 59, 58).unwrap()
+        + Duration::microseconds(123_456)
+}
+
+/// Migrates the given empty storage (if it has a schema) and runs every operation of the [`Storage`] trait against
+/// it.
+pub(crate) async fn exercise_storage<S: Storage>(storage: &S) {
+    migrate_storage(storage, false).await.unwrap();
+    let versions: Vec<i32> = MIGRATIONS.iter().map(|m| m.version).collect();
+    assert_eq!(storage.get_applied_migration_versions().await.unwrap(), versions);
+    assert!(migrate_storage(storage, false).await.unwrap().is_empty());
+    assert!(!storage.apply_migration(&MIGRATIONS[0]).await.unwrap());
+
+    let start_time = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+    let later_start_time = Utc.with_ymd_and_hms(2024, 2, 15, 0, 0, 0).unwrap();
+
+    // blood pressure
+    let mut bp_old = BloodPressureMeasurement::new(-1, timestamp(10), 120, 80, 60, Some(98));
+    bp_old.id = storage.add_blood_pressure_measurement(&bp_old).await.unwrap();
+    let mut bp = BloodPressureMeasurement::new(-1, timestamp(20), 135, 85, 72, None);
+    bp.id = storage.add_blood_pressure_measurement(&bp).await.unwrap();
+    assert_ne!(bp_old.id, bp.id);
+    assert_eq!(storage.get_blood_pressure_measurements_since(start_time, None).await.unwrap(), vec![bp_old, bp]);
+    assert_eq!(storage.get_blood_pressure_measurements_since(later_start_time, None).await.unwrap(), vec![bp]);
+    bp.spo2_percent = Some(95);
+    bp.systolic_mmhg = 130;
+    storage.update_blood_pressure_measurement(&bp).await.unwrap();
+    storage.remove_blood_pressure_measurement(bp_old.id).await.unwrap();
+    assert_eq!(storage.get_blood_pressure_measurements_since(start_time, None).await.unwrap(), vec![bp]);
+
+    // body mass
+    let composition = BodyComposition {
+        body_fat_percent: Some(Rational32::new(223, 10)),
+        muscle_mass_kg: Some(Rational32::new(1193, 20)),
+        visceral_fat_rating: Some(Rational32::new(9, 1)),
+        body_water_percent: None,
+    };
+    let mut mass = BodyMassMeasurement::new(-1, timestamp(20), Rational32::new(2401, 30), Some(Rational32::new(1, 3)), Some(Rational32::new(1021, 10)), composition, None);
+    mass.id = storage.add_mass_measurement(&mass).await.unwrap();
+    assert_eq!(storage.get_mass_measurements_since(start_time, None).await.unwrap(), vec![mass]);
+    // 100 lb; denominators this large must survive every backend unchanged
+    mass.mass_kg = Rational32::new(45_359_237, 1_000_000);
+    mass.waist_circum_cm = None;
+    mass.hip_circum_cm = None;
+    mass.composition.body_water_percent = Some(Rational32::new(553, 10));
+    storage.update_mass_measurement(&mass).await.unwrap();
+    assert_eq!(storage.get_mass_measurements_since(start_time, None).await.unwrap(), vec![mass]);
+    storage.remove_mass_measurement(mass.id).await.unwrap();
+    assert!(storage.get_mass_measurements_since(start_time, None).await.unwrap().is_empty());
+
+    // heights are returned regardless of their age
+    let mut height_old = HeightMeasurement::new(-1, timestamp(20) - Duration::days(400), Rational32::new(1523, 10));
+    height_old.id = storage.add_height_measurement(&height_old).await.unwrap();
+    let mut height = HeightMeasurement::new(-1, timestamp(20), Rational32::new(155, 1));
+    height.id = storage.add_height_measurement(&height).await.unwrap();
+    assert_eq!(storage.get_height_measurements(None).await.unwrap(), vec![height_old, height]);
+    storage.remove_height_measurement(height_old.id).await.unwrap();
+    assert_eq!(storage.get_height_measurements(None).await.unwrap(), vec![height]);
+
+    // temperature locations and measurements
+    let mut loc = BodyTemperatureLocation::new(-1, "rectal".to_owned(), Some(Rational32::new(-1, 2)), Some(Rational32::new(366, 10)), Some(Rational32::new(38, 1)));
+    loc.id = storage.add_temperature_location(&loc).await.unwrap();
+    let mut spare_loc = BodyTemperatureLocation::new(-1, "forehead".to_owned(), None, None, None);
+    spare_loc.id = storage.add_temperature_location(&spare_loc).await.unwrap();
+    loc.name = "oral".to_owned();
+    loc.normal_max_celsius = Some(Rational32::new(1139, 30));
+    storage.update_temperature_location(&loc).await.unwrap();
+    storage.remove_temperature_location(spare_loc.id).await.unwrap();
+    assert_eq!(storage.get_temperature_locations().await.unwrap(), vec![loc.clone()]);
+
+    let mut temperature = BodyTemperatureMeasurement::new(-1, timestamp(20), loc.id, Rational32::new(1117, 30), None, None);
+    temperature.id = storage.add_temperature_measurement(&temperature).await.unwrap();
+    assert_eq!(storage.get_temperature_measurements_since(start_time, None).await.unwrap(), vec![temperature]);
+    temperature.temperature_celsius = Rational32::new(-1, 20);
+    storage.update_temperature_measurement(&temperature).await.unwrap();
+    assert_eq!(storage.get_temperature_measurements_since(start_time, None).await.unwrap(), vec![temperature]);
+    // the location is still referenced, and unknown locations are refused
+    assert!(matches!(storage.remove_temperature_location(loc.id).await, Err(DatabaseError::StillReferenced(_, id)) if id == loc.id));
+    let unknown_loc = BodyTemperatureMeasurement::new(-1, timestamp(20), spare_loc.id, Rational32::new(37, 1), None, None);
+    assert!(storage.add_temperature_measurement(&unknown_loc).await.is_err());
+    storage.remove_temperature_measurement(temperature.id).await.unwrap();
+    assert!(storage.get_temperature_measurements_since(start_time, None).await.unwrap().is_empty());
+    storage.remove_temperature_location(loc.id).await.unwrap();
+    assert!(storage.get_temperature_locations().await.unwrap().is_empty());
+
+    // blood sugar
+    let mut sugar = BloodSugarMeasurement::new(-1, timestamp(20), Rational32::new(100, 18));
+    sugar.id = storage.add_blood_sugar_measurement(&sugar).await.unwrap();
+    assert_eq!(storage.get_blood_sugar_measurements_since(start_time, None).await.unwrap(), vec![sugar]);
+    sugar.sugar_mmol_per_l = Rational32::new(i32::MAX - 1, i32::MAX);
+    storage.update_blood_sugar_measurement(&sugar).await.unwrap();
+    assert_eq!(storage.get_blood_sugar_measurements_since(start_time, None).await.unwrap(), vec![sugar]);
+    storage.remove_blood_sugar_measurement(sugar.id).await.unwrap();
+    assert!(storage.get_blood_sugar_measurements_since(start_time, None).await.unwrap().is_empty());
+
+    // long-term blood sugar
+    let mut long_term_sugar = LongTermBloodSugarMeasurement::new(-1, timestamp(20), Rational32::new(4279, 100));
+    long_term_sugar.id = storage.add_long_term_blood_sugar_measurement(&long_term_sugar).await.unwrap();
+    assert_eq!(storage.get_long_term_blood_sugar_measurements_since(start_time, None).await.unwrap(), vec![long_term_sugar]);
+    // 6.575 % DCCT
+    long_term_sugar.hba1c_mmol_per_mol = Rational32::new(9_694_023, 200_000);
+    storage.update_long_term_blood_sugar_measurement(&long_term_sugar).await.unwrap();
+    assert_eq!(storage.get_long_term_blood_sugar_measurements_since(start_time, None).await.unwrap(), vec![long_term_sugar]);
+    storage.remove_long_term_blood_sugar_measurement(long_term_sugar.id).await.unwrap();
+    assert!(storage.get_long_term_blood_sugar_measurements_since(start_time, None).await.unwrap().is_empty());
+
+    // peak flow
+    let mut peak_flow = PeakFlowMeasurement::new(-1, timestamp(20), 480);
+    peak_flow.id = storage.add_peak_flow_measurement(&peak_flow).await.unwrap();
+    assert_eq!(storage.get_peak_flow_measurements_since(start_time, None).await.unwrap(), vec![peak_flow]);
+    peak_flow.peak_flow_l_per_min = 510;
+    storage.update_peak_flow_measurement(&peak_flow).await.unwrap();
+    assert_eq!(storage.get_peak_flow_measurements_since(start_time, None).await.unwrap(), vec![peak_flow]);
+    storage.remove_peak_flow_measurement(peak_flow.id).await.unwrap();
+    assert!(storage.get_peak_flow_measurements_since(start_time, None).await.unwrap().is_empty());
+
+    // ketones
+    let mut ketones = KetoneMeasurement::new(-1, timestamp(20), Rational32::new(3, 10));
+    ketones.id = storage.add_ketone_measurement(&ketones).await.unwrap();
+    assert_eq!(storage.get_ketone_measurements_since(start_time, None).await.unwrap(), vec![ketones]);
+    ketones.ketones_mmol_per_l = Rational32::from_integer(0);
+    storage.update_ketone_measurement(&ketones).await.unwrap();
+    assert_eq!(storage.get_ketone_measurements_since(start_time, None).await.unwrap(), vec![ketones]);
+    storage.remove_ketone_measurement(ketones.id).await.unwrap();
+    assert!(storage.get_ketone_measurements_since(start_time, None).await.unwrap().is_empty());
+
+    // lipid panel, with values left out
+    let mut lipids = LipidPanelMeasurement::new(
+        -1, timestamp(20), Some(Rational32::new(52, 10)), None, Some(Rational32::new(13, 10)), Some(Rational32::new(17, 10)),
+    );
+    lipids.id = storage.add_lipid_panel_measurement(&lipids).await.unwrap();
+    assert_eq!(storage.get_lipid_panel_measurements_since(start_time, None).await.unwrap(), vec![lipids]);
+    lipids.ldl_cholesterol_mmol_per_l = Some(Rational32::new(33, 10));
+    lipids.triglycerides_mmol_per_l = None;
+    storage.update_lipid_panel_measurement(&lipids).await.unwrap();
+    assert_eq!(storage.get_lipid_panel_measurements_since(start_time, None).await.unwrap(), vec![lipids]);
+    storage.remove_lipid_panel_measurement(lipids.id).await.unwrap();
+    assert!(storage.get_lipid_panel_measurements_since(start_time, None).await.unwrap().is_empty());
+
+    // custom metrics, filtered by metric
+    let mut spirometry = CustomMeasurement::new(
+        -1, timestamp(21), "spirometry".to_owned(),
+        BTreeMap::from([("fev1".to_owned(), Rational32::new(321, 100)), ("fvc".to_owned(), Rational32::new(41, 10))]),
+        BTreeMap::from([("position".to_owned(), "sitting".to_owned())]),
+    );
+    spirometry.id = storage.add_custom_measurement(&spirometry).await.unwrap();
+    let mut pain = CustomMeasurement::new(-1, timestamp(22), "pain".to_owned(), BTreeMap::from([("score".to_owned(), Rational32::from_integer(3))]), BTreeMap::new());
+    pain.id = storage.add_custom_measurement(&pain).await.unwrap();
+    assert_eq!(storage.get_custom_measurements_since(Some("spirometry"), start_time, None).await.unwrap(), vec![spirometry.clone()]);
+    assert_eq!(storage.get_custom_measurements_since(None, start_time, None).await.unwrap(), vec![spirometry.clone(), pain.clone()]);
+    spirometry.values.remove("fvc");
+    spirometry.categories.clear();
+    storage.update_custom_measurement(&spirometry).await.unwrap();
+    assert_eq!(storage.get_custom_measurements_since(Some("spirometry"), start_time, None).await.unwrap(), vec![spirometry.clone()]);
+    storage.remove_custom_measurement(spirometry.id).await.unwrap();
+    storage.remove_custom_measurement(pain.id).await.unwrap();
+    assert!(storage.get_custom_measurements_since(None, start_time, None).await.unwrap().is_empty());
+
+    // diary entries, with and without a wellbeing score
+    let symptoms = DiarySymptoms { headache: true, chest_pain: true, ..DiarySymptoms::default() };
+    let mut diary_entry = DiaryEntry::new(-1, timestamp(20), symptoms, Some(4), "after climbing the stairs".to_owned());
+    diary_entry.id = storage.add_diary_entry(&diary_entry).await.unwrap();
+    assert_eq!(storage.get_diary_entries_since(start_time, None).await.unwrap(), vec![diary_entry.clone()]);
+    diary_entry.symptoms = DiarySymptoms { shortness_of_breath: true, ..DiarySymptoms::default() };
+    diary_entry.wellbeing_score = None;
+    diary_entry.notes.clear();
+    storage.update_diary_entry(&diary_entry).await.unwrap();
+    assert_eq!(storage.get_diary_entries_since(start_time, None).await.unwrap(), vec![diary_entry.clone()]);
+    storage.remove_diary_entry(diary_entry.id).await.unwrap();
+    assert!(storage.get_diary_entries_since(start_time, None).await.unwrap().is_empty());
+
+    // alerts
+    let mut alert = Alert {
+        id: -1,
+        timestamp: timestamp(20),
+        rule_name: "low sugar".to_owned(),
+        measurement_kind: "sugar".to_owned(),
+        field: "sugar_mmol_per_l".to_owned(),
+        value: 100.0 / 18.0,
+        threshold: 3.9,
+        message: "low sugar: blood sugar 5.56 below 3.9".to_owned(),
+    };
+    alert.id = storage.add_alert(&alert).await.unwrap();
+    assert_eq!(storage.get_alerts_since(start_time, None).await.unwrap(), vec![alert.clone()]);
+
+    // the stored offset takes precedence over the display timezone
+    let tz = chrono_tz::America::St_Johns;
+    let converted = storage.get_blood_pressure_measurements_since(start_time, Some(tz)).await.unwrap();
+    assert_eq!(converted, vec![bp]);
+    assert_eq!(converted[0].timestamp.offset(), bp.timestamp.offset());
+
+    // the start time is compared as a point in time, not as text in the original offset
+    let after = timestamp(20).with_timezone(&Utc) + Duration::microseconds(1);
+    assert!(storage.get_blood_pressure_measurements_since(after, None).await.unwrap().is_empty());
+    assert!(storage.get_alerts_since(after, None).await.unwrap().is_empty());
+}
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Serialize;
+
+use crate::model::{BloodPressureMeasurement, BloodSugarMeasurement, DiaryEntry, Measurement};
+
+
+/// How many hours before or after a diary entry a reading may have been taken to be shown next to
+/// it.
+pub(crate) const NEARBY_HOURS: i64 = 3;
+
+
+/// A diary entry with the readings taken around the time it was written, so that symptoms can be
+/// correlated with values.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct DiaryEntryWithReadings {
+    pub entry: DiaryEntry,
+    pub blood_pressure: Vec<BloodPressureMeasurement>,
+    pub blood_sugar: Vec<BloodSugarMeasurement>,
+}
+impl DiaryEntryWithReadings {
+    pub fn collect(
+        entry: &DiaryEntry,
+        blood_pressure: &[BloodPressureMeasurement],
+        blood_sugar: &[BloodSugarMeasurement],
+    ) -> Self {
+        Self {
+            entry: entry.clone(),
+            blood_pressure: nearby(entry.timestamp, blood_pressure),
+            blood_sugar: nearby(entry.timestamp, blood_sugar),
+        }
+    }
+
+    pub fn collect_all(
+        entries: &[DiaryEntry],
+        blood_pressure: &[BloodPressureMeasurement],
+        blood_sugar: &[BloodSugarMeasurement],
+    ) -> Vec<Self> {
+        entries.iter()
+            .map(|e| Self::collect(e, blood_pressure, blood_sugar))
+            .collect()
+    }
+}
+
+
+/// Returns the measurements taken at most [`NEARBY_HOURS`] before or after `timestamp`.
+fn nearby<M: Measurement + Clone>(timestamp: DateTime<FixedOffset>, measurements: &[M]) -> Vec<M> {
+    let window = Duration::hours(NEARBY_HOURS);
+    measurements.iter()
+        .filter(|m| (m.timestamp() - timestamp).abs() <= window)
+        .cloned()
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use num_rational::Rational32;
+
+    use crate::model::DiarySymptoms;
+
+    fn at(hours: i64) -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(3600).unwrap()
+            .with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+            + Duration::hours(hours)
+    }
+
+    #[test]
+    fn readings_near_entries() {
+        let symptoms = DiarySymptoms { dizziness: true, ..DiarySymptoms::default() };
+        let entries = [
+            DiaryEntry::new(1, at(0), symptoms, Some(5), String::new()),
+            DiaryEntry::new(2, at(24), DiarySymptoms::default(), Some(8), String::new()),
+        ];
+        let blood_pressure = [
+            BloodPressureMeasurement::new(1, at(-4), 120, 80, 60, None),
+            BloodPressureMeasurement::new(2, at(-3), 150, 95, 90, None),
+            BloodPressureMeasurement::new(3, at(2), 140, 90, 80, None),
+            BloodPressureMeasurement::new(4, at(4), 125, 80, 65, None),
+        ];
+        let blood_sugar = [
+            BloodSugarMeasurement::new(1, at(1), Rational32::new(31, 10)),
+            BloodSugarMeasurement::new(2, at(23), Rational32::new(55, 10)),
+        ];
+
+        let collected = DiaryEntryWithReadings::collect_all(&entries, &blood_pressure, &blood_sugar);
+        assert_eq!(collected.len(), 2);
+        assert_eq!(collected[0].entry, entries[0]);
+        assert_eq!(collected[0].blood_pressure, vec![blood_pressure[1], blood_pressure[2]]);
+        assert_eq!(collected[0].blood_sugar, vec![blood_sugar[0]]);
+        assert!(collected[1].blood_pressure.is_empty());
+        assert_eq!(collected[1].blood_sugar, vec![blood_sugar[1]]);
+    }
+}
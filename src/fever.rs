@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, FixedOffset};
+use num_rational::Rational32;
+use num_traits::{CheckedAdd, CheckedSub, Zero};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{BodyTemperatureLocation, BodyTemperatureMeasurement};
+
+
+/// The lowest normal core body temperature, used for locations without their own range.
+const DEFAULT_CORE_NORMAL_MIN_CELSIUS: Rational32 = Rational32::new_raw(35, 1);
+
+/// The highest normal core body temperature, used for locations without their own range.
+const DEFAULT_CORE_NORMAL_MAX_CELSIUS: Rational32 = Rational32::new_raw(379, 10);
+
+/// Fever readings further apart than this belong to different episodes.
+const MAX_EPISODE_GAP_HOURS: i64 = 24;
+
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TemperatureStatus {
+    Hypothermia,
+    Normal,
+    Fever,
+}
+impl TemperatureStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hypothermia => "hypothermia",
+            Self::Normal => "normal",
+            Self::Fever => "fever",
+        }
+    }
+}
+
+
+/// The normal range of readings at a temperature location and how they relate to core body
+/// temperature.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct ReferenceRange {
+    pub core_offset_celsius: Rational32,
+    pub normal_min_celsius: Rational32,
+    pub normal_max_celsius: Rational32,
+}
+impl ReferenceRange {
+    /// Returns the reference range of the given location.
+    ///
+    /// Limits that are not configured on the location are derived from the default range of core
+    /// body temperature, shifted by the location's offset. Returns `None` if such a limit cannot be
+    /// represented as a 32-bit rational number.
+    pub fn for_location(location: &BodyTemperatureLocation) -> Option<Self> {
+        let core_offset_celsius = location.core_offset_celsius
+            .unwrap_or_else(Rational32::zero);
+        let normal_min_celsius = match location.normal_min_celsius {
+            Some(min) => min,
+            None => DEFAULT_CORE_NORMAL_MIN_CELSIUS.checked_sub(&core_offset_celsius)?,
+        };
+        let normal_max_celsius = match location.normal_max_celsius {
+            Some(max) => max,
+            None => DEFAULT_CORE_NORMAL_MAX_CELSIUS.checked_sub(&core_offset_celsius)?,
+        };
+        Some(Self {
+            core_offset_celsius,
+            normal_min_celsius,
+            normal_max_celsius,
+        })
+    }
+
+    /// Returns the core temperature corresponding to a reading at this location, or `None` if it
+    /// cannot be represented as a 32-bit rational number.
+    pub fn core_temperature(&self, temperature_celsius: Rational32) -> Option<Rational32> {
+        temperature_celsius.checked_add(&self.core_offset_celsius)
+    }
+
+    pub fn classify(&self, temperature_celsius: Rational32) -> TemperatureStatus {
+        if temperature_celsius < self.normal_min_celsius {
+            TemperatureStatus::Hypothermia
+        } else if temperature_celsius > self.normal_max_celsius {
+            TemperatureStatus::Fever
+        } else {
+            TemperatureStatus::Normal
+        }
+    }
+}
+
+
+/// Fills in the core temperature and status of each measurement from the reference range of its
+/// location. Measurements taken at unknown locations or at locations whose reference range cannot
+/// be derived are left alone, and the core temperature is left empty if it overflows.
+pub(crate) fn normalize_measurements(measurements: &mut [BodyTemperatureMeasurement], locations: &[BodyTemperatureLocation]) {
+    let location_id_to_range: HashMap<i64, ReferenceRange> = locations.iter()
+        .filter_map(|l| ReferenceRange::for_location(l).map(|r| (l.id, r)))
+        .collect();
+    for measurement in measurements {
+        if let Some(range) = location_id_to_range.get(&measurement.location_id) {
+            measurement.core_temperature_celsius = range.core_temperature(measurement.temperature_celsius);
+            measurement.status = Some(range.classify(measurement.temperature_celsius));
+        }
+    }
+}
+
+
+/// A run of consecutive fever readings.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct FeverEpisode {
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub onset: DateTime<FixedOffset>,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub last_fever_reading: DateTime<FixedOffset>,
+    /// The first normal reading after the episode, if one has been taken yet.
+    #[serde(with = "crate::ser_de::serde_datetime_offset_opt")] pub resolved: Option<DateTime<FixedOffset>>,
+    pub duration_minutes: i64,
+    pub reading_count: usize,
+    /// The reading with the highest core temperature during the episode.
+    pub peak: BodyTemperatureMeasurement,
+}
+impl FeverEpisode {
+    fn start(measurement: &BodyTemperatureMeasurement) -> Self {
+        Self {
+            onset: measurement.timestamp,
+            last_fever_reading: measurement.timestamp,
+            resolved: None,
+            duration_minutes: 0,
+            reading_count: 1,
+            peak: *measurement,
+        }
+    }
+
+    fn extend(&mut self, measurement: &BodyTemperatureMeasurement) {
+        self.last_fever_reading = measurement.timestamp;
+        self.reading_count += 1;
+        if measurement.core_temperature_celsius > self.peak.core_temperature_celsius {
+            self.peak = *measurement;
+        }
+    }
+
+    fn finish(mut self, resolved: Option<DateTime<FixedOffset>>) -> Self {
+        self.resolved = resolved;
+        let end = resolved.unwrap_or(self.last_fever_reading);
+        self.duration_minutes = (end - self.onset).num_minutes();
+        self
+    }
+
+    /// Detects fever episodes in the given measurements, which must be sorted by ascending
+    /// timestamp and normalized using [`normalize_measurements`].
+    ///
+    /// An episode starts with a fever reading and ends with the next reading that is not one. If
+    /// the next fever reading is more than a day later, the episode is considered to have ended
+    /// at an unknown time.
+    pub fn detect(measurements: &[BodyTemperatureMeasurement]) -> Vec<Self> {
+        let max_gap = Duration::hours(MAX_EPISODE_GAP_HOURS);
+
+        let mut episodes = Vec::new();
+        let mut current: Option<FeverEpisode> = None;
+        for measurement in measurements {
+            let is_fever = measurement.status == Some(TemperatureStatus::Fever);
+            current = match (current, is_fever) {
+                (None, false) => None,
+                (None, true) => Some(FeverEpisode::start(measurement)),
+                (Some(episode), false) => {
+                    let resolved = (measurement.timestamp - episode.last_fever_reading <= max_gap)
+                        .then_some(measurement.timestamp);
+                    episodes.push(episode.finish(resolved));
+                    None
+                },
+                (Some(mut episode), true) => {
+                    if measurement.timestamp - episode.last_fever_reading > max_gap {
+                        episodes.push(episode.finish(None));
+                        Some(FeverEpisode::start(measurement))
+                    } else {
+                        episode.extend(measurement);
+                        Some(episode)
+                    }
+                },
+            };
+        }
+        if let Some(episode) = current {
+            episodes.push(episode.finish(None));
+        }
+        episodes
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn measurement(hour: i64, location_id: i64, tenths: i32) -> BodyTemperatureMeasurement {
+        let start = FixedOffset::east_opt(3600).unwrap()
+            .with_ymd_and_hms(2024, 3, 1, 8, 0, 0).unwrap();
+        BodyTemperatureMeasurement::new(-1, start + Duration::hours(hour), location_id, Rational32::new(tenths, 10), None, None)
+    }
+
+    #[test]
+    fn reference_ranges() {
+        let mouth = BodyTemperatureLocation::new(1, "mouth".to_owned(), None, None, None);
+        let armpit = BodyTemperatureLocation::new(2, "armpit".to_owned(), Some(Rational32::new(1, 2)), None, Some(Rational32::new(373, 10)));
+
+        let mouth_range = ReferenceRange::for_location(&mouth).unwrap();
+        assert_eq!(mouth_range.classify(Rational32::new(349, 10)), TemperatureStatus::Hypothermia);
+        assert_eq!(mouth_range.classify(Rational32::new(379, 10)), TemperatureStatus::Normal);
+        assert_eq!(mouth_range.classify(Rational32::new(380, 10)), TemperatureStatus::Fever);
+
+        let armpit_range = ReferenceRange::for_location(&armpit).unwrap();
+        assert_eq!(armpit_range.normal_min_celsius, Rational32::new(345, 10));
+        assert_eq!(armpit_range.core_temperature(Rational32::new(374, 10)), Some(Rational32::new(379, 10)));
+        assert_eq!(armpit_range.classify(Rational32::new(374, 10)), TemperatureStatus::Fever);
+    }
+
+    #[test]
+    fn episodes() {
+        let locations = [
+            BodyTemperatureLocation::new(1, "mouth".to_owned(), None, None, None),
+            BodyTemperatureLocation::new(2, "rectum".to_owned(), Some(Rational32::new(-1, 2)), None, None),
+        ];
+        let mut measurements = vec![
+            measurement(0, 1, 366),
+            measurement(4, 1, 381),
+            measurement(8, 2, 396),
+            measurement(12, 1, 389),
+            measurement(16, 1, 372),
+            // a second episode that is still ongoing after a long gap
+            measurement(60, 1, 385),
+            measurement(100, 1, 383),
+        ];
+        normalize_measurements(&mut measurements, &locations);
+        assert_eq!(measurements[2].core_temperature_celsius, Some(Rational32::new(391, 10)));
+
+        let episodes = FeverEpisode::detect(&measurements);
+        assert_eq!(episodes.len(), 3);
+        assert_eq!(episodes[0].onset, measurements[1].timestamp);
+        assert_eq!(episodes[0].resolved, Some(measurements[4].timestamp));
+        assert_eq!(episodes[0].duration_minutes, 12 * 60);
+        assert_eq!(episodes[0].reading_count, 3);
+        assert_eq!(episodes[0].peak, measurements[2]);
+
+        assert_eq!(episodes[1].onset, measurements[5].timestamp);
+        assert_eq!(episodes[1].resolved, None);
+        assert_eq!(episodes[1].duration_minutes, 0);
+        assert_eq!(episodes[2].onset, measurements[6].timestamp);
+    }
+
+    #[test]
+    fn unrepresentable_temperatures() {
+        let locations = [
+            BodyTemperatureLocation::new(1, "armpit".to_owned(), Some(Rational32::new(1, 2)), None, None),
+            BodyTemperatureLocation::new(2, "ear".to_owned(), Some(Rational32::new(1, 1_000_000_007)), None, None),
+        ];
+        let mut measurements = vec![
+            measurement(0, 1, 366),
+            measurement(1, 2, 366),
+        ];
+        measurements[0].temperature_celsius = Rational32::new(2_000_000_001, 1_000_000_000);
+        normalize_measurements(&mut measurements, &locations);
+
+        assert!(ReferenceRange::for_location(&locations[1]).is_none());
+        assert_eq!(measurements[0].core_temperature_celsius, None);
+        assert_eq!(measurements[0].status, Some(TemperatureStatus::Hypothermia));
+        assert_eq!(measurements[1].core_temperature_celsius, None);
+        assert_eq!(measurements[1].status, None);
+        assert!(FeverEpisode::detect(&measurements).is_empty());
+    }
+}
@@ -1,15 +1,16 @@
-use chrono::{DateTime, Local, Timelike};
+use std::borrow::Borrow;
+
+use chrono::{DateTime, FixedOffset, Timelike};
 use num_rational::Rational32;
 
+use crate::numerism::r32_to_f64;
+use crate::units::Unit;
+
 
-pub(crate) fn ratio2float(value: &Rational32, digits: usize) -> Result<String, askama::Error> {
+pub(crate) fn ratio2float<D: Borrow<usize>>(value: &Rational32, digits: D) -> Result<String, askama::Error> {
     let num = *value.numer() as f64;
     let den = *value.denom() as f64;
-    Ok(format!("{:.*}", digits, num / den))
-}
-
-pub(crate) fn ratio2float_owned(value: Rational32, digits: usize) -> Result<String, askama::Error> {
-    ratio2float(&value, digits)
+    Ok(format!("{:.*}", *digits.borrow(), num / den))
 }
 
 pub(crate) fn ratio2floatraw(value: &Rational32) -> Result<String, askama::Error> {
@@ -18,11 +19,31 @@ pub(crate) fn ratio2floatraw(value: &Rational32) -> Result<String, askama::Error
     Ok(format!("{}", num / den))
 }
 
-pub(crate) fn unix_timestamp_ms(timestamp: &DateTime<Local>) -> Result<i64, askama::Error> {
+pub(crate) fn in_unit<D: Borrow<usize>>(value: &Rational32, unit: &Unit, digits: D) -> Result<String, askama::Error> {
+    Ok(format!("{:.*}", *digits.borrow(), unit.convert_from_canonical_f64(r32_to_f64(value))))
+}
+
+pub(crate) fn float_in_unit<D: Borrow<usize>>(value: &f64, unit: &Unit, digits: D) -> Result<String, askama::Error> {
+    Ok(format!("{:.*}", *digits.borrow(), unit.convert_from_canonical_f64(*value)))
+}
+
+pub(crate) fn float<D: Borrow<usize>>(value: &f64, digits: D) -> Result<String, askama::Error> {
+    Ok(format!("{:.*}", *digits.borrow(), value))
+}
+
+pub(crate) fn float_owned(value: f64, digits: usize) -> Result<String, askama::Error> {
+    float(&value, digits)
+}
+
+pub(crate) fn percent(value: &f64, digits: usize) -> Result<String, askama::Error> {
+    Ok(format!("{:.*}", digits, value * 100.0))
+}
+
+pub(crate) fn unix_timestamp_ms(timestamp: &DateTime<FixedOffset>) -> Result<i64, askama::Error> {
     Ok(timestamp.timestamp() * 1000)
 }
 
-pub(crate) fn time_of_day_ms(timestamp: &DateTime<Local>) -> Result<u32, askama::Error> {
+pub(crate) fn time_of_day_ms(timestamp: &DateTime<FixedOffset>) -> Result<u32, askama::Error> {
     let time_of_day = timestamp.time();
     let ms = time_of_day.hour() * 60 * 60 * 1000
         + time_of_day.minute() * 60 * 1000
@@ -30,6 +51,6 @@ pub(crate) fn time_of_day_ms(timestamp: &DateTime<Local>) -> Result<u32, askama:
     Ok(ms)
 }
 
-pub(crate) fn time(timestamp: &DateTime<Local>) -> Result<String, askama::Error> {
+pub(crate) fn time(timestamp: &DateTime<FixedOffset>) -> Result<String, askama::Error> {
     Ok(timestamp.format("%H:%M").to_string())
 }
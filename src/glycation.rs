@@ -0,0 +1,162 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Serialize;
+
+use crate::model::{
+    BloodSugarMeasurement, HBA1C_ADDITIVE_DENOM, HBA1C_ADDITIVE_NUMER, HBA1C_MULTIPLICATIVE_DENOM,
+    HBA1C_MULTIPLICATIVE_NUMER, LongTermBloodSugarMeasurement, SUGAR_MG_PER_DL_IN_MMOL_PER_L,
+};
+use crate::numerism::r32_to_f64;
+use crate::statistics::StatisticsField;
+use crate::units::Quantity;
+
+
+/// The number of days of blood sugar readings from which the glucose management indicator is
+/// calculated.
+pub(crate) const GMI_WINDOW_DAYS: i64 = 90;
+
+/// Glucose management indicator in % (DCCT) at a mean blood sugar of 0 mg/dL, after Bergenstal et
+/// al. (2018).
+const GMI_DCCT_PERCENT_OFFSET: f64 = 3.31;
+const GMI_DCCT_PERCENT_PER_MG_PER_DL: f64 = 0.02392;
+
+
+/// The HBA1c value that corresponds to the mean of the blood sugar readings in a time window.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub(crate) struct GlucoseManagementIndicator {
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub window_start: DateTime<FixedOffset>,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub window_end: DateTime<FixedOffset>,
+    pub reading_count: usize,
+    pub mean_sugar_mmol_per_l: f64,
+    pub gmi_dcct_percent: f64,
+    pub gmi_mmol_per_mol: f64,
+}
+impl GlucoseManagementIndicator {
+    /// Calculates the indicator from the readings taken in the [`GMI_WINDOW_DAYS`] days up to and
+    /// including `window_end`. Returns `None` if there are no such readings.
+    pub fn calculate(readings: &[BloodSugarMeasurement], window_end: DateTime<FixedOffset>) -> Option<Self> {
+        let window_start = window_end - Duration::days(GMI_WINDOW_DAYS);
+        let values: Vec<f64> = readings.iter()
+            .filter(|r| r.timestamp > window_start && r.timestamp <= window_end)
+            .map(|r| r32_to_f64(&r.sugar_mmol_per_l))
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+
+        let mean_sugar_mmol_per_l = values.iter().sum::<f64>() / (values.len() as f64);
+        let mean_sugar_mg_per_dl = mean_sugar_mmol_per_l * f64::from(SUGAR_MG_PER_DL_IN_MMOL_PER_L);
+        let gmi_dcct_percent = GMI_DCCT_PERCENT_OFFSET + GMI_DCCT_PERCENT_PER_MG_PER_DL * mean_sugar_mg_per_dl;
+        Some(Self {
+            window_start,
+            window_end,
+            reading_count: values.len(),
+            mean_sugar_mmol_per_l,
+            gmi_dcct_percent,
+            gmi_mmol_per_mol: dcct_percent_to_mmol_per_mol(gmi_dcct_percent),
+        })
+    }
+}
+
+
+/// A laboratory HBA1c value next to what the blood sugar readings before it suggest.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub(crate) struct GlycationComparison {
+    pub measurement: LongTermBloodSugarMeasurement,
+    pub hba1c_dcct_percent: f64,
+    pub eag_mmol_per_l: f64,
+    pub eag_mg_per_dl: f64,
+    pub gmi: Option<GlucoseManagementIndicator>,
+    /// The laboratory HBA1c minus the glucose management indicator, both in % (DCCT).
+    pub hba1c_minus_gmi_dcct_percent: Option<f64>,
+}
+impl GlycationComparison {
+    pub fn calculate(measurement: &LongTermBloodSugarMeasurement, readings: &[BloodSugarMeasurement]) -> Self {
+        let hba1c_dcct_percent = measurement.hba1c_dcct_percent();
+        let gmi = GlucoseManagementIndicator::calculate(readings, measurement.timestamp);
+        Self {
+            measurement: *measurement,
+            hba1c_dcct_percent,
+            eag_mmol_per_l: measurement.estimated_average_glucose_mmol_per_l(),
+            eag_mg_per_dl: measurement.estimated_average_glucose_mg_per_dl(),
+            gmi,
+            hba1c_minus_gmi_dcct_percent: gmi.map(|g| hba1c_dcct_percent - g.gmi_dcct_percent),
+        }
+    }
+
+    pub fn calculate_all(measurements: &[LongTermBloodSugarMeasurement], readings: &[BloodSugarMeasurement]) -> Vec<Self> {
+        measurements.iter()
+            .map(|m| Self::calculate(m, readings))
+            .collect()
+    }
+
+    pub const STATISTICS_FIELDS: &[StatisticsField<Self>] = &[
+        StatisticsField { key: "gmi_dcct_percent", label: "GMI", quantity: Quantity::Fixed("% DCCT"), digits: 1, extract: |c| c.gmi.map(|g| g.gmi_dcct_percent) },
+        StatisticsField { key: "hba1c_minus_gmi_dcct_percent", label: "HBA1c \u{2212} GMI", quantity: Quantity::Fixed("% DCCT"), digits: 1, extract: |c| c.hba1c_minus_gmi_dcct_percent },
+    ];
+}
+
+
+/// The glucose management indicator of the most recent readings and how earlier HBA1c values compare
+/// to the readings before them.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct GlycationSummary {
+    pub current_gmi: Option<GlucoseManagementIndicator>,
+    pub comparisons: Vec<GlycationComparison>,
+}
+impl GlycationSummary {
+    pub fn calculate(
+        measurements: &[LongTermBloodSugarMeasurement],
+        readings: &[BloodSugarMeasurement],
+        now: DateTime<FixedOffset>,
+    ) -> Self {
+        Self {
+            current_gmi: GlucoseManagementIndicator::calculate(readings, now),
+            comparisons: GlycationComparison::calculate_all(measurements, readings),
+        }
+    }
+}
+
+
+fn dcct_percent_to_mmol_per_mol(dcct_percent: f64) -> f64 {
+    let additive_factor = f64::from(HBA1C_ADDITIVE_NUMER) / f64::from(HBA1C_ADDITIVE_DENOM);
+    let multiplicative_factor = f64::from(HBA1C_MULTIPLICATIVE_NUMER) / f64::from(HBA1C_MULTIPLICATIVE_DENOM);
+    (dcct_percent - additive_factor) * multiplicative_factor
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use num_rational::Rational32;
+
+    fn at(days: i64) -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(0).unwrap()
+            .with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+            + Duration::days(days)
+    }
+
+    #[test]
+    fn eag_and_gmi() {
+        // 7% DCCT corresponds to 154 mg/dL
+        let hba1c = LongTermBloodSugarMeasurement::new_dcct_percent(-1, at(0), Rational32::new(7, 1)).unwrap();
+        assert!((hba1c.estimated_average_glucose_mg_per_dl() - 154.2).abs() < 0.01);
+
+        // the first and the last reading are outside of the window
+        let readings = [
+            BloodSugarMeasurement::new(-1, at(-91), Rational32::new(20, 1)),
+            BloodSugarMeasurement::new(-1, at(-60), Rational32::new(8, 1)),
+            BloodSugarMeasurement::new(-1, at(-1), Rational32::new(9, 1)),
+            BloodSugarMeasurement::new(-1, at(1), Rational32::new(20, 1)),
+        ];
+        let comparison = GlycationComparison::calculate(&hba1c, &readings);
+        let gmi = comparison.gmi.unwrap();
+        assert_eq!(gmi.reading_count, 2);
+        assert!((gmi.mean_sugar_mmol_per_l - 8.5).abs() < 1e-9);
+        // 8.5 mmol/L = 153 mg/dL
+        assert!((gmi.gmi_dcct_percent - 6.96976).abs() < 1e-9);
+        assert!((comparison.hba1c_minus_gmi_dcct_percent.unwrap() - 0.03024).abs() < 1e-6);
+
+        assert_eq!(GlycationComparison::calculate(&hba1c, &readings[..1]).gmi, None);
+    }
+}
@@ -1,23 +1,39 @@
+mod alerts;
+mod anthropometrics;
+mod cli;
 mod config;
+mod csv;
+mod custom_metrics;
 mod database;
+mod diary;
+mod fever;
 mod filters;
+mod glycation;
+mod migrations;
 mod model;
+mod notify;
 mod numerism;
+mod reminders;
 mod ser_de;
+mod statistics;
+mod timezone;
+mod trends;
+mod units;
 
 
 use std::collections::{BTreeMap, HashMap};
 use std::convert::Infallible;
 use std::error::Error;
-use std::ffi::OsString;
 use std::fmt;
+use std::future::Future;
 use std::net::{AddrParseError, SocketAddr};
 use std::path::PathBuf;
 use std::process::ExitCode;
 use std::result::Result;
 
 use askama::Template;
-use chrono::{Duration, Local, Timelike};
+use clap::Parser;
+use chrono::{Duration, NaiveDate, NaiveTime};
 use form_urlencoded;
 use http::request::Parts;
 use http_body_util::{BodyExt, Full};
@@ -29,25 +45,45 @@ use num_rational::Rational32;
 use num_traits::Zero;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use serde::Serialize;
 use tokio::net::TcpListener;
 use toml;
-use tracing::error;
+use tracing::{error, info};
 use url::Url;
 
-use crate::config::{AuthToken, CONFIG, CONFIG_PATH, load_config};
+use crate::alerts::check_alerts;
+use crate::cli::{Cli, Command, LogFormat, add, export, gen_token, import};
+use crate::config::{
+    AuthToken, CLI_OVERRIDES, CONFIG, CONFIG_PATH, CONFIG_PRECEDENCE_NOTE, ConfigProblem, Hours, load_config, watch_config,
+};
+use crate::custom_metrics::CustomMetric;
 use crate::database::{
-    add_blood_pressure_measurement, add_blood_sugar_measurement,
-    add_long_term_blood_sugar_measurement, add_mass_measurement, add_temperature_measurement,
+    DatabaseError, StoredMeasurement,
     get_recent_blood_pressure_measurements, get_recent_blood_sugar_measurements,
-    get_recent_long_term_blood_sugar_measurements, get_recent_mass_measurements,
-    get_recent_temperature_measurements, get_temperature_locations,
+    get_recent_alerts, get_temperature_locations, init_storage,
+    add_temperature_location, remove_temperature_location, update_temperature_location,
+    add_height_measurement, get_height_measurements, remove_height_measurement,
+    add_custom_measurement, get_recent_custom_measurements,
+    add_diary_entry, get_recent_diary_entries,
 };
+use crate::diary::{DiaryEntryWithReadings, NEARBY_HOURS};
+use crate::fever::FeverEpisode;
+use crate::glycation::{GMI_WINDOW_DAYS, GlycationComparison, GlycationSummary};
 use crate::model::{
-    DailyBloodPressureMeasurements, BloodPressureMeasurement, BloodSugarMeasurement,
-    BodyMassMeasurement, BodyTemperatureLocation, BodyTemperatureMeasurement,
-    LongTermBloodSugarMeasurement, MeasurementStatistics, SUGAR_MG_PER_DL_IN_MMOL_PER_L,
+    Alert, DailyBloodPressureMeasurements, BloodPressureMeasurement, BloodSugarMeasurement,
+    BodyComposition, BodyMassMeasurement, BodyTemperatureLocation, BodyTemperatureMeasurement, CustomMeasurement,
+    DiaryEntry, DiarySymptoms, HeightMeasurement, KetoneMeasurement, LipidPanelMeasurement, LongTermBloodSugarMeasurement, Measurement,
+    MeasurementKind, PeakFlowMeasurement, SUGAR_MG_PER_DL_IN_MMOL_PER_L, with_measurement_type,
+};
+use crate::migrations::migrate;
+use crate::numerism::{ParseRationalError, r32_from_decimal, r32_to_f64};
+use crate::reminders::{REMINDER_STATE, ReminderState, run_reminders};
+use crate::statistics::{
+    BloodPressureVariability, MeasurementStatistics, StatisticsWithExtras,
 };
-use crate::numerism::{ParseRationalError, r32_from_decimal};
+use crate::timezone::{MAX_UTC_OFFSET_MINUTES, now_at_offset};
+use crate::trends::FieldTrend;
+use crate::units::{Quantity, Unit, UnitPreferences, convert_json};
 
 
 static ABSOLUTE_ZERO_CELSIUS: Lazy<Rational32> = Lazy::new(|| Rational32::new(-27315, 100));
@@ -71,6 +107,21 @@ pub(crate) enum ServerError {
     ReadingConfigFile(std::io::Error),
     ParsingConfigFile(toml::de::Error),
     ParsingListenAddress(AddrParseError),
+    InvalidConfigOverride(String, String),
+    ConflictingConfigSources(String, String),
+    ReadingSecretFile(String, PathBuf, std::io::Error),
+    InvalidConfig(Vec<ConfigProblem>),
+    OpeningDatabase(DatabaseError),
+    Migrating(DatabaseError),
+    MigrationsPending(usize),
+    CheckingConfig(DatabaseError),
+    Storing(DatabaseError),
+    InvalidMeasurement(ClientError),
+    ReadingImport(std::io::Error),
+    ParsingImport(serde_json::Error),
+    UnknownImportedLocation(i64),
+    WritingExport(std::io::Error),
+    GeneratingToken(std::io::Error),
 }
 impl fmt::Display for ServerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -80,9 +131,44 @@ impl fmt::Display for ServerError {
             ServerError::ReadingConfigFile(e)
                 => write!(f, "error reading config file: {}", e),
             ServerError::ParsingConfigFile(e)
-                => write!(f, "error parsing config file: {}", e),
+                => write!(f, "error parsing config file: {}\n{}", e, CONFIG_PRECEDENCE_NOTE),
+            ServerError::InvalidConfigOverride(key, reason)
+                => write!(f, "invalid config override {}: {}\n{}", key, reason, CONFIG_PRECEDENCE_NOTE),
+            ServerError::ConflictingConfigSources(key, file_key)
+                => write!(f, "both {} and {} are set\n{}", key, file_key, CONFIG_PRECEDENCE_NOTE),
+            ServerError::ReadingSecretFile(key, path, e)
+                => write!(f, "error reading file {} named by {}: {}\n{}", path.display(), key, e, CONFIG_PRECEDENCE_NOTE),
             ServerError::ParsingListenAddress(e)
                 => write!(f, "error parsing listen address: {}", e),
+            ServerError::InvalidConfig(problems) => {
+                write!(f, "invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            },
+            ServerError::OpeningDatabase(e)
+                => write!(f, "error opening database: {}", e),
+            ServerError::Migrating(e)
+                => write!(f, "error migrating database schema: {}", e),
+            ServerError::MigrationsPending(count)
+                => write!(f, "{} database migration(s) pending; run `beepee migrate` first or set migrate_on_startup = true", count),
+            ServerError::CheckingConfig(e)
+                => write!(f, "error checking configuration against database: {}", e),
+            ServerError::Storing(e)
+                => write!(f, "database error: {}", e),
+            ServerError::InvalidMeasurement(e)
+                => write!(f, "invalid measurement: {}", e),
+            ServerError::ReadingImport(e)
+                => write!(f, "error reading import file: {}", e),
+            ServerError::ParsingImport(e)
+                => write!(f, "error parsing import file: {}", e),
+            ServerError::UnknownImportedLocation(location_id)
+                => write!(f, "imported temperature measurement refers to unknown location {}", location_id),
+            ServerError::WritingExport(e)
+                => write!(f, "error writing export: {}", e),
+            ServerError::GeneratingToken(e)
+                => write!(f, "error obtaining random bytes for token: {}", e),
         }
     }
 }
@@ -95,11 +181,15 @@ pub(crate) enum ClientError {
     MissingValue(String),
     FailedToParseIntValue(String, String, std::num::ParseIntError),
     FailedToParseRationalValue(String, String, ParseRationalError),
+    FailedToParseDateValue(String, String, chrono::ParseError),
     IntValueZeroOrLess(String, i32),
     RationalValueZeroOrLess(String, Rational32),
     IntValueTooHigh(String, i32, i32),
+    IntValueTooLow(String, i32, i32),
     RationalValueTooLow(String, Rational32, Rational32),
+    RationalValueTooHigh(String, Rational32, Rational32),
     ValueIsInvalidOption(String, String, Vec<String>),
+    StringValueTooLong(String, usize, usize),
 }
 impl fmt::Display for ClientError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -110,16 +200,24 @@ impl fmt::Display for ClientError {
                 => write!(f, "failed to parse value {:?} for key {:?} as integer: {}", value, key, err),
             ClientError::FailedToParseRationalValue(key, value, err)
                 => write!(f, "failed to parse value {:?} for key {:?} as a rational number: {}", value, key, err),
+            ClientError::FailedToParseDateValue(key, value, err)
+                => write!(f, "failed to parse value {:?} for key {:?} as a date: {}", value, key, err),
             ClientError::IntValueZeroOrLess(key, value)
                 => write!(f, "value {} for key {:?} is zero or less", value, key),
             ClientError::RationalValueZeroOrLess(key, value)
                 => write!(f, "value {} for key {:?} is zero or less", value, key),
             ClientError::IntValueTooHigh(key, value, max)
                 => write!(f, "value {} for key {:?} is too high (> {})", value, key, max),
+            ClientError::IntValueTooLow(key, value, min)
+                => write!(f, "value {} for key {:?} is too low (< {})", value, key, min),
             ClientError::RationalValueTooLow(key, value, min)
                 => write!(f, "value {} for key {:?} is too low (< {})", value, key, min),
+            ClientError::RationalValueTooHigh(key, value, max)
+                => write!(f, "value {} for key {:?} is too high (> {})", value, key, max),
             ClientError::ValueIsInvalidOption(key, value, valid_options)
                 => write!(f, "value {} for key {:?} is not a valid option; valid options are {:?}", value, key, valid_options),
+            ClientError::StringValueTooLong(key, length, max)
+                => write!(f, "value for key {:?} is too long ({} > {} characters)", key, length, max),
         }
     }
 }
@@ -151,6 +249,12 @@ struct Error405Template {
     allowed_methods: Vec<String>,
 }
 
+#[derive(Template)]
+#[template(path = "409.html")]
+struct Error409Template {
+    message: String,
+}
+
 #[derive(Template)]
 #[template(path = "redirect.html")]
 struct RedirectTemplate {
@@ -163,7 +267,10 @@ struct ListTemplate {
     token: AuthToken,
     measurements: Vec<BloodPressureMeasurement>,
     days_and_measurements: Vec<DailyBloodPressureMeasurements>,
-    statistics: Option<MeasurementStatistics<BloodPressureMeasurement>>,
+    statistics: MeasurementStatistics,
+    trends: Vec<FieldTrend>,
+    variability: BloodPressureVariability,
+    reminders: ReminderState,
 }
 impl ListTemplate {
     fn measurements_with_spo2(&self) -> impl Iterator<Item = &BloodPressureMeasurement> {
@@ -174,11 +281,11 @@ impl ListTemplate {
 }
 
 #[derive(Template)]
-#[template(path = "mass_list.html")]
-struct MassListTemplate {
+#[template(path = "heights.html")]
+struct HeightsTemplate {
     token: AuthToken,
-    measurements: Vec<BodyMassMeasurement>,
-    statistics: Option<MeasurementStatistics<BodyMassMeasurement>>,
+    heights: Vec<HeightMeasurement>,
+    fallback_height_cm: Option<i32>,
 }
 
 #[derive(Template)]
@@ -188,7 +295,9 @@ struct TemperatureListTemplate {
     measurements: Vec<BodyTemperatureMeasurement>,
     temperature_locations: Vec<BodyTemperatureLocation>,
     default_temperature_location_id: i64,
-    statistics: Option<MeasurementStatistics<BodyTemperatureMeasurement>>,
+    fever_episodes: Vec<FeverEpisode>,
+    statistics: MeasurementStatistics,
+    trends: Vec<FieldTrend>,
 }
 impl TemperatureListTemplate {
     fn location_id_to_name(&self) -> HashMap<i64, &String> {
@@ -200,19 +309,52 @@ impl TemperatureListTemplate {
 }
 
 #[derive(Template)]
-#[template(path = "sugar_list.html")]
-struct SugarListTemplate {
+#[template(path = "temperature_locations.html")]
+struct TemperatureLocationsTemplate {
     token: AuthToken,
-    measurements: Vec<BloodSugarMeasurement>,
-    statistics: Option<MeasurementStatistics<BloodSugarMeasurement>>,
+    temperature_locations: Vec<BodyTemperatureLocation>,
+    default_temperature_location_id: i64,
 }
 
 #[derive(Template)]
 #[template(path = "long_term_sugar_list.html")]
 struct LongTermSugarListTemplate {
     token: AuthToken,
-    measurements: Vec<LongTermBloodSugarMeasurement>,
-    statistics: Option<MeasurementStatistics<LongTermBloodSugarMeasurement>>,
+    glycation: GlycationSummary,
+    statistics: MeasurementStatistics,
+    trends: Vec<FieldTrend>,
+}
+
+#[derive(Template)]
+#[template(path = "custom_metrics.html")]
+struct CustomMetricsTemplate {
+    token: AuthToken,
+    metrics: Vec<CustomMetric>,
+}
+
+#[derive(Template)]
+#[template(path = "custom_list.html")]
+struct CustomListTemplate {
+    token: AuthToken,
+    metric: CustomMetric,
+    measurements: Vec<CustomMeasurement>,
+    statistics: MeasurementStatistics,
+    trends: Vec<FieldTrend>,
+}
+
+#[derive(Template)]
+#[template(path = "diary.html")]
+struct DiaryTemplate {
+    token: AuthToken,
+    entries: Vec<DiaryEntryWithReadings>,
+    nearby_hours: i64,
+}
+
+#[derive(Template)]
+#[template(path = "alerts.html")]
+struct AlertsTemplate {
+    token: AuthToken,
+    alerts: Vec<Alert>,
 }
 
 
@@ -338,6 +480,17 @@ async fn respond_405(allowed_methods: &[Method]) -> Result<Response<Full<Bytes>>
     ).await
 }
 
+async fn respond_409(message: String) -> Result<Response<Full<Bytes>>, Infallible> {
+    let template = Error409Template {
+        message,
+    };
+    respond_template(
+        &template,
+        409,
+        &HashMap::new(),
+    ).await
+}
+
 async fn redirect_to_self(parts: Parts) -> Result<Response<Full<Bytes>>, Infallible> {
     let req_uri_string = parts.uri.to_string();
     let req_uri_noslash = req_uri_string.trim_start_matches('/');
@@ -377,102 +530,246 @@ async fn redirect_to_self(parts: Parts) -> Result<Response<Full<Bytes>>, Infalli
     ).await
 }
 
-async fn get_index(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_blood_pressure_measurements(Duration::days(3*31)).await {
-        Ok(rm) => rm,
+/// The recent measurements of a kind, newest first, with their statistics and trends.
+struct MeasurementList<T> {
+    measurements: Vec<T>,
+    statistics: MeasurementStatistics,
+    trends: Vec<FieldTrend>,
+}
+impl<T: Measurement> MeasurementList<T> {
+    /// Calculates the statistics and trends of the given measurements, which are sorted oldest first.
+    fn calculate(mut measurements: Vec<T>, hours: &Hours, units: &UnitPreferences) -> Self {
+        let trends = FieldTrend::calculate_all(
+            &measurements,
+            T::STATISTICS_FIELDS,
+            T::timestamp,
+            units,
+        );
+        measurements.reverse();
+
+        let statistics = MeasurementStatistics::calculate(
+            &measurements,
+            T::STATISTICS_FIELDS,
+            hours,
+            T::timestamp,
+            units,
+        );
+        Self {
+            measurements,
+            statistics,
+            trends,
+        }
+    }
+}
+
+/// Obtains the measurements of the last `T::RECENT_DAYS` days, oldest first.
+async fn get_recent_measurements<T: StoredMeasurement>() -> Result<Vec<T>, DatabaseError> {
+    let mut measurements = T::get_recent(Duration::days(T::RECENT_DAYS)).await?;
+    measurements.sort_by_key(T::timestamp);
+    Ok(measurements)
+}
+
+/// A kind of measurement with a page that lists the recent measurements with their statistics and
+/// trends, and with the API endpoints under `/api/{kind}`.
+trait MeasurementPage: FormMeasurement + Serialize {
+    /// What is derived from the recent measurements beyond the statistics and trends of their fields,
+    /// such as fever episodes.
+    type Extras: Serialize + Send;
+
+    /// The segment under `/api/{kind}/` at which the extras are returned, if any. Extras at `stats`
+    /// are returned together with the statistics of the fields.
+    const EXTRAS_PATH: Option<&'static str> = None;
+
+    type Template: Template;
+
+    /// Derives the extras from the recent measurements, which are sorted oldest first.
+    fn get_extras(measurements: &[Self], hours: &Hours, units: &UnitPreferences) -> impl Future<Output = Result<Self::Extras, DatabaseError>> + Send;
+
+    /// Fills the template of the page.
+    fn page_template(token: &AuthToken, list: MeasurementList<Self>, extras: Self::Extras) -> impl Future<Output = Result<Self::Template, DatabaseError>> + Send;
+}
+
+/// Declares the template of the page of a kind of measurement that derives nothing beyond the
+/// statistics and trends of its fields, and implements `MeasurementPage` with it.
+macro_rules! impl_simple_measurement_page {
+    ($type:ty, $template:ident, $path:tt) => {
+        #[derive(Template)]
+        #[template(path = $path)]
+        struct $template {
+            token: AuthToken,
+            measurements: Vec<$type>,
+            statistics: MeasurementStatistics,
+            trends: Vec<FieldTrend>,
+        }
+
+        impl MeasurementPage for $type {
+            type Extras = ();
+            type Template = $template;
+
+            async fn get_extras(_measurements: &[Self], _hours: &Hours, _units: &UnitPreferences) -> Result<(), DatabaseError> {
+                Ok(())
+            }
+
+            async fn page_template(token: &AuthToken, list: MeasurementList<Self>, _extras: ()) -> Result<$template, DatabaseError> {
+                Ok($template {
+                    token: token.clone(),
+                    measurements: list.measurements,
+                    statistics: list.statistics,
+                    trends: list.trends,
+                })
+            }
+        }
+    };
+}
+
+impl_simple_measurement_page!(BodyMassMeasurement, MassListTemplate, "mass_list.html");
+impl_simple_measurement_page!(BloodSugarMeasurement, SugarListTemplate, "sugar_list.html");
+impl_simple_measurement_page!(PeakFlowMeasurement, PeakFlowListTemplate, "peak_flow_list.html");
+impl_simple_measurement_page!(KetoneMeasurement, KetonesListTemplate, "ketones_list.html");
+impl_simple_measurement_page!(LipidPanelMeasurement, LipidsListTemplate, "lipids_list.html");
+
+#[derive(Serialize)]
+struct BloodPressureExtras {
+    /// The measurements grouped by day, oldest first.
+    #[serde(skip)] days_and_measurements: Vec<DailyBloodPressureMeasurements>,
+    variability: BloodPressureVariability,
+}
+
+impl MeasurementPage for BloodPressureMeasurement {
+    type Extras = BloodPressureExtras;
+    const EXTRAS_PATH: Option<&'static str> = Some("stats");
+    type Template = ListTemplate;
+
+    async fn get_extras(measurements: &[Self], hours: &Hours, _units: &UnitPreferences) -> Result<BloodPressureExtras, DatabaseError> {
+        let days_and_measurements = DailyBloodPressureMeasurements::group_by_day(measurements, hours);
+        let variability = BloodPressureVariability::calculate(measurements, &days_and_measurements);
+        Ok(BloodPressureExtras {
+            days_and_measurements,
+            variability,
+        })
+    }
+
+    async fn page_template(token: &AuthToken, list: MeasurementList<Self>, extras: BloodPressureExtras) -> Result<ListTemplate, DatabaseError> {
+        let reminders = REMINDER_STATE
+            .read().await
+            .clone();
+
+        Ok(ListTemplate {
+            token: token.clone(),
+            measurements: list.measurements,
+            days_and_measurements: extras.days_and_measurements.into_iter().rev().collect(),
+            statistics: list.statistics,
+            trends: list.trends,
+            variability: extras.variability,
+            reminders,
+        })
+    }
+}
+
+impl MeasurementPage for BodyTemperatureMeasurement {
+    type Extras = Vec<FeverEpisode>;
+    const EXTRAS_PATH: Option<&'static str> = Some("episodes");
+    type Template = TemperatureListTemplate;
+
+    async fn get_extras(measurements: &[Self], _hours: &Hours, _units: &UnitPreferences) -> Result<Vec<FeverEpisode>, DatabaseError> {
+        Ok(FeverEpisode::detect(measurements))
+    }
+
+    async fn page_template(token: &AuthToken, list: MeasurementList<Self>, mut fever_episodes: Vec<FeverEpisode>) -> Result<TemperatureListTemplate, DatabaseError> {
+        fever_episodes.reverse();
+        let temperature_locations = get_temperature_locations().await?;
+        let default_temperature_location_id = {
+            let config = CONFIG
+                .get().unwrap()
+                .read().await;
+            config.default_temperature_location_id
+        };
+
+        Ok(TemperatureListTemplate {
+            token: token.clone(),
+            measurements: list.measurements,
+            temperature_locations,
+            default_temperature_location_id,
+            fever_episodes,
+            statistics: list.statistics,
+            trends: list.trends,
+        })
+    }
+}
+
+impl MeasurementPage for LongTermBloodSugarMeasurement {
+    type Extras = GlycationSummary;
+    const EXTRAS_PATH: Option<&'static str> = Some("glycation");
+    type Template = LongTermSugarListTemplate;
+
+    async fn get_extras(measurements: &[Self], _hours: &Hours, _units: &UnitPreferences) -> Result<GlycationSummary, DatabaseError> {
+        let sugar_measurements = get_recent_blood_sugar_measurements(Duration::days(Self::RECENT_DAYS + GMI_WINDOW_DAYS)).await?;
+        Ok(GlycationSummary::calculate(
+            measurements,
+            &sugar_measurements,
+            now_at_offset(None).await,
+        ))
+    }
+
+    async fn page_template(token: &AuthToken, list: MeasurementList<Self>, mut glycation: GlycationSummary) -> Result<LongTermSugarListTemplate, DatabaseError> {
+        let mut trends = list.trends;
+        trends.extend(FieldTrend::calculate_all(
+            &glycation.comparisons,
+            GlycationComparison::STATISTICS_FIELDS,
+            |c| c.measurement.timestamp,
+            &token.units(),
+        ));
+        glycation.comparisons.reverse();
+
+        Ok(LongTermSugarListTemplate {
+            token: token.clone(),
+            glycation,
+            statistics: list.statistics,
+            trends,
+        })
+    }
+}
+
+/// Returns the kind of measurement whose page is at `path`; blood pressure is on the main page.
+fn measurement_page_kind(path: &str) -> Option<MeasurementKind> {
+    if path == "/" {
+        return Some(MeasurementKind::Bp);
+    }
+    MeasurementKind::by_name(path.strip_prefix('/')?)
+        .filter(|kind| *kind != MeasurementKind::Bp)
+}
+
+async fn get_measurement_page<T: MeasurementPage>(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+    let measurements = match get_recent_measurements::<T>().await {
+        Ok(m) => m,
         Err(e) => {
             error!("error obtaining recent measurements: {}", e);
             return respond_500();
         },
     };
-    recent_measurements.sort_by_key(|m| m.timestamp);
 
-    // group measurements by day
     let hours = {
         let config_guard = CONFIG
             .get().unwrap()
             .read().await;
         config_guard.hours
     };
-    let mut day_to_measurements: BTreeMap<String, DailyBloodPressureMeasurements> = BTreeMap::new();
-    let mut max_measurement: Option<BloodPressureMeasurement> = None;
-    let mut min_measurement: Option<BloodPressureMeasurement> = None;
-    for measurement in &recent_measurements {
-        if let Some(mm) = &mut max_measurement {
-            *mm = mm.values_max(&measurement);
-        } else {
-            max_measurement = Some(measurement.clone());
-        }
-
-        if let Some(mm) = &mut min_measurement {
-            *mm = mm.values_min(&measurement);
-        } else {
-            min_measurement = Some(measurement.clone());
-        }
-
-        let mut day = measurement.timestamp.date_naive();
-        if measurement.timestamp.hour() < hours.morning_start {
-            // count this as (the evening of) the previous day
-            day = day.pred_opt().expect("no previous day?!");
-        }
-
-        let date_string = day.format("%Y-%m-%d").to_string();
-
-        let entry = day_to_measurements
-            .entry(date_string.clone())
-            .or_insert_with(|| DailyBloodPressureMeasurements::new_empty(date_string));
-
-        let this_hour = measurement.timestamp.hour();
-
-        if this_hour < hours.morning_start && entry.evening.is_none() {
-            // night (previous day)
-            entry.evening = Some(measurement.clone());
-        } else if this_hour >= hours.morning_start && this_hour < hours.morning_end && entry.morning.is_none() {
-            // morning
-            entry.morning = Some(measurement.clone());
-        } else if this_hour >= hours.midday_start && this_hour < hours.midday_end && entry.midday.is_none() {
-            // midday
-            entry.midday = Some(measurement.clone());
-        } else if this_hour >= hours.evening_start && entry.evening.is_none() {
-            // night
-            entry.evening = Some(measurement.clone());
-        } else {
-            entry.other.push(measurement.clone());
-        }
-    }
-
-    let days_and_measurements: Vec<DailyBloodPressureMeasurements> = day_to_measurements
-        .values()
-        .rev()
-        .map(|v| v.clone())
-        .collect();
-
-    let statistics = if recent_measurements.len() > 0 {
-        // calculate percentiles
-        let average = BloodPressureMeasurement::average(&recent_measurements);
-        let quasi_q1 = BloodPressureMeasurement::quasi_n_tile(&recent_measurements, 1, 4);
-        let quasi_q2 = BloodPressureMeasurement::quasi_n_tile(&recent_measurements, 1, 2);
-        let quasi_q3 = BloodPressureMeasurement::quasi_n_tile(&recent_measurements, 3, 4);
-
-        Some(MeasurementStatistics {
-            maximum: max_measurement.unwrap(),
-            minimum: min_measurement.unwrap(),
-            average,
-            quasi_q1,
-            quasi_q2,
-            quasi_q3,
-        })
-    } else {
-        None
+    let extras = match T::get_extras(&measurements, &hours, &token.units()).await {
+        Ok(e) => e,
+        Err(e) => {
+            error!("error deriving from recent measurements: {}", e);
+            return respond_500();
+        },
     };
+    let list = MeasurementList::calculate(measurements, &hours, &token.units());
 
-    let template = ListTemplate {
-        token: token.clone(),
-        measurements: recent_measurements,
-        days_and_measurements,
-        statistics,
+    let template = match T::page_template(token, list, extras).await {
+        Ok(t) => t,
+        Err(e) => {
+            error!("error obtaining page data: {}", e);
+            return respond_500();
+        },
     };
-
     respond_template(
         &template,
         200,
@@ -480,55 +777,27 @@ async fn get_index(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallibl
     ).await
 }
 
-async fn get_mass(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_mass_measurements(Duration::days(3*31)).await {
-        Ok(rm) => rm,
+async fn get_heights_page(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+    let mut heights = match get_height_measurements().await {
+        Ok(h) => h,
         Err(e) => {
-            error!("error obtaining recent measurements: {}", e);
+            error!("error obtaining height measurements: {}", e);
             return respond_500();
         },
     };
-    recent_measurements.sort_by_key(|m| m.timestamp);
-    recent_measurements.reverse();
-
-    let mut max_measurement: Option<BodyMassMeasurement> = None;
-    let mut min_measurement: Option<BodyMassMeasurement> = None;
-    for measurement in &recent_measurements {
-        if let Some(mm) = &mut max_measurement {
-            *mm = mm.values_max(&measurement);
-        } else {
-            max_measurement = Some(measurement.clone());
-        }
-
-        if let Some(mm) = &mut min_measurement {
-            *mm = mm.values_min(&measurement);
-        } else {
-            min_measurement = Some(measurement.clone());
-        }
-    }
-
-    let statistics = if recent_measurements.len() > 0 {
-        let average = BodyMassMeasurement::average(&recent_measurements);
-        let quasi_q1 = BodyMassMeasurement::quasi_n_tile(&recent_measurements, 1, 4);
-        let quasi_q2 = BodyMassMeasurement::quasi_n_tile(&recent_measurements, 1, 2);
-        let quasi_q3 = BodyMassMeasurement::quasi_n_tile(&recent_measurements, 3, 4);
+    heights.reverse();
 
-        Some(MeasurementStatistics {
-            minimum: min_measurement.unwrap(),
-            maximum: max_measurement.unwrap(),
-            average,
-            quasi_q1,
-            quasi_q3,
-            quasi_q2,
-        })
-    } else {
-        None
+    let fallback_height_cm = {
+        let config = CONFIG
+            .get().unwrap()
+            .read().await;
+        config.height_cm
     };
 
-    let template = MassListTemplate {
+    let template = HeightsTemplate {
         token: token.clone(),
-        measurements: recent_measurements,
-        statistics,
+        heights,
+        fallback_height_cm,
     };
     respond_template(
         &template,
@@ -537,33 +806,7 @@ async fn get_mass(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible
     ).await
 }
 
-async fn get_temperature(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_temperature_measurements(Duration::days(3*31)).await {
-        Ok(rm) => rm,
-        Err(e) => {
-            error!("error obtaining recent measurements: {}", e);
-            return respond_500();
-        },
-    };
-    recent_measurements.sort_by_key(|m| m.timestamp);
-    recent_measurements.reverse();
-
-    let mut max_measurement: Option<BodyTemperatureMeasurement> = None;
-    let mut min_measurement: Option<BodyTemperatureMeasurement> = None;
-    for measurement in &recent_measurements {
-        if let Some(mm) = &mut max_measurement {
-            *mm = mm.values_max(&measurement);
-        } else {
-            max_measurement = Some(measurement.clone());
-        }
-
-        if let Some(mm) = &mut min_measurement {
-            *mm = mm.values_min(&measurement);
-        } else {
-            min_measurement = Some(measurement.clone());
-        }
-    }
-
+async fn get_temperature_locations_page(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
     let temperature_locations = match get_temperature_locations().await {
         Ok(l) => l,
         Err(e) => {
@@ -572,24 +815,6 @@ async fn get_temperature(token: &AuthToken) -> Result<Response<Full<Bytes>>, Inf
         }
     };
 
-    let statistics = if recent_measurements.len() > 0 {
-        let average = BodyTemperatureMeasurement::average(&recent_measurements);
-        let quasi_q1 = BodyTemperatureMeasurement::quasi_n_tile(&recent_measurements, 1, 4);
-        let quasi_q2 = BodyTemperatureMeasurement::quasi_n_tile(&recent_measurements, 1, 2);
-        let quasi_q3 = BodyTemperatureMeasurement::quasi_n_tile(&recent_measurements, 3, 4);
-
-        Some(MeasurementStatistics {
-            minimum: min_measurement.unwrap(),
-            maximum: max_measurement.unwrap(),
-            average,
-            quasi_q1,
-            quasi_q3,
-            quasi_q2,
-        })
-    } else {
-        None
-    };
-
     let default_temperature_location_id = {
         let config = CONFIG
             .get().unwrap()
@@ -597,12 +822,10 @@ async fn get_temperature(token: &AuthToken) -> Result<Response<Full<Bytes>>, Inf
         config.default_temperature_location_id
     };
 
-    let template = TemperatureListTemplate {
+    let template = TemperatureLocationsTemplate {
         token: token.clone(),
-        measurements: recent_measurements,
         temperature_locations,
         default_temperature_location_id,
-        statistics,
     };
     respond_template(
         &template,
@@ -611,8 +834,43 @@ async fn get_temperature(token: &AuthToken) -> Result<Response<Full<Bytes>>, Inf
     ).await
 }
 
-async fn get_sugar(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_blood_sugar_measurements(Duration::days(3*31)).await {
+async fn get_custom_metric(key: &str) -> Option<CustomMetric> {
+    let config_guard = CONFIG
+        .get().unwrap()
+        .read().await;
+    config_guard.custom_metrics
+        .iter()
+        .find(|m| m.key == key)
+        .cloned()
+}
+
+async fn get_custom(token: &AuthToken, metric_key: Option<&String>) -> Result<Response<Full<Bytes>>, Infallible> {
+    let metric_key = match metric_key {
+        Some(mk) => mk,
+        None => {
+            let metrics = {
+                let config_guard = CONFIG
+                    .get().unwrap()
+                    .read().await;
+                config_guard.custom_metrics.clone()
+            };
+            let template = CustomMetricsTemplate {
+                token: token.clone(),
+                metrics,
+            };
+            return respond_template(
+                &template,
+                200,
+                &HashMap::new(),
+            ).await;
+        },
+    };
+    let metric = match get_custom_metric(metric_key).await {
+        Some(m) => m,
+        None => return respond_404().await,
+    };
+
+    let mut recent_measurements = match get_recent_custom_measurements(Some(&metric.key), Duration::days(3*31)).await {
         Ok(rm) => rm,
         Err(e) => {
             error!("error obtaining recent measurements: {}", e);
@@ -620,46 +878,23 @@ async fn get_sugar(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallibl
         },
     };
     recent_measurements.sort_by_key(|m| m.timestamp);
+    let trends = metric.trends(&recent_measurements);
     recent_measurements.reverse();
 
-    let mut max_measurement: Option<BloodSugarMeasurement> = None;
-    let mut min_measurement: Option<BloodSugarMeasurement> = None;
-    for measurement in &recent_measurements {
-        if let Some(mm) = &mut max_measurement {
-            *mm = mm.values_max(&measurement);
-        } else {
-            max_measurement = Some(measurement.clone());
-        }
-
-        if let Some(mm) = &mut min_measurement {
-            *mm = mm.values_min(&measurement);
-        } else {
-            min_measurement = Some(measurement.clone());
-        }
-    }
-
-    let statistics = if recent_measurements.len() > 0 {
-        let average = BloodSugarMeasurement::average(&recent_measurements);
-        let quasi_q1 = BloodSugarMeasurement::quasi_n_tile(&recent_measurements, 1, 4);
-        let quasi_q2 = BloodSugarMeasurement::quasi_n_tile(&recent_measurements, 1, 2);
-        let quasi_q3 = BloodSugarMeasurement::quasi_n_tile(&recent_measurements, 3, 4);
-
-        Some(MeasurementStatistics {
-            minimum: min_measurement.unwrap(),
-            maximum: max_measurement.unwrap(),
-            average,
-            quasi_q1,
-            quasi_q3,
-            quasi_q2,
-        })
-    } else {
-        None
+    let hours = {
+        let config_guard = CONFIG
+            .get().unwrap()
+            .read().await;
+        config_guard.hours
     };
+    let statistics = metric.statistics(&recent_measurements, &hours);
 
-    let template = SugarListTemplate {
+    let template = CustomListTemplate {
         token: token.clone(),
+        metric,
         measurements: recent_measurements,
         statistics,
+        trends,
     };
     respond_template(
         &template,
@@ -668,55 +903,33 @@ async fn get_sugar(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallibl
     ).await
 }
 
-async fn get_long_term_sugar(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_long_term_blood_sugar_measurements(Duration::days(3*365)).await {
-        Ok(rm) => rm,
+/// Returns the recent diary entries, newest first, each with the readings taken around it.
+async fn get_recent_diary_entries_with_readings() -> Result<Vec<DiaryEntryWithReadings>, DatabaseError> {
+    let ago = Duration::days(3*31);
+    let mut entries = get_recent_diary_entries(ago).await?;
+    entries.sort_by_key(|e| e.timestamp);
+    entries.reverse();
+
+    // readings taken shortly before the oldest entry are also nearby
+    let readings_ago = ago + Duration::hours(NEARBY_HOURS);
+    let blood_pressure = get_recent_blood_pressure_measurements(readings_ago).await?;
+    let blood_sugar = get_recent_blood_sugar_measurements(readings_ago).await?;
+    Ok(DiaryEntryWithReadings::collect_all(&entries, &blood_pressure, &blood_sugar))
+}
+
+async fn get_diary(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+    let entries = match get_recent_diary_entries_with_readings().await {
+        Ok(e) => e,
         Err(e) => {
-            error!("error obtaining recent measurements: {}", e);
+            error!("error obtaining recent diary entries: {}", e);
             return respond_500();
         },
     };
-    recent_measurements.sort_by_key(|m| m.timestamp);
-    recent_measurements.reverse();
-
-    let mut max_measurement: Option<LongTermBloodSugarMeasurement> = None;
-    let mut min_measurement: Option<LongTermBloodSugarMeasurement> = None;
-    for measurement in &recent_measurements {
-        if let Some(mm) = &mut max_measurement {
-            *mm = mm.values_max(&measurement);
-        } else {
-            max_measurement = Some(measurement.clone());
-        }
-
-        if let Some(mm) = &mut min_measurement {
-            *mm = mm.values_min(&measurement);
-        } else {
-            min_measurement = Some(measurement.clone());
-        }
-    }
-
-    let statistics = if recent_measurements.len() > 0 {
-        let average = LongTermBloodSugarMeasurement::average(&recent_measurements);
-        let quasi_q1 = LongTermBloodSugarMeasurement::quasi_n_tile(&recent_measurements, 1, 4);
-        let quasi_q2 = LongTermBloodSugarMeasurement::quasi_n_tile(&recent_measurements, 1, 2);
-        let quasi_q3 = LongTermBloodSugarMeasurement::quasi_n_tile(&recent_measurements, 3, 4);
-
-        Some(MeasurementStatistics {
-            minimum: min_measurement.unwrap(),
-            maximum: max_measurement.unwrap(),
-            average,
-            quasi_q1,
-            quasi_q3,
-            quasi_q2,
-        })
-    } else {
-        None
-    };
 
-    let template = LongTermSugarListTemplate {
+    let template = DiaryTemplate {
         token: token.clone(),
-        measurements: recent_measurements,
-        statistics,
+        entries,
+        nearby_hours: NEARBY_HOURS,
     };
     respond_template(
         &template,
@@ -725,110 +938,150 @@ async fn get_long_term_sugar(token: &AuthToken) -> Result<Response<Full<Bytes>>,
     ).await
 }
 
-async fn get_api_bp() -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_blood_pressure_measurements(Duration::days(3*31)).await {
-        Ok(rm) => rm,
+async fn get_alerts(token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+    let mut alerts = match get_recent_alerts(Duration::days(3*31)).await {
+        Ok(a) => a,
         Err(e) => {
-            error!("error obtaining recent measurements: {}", e);
+            error!("error obtaining recent alerts: {}", e);
             return respond_500();
         },
     };
+    alerts.sort_by_key(|a| a.timestamp);
+    alerts.reverse();
 
-    recent_measurements.sort_by_key(|m| m.timestamp);
-
-    // make it a JSON
-    let recent_json = match serde_json::to_string(&recent_measurements) {
-        Ok(rj) => rj,
-        Err(e) => {
-            error!("error serializing recent measurements to JSON: {}", e);
-            return respond_500();
-        },
+    let template = AlertsTemplate {
+        token: token.clone(),
+        alerts,
     };
-
-    // spit it out
-    let response_res = Response::builder()
-        .status(200)
-        .header("Content-Type", "application/json")
-        .body(Full::new(Bytes::from(recent_json)));
-    match response_res {
-        Ok(r) => Ok(r),
-        Err(e) => {
-            error!("failed to create response: {}", e);
-            return respond_500();
-        },
-    }
+    respond_template(
+        &template,
+        200,
+        &HashMap::new(),
+    ).await
 }
 
-async fn get_api_mass() -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_mass_measurements(Duration::days(3*31)).await {
-        Ok(rm) => rm,
+async fn get_api_alerts(output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let alerts = match get_recent_alerts(Duration::days(3*31)).await {
+        Ok(a) => a,
         Err(e) => {
-            error!("error obtaining recent measurements: {}", e);
+            error!("error obtaining recent alerts: {}", e);
             return respond_500();
         },
     };
+    respond_api(&alerts, output)
+}
 
-    recent_measurements.sort_by_key(|m| m.timestamp);
-
-    // make it a JSON
-    let recent_json = match serde_json::to_string(&recent_measurements) {
-        Ok(rj) => rj,
+async fn get_api_temperature_locations() -> Result<Response<Full<Bytes>>, Infallible> {
+    let temperature_locations = match get_temperature_locations().await {
+        Ok(l) => l,
         Err(e) => {
-            error!("error serializing recent measurements to JSON: {}", e);
+            error!("error obtaining temperature locations: {}", e);
             return respond_500();
-        },
+        }
     };
+    respond_json(&temperature_locations)
+}
 
-    // spit it out
-    let response_res = Response::builder()
-        .status(200)
-        .header("Content-Type", "application/json")
-        .body(Full::new(Bytes::from(recent_json)));
-    match response_res {
-        Ok(r) => Ok(r),
+async fn get_api_heights(output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let heights = match get_height_measurements().await {
+        Ok(h) => h,
         Err(e) => {
-            error!("failed to create response: {}", e);
+            error!("error obtaining height measurements: {}", e);
             return respond_500();
         },
-    }
+    };
+    respond_api(&heights, output)
 }
 
-async fn get_api_temperature() -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_temperature_measurements(Duration::days(3*31)).await {
+async fn get_api_measurements<T: StoredMeasurement + Serialize>(output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let recent_measurements = match get_recent_measurements::<T>().await {
         Ok(rm) => rm,
         Err(e) => {
             error!("error obtaining recent measurements: {}", e);
             return respond_500();
         },
     };
+    respond_api(&recent_measurements, output)
+}
 
-    recent_measurements.sort_by_key(|m| m.timestamp);
+/// Splits a path of the form `/api/{kind}`, `/api/{kind}/stats`, `/api/{kind}/trends` or
+/// `/api/{kind}/{extras path}` into the kind of measurement and the last segment (empty for the
+/// measurements themselves).
+fn split_api_measurements_path(path: &str) -> Option<(MeasurementKind, &str)> {
+    let rest = path.strip_prefix("/api/")?;
+    let (kind_name, sub_path) = match rest.split_once('/') {
+        Some((_, "")) => return None,
+        Some((kn, sp)) => (kn, sp),
+        None => (rest, ""),
+    };
+    let kind = MeasurementKind::by_name(kind_name)?;
+    let extras_path = with_measurement_type!(kind, T => T::EXTRAS_PATH);
+    if sub_path.is_empty() || sub_path == "stats" || sub_path == "trends" || Some(sub_path) == extras_path {
+        Some((kind, sub_path))
+    } else {
+        None
+    }
+}
+
+async fn get_api_measurements_path<T: MeasurementPage>(sub_path: &str, output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    match sub_path {
+        "stats" => get_api_stats::<T>(output).await,
+        "trends" => get_api_trends::<T>(output).await,
+        "" => get_api_measurements::<T>(output).await,
+        _ => get_api_extras::<T>(output).await,
+    }
+}
+
+async fn get_api_measurements_of_kind(kind: MeasurementKind, sub_path: &str, output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    with_measurement_type!(kind, T => get_api_measurements_path::<T>(sub_path, output).await)
+}
 
-    // make it a JSON
-    let recent_json = match serde_json::to_string(&recent_measurements) {
-        Ok(rj) => rj,
+async fn get_api_diary(output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let mut entries = match get_recent_diary_entries_with_readings().await {
+        Ok(e) => e,
         Err(e) => {
-            error!("error serializing recent measurements to JSON: {}", e);
+            error!("error obtaining recent diary entries: {}", e);
             return respond_500();
         },
     };
 
-    // spit it out
-    let response_res = Response::builder()
-        .status(200)
-        .header("Content-Type", "application/json")
-        .body(Full::new(Bytes::from(recent_json)));
-    match response_res {
-        Ok(r) => Ok(r),
+    entries.reverse();
+    respond_api(&entries, output)
+}
+
+async fn get_api_custom_metrics() -> Result<Response<Full<Bytes>>, Infallible> {
+    let metrics = {
+        let config_guard = CONFIG
+            .get().unwrap()
+            .read().await;
+        config_guard.custom_metrics.clone()
+    };
+    respond_json(&metrics)
+}
+
+async fn get_api_custom(metric_key: &str, output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let metric = match get_custom_metric(metric_key).await {
+        Some(m) => m,
+        None => return respond_404().await,
+    };
+    let mut recent_measurements = match get_recent_custom_measurements(Some(&metric.key), Duration::days(3*31)).await {
+        Ok(rm) => rm,
         Err(e) => {
-            error!("failed to create response: {}", e);
+            error!("error obtaining recent measurements: {}", e);
             return respond_500();
         },
-    }
+    };
+
+    recent_measurements.sort_by_key(|m| m.timestamp);
+    respond_api(&recent_measurements, output)
 }
 
-async fn get_api_sugar() -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_blood_sugar_measurements(Duration::days(3*31)).await {
+async fn get_api_custom_stats(metric_key: &str, output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let metric = match get_custom_metric(metric_key).await {
+        Some(m) => m,
+        None => return respond_404().await,
+    };
+    let recent_measurements = match get_recent_custom_measurements(Some(&metric.key), Duration::days(3*31)).await {
         Ok(rm) => rm,
         Err(e) => {
             error!("error obtaining recent measurements: {}", e);
@@ -836,33 +1089,128 @@ async fn get_api_sugar() -> Result<Response<Full<Bytes>>, Infallible> {
         },
     };
 
+    let hours = {
+        let config_guard = CONFIG
+            .get().unwrap()
+            .read().await;
+        config_guard.hours
+    };
+    let statistics = metric.statistics(&recent_measurements, &hours);
+    respond_api(&statistics, output)
+}
+
+async fn get_api_custom_trends(metric_key: &str, output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let metric = match get_custom_metric(metric_key).await {
+        Some(m) => m,
+        None => return respond_404().await,
+    };
+    let mut recent_measurements = match get_recent_custom_measurements(Some(&metric.key), Duration::days(3*31)).await {
+        Ok(rm) => rm,
+        Err(e) => {
+            error!("error obtaining recent measurements: {}", e);
+            return respond_500();
+        },
+    };
     recent_measurements.sort_by_key(|m| m.timestamp);
 
-    // make it a JSON
-    let recent_json = match serde_json::to_string(&recent_measurements) {
-        Ok(rj) => rj,
+    let trends = metric.trends(&recent_measurements);
+    respond_api(&trends, output)
+}
+
+fn respond_json<T: Serialize>(value: &T) -> Result<Response<Full<Bytes>>, Infallible> {
+    let json = match serde_json::to_string(value) {
+        Ok(j) => j,
         Err(e) => {
-            error!("error serializing recent measurements to JSON: {}", e);
+            error!("error serializing to JSON: {}", e);
             return respond_500();
         },
     };
 
-    // spit it out
     let response_res = Response::builder()
         .status(200)
         .header("Content-Type", "application/json")
-        .body(Full::new(Bytes::from(recent_json)));
+        .body(Full::new(Bytes::from(json)));
     match response_res {
         Ok(r) => Ok(r),
         Err(e) => {
             error!("failed to create response: {}", e);
+            respond_500()
+        },
+    }
+}
+
+/// How API responses are output, as chosen by the `units` and `format` query parameters.
+#[derive(Clone, Copy, Debug, Default)]
+struct ApiOutput {
+    /// The units to convert values into; values are output in the canonical units if unset.
+    units: Option<UnitPreferences>,
+    csv: bool,
+}
+impl ApiOutput {
+    fn units(&self) -> UnitPreferences {
+        self.units.unwrap_or_default()
+    }
+}
+
+fn get_api_output(query_kv: &HashMap<String, String>, token: &AuthToken) -> Result<ApiOutput, ClientError> {
+    let units = match query_kv.get("units").map(|u| u.as_str()) {
+        None|Some("canonical") => None,
+        Some("preferred") => Some(token.units()),
+        Some(other) => return Err(ClientError::ValueIsInvalidOption(
+            "units".to_owned(),
+            other.to_owned(),
+            vec!["canonical".to_owned(), "preferred".to_owned()],
+        )),
+    };
+    let csv = match query_kv.get("format").map(|f| f.as_str()) {
+        None|Some("json") => false,
+        Some("csv") => true,
+        Some(other) => return Err(ClientError::ValueIsInvalidOption(
+            "format".to_owned(),
+            other.to_owned(),
+            vec!["json".to_owned(), "csv".to_owned()],
+        )),
+    };
+    Ok(ApiOutput {
+        units,
+        csv,
+    })
+}
+
+fn respond_api<T: Serialize>(value: &T, output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    if output.units.is_none() && !output.csv {
+        return respond_json(value);
+    }
+
+    let mut json_value = match serde_json::to_value(value) {
+        Ok(jv) => jv,
+        Err(e) => {
+            error!("error serializing to JSON: {}", e);
             return respond_500();
         },
+    };
+    if let Some(units) = &output.units {
+        convert_json(&mut json_value, units);
+    }
+    if !output.csv {
+        return respond_json(&json_value);
+    }
+
+    let response_res = Response::builder()
+        .status(200)
+        .header("Content-Type", "text/csv; charset=utf-8")
+        .body(Full::new(Bytes::from(csv::json_to_csv(&json_value))));
+    match response_res {
+        Ok(r) => Ok(r),
+        Err(e) => {
+            error!("failed to create response: {}", e);
+            respond_500()
+        },
     }
 }
 
-async fn get_api_long_term_sugar() -> Result<Response<Full<Bytes>>, Infallible> {
-    let mut recent_measurements = match get_recent_long_term_blood_sugar_measurements(Duration::days(3*365)).await {
+async fn get_api_stats<T: MeasurementPage>(output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let recent_measurements = match get_recent_measurements::<T>().await {
         Ok(rm) => rm,
         Err(e) => {
             error!("error obtaining recent measurements: {}", e);
@@ -870,29 +1218,87 @@ async fn get_api_long_term_sugar() -> Result<Response<Full<Bytes>>, Infallible>
         },
     };
 
-    recent_measurements.sort_by_key(|m| m.timestamp);
+    let hours = {
+        let config_guard = CONFIG
+            .get().unwrap()
+            .read().await;
+        config_guard.hours
+    };
+    let statistics = MeasurementStatistics::calculate(
+        &recent_measurements,
+        T::STATISTICS_FIELDS,
+        &hours,
+        T::timestamp,
+        &output.units(),
+    );
+    if T::EXTRAS_PATH != Some("stats") {
+        return respond_api(&statistics, output);
+    }
 
-    // make it a JSON
-    let recent_json = match serde_json::to_string(&recent_measurements) {
-        Ok(rj) => rj,
+    let extras = match T::get_extras(&recent_measurements, &hours, &output.units()).await {
+        Ok(e) => e,
         Err(e) => {
-            error!("error serializing recent measurements to JSON: {}", e);
+            error!("error deriving from recent measurements: {}", e);
             return respond_500();
         },
     };
+    respond_api(&StatisticsWithExtras { statistics, extras }, output)
+}
 
-    // spit it out
-    let response_res = Response::builder()
-        .status(200)
-        .header("Content-Type", "application/json")
-        .body(Full::new(Bytes::from(recent_json)));
-    match response_res {
-        Ok(r) => Ok(r),
+async fn get_api_extras<T: MeasurementPage>(output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let recent_measurements = match get_recent_measurements::<T>().await {
+        Ok(rm) => rm,
         Err(e) => {
-            error!("failed to create response: {}", e);
+            error!("error obtaining recent measurements: {}", e);
+            return respond_500();
+        },
+    };
+
+    let hours = {
+        let config_guard = CONFIG
+            .get().unwrap()
+            .read().await;
+        config_guard.hours
+    };
+    let extras = match T::get_extras(&recent_measurements, &hours, &output.units()).await {
+        Ok(e) => e,
+        Err(e) => {
+            error!("error deriving from recent measurements: {}", e);
+            return respond_500();
+        },
+    };
+    respond_api(&extras, output)
+}
+
+async fn get_api_trends<T: StoredMeasurement>(output: &ApiOutput) -> Result<Response<Full<Bytes>>, Infallible> {
+    let recent_measurements = match get_recent_measurements::<T>().await {
+        Ok(rm) => rm,
+        Err(e) => {
+            error!("error obtaining recent measurements: {}", e);
             return respond_500();
         },
+    };
+
+    let trends = FieldTrend::calculate_all(
+        &recent_measurements,
+        T::STATISTICS_FIELDS,
+        T::timestamp,
+        &output.units(),
+    );
+    respond_api(&trends, output)
+}
+
+fn get_form_i32(req_kv: &HashMap<String, String>, key: &str) -> Result<Option<i32>, ClientError> {
+    let string_value = match req_kv.get(key) {
+        Some(sv) => sv,
+        None => return Ok(None),
+    };
+    if string_value.is_empty() {
+        return Ok(None);
     }
+    let i32_value: i32 = string_value.parse()
+        .map_err(|e| ClientError::FailedToParseIntValue(String::from(key), string_value.clone(), e))?;
+    Ok(Some(i32_value))
 }
 
 fn get_form_i32_gt0(req_kv: &HashMap<String, String>, key: &str) -> Result<Option<i32>, ClientError> {
@@ -967,23 +1373,54 @@ fn get_form_r32_gt0(req_kv: &HashMap<String, String>, key: &str) -> Result<Optio
     }
 }
 
-fn get_req_form_r32(req_kv: &HashMap<String, String>, key: &str) -> Result<Rational32, ClientError> {
-    match get_form_r32(req_kv, key) {
+fn get_req_form_r32_gt0(req_kv: &HashMap<String, String>, key: &str) -> Result<Rational32, ClientError> {
+    match get_form_r32_gt0(req_kv, key) {
         Ok(Some(i)) => Ok(i),
         Ok(None) => Err(ClientError::MissingValue(String::from(key))),
         Err(e) => Err(e),
     }
 }
 
-fn get_req_form_r32_gt0(req_kv: &HashMap<String, String>, key: &str) -> Result<Rational32, ClientError> {
-    match get_form_r32_gt0(req_kv, key) {
-        Ok(Some(i)) => Ok(i),
-        Ok(None) => Err(ClientError::MissingValue(String::from(key))),
-        Err(e) => Err(e),
+/// Obtains a value of a quantity whose unit can be chosen and converts it into the canonical unit.
+///
+/// The value is given either as `{prefix}_value` in the unit named by `{prefix}_unit_key` or as
+/// `canonical_key` in the canonical unit.
+fn get_form_quantity(req_kv: &HashMap<String, String>, prefix: &str, quantity: Quantity, canonical_key: &str) -> Result<Option<Rational32>, ClientError> {
+    let value_key = format!("{}_value", prefix);
+    let value = match get_form_r32(req_kv, &value_key)? {
+        Some(v) => v,
+        None => return get_form_r32(req_kv, canonical_key),
+    };
+
+    let unit_key_key = format!("{}_unit_key", prefix);
+    let unit_key = match req_kv.get(&unit_key_key) {
+        Some(uk) => uk,
+        None => return Err(ClientError::MissingValue(unit_key_key)),
+    };
+    let unit = match Unit::by_key(quantity, unit_key) {
+        Some(u) => u,
+        None => return Err(ClientError::ValueIsInvalidOption(unit_key_key, unit_key.clone(), Unit::keys(quantity))),
+    };
+    match unit.to_canonical(value) {
+        Some(v) => Ok(Some(v)),
+        None => Err(ClientError::FailedToParseRationalValue(value_key.clone(), req_kv[&value_key].clone(), ParseRationalError::OutOfRange)),
+    }
+}
+
+fn get_form_utc_offset_minutes(req_kv: &HashMap<String, String>) -> Result<Option<i32>, ClientError> {
+    let utc_offset_minutes = get_form_i32(req_kv, "utc_offset_minutes")?;
+    if let Some(offset) = utc_offset_minutes {
+        if offset > MAX_UTC_OFFSET_MINUTES {
+            return Err(ClientError::IntValueTooHigh("utc_offset_minutes".into(), offset, MAX_UTC_OFFSET_MINUTES));
+        }
+        if offset < -MAX_UTC_OFFSET_MINUTES {
+            return Err(ClientError::IntValueTooLow("utc_offset_minutes".into(), offset, -MAX_UTC_OFFSET_MINUTES));
+        }
     }
+    Ok(utc_offset_minutes)
 }
 
-fn get_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<BloodPressureMeasurement, ClientError> {
+async fn get_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<BloodPressureMeasurement, ClientError> {
     let systolic_mmhg: i32 = get_req_form_i32_gt0(&req_kv, "systolic_mmhg")?;
     let diastolic_mmhg: i32 = get_req_form_i32_gt0(&req_kv, "diastolic_mmhg")?;
     let pulse_bpm: i32 = get_req_form_i32_gt0(&req_kv, "pulse_bpm")?;
@@ -995,7 +1432,8 @@ fn get_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<BloodPr
         }
     }
 
-    let local_now = Local::now();
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
     let measurement = BloodPressureMeasurement::new(
         -1,
         local_now,
@@ -1008,49 +1446,117 @@ fn get_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<BloodPr
 }
 
 async fn get_mass_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<BodyMassMeasurement, ClientError> {
-    let mass_kg: Rational32 = get_req_form_r32_gt0(&req_kv, "mass_kg")?;
-    let waist_circum_cm: Option<Rational32> = get_form_r32_gt0(&req_kv, "waist_circum_cm")?;
+    let mass_kg: Rational32 = match get_form_quantity(req_kv, "mass", Quantity::Mass, "mass_kg")? {
+        Some(m) => m,
+        None => return Err(ClientError::MissingValue("mass_value".to_owned())),
+    };
+    if mass_kg < Zero::zero() {
+        return Err(ClientError::RationalValueZeroOrLess("mass_value".to_owned(), mass_kg));
+    }
+    let waist_circum_cm: Option<Rational32> = get_form_quantity(req_kv, "waist_circum", Quantity::Length, "waist_circum_cm")?;
+    if let Some(wc) = waist_circum_cm {
+        if wc < Zero::zero() {
+            return Err(ClientError::RationalValueZeroOrLess("waist_circum_value".to_owned(), wc));
+        }
+    }
+    let hip_circum_cm: Option<Rational32> = get_form_quantity(req_kv, "hip_circum", Quantity::Length, "hip_circum_cm")?;
+    if let Some(hc) = hip_circum_cm {
+        if hc < Zero::zero() {
+            return Err(ClientError::RationalValueZeroOrLess("hip_circum_value".to_owned(), hc));
+        }
+    }
 
-    let height_cm: Option<i32> = {
-        let config_guard = CONFIG
-            .get().expect("initial config not set")
-            .read().await;
-        config_guard.height_cm
-    };
-    let height_m = height_cm
-        .map(|h| Rational32::new(h, 100));
-    let square_height_m2 = height_m
-        .map(|h| h * h);
-    let bmi: Option<Rational32> = square_height_m2.map(|sqh|
-        mass_kg / sqh
-    );
+    let muscle_mass_kg: Option<Rational32> = get_form_quantity(req_kv, "muscle_mass", Quantity::Mass, "muscle_mass_kg")?;
+    if let Some(mm) = muscle_mass_kg {
+        if mm < Zero::zero() {
+            return Err(ClientError::RationalValueZeroOrLess("muscle_mass_value".to_owned(), mm));
+        }
+    }
+    let body_fat_percent: Option<Rational32> = get_form_r32_gt0(req_kv, "body_fat_percent")?;
+    if let Some(bf) = body_fat_percent {
+        if bf > Rational32::from_integer(100) {
+            return Err(ClientError::RationalValueTooHigh("body_fat_percent".to_owned(), bf, Rational32::from_integer(100)));
+        }
+    }
+    let body_water_percent: Option<Rational32> = get_form_r32_gt0(req_kv, "body_water_percent")?;
+    if let Some(bw) = body_water_percent {
+        if bw > Rational32::from_integer(100) {
+            return Err(ClientError::RationalValueTooHigh("body_water_percent".to_owned(), bw, Rational32::from_integer(100)));
+        }
+    }
+    let composition = BodyComposition {
+        body_fat_percent,
+        muscle_mass_kg,
+        visceral_fat_rating: get_form_r32_gt0(req_kv, "visceral_fat_rating")?,
+        body_water_percent,
+    };
 
-    let local_now = Local::now();
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
     let measurement = BodyMassMeasurement::new(
         -1,
         local_now,
         mass_kg,
         waist_circum_cm,
-        bmi,
+        hip_circum_cm,
+        composition,
+        None,
     );
     Ok(measurement)
 }
 
+async fn get_height_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<HeightMeasurement, ClientError> {
+    let height_cm: Rational32 = match get_form_quantity(req_kv, "height", Quantity::Length, "height_cm")? {
+        Some(h) => h,
+        None => return Err(ClientError::MissingValue("height_value".to_owned())),
+    };
+    if height_cm <= Zero::zero() {
+        return Err(ClientError::RationalValueZeroOrLess("height_value".to_owned(), height_cm));
+    }
+
+    // heights are often entered after the fact, e.g. from a child's health record
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
+    let timestamp = match req_kv.get("date").filter(|d| !d.is_empty()) {
+        Some(date_str) => {
+            let date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
+                .map_err(|e| ClientError::FailedToParseDateValue("date".to_owned(), date_str.clone(), e))?;
+            date.and_time(NaiveTime::MIN)
+                .and_local_timezone(*local_now.offset())
+                .single()
+                .expect("fixed offsets are never ambiguous")
+        },
+        None => local_now,
+    };
+
+    Ok(HeightMeasurement::new(
+        -1,
+        timestamp,
+        height_cm,
+    ))
+}
+
 async fn get_temperature_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<BodyTemperatureMeasurement, ClientError> {
     let location_id: i64 = get_req_form_i64(req_kv, "location")?;
 
-    let temp_celsius: Rational32 = get_req_form_r32(&req_kv, "temperature_celsius")?;
+    let temp_celsius: Rational32 = match get_form_quantity(req_kv, "temperature", Quantity::Temperature, "temperature_celsius")? {
+        Some(t) => t,
+        None => return Err(ClientError::MissingValue("temperature_value".to_owned())),
+    };
     if temp_celsius < *ABSOLUTE_ZERO_CELSIUS {
         // temperature below absolute zero?!
         return Err(ClientError::RationalValueTooLow("temperature_celsius".into(), temp_celsius, *ABSOLUTE_ZERO_CELSIUS));
     }
 
-    let local_now = Local::now();
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
     let measurement = BodyTemperatureMeasurement::new(
         -1,
         local_now,
         location_id,
         temp_celsius,
+        None,
+        None,
     );
     Ok(measurement)
 }
@@ -1075,7 +1581,8 @@ async fn get_sugar_measurement_from_form(req_kv: &HashMap<String, String>) -> Re
     let sugar_value: Rational32 = get_req_form_r32_gt0(&req_kv, "sugar_value")?;
     let sugar_mmol_per_l: Rational32 = sugar_value * factor_to_mmol_per_l;
 
-    let local_now = Local::now();
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
     let measurement = BloodSugarMeasurement::new(
         -1,
         local_now,
@@ -1090,7 +1597,8 @@ async fn get_long_term_sugar_measurement_from_form(req_kv: &HashMap<String, Stri
         None => return Err(ClientError::MissingValue("hba1c_unit_key".to_owned())),
     };
     let hba1c_value: Rational32 = get_req_form_r32_gt0(&req_kv, "hba1c_value")?;
-    let local_now = Local::now();
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
     if unit_key == "mmol-per-mol" {
         Ok(LongTermBloodSugarMeasurement::new(
             -1,
@@ -1098,11 +1606,11 @@ async fn get_long_term_sugar_measurement_from_form(req_kv: &HashMap<String, Stri
             hba1c_value,
         ))
     } else if unit_key == "dcct-percent" {
-        Ok(LongTermBloodSugarMeasurement::new_dcct_percent(
+        LongTermBloodSugarMeasurement::new_dcct_percent(
             -1,
             local_now,
             hba1c_value,
-        ))
+        ).ok_or_else(|| ClientError::FailedToParseRationalValue("hba1c_value".to_owned(), req_kv["hba1c_value"].clone(), ParseRationalError::OutOfRange))
     } else {
         Err(ClientError::ValueIsInvalidOption(
             "hba1c_unit_key".to_owned(),
@@ -1112,136 +1620,629 @@ async fn get_long_term_sugar_measurement_from_form(req_kv: &HashMap<String, Stri
     }
 }
 
-async fn post_index(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+async fn get_peak_flow_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<PeakFlowMeasurement, ClientError> {
+    let peak_flow_l_per_min: i32 = get_req_form_i32_gt0(req_kv, "peak_flow_l_per_min")?;
+
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
+    let measurement = PeakFlowMeasurement::new(
+        -1,
+        local_now,
+        peak_flow_l_per_min,
+    );
+    Ok(measurement)
+}
+
+async fn get_ketone_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<KetoneMeasurement, ClientError> {
+    // meters commonly report a reading of 0.0 mmol/l, so zero is allowed here
+    let ketones_mmol_per_l: Rational32 = match get_form_r32(req_kv, "ketones_mmol_per_l")? {
+        Some(k) => k,
+        None => return Err(ClientError::MissingValue("ketones_mmol_per_l".to_owned())),
+    };
+    if ketones_mmol_per_l < Zero::zero() {
+        return Err(ClientError::RationalValueTooLow("ketones_mmol_per_l".to_owned(), ketones_mmol_per_l, Zero::zero()));
+    }
+
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
+    let measurement = KetoneMeasurement::new(
+        -1,
+        local_now,
+        ketones_mmol_per_l,
+    );
+    Ok(measurement)
+}
+
+async fn get_lipid_panel_measurement_from_form(req_kv: &HashMap<String, String>) -> Result<LipidPanelMeasurement, ClientError> {
+    let total_cholesterol_mmol_per_l = get_form_r32_gt0(req_kv, "total_cholesterol_mmol_per_l")?;
+    let ldl_cholesterol_mmol_per_l = get_form_r32_gt0(req_kv, "ldl_cholesterol_mmol_per_l")?;
+    let hdl_cholesterol_mmol_per_l = get_form_r32_gt0(req_kv, "hdl_cholesterol_mmol_per_l")?;
+    let triglycerides_mmol_per_l = get_form_r32_gt0(req_kv, "triglycerides_mmol_per_l")?;
+
+    // labs do not always report every value, but a panel without any of them is a mistake
+    let values = [total_cholesterol_mmol_per_l, ldl_cholesterol_mmol_per_l, hdl_cholesterol_mmol_per_l, triglycerides_mmol_per_l];
+    if values.iter().all(|v| v.is_none()) {
+        return Err(ClientError::MissingValue("total_cholesterol_mmol_per_l".to_owned()));
+    }
+
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
+    let measurement = LipidPanelMeasurement::new(
+        -1,
+        local_now,
+        total_cholesterol_mmol_per_l,
+        ldl_cholesterol_mmol_per_l,
+        hdl_cholesterol_mmol_per_l,
+        triglycerides_mmol_per_l,
+    );
+    Ok(measurement)
+}
+
+/// A kind of measurement that can be entered using a form, on its page or with the `add` command.
+pub(crate) trait FormMeasurement: StoredMeasurement {
+    fn from_form(req_kv: &HashMap<String, String>) -> impl Future<Output = Result<Self, ClientError>> + Send;
+}
+
+macro_rules! impl_form_measurement {
+    ($type:ty, $from_form:ident) => {
+        impl FormMeasurement for $type {
+            async fn from_form(req_kv: &HashMap<String, String>) -> Result<Self, ClientError> {
+                $from_form(req_kv).await
+            }
+        }
+    };
+}
+
+impl_form_measurement!(BloodPressureMeasurement, get_measurement_from_form);
+impl_form_measurement!(BodyMassMeasurement, get_mass_measurement_from_form);
+impl_form_measurement!(BodyTemperatureMeasurement, get_temperature_measurement_from_form);
+impl_form_measurement!(BloodSugarMeasurement, get_sugar_measurement_from_form);
+impl_form_measurement!(LongTermBloodSugarMeasurement, get_long_term_sugar_measurement_from_form);
+impl_form_measurement!(PeakFlowMeasurement, get_peak_flow_measurement_from_form);
+impl_form_measurement!(KetoneMeasurement, get_ketone_measurement_from_form);
+impl_form_measurement!(LipidPanelMeasurement, get_lipid_panel_measurement_from_form);
+
+async fn get_custom_measurement_from_form(metric: &CustomMetric, req_kv: &HashMap<String, String>) -> Result<CustomMeasurement, ClientError> {
+    let mut values = BTreeMap::new();
+    for field in &metric.fields {
+        let value = match get_form_r32(req_kv, &field.key)? {
+            Some(v) => v,
+            None => {
+                if field.required {
+                    return Err(ClientError::MissingValue(field.key.clone()));
+                }
+                continue;
+            },
+        };
+        if let Some(min) = field.min {
+            if r32_to_f64(&value) < min {
+                let min_r32 = Rational32::approximate_float(min).unwrap_or_default();
+                return Err(ClientError::RationalValueTooLow(field.key.clone(), value, min_r32));
+            }
+        }
+        if let Some(max) = field.max {
+            if r32_to_f64(&value) > max {
+                let max_r32 = Rational32::approximate_float(max).unwrap_or_default();
+                return Err(ClientError::RationalValueTooHigh(field.key.clone(), value, max_r32));
+            }
+        }
+        values.insert(field.key.clone(), value);
+    }
+
+    // a measurement consisting only of categories carries no information
+    if values.is_empty() {
+        let first_key = metric.fields.first()
+            .map(|f| f.key.clone())
+            .unwrap_or_default();
+        return Err(ClientError::MissingValue(first_key));
+    }
+
+    let mut categories = BTreeMap::new();
+    for category in &metric.categories {
+        let value = match req_kv.get(&category.key) {
+            Some(v) if !v.is_empty() => v,
+            _ => {
+                if category.required {
+                    return Err(ClientError::MissingValue(category.key.clone()));
+                }
+                continue;
+            },
+        };
+        if !category.options.contains(value) {
+            return Err(ClientError::ValueIsInvalidOption(category.key.clone(), value.clone(), category.options.clone()));
+        }
+        categories.insert(category.key.clone(), value.clone());
+    }
+
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
+    let measurement = CustomMeasurement::new(
+        -1,
+        local_now,
+        metric.key.clone(),
+        values,
+        categories,
+    );
+    Ok(measurement)
+}
+
+/// The maximum length of the notes of a diary entry.
+const MAX_DIARY_NOTES_LENGTH: usize = 4096;
+
+async fn get_diary_entry_from_form(req_kv: &HashMap<String, String>) -> Result<DiaryEntry, ClientError> {
+    // unticked checkboxes are not submitted at all
+    let ticked = |key: &str| req_kv.get(key).is_some_and(|v| !v.is_empty());
+    let symptoms = DiarySymptoms {
+        headache: ticked("headache"),
+        dizziness: ticked("dizziness"),
+        palpitations: ticked("palpitations"),
+        chest_pain: ticked("chest_pain"),
+        shortness_of_breath: ticked("shortness_of_breath"),
+    };
+
+    let wellbeing_score = get_form_i32(req_kv, "wellbeing_score")?;
+    if let Some(score) = wellbeing_score {
+        if score < 1 {
+            return Err(ClientError::IntValueTooLow("wellbeing_score".to_owned(), score, 1));
+        }
+        if score > 10 {
+            return Err(ClientError::IntValueTooHigh("wellbeing_score".to_owned(), score, 10));
+        }
+    }
+
+    let notes = req_kv.get("notes")
+        .map(|n| n.trim().to_owned())
+        .unwrap_or_default();
+    let length = notes.chars().count();
+    if length > MAX_DIARY_NOTES_LENGTH {
+        return Err(ClientError::StringValueTooLong("notes".to_owned(), length, MAX_DIARY_NOTES_LENGTH));
+    }
+
+    // an entry without symptoms, score or notes carries no information
+    if !symptoms.any() && wellbeing_score.is_none() && notes.is_empty() {
+        return Err(ClientError::MissingValue("wellbeing_score".to_owned()));
+    }
+
+    let utc_offset_minutes = get_form_utc_offset_minutes(req_kv)?;
+    let local_now = now_at_offset(utc_offset_minutes).await;
+    let entry = DiaryEntry::new(
+        -1,
+        local_now,
+        symptoms,
+        wellbeing_score,
+        notes,
+    );
+    Ok(entry)
+}
+
+/// Decodes keys and values in `application/x-www-form-urlencoded` format, such as a query string.
+fn parse_form(bytes: &[u8]) -> HashMap<String, String> {
+    form_urlencoded::parse(bytes)
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Reads a request body submitted by a form into its keys and values.
+async fn read_form(body: Incoming) -> Result<HashMap<String, String>, hyper::Error> {
+    let body_bytes = body.collect().await?.to_bytes();
+    Ok(parse_form(&body_bytes))
+}
+
+/// Stores a measurement entered using the form on its page and checks the alert rules.
+async fn post_measurement<T: FormMeasurement>(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+    if !token.write {
+        return respond_403_ro().await;
+    }
+
+    let (req_parts, req_body) = req.into_parts();
+    let req_kv = match read_form(req_body).await {
+        Ok(kv) => kv,
+        Err(e) => {
+            error!("error reading request bytes: {}", e);
+            return respond_500();
+        },
+    };
+
+    let new_measurement = match T::from_form(&req_kv).await {
+        Ok(nm) => nm,
+        Err(e) => {
+            return respond_400(e).await;
+        },
+    };
+
+    let new_measurement_id = match new_measurement.add().await {
+        Ok(id) => id,
+        Err(e) => {
+            error!("error adding measurement: {}", e);
+            return respond_500();
+        },
+    };
+
+    tokio::spawn(check_alerts::<T>(new_measurement_id));
+
+    redirect_to_self(req_parts).await
+}
+
+/// The maximum length of the name of a temperature location, as limited by the database schema.
+const MAX_TEMPERATURE_LOCATION_NAME_LENGTH: usize = 256;
+
+/// The maximum difference between a reading at a temperature location and core body temperature.
+const MAX_CORE_OFFSET_CELSIUS: i32 = 5;
+
+fn get_temperature_location_from_form(location_id: i64, req_kv: &HashMap<String, String>) -> Result<BodyTemperatureLocation, ClientError> {
+    let name = get_temperature_location_name_from_form(req_kv)?;
+
+    let core_offset_celsius = get_form_r32(req_kv, "core_offset_celsius")?;
+    if let Some(offset) = core_offset_celsius {
+        let max_offset = Rational32::from_integer(MAX_CORE_OFFSET_CELSIUS);
+        if offset > max_offset {
+            return Err(ClientError::RationalValueTooHigh("core_offset_celsius".into(), offset, max_offset));
+        }
+        if offset < -max_offset {
+            return Err(ClientError::RationalValueTooLow("core_offset_celsius".into(), offset, -max_offset));
+        }
+    }
+
+    let normal_min_celsius = get_form_r32_gt0(req_kv, "normal_min_celsius")?;
+    let normal_max_celsius = get_form_r32_gt0(req_kv, "normal_max_celsius")?;
+    if let (Some(min), Some(max)) = (normal_min_celsius, normal_max_celsius) {
+        if max < min {
+            return Err(ClientError::RationalValueTooLow("normal_max_celsius".into(), max, min));
+        }
+    }
+
+    Ok(BodyTemperatureLocation::new(
+        location_id,
+        name,
+        core_offset_celsius,
+        normal_min_celsius,
+        normal_max_celsius,
+    ))
+}
+
+fn get_temperature_location_name_from_form(req_kv: &HashMap<String, String>) -> Result<String, ClientError> {
+    let name = match req_kv.get("name") {
+        Some(n) => n.trim(),
+        None => return Err(ClientError::MissingValue("name".to_owned())),
+    };
+    if name.is_empty() {
+        return Err(ClientError::MissingValue("name".to_owned()));
+    }
+    let length = name.chars().count();
+    if length > MAX_TEMPERATURE_LOCATION_NAME_LENGTH {
+        return Err(ClientError::StringValueTooLong("name".to_owned(), length, MAX_TEMPERATURE_LOCATION_NAME_LENGTH));
+    }
+    Ok(name.to_owned())
+}
+
+
+/// Why a temperature location could not be added, updated or removed.
+enum TemperatureLocationChangeError {
+    Invalid(ClientError),
+    NotFound,
+    InUse(String),
+    Database(DatabaseError),
+}
+
+async fn respond_temperature_location_change_error(error: TemperatureLocationChangeError) -> Result<Response<Full<Bytes>>, Infallible> {
+    match error {
+        TemperatureLocationChangeError::Invalid(e) => respond_400(e).await,
+        TemperatureLocationChangeError::NotFound => respond_404().await,
+        TemperatureLocationChangeError::InUse(message) => respond_409(message).await,
+        TemperatureLocationChangeError::Database(e) => {
+            error!("error changing temperature location: {}", e);
+            respond_500()
+        },
+    }
+}
+
+async fn find_temperature_location(location_id: i64) -> Result<BodyTemperatureLocation, TemperatureLocationChangeError> {
+    let locations = get_temperature_locations().await
+        .map_err(TemperatureLocationChangeError::Database)?;
+    locations
+        .into_iter()
+        .find(|l| l.id == location_id)
+        .ok_or(TemperatureLocationChangeError::NotFound)
+}
+
+async fn add_temperature_location_from_form(req_kv: &HashMap<String, String>) -> Result<BodyTemperatureLocation, TemperatureLocationChangeError> {
+    let mut location = get_temperature_location_from_form(-1, req_kv)
+        .map_err(TemperatureLocationChangeError::Invalid)?;
+    location.id = add_temperature_location(&location).await
+        .map_err(TemperatureLocationChangeError::Database)?;
+    Ok(location)
+}
+
+async fn update_temperature_location_from_form(location_id: i64, req_kv: &HashMap<String, String>) -> Result<BodyTemperatureLocation, TemperatureLocationChangeError> {
+    let location = get_temperature_location_from_form(location_id, req_kv)
+        .map_err(TemperatureLocationChangeError::Invalid)?;
+    find_temperature_location(location_id).await?;
+    update_temperature_location(&location).await
+        .map_err(TemperatureLocationChangeError::Database)?;
+    Ok(location)
+}
+
+/// Removes a temperature location unless it is the configured default or measurements still refer
+/// to it.
+async fn remove_temperature_location_checked(location_id: i64) -> Result<(), TemperatureLocationChangeError> {
+    let location = find_temperature_location(location_id).await?;
+
+    let default_temperature_location_id = {
+        let config = CONFIG
+            .get().unwrap()
+            .read().await;
+        config.default_temperature_location_id
+    };
+    if location.id == default_temperature_location_id {
+        return Err(TemperatureLocationChangeError::InUse(format!(
+            "temperature location {:?} is the default location (default_temperature_location_id in the configuration)",
+            location.name,
+        )));
+    }
+
+    match remove_temperature_location(location.id).await {
+        Ok(()) => Ok(()),
+        Err(DatabaseError::StillReferenced(_, _)) => Err(TemperatureLocationChangeError::InUse(format!(
+            "temperature location {:?} is still used by temperature measurements",
+            location.name,
+        ))),
+        Err(e) => Err(TemperatureLocationChangeError::Database(e)),
+    }
+}
+
+async fn post_temperature_locations_page(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
     if !token.write {
         return respond_403_ro().await;
     }
 
     let (req_parts, req_body) = req.into_parts();
-    let req_body_bytes = match req_body.collect().await {
-        Ok(rbc) => rbc.to_bytes().to_vec(),
+    let req_kv = match read_form(req_body).await {
+        Ok(kv) => kv,
         Err(e) => {
             error!("error reading request bytes: {}", e);
             return respond_500();
         },
     };
-    let req_kv: HashMap<String, String> = form_urlencoded::parse(&req_body_bytes)
-        .map(|(a, b)| (a.to_string(), b.to_string()))
-        .collect();
 
-    let new_measurement = match get_measurement_from_form(&req_kv) {
-        Ok(nm) => nm,
-        Err(e) => {
-            return respond_400(e).await;
-        },
+    let action = match req_kv.get("action") {
+        Some(a) => a.as_str(),
+        None => return respond_400(ClientError::MissingValue("action".to_owned())).await,
+    };
+    let result = if action == "add" {
+        add_temperature_location_from_form(&req_kv).await
+            .map(|_| ())
+    } else if action == "update" || action == "remove" {
+        let location_id = match get_req_form_i64(&req_kv, "id") {
+            Ok(id) => id,
+            Err(e) => return respond_400(e).await,
+        };
+        if action == "update" {
+            update_temperature_location_from_form(location_id, &req_kv).await
+                .map(|_| ())
+        } else {
+            remove_temperature_location_checked(location_id).await
+        }
+    } else {
+        return respond_400(ClientError::ValueIsInvalidOption(
+            "action".to_owned(),
+            action.to_owned(),
+            vec!["add".to_owned(), "update".to_owned(), "remove".to_owned()],
+        )).await;
     };
 
-    match add_blood_pressure_measurement(&new_measurement).await {
-        Ok(rm) => rm,
+    match result {
+        Ok(()) => redirect_to_self(req_parts).await,
+        Err(e) => respond_temperature_location_change_error(e).await,
+    }
+}
+
+async fn post_api_temperature_locations(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+    if !token.write {
+        return respond_403_ro().await;
+    }
+
+    let req_kv = match read_form(req.into_body()).await {
+        Ok(kv) => kv,
         Err(e) => {
-            error!("error adding measurement: {}", e);
+            error!("error reading request bytes: {}", e);
             return respond_500();
         },
     };
 
-    redirect_to_self(req_parts).await
+    match add_temperature_location_from_form(&req_kv).await {
+        Ok(location) => {
+            let mut response = respond_json(&location);
+            if let Ok(r) = &mut response {
+                *r.status_mut() = hyper::StatusCode::CREATED;
+            }
+            response
+        },
+        Err(e) => respond_temperature_location_change_error(e).await,
+    }
 }
 
-async fn post_mass(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+async fn put_api_temperature_location(req: Request<Incoming>, token: &AuthToken, location_id: i64) -> Result<Response<Full<Bytes>>, Infallible> {
     if !token.write {
         return respond_403_ro().await;
     }
 
-    let (req_parts, req_body) = req.into_parts();
-    let req_body_bytes = match req_body.collect().await {
-        Ok(rbc) => rbc.to_bytes().to_vec(),
+    let req_kv = match read_form(req.into_body()).await {
+        Ok(kv) => kv,
         Err(e) => {
             error!("error reading request bytes: {}", e);
             return respond_500();
         },
     };
-    let req_kv: HashMap<String, String> = form_urlencoded::parse(&req_body_bytes)
-        .map(|(a, b)| (a.to_string(), b.to_string()))
-        .collect();
 
-    let new_measurement = match get_mass_measurement_from_form(&req_kv).await {
-        Ok(nm) => nm,
+    match update_temperature_location_from_form(location_id, &req_kv).await {
+        Ok(location) => respond_json(&location),
+        Err(e) => respond_temperature_location_change_error(e).await,
+    }
+}
+
+async fn delete_api_temperature_location(token: &AuthToken, location_id: i64) -> Result<Response<Full<Bytes>>, Infallible> {
+    if !token.write {
+        return respond_403_ro().await;
+    }
+
+    if let Err(e) = remove_temperature_location_checked(location_id).await {
+        return respond_temperature_location_change_error(e).await;
+    }
+
+    let response_res = Response::builder()
+        .status(204)
+        .body(Full::new(Bytes::new()));
+    match response_res {
+        Ok(r) => Ok(r),
         Err(e) => {
-            return respond_400(e).await;
+            error!("failed to create response: {}", e);
+            respond_500()
         },
-    };
+    }
+}
 
-    match add_mass_measurement(&new_measurement).await {
-        Ok(rm) => rm,
+async fn post_heights_page(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+    if !token.write {
+        return respond_403_ro().await;
+    }
+
+    let (req_parts, req_body) = req.into_parts();
+    let req_kv = match read_form(req_body).await {
+        Ok(kv) => kv,
         Err(e) => {
-            error!("error adding measurement: {}", e);
+            error!("error reading request bytes: {}", e);
             return respond_500();
         },
     };
 
-    redirect_to_self(req_parts).await
+    let action = match req_kv.get("action") {
+        Some(a) => a.as_str(),
+        None => return respond_400(ClientError::MissingValue("action".to_owned())).await,
+    };
+    let result = if action == "add" {
+        let new_height = match get_height_measurement_from_form(&req_kv).await {
+            Ok(nh) => nh,
+            Err(e) => return respond_400(e).await,
+        };
+        add_height_measurement(&new_height).await
+            .map(|_| ())
+    } else if action == "remove" {
+        let height_id = match get_req_form_i64(&req_kv, "id") {
+            Ok(id) => id,
+            Err(e) => return respond_400(e).await,
+        };
+        remove_height_measurement(height_id).await
+    } else {
+        return respond_400(ClientError::ValueIsInvalidOption(
+            "action".to_owned(),
+            action.to_owned(),
+            vec!["add".to_owned(), "remove".to_owned()],
+        )).await;
+    };
+
+    match result {
+        Ok(()) => redirect_to_self(req_parts).await,
+        Err(e) => {
+            error!("error changing height measurements: {}", e);
+            respond_500()
+        },
+    }
 }
 
-async fn post_temperature(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+async fn post_api_heights(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
     if !token.write {
         return respond_403_ro().await;
     }
 
-    let (req_parts, req_body) = req.into_parts();
-    let req_body_bytes = match req_body.collect().await {
-        Ok(rbc) => rbc.to_bytes().to_vec(),
+    let req_kv = match read_form(req.into_body()).await {
+        Ok(kv) => kv,
         Err(e) => {
             error!("error reading request bytes: {}", e);
             return respond_500();
         },
     };
-    let req_kv: HashMap<String, String> = form_urlencoded::parse(&req_body_bytes)
-        .map(|(a, b)| (a.to_string(), b.to_string()))
-        .collect();
 
-    let new_measurement = match get_temperature_measurement_from_form(&req_kv).await {
-        Ok(nm) => nm,
+    let mut new_height = match get_height_measurement_from_form(&req_kv).await {
+        Ok(nh) => nh,
+        Err(e) => return respond_400(e).await,
+    };
+    new_height.id = match add_height_measurement(&new_height).await {
+        Ok(id) => id,
         Err(e) => {
-            return respond_400(e).await;
+            error!("error adding height measurement: {}", e);
+            return respond_500();
         },
     };
 
-    match add_temperature_measurement(&new_measurement).await {
-        Ok(rm) => rm,
+    let mut response = respond_json(&new_height);
+    if let Ok(r) = &mut response {
+        *r.status_mut() = hyper::StatusCode::CREATED;
+    }
+    response
+}
+
+async fn delete_api_height(token: &AuthToken, height_id: i64) -> Result<Response<Full<Bytes>>, Infallible> {
+    if !token.write {
+        return respond_403_ro().await;
+    }
+
+    let heights = match get_height_measurements().await {
+        Ok(h) => h,
         Err(e) => {
-            error!("error adding measurement: {}", e);
+            error!("error obtaining height measurements: {}", e);
             return respond_500();
         },
     };
+    if !heights.iter().any(|h| h.id == height_id) {
+        return respond_404().await;
+    }
+    if let Err(e) = remove_height_measurement(height_id).await {
+        error!("error removing height measurement: {}", e);
+        return respond_500();
+    }
 
-    redirect_to_self(req_parts).await
+    let response_res = Response::builder()
+        .status(204)
+        .body(Full::new(Bytes::new()));
+    match response_res {
+        Ok(r) => Ok(r),
+        Err(e) => {
+            error!("failed to create response: {}", e);
+            respond_500()
+        },
+    }
 }
 
-async fn post_sugar(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+async fn post_custom(req: Request<Incoming>, token: &AuthToken, metric_key: Option<&String>) -> Result<Response<Full<Bytes>>, Infallible> {
     if !token.write {
         return respond_403_ro().await;
     }
 
+    let metric = match metric_key {
+        Some(mk) => match get_custom_metric(mk).await {
+            Some(m) => m,
+            None => return respond_404().await,
+        },
+        None => return respond_404().await,
+    };
+
     let (req_parts, req_body) = req.into_parts();
-    let req_body_bytes = match req_body.collect().await {
-        Ok(rbc) => rbc.to_bytes().to_vec(),
+    let req_kv = match read_form(req_body).await {
+        Ok(kv) => kv,
         Err(e) => {
             error!("error reading request bytes: {}", e);
             return respond_500();
         },
     };
-    let req_kv: HashMap<String, String> = form_urlencoded::parse(&req_body_bytes)
-        .map(|(a, b)| (a.to_string(), b.to_string()))
-        .collect();
 
-    let new_measurement = match get_sugar_measurement_from_form(&req_kv).await {
+    let new_measurement = match get_custom_measurement_from_form(&metric, &req_kv).await {
         Ok(nm) => nm,
         Err(e) => {
             return respond_400(e).await;
         },
     };
 
-    match add_blood_sugar_measurement(&new_measurement).await {
+    match add_custom_measurement(&new_measurement).await {
         Ok(rm) => rm,
         Err(e) => {
             error!("error adding measurement: {}", e);
@@ -1252,34 +2253,31 @@ async fn post_sugar(req: Request<Incoming>, token: &AuthToken) -> Result<Respons
     redirect_to_self(req_parts).await
 }
 
-async fn post_long_term_sugar(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
+async fn post_diary(req: Request<Incoming>, token: &AuthToken) -> Result<Response<Full<Bytes>>, Infallible> {
     if !token.write {
         return respond_403_ro().await;
     }
 
     let (req_parts, req_body) = req.into_parts();
-    let req_body_bytes = match req_body.collect().await {
-        Ok(rbc) => rbc.to_bytes().to_vec(),
+    let req_kv = match read_form(req_body).await {
+        Ok(kv) => kv,
         Err(e) => {
             error!("error reading request bytes: {}", e);
             return respond_500();
         },
     };
-    let req_kv: HashMap<String, String> = form_urlencoded::parse(&req_body_bytes)
-        .map(|(a, b)| (a.to_string(), b.to_string()))
-        .collect();
 
-    let new_measurement = match get_long_term_sugar_measurement_from_form(&req_kv).await {
-        Ok(nm) => nm,
+    let new_entry = match get_diary_entry_from_form(&req_kv).await {
+        Ok(ne) => ne,
         Err(e) => {
             return respond_400(e).await;
         },
     };
 
-    match add_long_term_blood_sugar_measurement(&new_measurement).await {
-        Ok(rm) => rm,
+    match add_diary_entry(&new_entry).await {
+        Ok(id) => id,
         Err(e) => {
-            error!("error adding measurement: {}", e);
+            error!("error adding diary entry: {}", e);
             return respond_500();
         },
     };
@@ -1350,101 +2348,160 @@ async fn handle_request(req: Request<Incoming>) -> Result<Response<Full<Bytes>>,
         None => return respond_403().await,
         Some(q) => q,
     };
-    let query_kv: HashMap<String, String> = form_urlencoded::parse(query_str.as_bytes())
-        .map(|(k, v)| (k.to_string(), v.to_string()))
-        .collect();
+    let query_kv = parse_form(query_str.as_bytes());
     let token_value = match query_kv.get("token") {
         None => return respond_403().await,
         Some(tv) => tv,
     };
 
-    let token_opt = {
-        CONFIG
+    let (token_opt, default_units) = {
+        let config_guard = CONFIG
             .get().expect("config is set")
-            .read().await
+            .read().await;
+        let token_opt = config_guard
             .auth_tokens
             .iter()
             .filter(|t| &t.token == token_value)
             .map(|t| t.clone())
-            .nth(0)
+            .nth(0);
+        (token_opt, config_guard.units)
     };
-    let token = match token_opt {
+    let mut token = match token_opt {
         Some(t) => t,
         None => {
             // no such token found, at all
             return respond_403().await;
         },
     };
+    token.units.get_or_insert(default_units);
+
+    let api_output = if req.uri().path().starts_with("/api/") {
+        match get_api_output(&query_kv, &token) {
+            Ok(ao) => ao,
+            Err(e) => return respond_400(e).await,
+        }
+    } else {
+        ApiOutput::default()
+    };
 
     // authenticated-only endpoints beyond this line
 
-    if req.uri().path() == "/" {
+    if let Some(kind) = measurement_page_kind(req.uri().path()) {
         if req.method() == Method::GET {
-            get_index(&token).await
+            with_measurement_type!(kind, T => get_measurement_page::<T>(&token).await)
         } else if req.method() == Method::POST {
-            post_index(req, &token).await
+            with_measurement_type!(kind, T => post_measurement::<T>(req, &token).await)
         } else {
             respond_405(&[Method::GET, Method::POST]).await
         }
-    } else if req.uri().path() == "/mass" {
+    } else if req.uri().path() == "/heights" {
         if req.method() == Method::GET {
-            get_mass(&token).await
+            get_heights_page(&token).await
         } else if req.method() == Method::POST {
-            post_mass(req, &token).await
+            post_heights_page(req, &token).await
         } else {
             respond_405(&[Method::GET, Method::POST]).await
         }
-    } else if req.uri().path() == "/temperature" {
+    } else if req.uri().path() == "/temperature-locations" {
         if req.method() == Method::GET {
-            get_temperature(&token).await
+            get_temperature_locations_page(&token).await
         } else if req.method() == Method::POST {
-            post_temperature(req, &token).await
+            post_temperature_locations_page(req, &token).await
         } else {
             respond_405(&[Method::GET, Method::POST]).await
         }
-    } else if req.uri().path() == "/sugar" {
+    } else if req.uri().path() == "/custom" {
         if req.method() == Method::GET {
-            get_sugar(&token).await
+            get_custom(&token, query_kv.get("metric")).await
         } else if req.method() == Method::POST {
-            post_sugar(req, &token).await
+            post_custom(req, &token, query_kv.get("metric")).await
         } else {
             respond_405(&[Method::GET, Method::POST]).await
         }
-    } else if req.uri().path() == "/long-term-sugar" {
+    } else if req.uri().path() == "/diary" {
         if req.method() == Method::GET {
-            get_long_term_sugar(&token).await
+            get_diary(&token).await
         } else if req.method() == Method::POST {
-            post_long_term_sugar(req, &token).await
+            post_diary(req, &token).await
         } else {
             respond_405(&[Method::GET, Method::POST]).await
         }
-    } else if req.uri().path() == "/api/bp" {
+    } else if req.uri().path() == "/alerts" {
         if req.method() == Method::GET {
-            get_api_bp().await
+            get_alerts(&token).await
         } else {
             respond_405(&[Method::GET]).await
         }
-    } else if req.uri().path() == "/api/mass" {
+    } else if let Some((kind, sub_path)) = split_api_measurements_path(req.uri().path()) {
         if req.method() == Method::GET {
-            get_api_mass().await
+            get_api_measurements_of_kind(kind, sub_path, &api_output).await
         } else {
             respond_405(&[Method::GET]).await
         }
-    } else if req.uri().path() == "/api/temperature" {
+    } else if req.uri().path() == "/api/heights" {
         if req.method() == Method::GET {
-            get_api_temperature().await
+            get_api_heights(&api_output).await
+        } else if req.method() == Method::POST {
+            post_api_heights(req, &token).await
+        } else {
+            respond_405(&[Method::GET, Method::POST]).await
+        }
+    } else if let Some(height_id_str) = req.uri().path().strip_prefix("/api/heights/") {
+        let height_id: i64 = match height_id_str.parse() {
+            Ok(hi) => hi,
+            Err(_) => return respond_404().await,
+        };
+        if req.method() == Method::DELETE {
+            delete_api_height(&token, height_id).await
+        } else {
+            respond_405(&[Method::DELETE]).await
+        }
+    } else if req.uri().path() == "/api/temperature/locations" {
+        if req.method() == Method::GET {
+            get_api_temperature_locations().await
+        } else if req.method() == Method::POST {
+            post_api_temperature_locations(req, &token).await
+        } else {
+            respond_405(&[Method::GET, Method::POST]).await
+        }
+    } else if let Some(location_id_str) = req.uri().path().strip_prefix("/api/temperature/locations/") {
+        let location_id: i64 = match location_id_str.parse() {
+            Ok(li) => li,
+            Err(_) => return respond_404().await,
+        };
+        if req.method() == Method::PUT {
+            put_api_temperature_location(req, &token, location_id).await
+        } else if req.method() == Method::DELETE {
+            delete_api_temperature_location(&token, location_id).await
+        } else {
+            respond_405(&[Method::PUT, Method::DELETE]).await
+        }
+    } else if req.uri().path() == "/api/diary" {
+        if req.method() == Method::GET {
+            get_api_diary(&api_output).await
         } else {
             respond_405(&[Method::GET]).await
         }
-    } else if req.uri().path() == "/api/sugar" {
+    } else if req.uri().path() == "/api/custom" {
         if req.method() == Method::GET {
-            get_api_sugar().await
+            get_api_custom_metrics().await
         } else {
             respond_405(&[Method::GET]).await
         }
-    } else if req.uri().path() == "/api/long-term-sugar" {
+    } else if let Some(custom_path) = req.uri().path().strip_prefix("/api/custom/") {
+        if req.method() != Method::GET {
+            return respond_405(&[Method::GET]).await;
+        }
+        if let Some(metric_key) = custom_path.strip_suffix("/stats") {
+            get_api_custom_stats(metric_key, &api_output).await
+        } else if let Some(metric_key) = custom_path.strip_suffix("/trends") {
+            get_api_custom_trends(metric_key, &api_output).await
+        } else {
+            get_api_custom(custom_path, &api_output).await
+        }
+    } else if req.uri().path() == "/api/alerts" {
         if req.method() == Method::GET {
-            get_api_long_term_sugar().await
+            get_api_alerts(&api_output).await
         } else {
             respond_405(&[Method::GET]).await
         }
@@ -1453,23 +2510,55 @@ async fn handle_request(req: Request<Incoming>) -> Result<Response<Full<Bytes>>,
     }
 }
 
-async fn run() -> Result<(), ServerError> {
-    // set up tracing
-    let (stdout_non_blocking, _guard) = tracing_appender::non_blocking(std::io::stdout());
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_writer(stdout_non_blocking)
-        .init();
+/// Accepts connections and serves requests on them. Runs forever.
+async fn serve(listener: TcpListener) -> ! {
+    loop {
+        let (stream, remote_addr) = listener.accept().await
+            .expect("failed to accept connection");
+        let io = TokioIo::new(stream);
+        tokio::task::spawn(async move {
+            let res = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                .http1()
+                .http2()
+                .serve_connection(io, service_fn(handle_request))
+                .await;
+            if let Err(e) = res {
+                error!("error serving connection from {}: {}", remote_addr, e);
+            }
+        });
+    }
+}
 
-    let args: Vec<OsString> = std::env::args_os().collect();
-    let config_path = match args.get(1) {
-        Some(cp) => PathBuf::from(cp),
-        None => PathBuf::from("config.toml"),
+/// Applies pending database migrations if `migrate_on_startup` is set, and fails if any are
+/// pending otherwise, before the missing tables and columns trip up the command that was run.
+async fn prepare_storage() -> Result<(), ServerError> {
+    let migrate_on_startup = {
+        CONFIG
+            .get().expect("no config lock")
+            .read().await
+            .migrate_on_startup
     };
-    CONFIG_PATH
-        .set(config_path).expect("failed to set config path");
+    if migrate_on_startup {
+        let migrations = migrate(false).await
+            .map_err(ServerError::Migrating)?;
+        for migration in migrations {
+            info!("applied migration {:04} {}", migration.version, migration.name);
+        }
+    } else {
+        let pending = migrate(true).await
+            .map_err(ServerError::Migrating)?;
+        if !pending.is_empty() {
+            return Err(ServerError::MigrationsPending(pending.len()));
+        }
+    }
+    Ok(())
+}
 
-    load_config().await?;
+async fn run_server() -> Result<(), ServerError> {
+    CONFIG
+        .get().expect("no config lock")
+        .read().await
+        .check_storage().await?;
 
     let addr: SocketAddr = {
         CONFIG
@@ -1483,20 +2572,73 @@ async fn run() -> Result<(), ServerError> {
     let listener = TcpListener::bind(addr).await
         .expect("failed to bind to listen address");
 
-    loop {
-        let (stream, remote_addr) = listener.accept().await
-            .expect("failed to accept connection");
-        let io = TokioIo::new(stream);
-        tokio::task::spawn(async move {
-            let res = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
-                .http1()
-                .http2()
-                .serve_connection(io, service_fn(handle_request))
-                .await;
-            if let Err(e) = res {
-                error!("error serving connection from {}: {}", remote_addr, e);
+    tokio::spawn(run_reminders());
+    tokio::spawn(watch_config());
+
+    serve(listener).await
+}
+
+async fn run() -> Result<(), ServerError> {
+    let cli = Cli::parse();
+
+    // set up tracing
+    let (stdout_non_blocking, _guard) = tracing_appender::non_blocking(std::io::stdout());
+    let subscriber_builder = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(stdout_non_blocking);
+    match cli.log_format {
+        LogFormat::Text => subscriber_builder.init(),
+        LogFormat::Json => subscriber_builder.json().init(),
+    }
+
+    if let Some(Command::GenToken { write }) = cli.command {
+        return gen_token(write);
+    }
+
+    CONFIG_PATH
+        .set(cli.config_path()).expect("failed to set config path");
+    let mut cli_overrides = Vec::new();
+    if let Some(listen) = &cli.listen {
+        cli_overrides.push(("BEEPEE_HTTP_LISTEN".to_owned(), listen.clone()));
+    }
+    CLI_OVERRIDES
+        .set(cli_overrides).expect("failed to set command-line overrides");
+
+    load_config().await?;
+    init_storage().await
+        .map_err(ServerError::OpeningDatabase)?;
+
+    let command = cli.command.unwrap_or(Command::Serve);
+    if !matches!(command, Command::Migrate { .. }) {
+        prepare_storage().await?;
+    }
+
+    match command {
+        Command::Serve => run_server().await,
+        Command::Migrate { dry_run } => {
+            let migrations = migrate(dry_run).await
+                .map_err(ServerError::Migrating)?;
+            if migrations.is_empty() {
+                println!("database schema is up to date");
             }
-        });
+            for migration in migrations {
+                let verb = if dry_run { "would apply" } else { "applied" };
+                println!("{} migration {:04} {}", verb, migration.version, migration.name);
+            }
+            Ok(())
+        },
+        Command::CheckConfig => {
+            CONFIG
+                .get().expect("no config lock")
+                .read().await
+                .check_storage().await?;
+            println!("configuration is valid");
+            Ok(())
+        },
+        Command::Import { file } => import(file).await,
+        Command::Export { output, days } => export(output, days).await,
+        Command::Add { kind, fields } => add(kind, fields).await,
+        Command::GenToken { .. } => unreachable!("handled before loading the configuration"),
     }
 }
 
@@ -1517,3 +2659,577 @@ fn main() -> ExitCode {
         },
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    use hyper::StatusCode;
+    use tokio::net::TcpStream;
+    use tokio::sync::RwLock;
+
+    use crate::anthropometrics::RiskCategory;
+    use crate::config::Config;
+    use crate::database::add_temperature_location;
+    use crate::database::testing::create_test_database_if_available;
+    use crate::model::{BodyTemperatureLocation, HeightMeasurement};
+    use crate::numerism::r32_to_f64;
+    use crate::ser_de::string_to_rat32;
+
+    const TEST_CONFIG: &str = r#"
+base_url = "http://127.0.0.1/"
+db_conn_string = "memory:"
+http_listen = "127.0.0.1:0"
+auth_tokens = [
+    { token = "writer", write = true },
+    { token = "reader", write = false },
+    { token = "imperial", write = true, units = { mass = "lb", length = "in", temperature = "fahrenheit", blood_sugar = "mg-per-dl" } },
+]
+height_cm = 180
+sex = "male"
+default_temperature_location_id = 1
+display_timezone = "Europe/Vienna"
+
+[hours]
+morning_start = 5
+morning_end = 13
+midday_start = 11
+midday_end = 20
+evening_start = 17
+
+[[alert_rules]]
+name = "hypertensive crisis"
+measurement = "bp"
+field = "systolic_mmhg"
+comparison = "above"
+threshold = 180
+
+[[custom_metrics]]
+key = "spirometry"
+name = "Spirometry"
+unit = "l"
+fields = [
+    { key = "fev1", label = "FEV1", min = 0, max = 10, digits = 2, required = true },
+    { key = "fvc", label = "FVC", min = 0, max = 10, digits = 2 },
+]
+categories = [{ key = "position", label = "position", options = ["sitting", "standing"] }]
+"#;
+
+    /// Starts a server on its own thread and returns its address.
+    ///
+    /// The server stores its data in a disposable PostgreSQL database if a server is available and
+    /// the PostgreSQL tests are not skipped, and in memory otherwise, so that the handler tests also
+    /// run without PostgreSQL. It is shared by all tests, so tests must not rely on the storage
+    /// being empty.
+    static SERVER_ADDR: Lazy<SocketAddr> = Lazy::new(|| {
+        let (addr_sender, addr_receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async move {
+                    let mut config: Config = toml::from_str(TEST_CONFIG).unwrap();
+                    let postgres_conn_string = create_test_database_if_available().await;
+                    if let Some(conn_string) = &postgres_conn_string {
+                        config.db_conn_string = conn_string.clone();
+                    }
+                    CONFIG
+                        .set(RwLock::new(config)).expect("config already set");
+                    init_storage().await.unwrap();
+                    if postgres_conn_string.is_some() {
+                        migrate(false).await.unwrap();
+                    }
+                    add_temperature_location(&BodyTemperatureLocation::new(-1, "mouth".to_owned(), None, None, None)).await.unwrap();
+
+                    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                    addr_sender.send(listener.local_addr().unwrap()).unwrap();
+                    serve(listener).await
+                })
+        });
+        addr_receiver.recv().unwrap()
+    });
+
+    async fn request(method: Method, path_and_query: &str, form: Option<&str>) -> (StatusCode, String) {
+        let stream = TcpStream::connect(*SERVER_ADDR).await.unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(path_and_query)
+            .header("Host", "127.0.0.1");
+        if form.is_some() {
+            builder = builder.header("Content-Type", "application/x-www-form-urlencoded");
+        }
+        let req = builder
+            .body(Full::new(Bytes::from(form.unwrap_or("").to_owned())))
+            .unwrap();
+        let response = sender.send_request(req).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn authentication() {
+        assert_eq!(request(Method::GET, "/", None).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(request(Method::GET, "/?token=nobody", None).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(request(Method::GET, "/api/bp?other=writer", None).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(request(Method::GET, "/?token=reader", None).await.0, StatusCode::OK);
+
+        // static files do not require a token
+        assert_eq!(request(Method::GET, "/static/style.css", None).await.0, StatusCode::OK);
+        assert_eq!(request(Method::GET, "/static/missing.css", None).await.0, StatusCode::NOT_FOUND);
+
+        let (status, _) = request(Method::POST, "/?token=reader", Some("systolic_mmhg=120&diastolic_mmhg=80&pulse_bpm=60")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn storage_problems_reject_configs() {
+        Lazy::force(&SERVER_ADDR);
+
+        let mut config: Config = toml::from_str(TEST_CONFIG).unwrap();
+        config.check_storage().await.unwrap();
+
+        config.default_temperature_location_id = 999_999;
+        match config.check_storage().await {
+            Err(ServerError::InvalidConfig(problems)) => {
+                assert_eq!(problems.len(), 1);
+                assert!(problems[0].to_string().starts_with("default_temperature_location_id:"), "{}", problems[0]);
+            },
+            other => panic!("unexpected result: {:?}", other.map_err(|e| e.to_string())),
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_routes_and_methods() {
+        assert_eq!(request(Method::GET, "/nothing-here?token=reader", None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(request(Method::DELETE, "/?token=writer", None).await.0, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(request(Method::POST, "/alerts?token=writer", Some("")).await.0, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(request(Method::POST, "/api/bp?token=writer", Some("")).await.0, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(request(Method::PUT, "/api/mass/stats?token=writer", None).await.0, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn trend_routes() {
+        for kind in ["bp", "mass", "temperature", "sugar", "long-term-sugar"] {
+            let trends_path = format!("/api/{}/trends?token=reader", kind);
+            assert_eq!(request(Method::GET, &trends_path, None).await.0, StatusCode::OK, "{}", trends_path);
+            assert_eq!(request(Method::POST, &trends_path, Some("")).await.0, StatusCode::METHOD_NOT_ALLOWED, "{}", trends_path);
+
+            let stats_path = format!("/api/{}/stats?token=reader", kind);
+            assert_eq!(request(Method::POST, &stats_path, Some("")).await.0, StatusCode::METHOD_NOT_ALLOWED, "{}", stats_path);
+        }
+        assert_eq!(request(Method::GET, "/api/bp/nonsense?token=reader", None).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn stats_include_extras() {
+        let (status, body) = request(Method::GET, "/api/bp/stats?token=reader", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let statistics: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(statistics.get("overall").is_some(), "{}", body);
+        assert!(statistics.get("variability").is_some(), "{}", body);
+        assert!(statistics.get("days_and_measurements").is_none(), "{}", body);
+
+        let (status, body) = request(Method::GET, "/api/temperature/stats?token=reader", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let statistics: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(statistics.get("overall").is_some(), "{}", body);
+        assert_eq!(statistics.as_object().unwrap().len(), 5, "{}", body);
+    }
+
+    #[tokio::test]
+    async fn every_route_checks_token_and_method() {
+        let routes: &[(&str, &[Method])] = &[
+            ("/", &[Method::GET, Method::POST]),
+            ("/mass", &[Method::GET, Method::POST]),
+            ("/temperature", &[Method::GET, Method::POST]),
+            ("/sugar", &[Method::GET, Method::POST]),
+            ("/long-term-sugar", &[Method::GET, Method::POST]),
+            ("/peak-flow", &[Method::GET, Method::POST]),
+            ("/ketones", &[Method::GET, Method::POST]),
+            ("/lipids", &[Method::GET, Method::POST]),
+            ("/temperature-locations", &[Method::GET, Method::POST]),
+            ("/heights", &[Method::GET, Method::POST]),
+            ("/custom", &[Method::GET, Method::POST]),
+            ("/diary", &[Method::GET, Method::POST]),
+            ("/alerts", &[Method::GET]),
+            ("/api/bp", &[Method::GET]),
+            ("/api/bp/stats", &[Method::GET]),
+            ("/api/bp/trends", &[Method::GET]),
+            ("/api/mass", &[Method::GET]),
+            ("/api/mass/stats", &[Method::GET]),
+            ("/api/mass/trends", &[Method::GET]),
+            ("/api/heights", &[Method::GET, Method::POST]),
+            ("/api/temperature", &[Method::GET]),
+            ("/api/temperature/stats", &[Method::GET]),
+            ("/api/temperature/trends", &[Method::GET]),
+            ("/api/temperature/episodes", &[Method::GET]),
+            ("/api/temperature/locations", &[Method::GET, Method::POST]),
+            ("/api/sugar", &[Method::GET]),
+            ("/api/sugar/stats", &[Method::GET]),
+            ("/api/sugar/trends", &[Method::GET]),
+            ("/api/long-term-sugar", &[Method::GET]),
+            ("/api/long-term-sugar/stats", &[Method::GET]),
+            ("/api/long-term-sugar/trends", &[Method::GET]),
+            ("/api/long-term-sugar/glycation", &[Method::GET]),
+            ("/api/peak-flow", &[Method::GET]),
+            ("/api/peak-flow/stats", &[Method::GET]),
+            ("/api/peak-flow/trends", &[Method::GET]),
+            ("/api/ketones", &[Method::GET]),
+            ("/api/ketones/stats", &[Method::GET]),
+            ("/api/ketones/trends", &[Method::GET]),
+            ("/api/lipids", &[Method::GET]),
+            ("/api/lipids/stats", &[Method::GET]),
+            ("/api/lipids/trends", &[Method::GET]),
+            ("/api/custom", &[Method::GET]),
+            ("/api/custom/spirometry", &[Method::GET]),
+            ("/api/custom/spirometry/stats", &[Method::GET]),
+            ("/api/custom/spirometry/trends", &[Method::GET]),
+            ("/api/diary", &[Method::GET]),
+            ("/api/alerts", &[Method::GET]),
+        ];
+        for (path, methods) in routes {
+            assert_eq!(request(Method::GET, path, None).await.0, StatusCode::FORBIDDEN, "{}", path);
+            assert_eq!(request(Method::GET, &format!("{}?token=reader", path), None).await.0, StatusCode::OK, "{}", path);
+            for method in [Method::POST, Method::PUT, Method::DELETE, Method::PATCH] {
+                if methods.contains(&method) {
+                    continue;
+                }
+                let (status, _) = request(method.clone(), &format!("{}?token=writer", path), Some("")).await;
+                assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "{} {}", method, path);
+            }
+        }
+    }
+
+    #[test]
+    fn api_measurements_paths() {
+        assert_eq!(split_api_measurements_path("/api/long-term-sugar"), Some((MeasurementKind::LongTermSugar, "")));
+        assert_eq!(split_api_measurements_path("/api/bp/stats"), Some((MeasurementKind::Bp, "stats")));
+        assert_eq!(split_api_measurements_path("/api/peak-flow/trends"), Some((MeasurementKind::PeakFlow, "trends")));
+        assert_eq!(split_api_measurements_path("/api/bp/"), None);
+        assert_eq!(split_api_measurements_path("/api/temperature/episodes"), Some((MeasurementKind::Temperature, "episodes")));
+        assert_eq!(split_api_measurements_path("/api/bp/episodes"), None);
+        assert_eq!(split_api_measurements_path("/api/temperature/locations"), None);
+        assert_eq!(split_api_measurements_path("/api/heights"), None);
+        assert_eq!(split_api_measurements_path("/mass"), None);
+    }
+
+    #[tokio::test]
+    async fn unrepresentable_derived_values() {
+        let (status, _) = request(Method::POST, "/mass?token=writer", Some("mass_kg=80&waist_circum_cm=10.00000001&hip_circum_cm=100.3")).await;
+        assert_eq!(status, StatusCode::FOUND);
+
+        let (status, body) = request(Method::POST, "/api/temperature/locations?token=writer", Some("name=forehead&core_offset_celsius=0.5")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let location: BodyTemperatureLocation = serde_json::from_str(&body).unwrap();
+        let (status, _) = request(Method::POST, "/temperature?token=writer", Some(&format!("location={}&temperature_celsius=2.000000001", location.id))).await;
+        assert_eq!(status, StatusCode::FOUND);
+
+        let (status, _) = request(Method::POST, "/lipids?token=writer", Some("total_cholesterol_mmol_per_l=5.00000001&hdl_cholesterol_mmol_per_l=1.000000007")).await;
+        assert_eq!(status, StatusCode::FOUND);
+
+        for path in ["/mass", "/api/mass", "/temperature", "/api/temperature", "/api/temperature/episodes", "/lipids", "/api/lipids", "/api/lipids/stats"] {
+            let (status, body) = request(Method::GET, &format!("{}?token=reader", path), None).await;
+            assert_eq!(status, StatusCode::OK, "{} {}", path, body);
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_forms() {
+        let cases = [
+            ("/", "systolic_mmhg=120&diastolic_mmhg=80"),
+            ("/", "systolic_mmhg=-120&diastolic_mmhg=80&pulse_bpm=60"),
+            ("/mass", "mass_kg=abc"),
+            ("/temperature", "location=1&temperature_celsius=-300"),
+            ("/sugar", "sugar_unit_key=furlongs&sugar_value=5"),
+            ("/long-term-sugar", "hba1c_value=40"),
+            ("/long-term-sugar", "hba1c_unit_key=dcct-percent&hba1c_value=6.57512345"),
+            ("/peak-flow", "peak_flow_l_per_min=fast"),
+            ("/ketones", "ketones_mmol_per_l=-0.1"),
+            ("/lipids", "total_cholesterol_mmol_per_l=&hdl_cholesterol_mmol_per_l="),
+            ("/diary", "wellbeing_score=11"),
+            ("/diary", "headache=&notes=+"),
+        ];
+        for (path, form) in cases {
+            let (status, _) = request(Method::POST, &format!("{}?token=writer", path), Some(form)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{} {}", path, form);
+        }
+    }
+
+    #[tokio::test]
+    async fn measurements_end_to_end() {
+        let cases = [
+            ("/", "systolic_mmhg=117&diastolic_mmhg=77&pulse_bpm=57&spo2_percent=97", "/api/bp", "\"systolic_mmhg\":117"),
+            ("/mass", "mass_kg=81.7&waist_circum_cm=93.5", "/api/mass", "\"mass_kg\":\"817/10\""),
+            ("/temperature", "location=1&temperature_celsius=37.7", "/api/temperature", "\"temperature_celsius\":\"377/10\""),
+            ("/sugar", "sugar_unit_key=mmol-per-l&sugar_value=5.7", "/api/sugar", "\"sugar_mmol_per_l\":\"57/10\""),
+            ("/long-term-sugar", "hba1c_unit_key=mmol-per-mol&hba1c_value=37", "/api/long-term-sugar", "\"hba1c_mmol_per_mol\":\"37/1\""),
+            ("/peak-flow", "peak_flow_l_per_min=470", "/api/peak-flow", "\"peak_flow_l_per_min\":470"),
+            ("/ketones", "ketones_mmol_per_l=0.4", "/api/ketones", "\"ketones_mmol_per_l\":\"2/5\""),
+            ("/lipids", "total_cholesterol_mmol_per_l=5.2&hdl_cholesterol_mmol_per_l=1.3", "/api/lipids", "\"total_cholesterol_mmol_per_l\":\"26/5\""),
+        ];
+        for (path, form, api_path, expected) in cases {
+            let (status, _) = request(Method::POST, &format!("{}?token=writer", path), Some(form)).await;
+            assert_eq!(status, StatusCode::FOUND, "{} {}", path, form);
+
+            let (status, body) = request(Method::GET, &format!("{}?token=reader", api_path), None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(body.contains(expected), "{} does not contain {}", body, expected);
+
+            for page in [path.to_owned(), format!("{}/stats", api_path), format!("{}/trends", api_path)] {
+                let (status, _) = request(Method::GET, &format!("{}?token=reader", page), None).await;
+                assert_eq!(status, StatusCode::OK, "{}", page);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_metrics() {
+        let (status, body) = request(Method::GET, "/custom?token=reader", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("custom?metric=spirometry&amp;token=reader"), "{}", body);
+
+        let (status, _) = request(Method::POST, "/custom?metric=spirometry&token=writer", Some("fev1=3.21&fvc=&position=standing")).await;
+        assert_eq!(status, StatusCode::FOUND);
+        let (_, body) = request(Method::GET, "/api/custom/spirometry?token=reader", None).await;
+        assert!(body.contains("\"metric\":\"spirometry\",\"values\":{\"fev1\":\"321/100\"},\"categories\":{\"position\":\"standing\"}"), "{}", body);
+        let (_, body) = request(Method::GET, "/api/custom/spirometry/stats?token=reader", None).await;
+        assert!(body.contains("\"key\":\"fev1\""), "{}", body);
+        let (_, body) = request(Method::GET, "/custom?metric=spirometry&token=reader", None).await;
+        assert!(body.contains("3.21"), "{}", body);
+
+        // missing, out-of-range and unknown values
+        for form in ["fvc=4.1", "fev1=11", "fev1=3&position=lying"] {
+            let (status, _) = request(Method::POST, "/custom?metric=spirometry&token=writer", Some(form)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", form);
+        }
+        assert_eq!(request(Method::POST, "/custom?metric=spirometry&token=reader", Some("fev1=3")).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(request(Method::GET, "/custom?metric=nonsense&token=reader", None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(request(Method::POST, "/custom?metric=nonsense&token=writer", Some("fev1=3")).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(request(Method::GET, "/api/custom/nonsense/trends?token=reader", None).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn diary() {
+        let (status, _) = request(Method::POST, "/?token=writer", Some("systolic_mmhg=163&diastolic_mmhg=97&pulse_bpm=88")).await;
+        assert_eq!(status, StatusCode::FOUND);
+        let (status, _) = request(Method::POST, "/diary?token=writer", Some("headache=on&palpitations=on&wellbeing_score=3&notes=pounding+head")).await;
+        assert_eq!(status, StatusCode::FOUND);
+
+        let (status, body) = request(Method::GET, "/api/diary?token=reader", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let entries: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let entry = entries.as_array().unwrap().iter()
+            .find(|e| e["entry"]["notes"] == "pounding head")
+            .unwrap();
+        assert_eq!(entry["entry"]["headache"], true);
+        assert_eq!(entry["entry"]["dizziness"], false);
+        assert_eq!(entry["entry"]["wellbeing_score"], 3);
+        assert!(entry["blood_pressure"].as_array().unwrap().iter().any(|bp| bp["systolic_mmhg"] == 163), "{}", body);
+
+        let (status, body) = request(Method::GET, "/diary?token=reader", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("headache, palpitations"), "{}", body);
+        assert!(body.contains("163/97"), "{}", body);
+
+        assert_eq!(request(Method::POST, "/diary?token=reader", Some("headache=on")).await.0, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn temperature_locations() {
+        let (status, body) = request(Method::POST, "/api/temperature/locations?token=writer", Some("name=+tympanic+left+")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let location: BodyTemperatureLocation = serde_json::from_str(&body).unwrap();
+        assert_eq!(location.name, "tympanic left");
+        let location_path = format!("/api/temperature/locations/{}", location.id);
+
+        let (status, body) = request(Method::PUT, &format!("{}?token=writer", location_path), Some("name=tympanic+right&core_offset_celsius=-0.2&normal_min_celsius=35.8&normal_max_celsius=38")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("\"tympanic right\""));
+        assert!(body.contains("\"core_offset_celsius\":\"-1/5\""));
+        let (_, body) = request(Method::GET, "/temperature-locations?token=reader", None).await;
+        assert!(body.contains("tympanic right"));
+
+        // invalid names, read-only tokens and unknown locations
+        assert_eq!(request(Method::PUT, &format!("{}?token=writer", location_path), Some("name=+")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(request(Method::PUT, &format!("{}?token=writer", location_path), Some("name=x&core_offset_celsius=6")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(request(Method::PUT, &format!("{}?token=writer", location_path), Some("name=x&normal_min_celsius=38&normal_max_celsius=36")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(request(Method::PUT, &format!("{}?token=reader", location_path), Some("name=x")).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(request(Method::DELETE, "/api/temperature/locations/999999?token=writer", None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(request(Method::DELETE, "/api/temperature/locations/abc?token=writer", None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(request(Method::GET, &format!("{}?token=reader", location_path), None).await.0, StatusCode::METHOD_NOT_ALLOWED);
+
+        // locations that are in use cannot be removed
+        let (status, _) = request(Method::POST, "/temperature?token=writer", Some(&format!("location={}&temperature_celsius=38.5", location.id))).await;
+        assert_eq!(status, StatusCode::FOUND);
+        let (_, body) = request(Method::GET, "/api/temperature?token=reader", None).await;
+        assert!(body.contains("\"temperature_celsius\":\"77/2\",\"core_temperature_celsius\":\"383/10\",\"status\":\"fever\""), "{}", body);
+        let (_, body) = request(Method::GET, "/api/temperature/episodes?token=reader", None).await;
+        assert!(body.contains("\"temperature_celsius\":\"77/2\""), "{}", body);
+        assert_eq!(request(Method::DELETE, &format!("{}?token=writer", location_path), None).await.0, StatusCode::CONFLICT);
+        let (status, _) = request(Method::POST, "/temperature-locations?token=writer", Some("action=remove&id=1")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = request(Method::POST, "/temperature-locations?token=writer", Some("action=add&name=rectal+%28child%29")).await;
+        assert_eq!(status, StatusCode::FOUND);
+        let (_, body) = request(Method::GET, "/api/temperature/locations?token=reader", None).await;
+        let locations: Vec<BodyTemperatureLocation> = serde_json::from_str(&body).unwrap();
+        let unused = locations.iter().find(|l| l.name == "rectal (child)").unwrap();
+        let (status, _) = request(Method::DELETE, &format!("/api/temperature/locations/{}?token=writer", unused.id), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn height_history() {
+        // the same as the configured height, so that the BMI of other tests' measurements stays the same
+        let (status, body) = request(Method::POST, "/api/heights?token=writer", Some("height_cm=180&date=2001-02-03&utc_offset_minutes=60")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let height: HeightMeasurement = serde_json::from_str(&body).unwrap();
+        assert_eq!(height.timestamp.to_rfc3339(), "2001-02-03T00:00:00+01:00");
+        assert_eq!(height.height_cm, Rational32::new(180, 1));
+
+        let (_, body) = request(Method::GET, "/api/heights?token=reader&units=preferred&format=csv", None).await;
+        assert!(body.starts_with("id,timestamp,height_cm\r\n"), "{}", body);
+        let (_, body) = request(Method::GET, "/api/heights?token=imperial&units=preferred", None).await;
+        assert!(body.contains("\"height_in\":"), "{}", body);
+        let (_, body) = request(Method::GET, "/heights?token=imperial", None).await;
+        assert!(body.contains("<td class=\"height\">70.9</td>"), "{}", body);
+
+        assert_eq!(request(Method::POST, "/api/heights?token=writer", Some("height_cm=0")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(request(Method::POST, "/api/heights?token=writer", Some("height_cm=180&date=3.2.2001")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(request(Method::POST, "/api/heights?token=reader", Some("height_cm=180")).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(request(Method::DELETE, "/api/heights/999999?token=writer", None).await.0, StatusCode::NOT_FOUND);
+        let (status, _) = request(Method::DELETE, &format!("/api/heights/{}?token=writer", height.id), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        // in the future, so that it does not apply to other tests' measurements either
+        let (status, _) = request(Method::POST, "/heights?token=imperial", Some("action=add&height_unit_key=in&height_value=70&date=2999-02-03")).await;
+        assert_eq!(status, StatusCode::FOUND);
+        let (_, body) = request(Method::GET, "/api/heights?token=reader", None).await;
+        let heights: Vec<HeightMeasurement> = serde_json::from_str(&body).unwrap();
+        let added = heights.iter().find(|h| h.height_cm == Rational32::new(889, 5)).unwrap();
+        let (status, _) = request(Method::POST, "/heights?token=writer", Some(&format!("action=remove&id={}", added.id))).await;
+        assert_eq!(status, StatusCode::FOUND);
+    }
+
+    #[tokio::test]
+    async fn glycation_comparison() {
+        let (status, _) = request(Method::POST, "/sugar?token=writer", Some("sugar_unit_key=mg-per-dl&sugar_value=126")).await;
+        assert_eq!(status, StatusCode::FOUND);
+        let (status, _) = request(Method::POST, "/long-term-sugar?token=writer", Some("hba1c_unit_key=dcct-percent&hba1c_value=6.5")).await;
+        assert_eq!(status, StatusCode::FOUND);
+
+        let (status, body) = request(Method::GET, "/api/long-term-sugar/glycation?token=reader", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let glycation: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(glycation["current_gmi"]["reading_count"].as_u64().unwrap() >= 1);
+        let comparison = glycation["comparisons"].as_array().unwrap().iter()
+            .find(|c| c["measurement"]["hba1c_mmol_per_mol"] == "1191261/25000")
+            .unwrap();
+        // 6.5% DCCT corresponds to an eAG of 140 mg/dl
+        assert!((comparison["eag_mg_per_dl"].as_f64().unwrap() - 139.85).abs() < 0.01);
+        assert!(comparison["gmi"]["gmi_dcct_percent"].is_f64());
+
+        let (status, body) = request(Method::GET, "/long-term-sugar?token=reader", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("GMI"));
+    }
+
+    #[tokio::test]
+    async fn unit_preferences() {
+        let (status, _) = request(Method::POST, "/mass?token=imperial", Some("mass_unit_key=lb&mass_value=180&waist_circum_unit_key=in&waist_circum_value=35")).await;
+        assert_eq!(status, StatusCode::FOUND);
+        let (status, _) = request(Method::POST, "/temperature?token=imperial", Some("location=1&temperature_unit_key=fahrenheit&temperature_value=98.6")).await;
+        assert_eq!(status, StatusCode::FOUND);
+
+        // values are stored in the canonical units
+        let (_, body) = request(Method::GET, "/api/mass?token=imperial", None).await;
+        let measurements: Vec<BodyMassMeasurement> = serde_json::from_str(&body).unwrap();
+        let measurement = measurements.iter()
+            .find(|m| m.waist_circum_cm == Some(Rational32::new(889, 10)))
+            .unwrap();
+        assert!((r32_to_f64(&measurement.mass_kg) - 81.6466266).abs() < 1e-9);
+        let (_, body) = request(Method::GET, "/api/temperature?token=imperial", None).await;
+        assert!(body.contains("\"temperature_celsius\":\"37/1\""), "{}", body);
+
+        // and converted back on request
+        let (_, body) = request(Method::GET, "/api/mass?token=imperial&units=preferred", None).await;
+        let measurements: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+        let measurement = measurements.iter()
+            .find(|m| m["waist_circum_in"] == "35/1")
+            .unwrap();
+        let mass_lb = string_to_rat32(measurement["mass_lb"].as_str().unwrap()).unwrap();
+        assert!((r32_to_f64(&mass_lb) - 180.0).abs() < 1e-6);
+        assert!(measurement.get("mass_kg").is_none());
+        let (_, body) = request(Method::GET, "/api/temperature/stats?token=imperial&units=preferred", None).await;
+        assert!(body.contains("\"unit\":\"°F\""), "{}", body);
+
+        let (status, body) = request(Method::GET, "/api/mass?token=imperial&units=preferred&format=csv", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with(concat!(
+            "id,timestamp,bmi,body_fat_percent,body_water_percent,hip_circum_in,mass_lb,muscle_mass_lb,",
+            "visceral_fat_rating,waist_circum_in,waist_to_height_ratio,waist_to_height_risk,waist_to_hip_ratio,waist_to_hip_risk\r\n",
+        )), "{}", body);
+        assert!(body.contains(",35,"), "{}", body);
+
+        let (_, body) = request(Method::GET, "/mass?token=imperial", None).await;
+        assert!(body.contains("<td class=\"mass\">180.00</td>"), "{}", body);
+        let (_, body) = request(Method::GET, "/mass?token=reader", None).await;
+        assert!(body.contains("<td class=\"mass\">81.65</td>"), "{}", body);
+
+        assert_eq!(request(Method::GET, "/api/mass?token=imperial&units=metric", None).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(request(Method::GET, "/api/mass?token=imperial&format=xml", None).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(request(Method::POST, "/mass?token=imperial", Some("mass_unit_key=oz&mass_value=3000")).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn body_composition() {
+        let form = "mass_kg=81&waist_circum_cm=93&hip_circum_cm=100&body_fat_percent=23.5&muscle_mass_kg=60.2&visceral_fat_rating=9&body_water_percent=55";
+        let (status, _) = request(Method::POST, "/mass?token=writer", Some(form)).await;
+        assert_eq!(status, StatusCode::FOUND);
+
+        let (_, body) = request(Method::GET, "/api/mass?token=reader", None).await;
+        let measurements: Vec<BodyMassMeasurement> = serde_json::from_str(&body).unwrap();
+        let measurement = measurements.iter()
+            .find(|m| m.hip_circum_cm == Some(Rational32::new(100, 1)))
+            .unwrap();
+        assert_eq!(measurement.composition.body_fat_percent, Some(Rational32::new(47, 2)));
+        assert_eq!(measurement.composition.muscle_mass_kg, Some(Rational32::new(301, 5)));
+        assert_eq!(measurement.bmi, Some(Rational32::new(25, 1)));
+        assert_eq!(measurement.waist_to_hip_ratio, Some(Rational32::new(93, 100)));
+        assert_eq!(measurement.waist_to_hip_risk, Some(RiskCategory::Increased));
+        assert_eq!(measurement.waist_to_height_ratio, Some(Rational32::new(31, 60)));
+        assert_eq!(measurement.waist_to_height_risk, Some(RiskCategory::Increased));
+
+        let (_, body) = request(Method::GET, "/mass?token=reader", None).await;
+        assert!(body.contains("0.930 (increased risk)"), "{}", body);
+
+        assert_eq!(request(Method::POST, "/mass?token=writer", Some("mass_kg=81&body_fat_percent=101")).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn alerts_are_recorded() {
+        let (status, _) = request(Method::POST, "/?token=writer", Some("systolic_mmhg=191&diastolic_mmhg=95&pulse_bpm=80")).await;
+        assert_eq!(status, StatusCode::FOUND);
+
+        // alert rules are checked in the background
+        for _ in 0..50 {
+            let (status, body) = request(Method::GET, "/api/alerts?token=reader", None).await;
+            assert_eq!(status, StatusCode::OK);
+            if body.contains("systolic BP 191.0 above 180.0") {
+                let (status, body) = request(Method::GET, "/alerts?token=reader", None).await;
+                assert_eq!(status, StatusCode::OK);
+                assert!(body.contains("hypertensive crisis"));
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("no alert recorded");
+    }
+}
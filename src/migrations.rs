@@ -0,0 +1,142 @@
+use crate::database::{DatabaseError, Storage, storage};
+
+
+/// A versioned change to the database schema.
+///
+/// Migrations are applied in ascending order of version, each in its own transaction. Every migration
+/// has one variant per storage backend. The PostgreSQL statements are written so that they can also be
+/// applied to a database that was set up by hand before migrations were introduced.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub postgres_sql: &'static str,
+    pub sqlite_sql: &'static str,
+}
+
+
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        postgres_sql: include_str!("../db/migrations/postgres/0001_initial.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "utc_offsets",
+        postgres_sql: include_str!("../db/migrations/postgres/0002_utc_offsets.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0002_utc_offsets.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "alerts",
+        postgres_sql: include_str!("../db/migrations/postgres/0003_alerts.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0003_alerts.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "exact_rationals",
+        postgres_sql: include_str!("../db/migrations/postgres/0004_exact_rationals.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0004_exact_rationals.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "temperature_reference_ranges",
+        postgres_sql: include_str!("../db/migrations/postgres/0005_temperature_reference_ranges.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0005_temperature_reference_ranges.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "body_composition",
+        postgres_sql: include_str!("../db/migrations/postgres/0006_body_composition.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0006_body_composition.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "height_measurements",
+        postgres_sql: include_str!("../db/migrations/postgres/0007_height_measurements.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0007_height_measurements.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "vital_signs",
+        postgres_sql: include_str!("../db/migrations/postgres/0008_vital_signs.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0008_vital_signs.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "custom_measurements",
+        postgres_sql: include_str!("../db/migrations/postgres/0009_custom_measurements.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0009_custom_measurements.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "diary_entries",
+        postgres_sql: include_str!("../db/migrations/postgres/0010_diary_entries.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0010_diary_entries.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "exact_numeric_scale",
+        postgres_sql: include_str!("../db/migrations/postgres/0011_exact_numeric_scale.sql"),
+        sqlite_sql: include_str!("../db/migrations/sqlite/0011_exact_numeric_scale.sql"),
+    },
+];
+
+
+/// Returns the migrations that have not been applied to the database yet.
+pub(crate) fn pending_migrations(applied_versions: &[i32]) -> Vec<&'static Migration> {
+    MIGRATIONS.iter()
+        .filter(|m| !applied_versions.contains(&m.version))
+        .collect()
+}
+
+/// Applies all pending migrations and returns them.
+///
+/// If `dry_run` is set, the database is not modified and the migrations that would be applied are
+/// returned.
+pub(crate) async fn migrate(dry_run: bool) -> Result<Vec<&'static Migration>, DatabaseError> {
+    migrate_storage(storage(), dry_run)
+        .await
+}
+
+/// Applies all pending migrations to the given storage and returns them.
+pub(crate) async fn migrate_storage<S: Storage>(storage: &S, dry_run: bool) -> Result<Vec<&'static Migration>, DatabaseError> {
+    let applied_versions = storage.get_applied_migration_versions()
+        .await?;
+    let pending = pending_migrations(&applied_versions);
+    if dry_run || pending.is_empty() {
+        return Ok(pending);
+    }
+
+    let mut applied = Vec::with_capacity(pending.len());
+    for migration in pending {
+        // another instance might have applied the migration in the meantime
+        let newly_applied = storage.apply_migration(migration)
+            .await?;
+        if newly_applied {
+            applied.push(migration);
+        }
+    }
+
+    Ok(applied)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_ascending() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i32::try_from(i).unwrap() + 1);
+        }
+        let pending: Vec<i32> = pending_migrations(&[1, 2])
+            .iter()
+            .map(|m| m.version)
+            .collect();
+        assert_eq!(pending, vec![3, 4, 5, 6, 7, 8, 9, 10, 11]);
+    }
+}
@@ -1,11 +1,16 @@
-use std::convert::TryInto;
+use std::collections::BTreeMap;
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, FixedOffset, Timelike};
 use num_rational::Rational32;
-use num_traits::Zero;
+use num_traits::{CheckedDiv, CheckedMul, CheckedSub};
 use serde::{Deserialize, Serialize};
 
-use crate::numerism::{optional_max, optional_min, quasi_n_tile_index};
+use crate::anthropometrics::RiskCategory;
+use crate::config::Hours;
+use crate::fever::TemperatureStatus;
+use crate::numerism::r32_to_f64;
+use crate::statistics::StatisticsField;
+use crate::units::Quantity;
 
 
 pub(crate) const SUGAR_MG_PER_DL_IN_MMOL_PER_L: i32 = 18;
@@ -13,12 +18,38 @@ pub(crate) const HBA1C_ADDITIVE_NUMER: i32 = 214;
 pub(crate) const HBA1C_ADDITIVE_DENOM: i32 = 100;
 pub(crate) const HBA1C_MULTIPLICATIVE_NUMER: i32 = 10_929;
 pub(crate) const HBA1C_MULTIPLICATIVE_DENOM: i32 = 1_000;
+/// Estimated average glucose in mg/dL per percentage point of HBA1c (DCCT), after Nathan et al. (2008).
+pub(crate) const EAG_MG_PER_DL_PER_DCCT_PERCENT: f64 = 28.7;
+pub(crate) const EAG_MG_PER_DL_OFFSET: f64 = -46.7;
+
+
+/// A kind of measurement with a fixed set of fields.
+///
+/// Statistics, trends, alert rules and the generic API handlers work on any type implementing this
+/// trait; a new kind of measurement provides its field descriptors here and is listed in the
+/// `measurement_kinds!` invocation below.
+pub(crate) trait Measurement: Sized + 'static {
+    /// The kind of measurement, which also names its URL paths.
+    const KIND: MeasurementKind;
+
+    /// The fields of which statistics and trends are calculated and to which alert rules apply.
+    const STATISTICS_FIELDS: &'static [StatisticsField<Self>];
+
+    /// The number of days of measurements shown on the page and returned by the API.
+    const RECENT_DAYS: i64 = 3*31;
+
+    /// The number of days of measurements on which alert rules are evaluated.
+    const ALERT_WINDOW_DAYS: i64 = 31;
+
+    fn id(&self) -> i64;
+    fn timestamp(&self) -> DateTime<FixedOffset>;
+}
 
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub(crate) struct BloodPressureMeasurement {
     pub id: i64,
-    #[serde(with = "crate::ser_de::serde_datetime_local")] pub timestamp: DateTime<Local>,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
     pub systolic_mmhg: i32,
     pub diastolic_mmhg: i32,
     pub pulse_bpm: i32,
@@ -27,7 +58,7 @@ pub(crate) struct BloodPressureMeasurement {
 impl BloodPressureMeasurement {
     pub fn new(
         id: i64,
-        timestamp: DateTime<Local>,
+        timestamp: DateTime<FixedOffset>,
         systolic_mmhg: i32,
         diastolic_mmhg: i32,
         pulse_bpm: i32,
@@ -43,81 +74,34 @@ impl BloodPressureMeasurement {
         }
     }
 
-    pub fn values_max(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.max(other.timestamp),
-            self.systolic_mmhg.max(other.systolic_mmhg),
-            self.diastolic_mmhg.max(other.diastolic_mmhg),
-            self.pulse_bpm.max(other.pulse_bpm),
-            optional_max(self.spo2_percent, other.spo2_percent),
-        )
-    }
-
-    pub fn values_min(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.min(other.timestamp),
-            self.systolic_mmhg.min(other.systolic_mmhg),
-            self.diastolic_mmhg.min(other.diastolic_mmhg),
-            self.pulse_bpm.min(other.pulse_bpm),
-            optional_min(self.spo2_percent, other.spo2_percent),
-        )
-    }
-
-    pub fn average(measurements: &[Self]) -> Self {
-        assert_ne!(measurements.len(), 0);
-        let len_i32: i32 = measurements.len().try_into().unwrap();
-
-        let systolic_sum: i32 = measurements.iter().map(|m| m.systolic_mmhg).sum();
-        let diastolic_sum: i32 = measurements.iter().map(|m| m.diastolic_mmhg).sum();
-        let pulse_sum: i32 = measurements.iter().map(|m| m.pulse_bpm).sum();
-
-        let spo2s: Vec<i32> = measurements.iter().filter_map(|m| m.spo2_percent).collect();
-        let spo2s_sum: i32 = spo2s.iter().sum();
-        let spo2s_len_i32: i32 = spo2s.len().try_into().unwrap();
-        let spo2_percent = if spo2s_len_i32 > 0 {
-            Some(spo2s_sum / spo2s_len_i32)
-        } else {
-            None
-        };
-
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            systolic_sum / len_i32,
-            diastolic_sum / len_i32,
-            pulse_sum / len_i32,
-            spo2_percent,
-        )
+    /// The difference between systolic and diastolic blood pressure.
+    pub fn pulse_pressure_mmhg(&self) -> i32 {
+        self.systolic_mmhg - self.diastolic_mmhg
     }
 
-    pub fn quasi_n_tile(measurements: &[Self], n_num: usize, n_den: usize) -> Self {
-        assert_ne!(measurements.len(), 0);
-
-        let index = quasi_n_tile_index(measurements.len(), n_num, n_den);
-
-        let mut systolics: Vec<i32> = measurements.iter().map(|m| m.systolic_mmhg).collect();
-        systolics.sort_unstable();
-
-        let mut diastolics: Vec<i32> = measurements.iter().map(|m| m.diastolic_mmhg).collect();
-        diastolics.sort_unstable();
-
-        let mut pulses: Vec<i32> = measurements.iter().map(|m| m.pulse_bpm).collect();
-        pulses.sort_unstable();
+    /// The mean arterial pressure, estimated as diastolic pressure plus a third of the pulse
+    /// pressure.
+    pub fn mean_arterial_pressure_mmhg(&self) -> Rational32 {
+        Rational32::new(2 * self.diastolic_mmhg + self.systolic_mmhg, 3)
+    }
+}
+impl Measurement for BloodPressureMeasurement {
+    const KIND: MeasurementKind = MeasurementKind::Bp;
+    const STATISTICS_FIELDS: &'static [StatisticsField<Self>] = &[
+        StatisticsField { key: "systolic_mmhg", label: "systolic BP", quantity: Quantity::Fixed("mmHg"), digits: 1, extract: |m| Some(m.systolic_mmhg.into()) },
+        StatisticsField { key: "diastolic_mmhg", label: "diastolic BP", quantity: Quantity::Fixed("mmHg"), digits: 1, extract: |m| Some(m.diastolic_mmhg.into()) },
+        StatisticsField { key: "pulse_pressure_mmhg", label: "pulse pressure", quantity: Quantity::Fixed("mmHg"), digits: 1, extract: |m| Some(m.pulse_pressure_mmhg().into()) },
+        StatisticsField { key: "mean_arterial_pressure_mmhg", label: "mean arterial pressure", quantity: Quantity::Fixed("mmHg"), digits: 1, extract: |m| Some(r32_to_f64(&m.mean_arterial_pressure_mmhg())) },
+        StatisticsField { key: "pulse_bpm", label: "pulse", quantity: Quantity::Fixed("bpm"), digits: 1, extract: |m| Some(m.pulse_bpm.into()) },
+        StatisticsField { key: "spo2_percent", label: "SpO₂", quantity: Quantity::Fixed("%"), digits: 1, extract: |m| m.spo2_percent.map(|s| s.into()) },
+    ];
 
-        let mut spo2s: Vec<i32> = measurements.iter().filter_map(|m| m.spo2_percent).collect();
-        let spo2_index = quasi_n_tile_index(spo2s.len(), n_num, n_den);
-        spo2s.sort_unstable();
+    fn id(&self) -> i64 {
+        self.id
+    }
 
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            systolics[index],
-            diastolics[index],
-            pulses[index],
-            if spo2s.len() > 0 { Some(spo2s[spo2_index]) } else { None },
-        )
+    fn timestamp(&self) -> DateTime<FixedOffset> {
+        self.timestamp
     }
 }
 
@@ -150,23 +134,84 @@ impl DailyBloodPressureMeasurements {
     pub fn new_empty(date_string: String) -> Self {
         Self::new(date_string, None, None, None, Vec::new())
     }
+
+    /// Groups the given measurements by day, in ascending order of date.
+    ///
+    /// The first measurement of each part of the day is assigned to the respective slot; any further
+    /// measurements end up in `other`.
+    pub fn group_by_day(measurements: &[BloodPressureMeasurement], hours: &Hours) -> Vec<Self> {
+        let mut day_to_measurements: BTreeMap<String, Self> = BTreeMap::new();
+        for measurement in measurements {
+            let mut day = measurement.timestamp.date_naive();
+            if measurement.timestamp.hour() < hours.morning_start {
+                // count this as (the evening of) the previous day
+                day = day.pred_opt().expect("no previous day?!");
+            }
+
+            let date_string = day.format("%Y-%m-%d").to_string();
+
+            let entry = day_to_measurements
+                .entry(date_string.clone())
+                .or_insert_with(|| Self::new_empty(date_string));
+
+            let this_hour = measurement.timestamp.hour();
+
+            if this_hour < hours.morning_start && entry.evening.is_none() {
+                // night (previous day)
+                entry.evening = Some(*measurement);
+            } else if this_hour >= hours.morning_start && this_hour < hours.morning_end && entry.morning.is_none() {
+                // morning
+                entry.morning = Some(*measurement);
+            } else if this_hour >= hours.midday_start && this_hour < hours.midday_end && entry.midday.is_none() {
+                // midday
+                entry.midday = Some(*measurement);
+            } else if this_hour >= hours.evening_start && entry.evening.is_none() {
+                // night
+                entry.evening = Some(*measurement);
+            } else {
+                entry.other.push(*measurement);
+            }
+        }
+
+        day_to_measurements
+            .into_values()
+            .collect()
+    }
 }
 
 
+/// Values reported by body composition (bioelectrical impedance) scales.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct BodyComposition {
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub body_fat_percent: Option<Rational32>,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub muscle_mass_kg: Option<Rational32>,
+    /// The manufacturer-specific visceral fat level, usually between 1 and 59.
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub visceral_fat_rating: Option<Rational32>,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub body_water_percent: Option<Rational32>,
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub(crate) struct BodyMassMeasurement {
     pub id: i64,
-    #[serde(with = "crate::ser_de::serde_datetime_local")] pub timestamp: DateTime<Local>,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
     #[serde(with = "crate::ser_de::serde_rat32")] pub mass_kg: Rational32,
     #[serde(with = "crate::ser_de::serde_rat32_opt")] pub waist_circum_cm: Option<Rational32>,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub hip_circum_cm: Option<Rational32>,
+    #[serde(flatten)] pub composition: BodyComposition,
     #[serde(with = "crate::ser_de::serde_rat32_opt")] pub bmi: Option<Rational32>,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub waist_to_hip_ratio: Option<Rational32>,
+    #[serde(default)] pub waist_to_hip_risk: Option<RiskCategory>,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub waist_to_height_ratio: Option<Rational32>,
+    #[serde(default)] pub waist_to_height_risk: Option<RiskCategory>,
 }
 impl BodyMassMeasurement {
     pub fn new(
         id: i64,
-        timestamp: DateTime<Local>,
+        timestamp: DateTime<FixedOffset>,
         mass_kg: Rational32,
         waist_circum_cm: Option<Rational32>,
+        hip_circum_cm: Option<Rational32>,
+        composition: BodyComposition,
         bmi: Option<Rational32>,
     ) -> Self {
         Self {
@@ -174,77 +219,59 @@ impl BodyMassMeasurement {
             timestamp,
             mass_kg,
             waist_circum_cm,
+            hip_circum_cm,
+            composition,
             bmi,
+            waist_to_hip_ratio: None,
+            waist_to_hip_risk: None,
+            waist_to_height_ratio: None,
+            waist_to_height_risk: None,
         }
     }
-
-    pub fn values_max(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.max(other.timestamp),
-            self.mass_kg.max(other.mass_kg),
-            optional_max(self.waist_circum_cm, other.waist_circum_cm),
-            optional_max(self.bmi, other.bmi),
-        )
-    }
-
-    pub fn values_min(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.min(other.timestamp),
-            self.mass_kg.min(other.mass_kg),
-            optional_min(self.waist_circum_cm, other.waist_circum_cm),
-            optional_min(self.bmi, other.bmi),
-        )
-    }
-
-    pub fn average(measurements: &[Self]) -> Self {
-        assert_ne!(measurements.len(), 0);
-        let len_i32: i32 = measurements.len().try_into().unwrap();
-        let len_r32: Rational32 = len_i32.into();
-
-        let circum_len_i32: i32 = measurements.iter().filter(|m| m.waist_circum_cm.is_some()).count().try_into().unwrap();
-        let circum_len_r32: Rational32 = circum_len_i32.into();
-
-        let bmi_len_i32: i32 = measurements.iter().filter(|m| m.bmi.is_some()).count().try_into().unwrap();
-        let bmi_len_r32: Rational32 = bmi_len_i32.into();
-
-        let mass_sum: Rational32 = measurements.iter().map(|m| m.mass_kg).sum();
-        let bmi_sum: Rational32 = measurements.iter().filter_map(|m| m.bmi).sum();
-        let circum_sum: Rational32 = measurements.iter().filter_map(|m| m.waist_circum_cm).sum();
-
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            mass_sum / len_r32,
-            if circum_len_r32 != Rational32::zero() { Some(circum_sum / circum_len_r32) } else { None },
-            if bmi_len_r32 != Rational32::zero() { Some(bmi_sum / bmi_len_r32) } else { None },
-        )
+}
+impl Measurement for BodyMassMeasurement {
+    const KIND: MeasurementKind = MeasurementKind::Mass;
+    const STATISTICS_FIELDS: &'static [StatisticsField<Self>] = &[
+        StatisticsField { key: "mass_kg", label: "mass", quantity: Quantity::Mass, digits: 2, extract: |m| Some(r32_to_f64(&m.mass_kg)) },
+        StatisticsField { key: "waist_circum_cm", label: "waist circumference", quantity: Quantity::Length, digits: 2, extract: |m| m.waist_circum_cm.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "hip_circum_cm", label: "hip circumference", quantity: Quantity::Length, digits: 2, extract: |m| m.hip_circum_cm.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "bmi", label: "BMI", quantity: Quantity::Fixed("kg/m²"), digits: 2, extract: |m| m.bmi.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "waist_to_hip_ratio", label: "waist-to-hip ratio", quantity: Quantity::Fixed(""), digits: 3, extract: |m| m.waist_to_hip_ratio.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "waist_to_height_ratio", label: "waist-to-height ratio", quantity: Quantity::Fixed(""), digits: 3, extract: |m| m.waist_to_height_ratio.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "body_fat_percent", label: "body fat", quantity: Quantity::Fixed("%"), digits: 1, extract: |m| m.composition.body_fat_percent.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "muscle_mass_kg", label: "muscle mass", quantity: Quantity::Mass, digits: 2, extract: |m| m.composition.muscle_mass_kg.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "visceral_fat_rating", label: "visceral fat rating", quantity: Quantity::Fixed(""), digits: 1, extract: |m| m.composition.visceral_fat_rating.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "body_water_percent", label: "body water", quantity: Quantity::Fixed("%"), digits: 1, extract: |m| m.composition.body_water_percent.as_ref().map(r32_to_f64) },
+    ];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn timestamp(&self) -> DateTime<FixedOffset> {
+        self.timestamp
     }
+}
 
-    pub fn quasi_n_tile(measurements: &[Self], n_num: usize, n_den: usize) -> Self {
-        assert_ne!(measurements.len(), 0);
-
-        let mut masses: Vec<Rational32> = measurements.iter().map(|m| m.mass_kg).collect();
-        masses.sort_unstable();
-
-        let mut bmis: Vec<Rational32> = measurements.iter().filter_map(|m| m.bmi).collect();
-        bmis.sort_unstable();
-
-        let mut circums: Vec<Rational32> = measurements.iter().filter_map(|m| m.waist_circum_cm).collect();
-        circums.sort_unstable();
-
-        let index = quasi_n_tile_index(measurements.len(), n_num, n_den);
-        let bmi_index = quasi_n_tile_index(bmis.len(), n_num, n_den);
-        let circum_index = quasi_n_tile_index(circums.len(), n_num, n_den);
-
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            masses[index],
-            circums.get(circum_index).map(|c| c.clone()),
-            bmis.get(bmi_index).map(|b| b.clone()),
-        )
+/// A measurement of body height. The BMI and waist-to-height ratio of a body mass measurement are
+/// derived from the latest height measured at or before it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct HeightMeasurement {
+    pub id: i64,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
+    #[serde(with = "crate::ser_de::serde_rat32")] pub height_cm: Rational32,
+}
+impl HeightMeasurement {
+    pub fn new(
+        id: i64,
+        timestamp: DateTime<FixedOffset>,
+        height_cm: Rational32,
+    ) -> Self {
+        Self {
+            id,
+            timestamp,
+            height_cm,
+        }
     }
 }
 
@@ -252,15 +279,27 @@ impl BodyMassMeasurement {
 pub(crate) struct BodyTemperatureLocation {
     pub id: i64,
     pub name: String,
+    /// Added to a reading at this location to estimate the core body temperature.
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub core_offset_celsius: Option<Rational32>,
+    /// The lowest normal reading at this location; lower readings indicate hypothermia.
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub normal_min_celsius: Option<Rational32>,
+    /// The highest normal reading at this location; higher readings indicate fever.
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub normal_max_celsius: Option<Rational32>,
 }
 impl BodyTemperatureLocation {
     pub fn new(
         id: i64,
         name: String,
+        core_offset_celsius: Option<Rational32>,
+        normal_min_celsius: Option<Rational32>,
+        normal_max_celsius: Option<Rational32>,
     ) -> Self {
         Self {
             id,
             name,
+            core_offset_celsius,
+            normal_min_celsius,
+            normal_max_celsius,
         }
     }
 }
@@ -268,85 +307,57 @@ impl BodyTemperatureLocation {
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub(crate) struct BodyTemperatureMeasurement {
     pub id: i64,
-    #[serde(with = "crate::ser_de::serde_datetime_local")] pub timestamp: DateTime<Local>,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
     pub location_id: i64,
     #[serde(with = "crate::ser_de::serde_rat32")] pub temperature_celsius: Rational32,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub core_temperature_celsius: Option<Rational32>,
+    #[serde(default)] pub status: Option<TemperatureStatus>,
 }
 impl BodyTemperatureMeasurement {
     pub fn new(
         id: i64,
-        timestamp: DateTime<Local>,
+        timestamp: DateTime<FixedOffset>,
         location_id: i64,
         temperature_celsius: Rational32,
+        core_temperature_celsius: Option<Rational32>,
+        status: Option<TemperatureStatus>,
     ) -> Self {
         Self {
             id,
             timestamp,
             location_id,
             temperature_celsius,
+            core_temperature_celsius,
+            status,
         }
     }
+}
+impl Measurement for BodyTemperatureMeasurement {
+    const KIND: MeasurementKind = MeasurementKind::Temperature;
+    const STATISTICS_FIELDS: &'static [StatisticsField<Self>] = &[
+        StatisticsField { key: "temperature_celsius", label: "temperature", quantity: Quantity::Temperature, digits: 2, extract: |m| Some(r32_to_f64(&m.temperature_celsius)) },
+        StatisticsField { key: "core_temperature_celsius", label: "core temperature", quantity: Quantity::Temperature, digits: 2, extract: |m| m.core_temperature_celsius.as_ref().map(r32_to_f64) },
+    ];
 
-    pub fn values_max(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.max(other.timestamp),
-            self.location_id.max(other.location_id),
-            self.temperature_celsius.max(other.temperature_celsius),
-        )
-    }
-
-    pub fn values_min(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.min(other.timestamp),
-            self.location_id.min(other.location_id),
-            self.temperature_celsius.min(other.temperature_celsius),
-        )
-    }
-
-    pub fn average(measurements: &[Self]) -> Self {
-        assert_ne!(measurements.len(), 0);
-        let len_i32: i32 = measurements.len().try_into().unwrap();
-        let len_r32: Rational32 = len_i32.into();
-
-        let temperature_celsius_sum: Rational32 = measurements.iter().map(|m| m.temperature_celsius).sum();
-
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            measurements[0].location_id,
-            temperature_celsius_sum / len_r32,
-        )
+    fn id(&self) -> i64 {
+        self.id
     }
 
-    pub fn quasi_n_tile(measurements: &[Self], n_num: usize, n_den: usize) -> Self {
-        assert_ne!(measurements.len(), 0);
-
-        let mut temperatures: Vec<Rational32> = measurements.iter().map(|m| m.temperature_celsius).collect();
-        temperatures.sort_unstable();
-
-        let index = quasi_n_tile_index(measurements.len(), n_num, n_den);
-
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            measurements[0].location_id,
-            temperatures[index],
-        )
+    fn timestamp(&self) -> DateTime<FixedOffset> {
+        self.timestamp
     }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub(crate) struct BloodSugarMeasurement {
     pub id: i64,
-    #[serde(with = "crate::ser_de::serde_datetime_local")] pub timestamp: DateTime<Local>,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
     #[serde(with = "crate::ser_de::serde_rat32")] pub sugar_mmol_per_l: Rational32,
 }
 impl BloodSugarMeasurement {
     pub fn new(
         id: i64,
-        timestamp: DateTime<Local>,
+        timestamp: DateTime<FixedOffset>,
         sugar_mmol_per_l: Rational32,
     ) -> Self {
         Self {
@@ -356,79 +367,50 @@ impl BloodSugarMeasurement {
         }
     }
 
+    /// Creates a measurement from a value in mg/dL, or returns `None` if its value in mmol/L cannot
+    /// be represented as a 32-bit rational number.
     pub fn new_mg_per_dl(
         id: i64,
-        timestamp: DateTime<Local>,
+        timestamp: DateTime<FixedOffset>,
         sugar_mg_per_dl: Rational32,
-    ) -> Self {
-        let sugar_mmol_per_l = &sugar_mg_per_dl / SUGAR_MG_PER_DL_IN_MMOL_PER_L;
-        Self::new(
+    ) -> Option<Self> {
+        let sugar_mmol_per_l = sugar_mg_per_dl.checked_div(&Rational32::from_integer(SUGAR_MG_PER_DL_IN_MMOL_PER_L))?;
+        Some(Self::new(
             id,
             timestamp,
             sugar_mmol_per_l,
-        )
+        ))
     }
 
-    pub fn sugar_mg_per_dl(&self) -> Rational32 {
-        self.sugar_mmol_per_l * SUGAR_MG_PER_DL_IN_MMOL_PER_L
+    pub fn sugar_mg_per_dl(&self) -> f64 {
+        r32_to_f64(&self.sugar_mmol_per_l) * f64::from(SUGAR_MG_PER_DL_IN_MMOL_PER_L)
     }
+}
+impl Measurement for BloodSugarMeasurement {
+    const KIND: MeasurementKind = MeasurementKind::Sugar;
+    const STATISTICS_FIELDS: &'static [StatisticsField<Self>] = &[
+        StatisticsField { key: "sugar_mmol_per_l", label: "blood sugar", quantity: Quantity::BloodSugar, digits: 1, extract: |m| Some(r32_to_f64(&m.sugar_mmol_per_l)) },
+    ];
 
-    pub fn values_max(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.max(other.timestamp),
-            self.sugar_mmol_per_l.max(other.sugar_mmol_per_l),
-        )
+    fn id(&self) -> i64 {
+        self.id
     }
 
-    pub fn values_min(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.min(other.timestamp),
-            self.sugar_mmol_per_l.min(other.sugar_mmol_per_l),
-        )
-    }
-
-    pub fn average(measurements: &[Self]) -> Self {
-        assert_ne!(measurements.len(), 0);
-        let len_i32: i32 = measurements.len().try_into().unwrap();
-        let len_r32: Rational32 = len_i32.into();
-
-        let sugar_mmol_per_l_sum: Rational32 = measurements.iter().map(|m| m.sugar_mmol_per_l).sum();
-
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            sugar_mmol_per_l_sum / len_r32,
-        )
-    }
-
-    pub fn quasi_n_tile(measurements: &[Self], n_num: usize, n_den: usize) -> Self {
-        assert_ne!(measurements.len(), 0);
-
-        let mut sugars_mmol_per_l: Vec<Rational32> = measurements.iter().map(|m| m.sugar_mmol_per_l).collect();
-        sugars_mmol_per_l.sort_unstable();
-
-        let index = quasi_n_tile_index(measurements.len(), n_num, n_den);
-
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            sugars_mmol_per_l[index],
-        )
+    fn timestamp(&self) -> DateTime<FixedOffset> {
+        self.timestamp
     }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub(crate) struct LongTermBloodSugarMeasurement {
     pub id: i64,
-    #[serde(with = "crate::ser_de::serde_datetime_local")] pub timestamp: DateTime<Local>,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
     #[serde(with = "crate::ser_de::serde_rat32")] pub hba1c_mmol_per_mol: Rational32,
 }
 impl LongTermBloodSugarMeasurement {
     pub fn new(
         id: i64,
-        timestamp: DateTime<Local>,
+        timestamp: DateTime<FixedOffset>,
         hba1c_mmol_per_mol: Rational32,
     ) -> Self {
         Self {
@@ -438,81 +420,354 @@ impl LongTermBloodSugarMeasurement {
         }
     }
 
+    /// Creates a measurement from a value in % (DCCT), or returns `None` if its value in mmol/mol
+    /// cannot be represented as a 32-bit rational number.
     pub fn new_dcct_percent(
         id: i64,
-        timestamp: DateTime<Local>,
+        timestamp: DateTime<FixedOffset>,
         hba1c_dcct_percent: Rational32,
-    ) -> Self {
+    ) -> Option<Self> {
         let additive_factor = Rational32::new(HBA1C_ADDITIVE_NUMER, HBA1C_ADDITIVE_DENOM);
         let multiplicative_factor = Rational32::new(HBA1C_MULTIPLICATIVE_NUMER, HBA1C_MULTIPLICATIVE_DENOM);
-        let hba1c_mmol_per_mol = (hba1c_dcct_percent - additive_factor) * multiplicative_factor;
+        let hba1c_mmol_per_mol = hba1c_dcct_percent.checked_sub(&additive_factor)?
+            .checked_mul(&multiplicative_factor)?;
 
-        Self::new(
+        Some(Self::new(
             id,
             timestamp,
             hba1c_mmol_per_mol,
-        )
+        ))
     }
 
-    pub fn hba1c_dcct_percent(&self) -> Rational32 {
-        let additive_factor = Rational32::new(HBA1C_ADDITIVE_NUMER, HBA1C_ADDITIVE_DENOM);
-        let multiplicative_factor = Rational32::new(HBA1C_MULTIPLICATIVE_NUMER, HBA1C_MULTIPLICATIVE_DENOM);
+    pub fn hba1c_dcct_percent(&self) -> f64 {
+        let additive_factor = f64::from(HBA1C_ADDITIVE_NUMER) / f64::from(HBA1C_ADDITIVE_DENOM);
+        let multiplicative_factor = f64::from(HBA1C_MULTIPLICATIVE_NUMER) / f64::from(HBA1C_MULTIPLICATIVE_DENOM);
 
-        (self.hba1c_mmol_per_mol / multiplicative_factor) + additive_factor
+        r32_to_f64(&self.hba1c_mmol_per_mol) / multiplicative_factor + additive_factor
     }
 
-    pub fn values_max(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.max(other.timestamp),
-            self.hba1c_mmol_per_mol.max(other.hba1c_mmol_per_mol),
-        )
+    /// The average blood sugar over the preceding months that corresponds to this HBA1c value.
+    pub fn estimated_average_glucose_mg_per_dl(&self) -> f64 {
+        EAG_MG_PER_DL_PER_DCCT_PERCENT * self.hba1c_dcct_percent() + EAG_MG_PER_DL_OFFSET
     }
 
-    pub fn values_min(&self, other: &Self) -> Self {
-        Self::new(
-            -1,
-            self.timestamp.min(other.timestamp),
-            self.hba1c_mmol_per_mol.min(other.hba1c_mmol_per_mol),
-        )
+    pub fn estimated_average_glucose_mmol_per_l(&self) -> f64 {
+        self.estimated_average_glucose_mg_per_dl() / f64::from(SUGAR_MG_PER_DL_IN_MMOL_PER_L)
     }
+}
+impl Measurement for LongTermBloodSugarMeasurement {
+    const KIND: MeasurementKind = MeasurementKind::LongTermSugar;
+    const RECENT_DAYS: i64 = 3*365;
+    const ALERT_WINDOW_DAYS: i64 = 3*365;
+    const STATISTICS_FIELDS: &'static [StatisticsField<Self>] = &[
+        StatisticsField { key: "hba1c_mmol_per_mol", label: "HBA1c", quantity: Quantity::Hba1c, digits: 0, extract: |m| Some(r32_to_f64(&m.hba1c_mmol_per_mol)) },
+        StatisticsField { key: "eag_mmol_per_l", label: "eAG", quantity: Quantity::BloodSugar, digits: 1, extract: |m| Some(m.estimated_average_glucose_mmol_per_l()) },
+    ];
 
-    pub fn average(measurements: &[Self]) -> Self {
-        assert_ne!(measurements.len(), 0);
-        let len_i32: i32 = measurements.len().try_into().unwrap();
-        let len_r32: Rational32 = len_i32.into();
+    fn id(&self) -> i64 {
+        self.id
+    }
 
-        let sugar_mmol_per_l_sum: Rational32 = measurements.iter().map(|m| m.hba1c_mmol_per_mol).sum();
+    fn timestamp(&self) -> DateTime<FixedOffset> {
+        self.timestamp
+    }
+}
 
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            sugar_mmol_per_l_sum / len_r32,
-        )
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct PeakFlowMeasurement {
+    pub id: i64,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
+    pub peak_flow_l_per_min: i32,
+}
+impl PeakFlowMeasurement {
+    pub fn new(
+        id: i64,
+        timestamp: DateTime<FixedOffset>,
+        peak_flow_l_per_min: i32,
+    ) -> Self {
+        Self {
+            id,
+            timestamp,
+            peak_flow_l_per_min,
+        }
     }
+}
+impl Measurement for PeakFlowMeasurement {
+    const KIND: MeasurementKind = MeasurementKind::PeakFlow;
+    const STATISTICS_FIELDS: &'static [StatisticsField<Self>] = &[
+        StatisticsField { key: "peak_flow_l_per_min", label: "peak flow", quantity: Quantity::Fixed("l/min"), digits: 0, extract: |m| Some(m.peak_flow_l_per_min.into()) },
+    ];
 
-    pub fn quasi_n_tile(measurements: &[Self], n_num: usize, n_den: usize) -> Self {
-        assert_ne!(measurements.len(), 0);
+    fn id(&self) -> i64 {
+        self.id
+    }
 
-        let mut sugars_mmol_per_l: Vec<Rational32> = measurements.iter().map(|m| m.hba1c_mmol_per_mol).collect();
-        sugars_mmol_per_l.sort_unstable();
+    fn timestamp(&self) -> DateTime<FixedOffset> {
+        self.timestamp
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct KetoneMeasurement {
+    pub id: i64,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
+    #[serde(with = "crate::ser_de::serde_rat32")] pub ketones_mmol_per_l: Rational32,
+}
+impl KetoneMeasurement {
+    pub fn new(
+        id: i64,
+        timestamp: DateTime<FixedOffset>,
+        ketones_mmol_per_l: Rational32,
+    ) -> Self {
+        Self {
+            id,
+            timestamp,
+            ketones_mmol_per_l,
+        }
+    }
+}
+impl Measurement for KetoneMeasurement {
+    const KIND: MeasurementKind = MeasurementKind::Ketones;
+    const STATISTICS_FIELDS: &'static [StatisticsField<Self>] = &[
+        StatisticsField { key: "ketones_mmol_per_l", label: "ketones", quantity: Quantity::Fixed("mmol/l"), digits: 1, extract: |m| Some(r32_to_f64(&m.ketones_mmol_per_l)) },
+    ];
 
-        let index = quasi_n_tile_index(measurements.len(), n_num, n_den);
+    fn id(&self) -> i64 {
+        self.id
+    }
 
-        Self::new(
-            -1,
-            measurements[0].timestamp,
-            sugars_mmol_per_l[index],
-        )
+    fn timestamp(&self) -> DateTime<FixedOffset> {
+        self.timestamp
     }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
-pub(crate) struct MeasurementStatistics<T> {
-    pub maximum: T,
-    pub quasi_q3: T,
-    pub average: T,
-    pub quasi_q2: T,
-    pub quasi_q1: T,
-    pub minimum: T,
+pub(crate) struct LipidPanelMeasurement {
+    pub id: i64,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub total_cholesterol_mmol_per_l: Option<Rational32>,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub ldl_cholesterol_mmol_per_l: Option<Rational32>,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub hdl_cholesterol_mmol_per_l: Option<Rational32>,
+    #[serde(default, with = "crate::ser_de::serde_rat32_opt")] pub triglycerides_mmol_per_l: Option<Rational32>,
+}
+impl LipidPanelMeasurement {
+    pub fn new(
+        id: i64,
+        timestamp: DateTime<FixedOffset>,
+        total_cholesterol_mmol_per_l: Option<Rational32>,
+        ldl_cholesterol_mmol_per_l: Option<Rational32>,
+        hdl_cholesterol_mmol_per_l: Option<Rational32>,
+        triglycerides_mmol_per_l: Option<Rational32>,
+    ) -> Self {
+        Self {
+            id,
+            timestamp,
+            total_cholesterol_mmol_per_l,
+            ldl_cholesterol_mmol_per_l,
+            hdl_cholesterol_mmol_per_l,
+            triglycerides_mmol_per_l,
+        }
+    }
+
+    /// The cholesterol not carried by HDL, i.e. total minus HDL cholesterol.
+    pub fn non_hdl_cholesterol_mmol_per_l(&self) -> Option<Rational32> {
+        self.total_cholesterol_mmol_per_l?.checked_sub(&self.hdl_cholesterol_mmol_per_l?)
+    }
+
+    /// The ratio of total to HDL cholesterol.
+    pub fn total_to_hdl_ratio(&self) -> Option<Rational32> {
+        self.total_cholesterol_mmol_per_l?.checked_div(&self.hdl_cholesterol_mmol_per_l?)
+    }
+}
+impl Measurement for LipidPanelMeasurement {
+    const KIND: MeasurementKind = MeasurementKind::Lipids;
+    const RECENT_DAYS: i64 = 3*365;
+    const STATISTICS_FIELDS: &'static [StatisticsField<Self>] = &[
+        StatisticsField { key: "total_cholesterol_mmol_per_l", label: "total cholesterol", quantity: Quantity::Fixed("mmol/l"), digits: 2, extract: |m| m.total_cholesterol_mmol_per_l.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "ldl_cholesterol_mmol_per_l", label: "LDL cholesterol", quantity: Quantity::Fixed("mmol/l"), digits: 2, extract: |m| m.ldl_cholesterol_mmol_per_l.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "hdl_cholesterol_mmol_per_l", label: "HDL cholesterol", quantity: Quantity::Fixed("mmol/l"), digits: 2, extract: |m| m.hdl_cholesterol_mmol_per_l.as_ref().map(r32_to_f64) },
+        StatisticsField { key: "non_hdl_cholesterol_mmol_per_l", label: "non-HDL cholesterol", quantity: Quantity::Fixed("mmol/l"), digits: 2, extract: |m| m.non_hdl_cholesterol_mmol_per_l().as_ref().map(r32_to_f64) },
+        StatisticsField { key: "total_to_hdl_ratio", label: "total/HDL ratio", quantity: Quantity::Fixed(""), digits: 2, extract: |m| m.total_to_hdl_ratio().as_ref().map(r32_to_f64) },
+        StatisticsField { key: "triglycerides_mmol_per_l", label: "triglycerides", quantity: Quantity::Fixed("mmol/l"), digits: 2, extract: |m| m.triglycerides_mmol_per_l.as_ref().map(r32_to_f64) },
+    ];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn timestamp(&self) -> DateTime<FixedOffset> {
+        self.timestamp
+    }
+}
+
+/// A measurement of a custom metric defined in the configuration.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct CustomMeasurement {
+    pub id: i64,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
+
+    /// The key of the metric's definition.
+    pub metric: String,
+
+    /// The numeric fields that were entered, by key.
+    #[serde(default, with = "crate::ser_de::serde_rat32_map")] pub values: BTreeMap<String, Rational32>,
+
+    /// The categorical fields that were entered, by key.
+    #[serde(default)] pub categories: BTreeMap<String, String>,
+}
+impl CustomMeasurement {
+    pub fn new(
+        id: i64,
+        timestamp: DateTime<FixedOffset>,
+        metric: String,
+        values: BTreeMap<String, Rational32>,
+        categories: BTreeMap<String, String>,
+    ) -> Self {
+        Self {
+            id,
+            timestamp,
+            metric,
+            values,
+            categories,
+        }
+    }
+}
+
+/// The symptoms that can be ticked in a diary entry.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct DiarySymptoms {
+    #[serde(default)] pub headache: bool,
+    #[serde(default)] pub dizziness: bool,
+    #[serde(default)] pub palpitations: bool,
+    #[serde(default)] pub chest_pain: bool,
+    #[serde(default)] pub shortness_of_breath: bool,
+}
+impl DiarySymptoms {
+    /// Returns the labels of the symptoms that were present, in display order.
+    pub fn labels(&self) -> Vec<&'static str> {
+        [
+            (self.headache, "headache"),
+            (self.dizziness, "dizziness"),
+            (self.palpitations, "palpitations"),
+            (self.chest_pain, "chest pain"),
+            (self.shortness_of_breath, "shortness of breath"),
+        ]
+            .into_iter()
+            .filter(|(present, _label)| *present)
+            .map(|(_present, label)| label)
+            .collect()
+    }
+
+    pub fn any(&self) -> bool {
+        self.headache
+            || self.dizziness
+            || self.palpitations
+            || self.chest_pain
+            || self.shortness_of_breath
+    }
+}
+
+/// An entry in the symptom and wellbeing diary.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct DiaryEntry {
+    pub id: i64,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
+    #[serde(flatten)] pub symptoms: DiarySymptoms,
+
+    /// How well the user felt, from 1 (terrible) to 10 (excellent).
+    #[serde(default)] pub wellbeing_score: Option<i32>,
+
+    #[serde(default)] pub notes: String,
+}
+impl DiaryEntry {
+    pub fn new(
+        id: i64,
+        timestamp: DateTime<FixedOffset>,
+        symptoms: DiarySymptoms,
+        wellbeing_score: Option<i32>,
+        notes: String,
+    ) -> Self {
+        Self {
+            id,
+            timestamp,
+            symptoms,
+            wellbeing_score,
+            notes,
+        }
+    }
+}
+
+/// Defines `MeasurementKind` and `with_measurement_type!` from a single list of the kinds of
+/// measurement, each with its name in URL paths and its type.
+///
+/// The `$d` parameter is a `$` token, through which the generated macro declares its own metavariables.
+macro_rules! measurement_kinds {
+    ($d:tt $($variant:ident => $name:literal, $type:ty;)+) => {
+        /// The kinds of measurement, named as in the URL paths.
+        #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+        #[serde(rename_all = "kebab-case")]
+        pub(crate) enum MeasurementKind {
+            $($variant,)+
+        }
+        impl MeasurementKind {
+            pub const ALL: &'static [Self] = &[$(Self::$variant,)+];
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $name,)+
+                }
+            }
+        }
+
+        /// Evaluates `$body` with `$T` standing for the type of the measurements of kind `$kind`, so
+        /// that a generic function can be called for a kind chosen at runtime.
+        macro_rules! with_measurement_type {
+            ($d kind:expr, $d T:ident => $d body:expr) => {
+                match $d kind {
+                    $(crate::model::MeasurementKind::$variant => {
+                        type $d T = $type;
+                        $d body
+                    },)+
+                }
+            };
+        }
+        pub(crate) use with_measurement_type;
+    };
+}
+
+measurement_kinds! { $
+    Bp => "bp", BloodPressureMeasurement;
+    Mass => "mass", BodyMassMeasurement;
+    Temperature => "temperature", BodyTemperatureMeasurement;
+    Sugar => "sugar", BloodSugarMeasurement;
+    LongTermSugar => "long-term-sugar", LongTermBloodSugarMeasurement;
+    PeakFlow => "peak-flow", PeakFlowMeasurement;
+    Ketones => "ketones", KetoneMeasurement;
+    Lipids => "lipids", LipidPanelMeasurement;
+}
+
+impl MeasurementKind {
+    /// Returns the kind of measurement named `name` in URL paths.
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::ALL.iter()
+            .copied()
+            .find(|k| k.as_str() == name)
+    }
+
+    /// Returns the keys of the statistics fields of this kind of measurement.
+    pub fn statistics_field_keys(&self) -> Vec<&'static str> {
+        with_measurement_type!(self, T => T::STATISTICS_FIELDS.iter().map(|f| f.key).collect())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct Alert {
+    pub id: i64,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
+    pub rule_name: String,
+    pub measurement_kind: String,
+    pub field: String,
+    pub value: f64,
+    pub threshold: f64,
+    pub message: String,
 }
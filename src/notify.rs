@@ -0,0 +1,386 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::process::{ExitStatus, Stdio};
+
+use chrono::Utc;
+use http_body_util::{BodyExt, Full};
+use hyper::{Method, Request, StatusCode};
+use hyper::body::Bytes;
+use hyper_util::rt::tokio::TokioIo;
+use serde::Serialize;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+use tracing::error;
+use url::Url;
+
+use crate::config::NotificationSink;
+
+
+#[derive(Debug)]
+pub(crate) enum NotifyError {
+    ParsingUrl(url::ParseError),
+    UnsupportedScheme(String),
+    MissingHost,
+    Serializing(serde_json::Error),
+    Io(io::Error),
+    BuildingRequest(http::Error),
+    Http(hyper::Error),
+    HttpStatus(StatusCode),
+    UnexpectedSmtpReply(String),
+    CommandFailed(ExitStatus),
+}
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyError::ParsingUrl(e)
+                => write!(f, "error parsing URL: {}", e),
+            NotifyError::UnsupportedScheme(scheme)
+                => write!(f, "unsupported URL scheme {:?}; only \"http\" is supported", scheme),
+            NotifyError::MissingHost
+                => write!(f, "URL has no host"),
+            NotifyError::Serializing(e)
+                => write!(f, "error serializing notification: {}", e),
+            NotifyError::Io(e)
+                => write!(f, "I/O error: {}", e),
+            NotifyError::BuildingRequest(e)
+                => write!(f, "error building request: {}", e),
+            NotifyError::Http(e)
+                => write!(f, "HTTP error: {}", e),
+            NotifyError::HttpStatus(status)
+                => write!(f, "HTTP request failed with status {}", status),
+            NotifyError::UnexpectedSmtpReply(reply)
+                => write!(f, "unexpected SMTP reply: {:?}", reply),
+            NotifyError::CommandFailed(status)
+                => write!(f, "command failed: {}", status),
+        }
+    }
+}
+impl Error for NotifyError {
+}
+
+
+/// Something that can be delivered to a notification sink.
+///
+/// Webhooks receive the notification serialized as JSON. E-mails consist of the subject and the text.
+/// Commands receive the JSON on stdin and the subject, text and variables in their environment.
+pub(crate) trait Notification: Serialize {
+    fn subject(&self) -> String;
+    fn text(&self) -> String;
+
+    /// Additional environment variables for command sinks.
+    fn variables(&self) -> Vec<(&'static str, String)>;
+}
+
+
+
+pub(crate) async fn deliver<N: Notification>(sink: &NotificationSink, notification: &N) -> Result<(), NotifyError> {
+    match sink {
+        NotificationSink::Webhook { url }
+            => deliver_webhook(url, notification).await,
+        NotificationSink::Smtp { host, port, from, to }
+            => deliver_smtp(host, *port, from, to, notification).await,
+        NotificationSink::Command { program, args }
+            => deliver_command(program, args, notification).await,
+    }
+}
+
+
+async fn deliver_webhook<N: Notification>(url_str: &str, notification: &N) -> Result<(), NotifyError> {
+    let url = Url::parse(url_str)
+        .map_err(NotifyError::ParsingUrl)?;
+    if url.scheme() != "http" {
+        return Err(NotifyError::UnsupportedScheme(url.scheme().to_owned()));
+    }
+    let host = url.host_str()
+        .ok_or(NotifyError::MissingHost)?;
+    let port = url.port_or_known_default()
+        .unwrap_or(80);
+    let authority = match url.port() {
+        Some(p) => format!("{}:{}", host, p),
+        None => host.to_owned(),
+    };
+    let path_and_query = match url.query() {
+        Some(q) => format!("{}?{}", url.path(), q),
+        None => url.path().to_owned(),
+    };
+
+    let body = serde_json::to_vec(notification)
+        .map_err(NotifyError::Serializing)?;
+
+    let stream = TcpStream::connect((host, port)).await
+        .map_err(NotifyError::Io)?;
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await
+        .map_err(NotifyError::Http)?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            error!("webhook connection error: {}", e);
+        }
+    });
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(path_and_query)
+        .header("Host", authority)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .map_err(NotifyError::BuildingRequest)?;
+    let response = sender.send_request(request).await
+        .map_err(NotifyError::Http)?;
+    let status = response.status();
+    response.into_body().collect().await
+        .map_err(NotifyError::Http)?;
+    if !status.is_success() {
+        return Err(NotifyError::HttpStatus(status));
+    }
+
+    Ok(())
+}
+
+
+/// Reads an SMTP reply, which may span multiple lines, and checks its code.
+async fn read_smtp_reply<R: AsyncBufRead + Unpin>(reader: &mut R, expected_codes: &[&str]) -> Result<(), NotifyError> {
+    loop {
+        let mut line = String::new();
+        let read_count = reader.read_line(&mut line).await
+            .map_err(NotifyError::Io)?;
+        if read_count == 0 {
+            return Err(NotifyError::UnexpectedSmtpReply(line));
+        }
+
+        let code_matches = expected_codes.iter().any(|c| line.starts_with(c));
+        if !code_matches {
+            return Err(NotifyError::UnexpectedSmtpReply(line.trim_end().to_owned()));
+        }
+        // "250-..." is followed by more lines, "250 ..." is the last one
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+async fn send_smtp_command<R, W>(reader: &mut R, writer: &mut W, command: &str, expected_codes: &[&str]) -> Result<(), NotifyError>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+{
+    writer.write_all(format!("{}\r\n", command).as_bytes()).await
+        .map_err(NotifyError::Io)?;
+    read_smtp_reply(reader, expected_codes).await
+}
+
+async fn deliver_smtp<N: Notification>(host: &str, port: u16, from: &str, to: &[String], notification: &N) -> Result<(), NotifyError> {
+    let stream = TcpStream::connect((host, port)).await
+        .map_err(NotifyError::Io)?;
+    let (read_half, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    read_smtp_reply(&mut reader, &["220"]).await?;
+    send_smtp_command(&mut reader, &mut writer, "EHLO localhost", &["250"]).await?;
+    send_smtp_command(&mut reader, &mut writer, &format!("MAIL FROM:<{}>", from), &["250"]).await?;
+    for recipient in to {
+        send_smtp_command(&mut reader, &mut writer, &format!("RCPT TO:<{}>", recipient), &["250", "251"]).await?;
+    }
+    send_smtp_command(&mut reader, &mut writer, "DATA", &["354"]).await?;
+
+    let mut data = String::new();
+    data.push_str(&format!("From: <{}>\r\n", from));
+    let to_list: Vec<String> = to.iter()
+        .map(|t| format!("<{}>", t))
+        .collect();
+    data.push_str(&format!("To: {}\r\n", to_list.join(", ")));
+    data.push_str(&format!("Subject: [beepee] {}\r\n", notification.subject()));
+    data.push_str(&format!("Date: {}\r\n", Utc::now().to_rfc2822()));
+    data.push_str("MIME-Version: 1.0\r\n");
+    data.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+    data.push_str("Content-Transfer-Encoding: 8bit\r\n");
+    data.push_str("\r\n");
+    let body = notification.text();
+    for line in body.lines() {
+        // dot-stuffing, so that the line is not mistaken for the end of the data
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push_str(".\r\n");
+    writer.write_all(data.as_bytes()).await
+        .map_err(NotifyError::Io)?;
+    read_smtp_reply(&mut reader, &["250"]).await?;
+
+    send_smtp_command(&mut reader, &mut writer, "QUIT", &["221"]).await?;
+    Ok(())
+}
+
+
+async fn deliver_command<N: Notification>(program: &str, args: &[String], notification: &N) -> Result<(), NotifyError> {
+    let json = serde_json::to_vec(notification)
+        .map_err(NotifyError::Serializing)?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .env("BEEPEE_NOTIFICATION_SUBJECT", notification.subject())
+        .env("BEEPEE_NOTIFICATION_TEXT", notification.text())
+        .envs(notification.variables())
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(NotifyError::Io)?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // the command is free to ignore its input
+        match stdin.write_all(&json).await {
+            Ok(()) => {},
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {},
+            Err(e) => return Err(NotifyError::Io(e)),
+        }
+    }
+
+    let status = child.wait().await
+        .map_err(NotifyError::Io)?;
+    if !status.success() {
+        return Err(NotifyError::CommandFailed(status));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    #[derive(Serialize)]
+    struct TestNotification {
+        name: String,
+        threshold: f64,
+    }
+    impl Notification for TestNotification {
+        fn subject(&self) -> String {
+            self.name.clone()
+        }
+
+        fn text(&self) -> String {
+            format!("{} above {:.1}\n.leading dot", self.name, self.threshold)
+        }
+
+        fn variables(&self) -> Vec<(&'static str, String)> {
+            vec![("BEEPEE_TEST_NAME", self.name.clone())]
+        }
+    }
+
+    fn notification() -> TestNotification {
+        TestNotification {
+            name: "high systolic".to_owned(),
+            threshold: 180.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn webhook_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            // good enough for a stand-in: the request is small and the body is JSON without blank lines
+            while !String::from_utf8_lossy(&request).ends_with('}') {
+                let read_count = stream.read(&mut buf).await.unwrap();
+                assert_ne!(read_count, 0);
+                request.extend_from_slice(&buf[..read_count]);
+            }
+            stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let sink = NotificationSink::Webhook { url: format!("http://127.0.0.1:{}/hook?source=beepee", port) };
+        deliver(&sink, &notification()).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /hook?source=beepee HTTP/1.1\r\n"));
+        assert!(request.contains("\"name\":\"high systolic\""));
+    }
+
+    #[tokio::test]
+    async fn smtp_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(read_half);
+            let mut transcript = Vec::new();
+            writer.write_all(b"220 localhost ESMTP stand-in\r\n").await.unwrap();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                transcript.push(line.trim_end().to_owned());
+                let reply: &[u8] = if line.starts_with("EHLO") {
+                    b"250-localhost\r\n250 8BITMIME\r\n"
+                } else if line.starts_with("DATA") {
+                    writer.write_all(b"354 go ahead\r\n").await.unwrap();
+                    loop {
+                        let mut data_line = String::new();
+                        reader.read_line(&mut data_line).await.unwrap();
+                        if data_line == ".\r\n" {
+                            break;
+                        }
+                        transcript.push(data_line.trim_end().to_owned());
+                    }
+                    b"250 queued\r\n"
+                } else if line.starts_with("QUIT") {
+                    writer.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+            transcript
+        });
+
+        let sink = NotificationSink::Smtp {
+            host: "127.0.0.1".to_owned(),
+            port,
+            from: "beepee@example.com".to_owned(),
+            to: vec!["patient@example.com".to_owned(), "doctor@example.com".to_owned()],
+        };
+        deliver(&sink, &notification()).await.unwrap();
+
+        let transcript = server.await.unwrap();
+        assert_eq!(transcript[1], "MAIL FROM:<beepee@example.com>");
+        assert_eq!(transcript[2], "RCPT TO:<patient@example.com>");
+        assert_eq!(transcript[3], "RCPT TO:<doctor@example.com>");
+        assert!(transcript.contains(&"Subject: [beepee] high systolic".to_owned()));
+        assert!(transcript.contains(&"high systolic above 180.0".to_owned()));
+        assert!(transcript.contains(&"..leading dot".to_owned()));
+        assert_eq!(transcript.last().unwrap(), "QUIT");
+    }
+
+    #[tokio::test]
+    async fn command_sink() {
+        let output_path = std::env::temp_dir()
+            .join(format!("beepee-notify-command-{}", std::process::id()));
+        let sink = NotificationSink::Command {
+            program: "sh".to_owned(),
+            args: vec![
+                "-c".to_owned(),
+                "printf '%s\\n' \"$BEEPEE_TEST_NAME\" > \"$1\" && cat >> \"$1\"".to_owned(),
+                "sh".to_owned(),
+                output_path.to_string_lossy().into_owned(),
+            ],
+        };
+        deliver(&sink, &notification()).await.unwrap();
+
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert!(output.starts_with("high systolic\n{"));
+        assert!(output.contains("\"threshold\":180.0"));
+
+        let failing_sink = NotificationSink::Command { program: "false".to_owned(), args: Vec::new() };
+        assert!(matches!(deliver(&failing_sink, &notification()).await, Err(NotifyError::CommandFailed(_))));
+    }
+}
@@ -10,6 +10,7 @@ pub(crate) enum ParseRationalError {
     UnexpectedCharacter(usize, char),
     ErrorParsingMantissa(std::num::ParseIntError),
     DenominatorTooLarge,
+    OutOfRange,
 }
 impl fmt::Display for ParseRationalError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -22,6 +23,8 @@ impl fmt::Display for ParseRationalError {
                 => write!(f, "error parsing mantissa: {}", e),
             Self::DenominatorTooLarge
                 => write!(f, "denominator too large"),
+            Self::OutOfRange
+                => write!(f, "value cannot be represented as a 32-bit rational number"),
         }
     }
 }
@@ -73,32 +76,105 @@ pub(crate) fn r32_from_decimal(decimal_str: &str) -> Result<Rational32, ParseRat
     }
 }
 
-#[inline]
-pub(crate) fn quasi_n_tile_index(element_count: usize, n_num: usize, n_den: usize) -> usize {
-    if element_count == 0 {
-        0
+pub(crate) fn r32_to_f64(value: &Rational32) -> f64 {
+    (*value.numer() as f64) / (*value.denom() as f64)
+}
+
+/// The number of fractional digits with which rational numbers are stored as decimals.
+///
+/// Two distinct 32-bit rational numbers differ by more than `1/(2^31)^2`, which is more than
+/// `10^-19`, so at this scale no two of them round to the same decimal and every 32-bit rational
+/// number survives the round trip through `r32_to_scaled_decimal` and `r32_from_scaled_decimal`.
+pub(crate) const DECIMAL_STORAGE_SCALE: u32 = 20;
+
+/// Returns `numer / denom * 10^scale`, rounded half away from zero. `numer` must not be negative
+/// and `denom` must be positive.
+fn round_scaled(numer: i128, denom: i128, scale: u32) -> i128 {
+    (2 * numer * 10i128.pow(scale) + denom) / (2 * denom)
+}
+
+/// Converts a rational number into a decimal with `scale` fractional digits, returning the decimal
+/// multiplied by `10^scale`.
+///
+/// Values that cannot be represented exactly are rounded half away from zero.
+pub(crate) fn r32_to_scaled_decimal(value: &Rational32, scale: u32) -> i128 {
+    let magnitude = round_scaled(i128::from(*value.numer()).abs(), i128::from(*value.denom()).abs(), scale);
+    if (*value.numer() < 0) != (*value.denom() < 0) {
+        -magnitude
     } else {
-        (element_count - 1) * n_num / n_den
+        magnitude
     }
 }
 
-#[inline]
-fn optional_pick<T, A: FnMut(T, T) -> Option<T>>(left: Option<T>, right: Option<T>, mut arbitration: A) -> Option<T> {
-    match (left, right) {
-        (None, None) => None,
-        (Some(l), None) => Some(l),
-        (None, Some(r)) => Some(r),
-        (Some(l), Some(r)) => arbitration(l, r),
+/// Converts a decimal with `scale` fractional digits, given multiplied by `10^scale`, back into a
+/// rational number.
+///
+/// The result is the fraction with the smallest denominator that `r32_to_scaled_decimal` rounds to
+/// the given decimal, so values such as 1/18 survive the round trip through their rounded decimal
+/// representation. At `DECIMAL_STORAGE_SCALE`, this is exactly the rational number that was
+/// converted; at coarser scales, fractions with large denominators may come back as a simpler
+/// fraction close to them.
+pub(crate) fn r32_from_scaled_decimal(scaled: i128, scale: u32) -> Result<Rational32, ParseRationalError> {
+    let magnitude = scaled.checked_abs()
+        .ok_or(ParseRationalError::OutOfRange)?;
+    let power = 10i128.checked_pow(scale)
+        .ok_or(ParseRationalError::OutOfRange)?;
+    let rounds_back = |numer: i128, denom: i128| {
+        numer.checked_mul(2 * power).is_some() && round_scaled(numer, denom, scale) == magnitude
+    };
+
+    // walk through the convergents and semiconvergents of the continued fraction of the decimal in
+    // order of increasing denominator; the simplest fraction within the rounding interval is one of them
+    let (mut rest_numer, mut rest_denom) = (magnitude, power);
+    let mut term = rest_numer / rest_denom;
+    (rest_numer, rest_denom) = (rest_denom, rest_numer % rest_denom);
+    let (mut numer_prev, mut denom_prev) = (1i128, 0i128);
+    let (mut numer_cur, mut denom_cur) = (term, 1i128);
+    let found = loop {
+        if rounds_back(numer_cur, denom_cur) {
+            break (numer_cur, denom_cur);
+        }
+        if rest_denom == 0 {
+            // the decimal itself always rounds back to itself
+            unreachable!("no fraction rounds back to {}/{}", magnitude, power);
+        }
+
+        term = rest_numer / rest_denom;
+        (rest_numer, rest_denom) = (rest_denom, rest_numer % rest_denom);
+
+        // the semiconvergents approach the decimal monotonically, so the first one that rounds back can
+        // be found by bisection
+        let candidate = |j: i128| (numer_prev + j * numer_cur, denom_prev + j * denom_cur);
+        let (last_numer, last_denom) = candidate(term);
+        if rounds_back(last_numer, last_denom) {
+            let (mut low, mut high) = (1i128, term);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                let (mid_numer, mid_denom) = candidate(mid);
+                if rounds_back(mid_numer, mid_denom) {
+                    high = mid;
+                } else {
+                    low = mid + 1;
+                }
+            }
+            break candidate(low);
+        }
+
+        (numer_prev, denom_prev) = (numer_cur, denom_cur);
+        (numer_cur, denom_cur) = (last_numer, last_denom);
+    };
+
+    let (numer, denom) = found;
+    let numer = i32::try_from(numer)
+        .map_err(|_| ParseRationalError::OutOfRange)?;
+    let denom = i32::try_from(denom)
+        .map_err(|_| ParseRationalError::OutOfRange)?;
+    if scaled < 0 {
+        Ok(Rational32::new(-numer, denom))
+    } else {
+        Ok(Rational32::new(numer, denom))
     }
 }
-#[inline]
-pub(crate) fn optional_max<T: Ord>(left: Option<T>, right: Option<T>) -> Option<T> {
-    optional_pick(left, right, |l, r| Some(l.max(r)))
-}
-#[inline]
-pub(crate) fn optional_min<T: Ord>(left: Option<T>, right: Option<T>) -> Option<T> {
-    optional_pick(left, right, |l, r| Some(l.min(r)))
-}
 
 
 #[cfg(test)]
@@ -136,4 +212,47 @@ mod tests {
         test(0, 1, "0.0");
         test(-21, 5, "-4.2");
     }
+
+    fn round_trip(num: i32, den: i32, scaled: i128) {
+        let value = Rational32::new(num, den);
+        assert_eq!(r32_to_scaled_decimal(&value, DECIMAL_STORAGE_SCALE), scaled);
+        assert_eq!(r32_from_scaled_decimal(scaled, DECIMAL_STORAGE_SCALE).unwrap(), value);
+    }
+
+    #[test]
+    fn scaled_decimal_round_trip() {
+        round_trip(0, 1, 0);
+        round_trip(8033, 100, 8_033_000_000_000_000_000_000);
+        round_trip(80333, 1000, 8_033_300_000_000_000_000_000);
+        round_trip(1, 18, 5_555_555_555_555_555_556);
+        round_trip(-1, 18, -5_555_555_555_555_555_556);
+        round_trip(50, 9, 555_555_555_555_555_555_556);
+        round_trip(-2, 3, -66_666_666_666_666_666_667);
+        round_trip(1_191_261, 25_000, 4_765_044_000_000_000_000_000);
+        round_trip(i32::MAX, 1, i128::from(i32::MAX) * 100_000_000_000_000_000_000);
+        round_trip(1, 2048, 48_828_125_000_000_000);
+
+        // denominators beyond 10^5, e.g. 6.575 % DCCT in mmol/mol and 100 lb in kg
+        round_trip(9_694_023, 200_000, 4_847_011_500_000_000_000_000);
+        round_trip(45_359_237, 1_000_000, 4_535_923_700_000_000_000_000);
+        round_trip(6_790_033, 140_087, 4_847_011_499_996_430_789_438);
+        round_trip(i32::MAX - 1, i32::MAX, 99_999_999_953_433_871_248);
+        round_trip(1, i32::MAX, 46_566_128_752);
+        round_trip(-i32::MAX, i32::MAX - 1, -100_000_000_046_566_128_774);
+    }
+
+    #[test]
+    fn scaled_decimal_rounding() {
+        // half away from zero
+        assert_eq!(r32_to_scaled_decimal(&Rational32::new(1, 8), 2), 13);
+        assert_eq!(r32_to_scaled_decimal(&Rational32::new(-1, 8), 2), -13);
+        assert_eq!(r32_to_scaled_decimal(&Rational32::new(1, 3), 2), 33);
+
+        // the simplest fraction wins, even at a coarse scale
+        assert_eq!(r32_from_scaled_decimal(33, 2).unwrap(), Rational32::new(1, 3));
+        assert_eq!(r32_from_scaled_decimal(250, 2).unwrap(), Rational32::new(5, 2));
+        assert_eq!(r32_from_scaled_decimal(13, 2).unwrap(), Rational32::new(1, 8));
+        assert_eq!(r32_from_scaled_decimal(484_701_150_000, 10).unwrap(), Rational32::new(6_790_033, 140_087));
+        assert!(r32_from_scaled_decimal(i128::from(i32::MAX) * 100 + 100, 2).is_err());
+    }
 }
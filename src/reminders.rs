@@ -0,0 +1,263 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Timelike, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::RwLock;
+use tracing::error;
+
+use crate::config::{CONFIG, DayPart, Hours, Reminders};
+use crate::database::get_recent_blood_pressure_measurements;
+use crate::model::DailyBloodPressureMeasurements;
+use crate::notify::{Notification, deliver};
+use crate::timezone::in_display_timezone;
+
+
+pub(crate) static REMINDER_STATE: Lazy<RwLock<ReminderState>> = Lazy::new(|| RwLock::new(ReminderState::default()));
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum SlotStatus {
+    /// The deadline has not passed yet and no reading has been taken.
+    Pending,
+
+    /// A reading has been taken.
+    Taken,
+
+    /// The deadline has passed without a reading.
+    Missed,
+}
+impl SlotStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Taken => "taken",
+            Self::Missed => "missed",
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct SlotReminder {
+    pub day_part: DayPart,
+    pub deadline_hour: u32,
+    pub status: SlotStatus,
+    pub reminded_at: Option<DateTime<FixedOffset>>,
+}
+
+
+/// The reminder status of each slot of the current day, as shown on the index page.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub(crate) struct ReminderState {
+    pub date: Option<NaiveDate>,
+    pub slots: Vec<SlotReminder>,
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct Reminder {
+    pub date: String,
+    pub day_part: &'static str,
+    pub deadline_hour: u32,
+    pub message: String,
+}
+impl Notification for Reminder {
+    fn subject(&self) -> String {
+        format!("{} blood pressure reading missing", self.day_part)
+    }
+
+    fn text(&self) -> String {
+        format!("{}\n", self.message)
+    }
+
+    fn variables(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("BEEPEE_REMINDER_DATE", self.date.clone()),
+            ("BEEPEE_REMINDER_DAY_PART", self.day_part.to_owned()),
+            ("BEEPEE_REMINDER_DEADLINE_HOUR", self.deadline_hour.to_string()),
+        ]
+    }
+}
+
+
+/// Returns the hour counted from the start of the measurement day.
+///
+/// Hours before the start of the morning belong to the previous day, so they are counted past 24.
+fn hour_of_measurement_day(hour: u32, hours: &Hours) -> u32 {
+    if hour < hours.morning_start {
+        hour + 24
+    } else {
+        hour
+    }
+}
+
+/// Determines the status of each slot that has a deadline.
+///
+/// `day` holds the readings of the current measurement day; `hour` is the current hour as returned by
+/// `hour_of_measurement_day`.
+pub(crate) fn slot_statuses(
+    reminders: &Reminders,
+    hours: &Hours,
+    day: Option<&DailyBloodPressureMeasurements>,
+    hour: u32,
+) -> Vec<(DayPart, u32, SlotStatus)> {
+    let slots = [
+        (DayPart::Morning, reminders.morning_deadline, day.and_then(|d| d.morning.as_ref())),
+        (DayPart::Midday, reminders.midday_deadline, day.and_then(|d| d.midday.as_ref())),
+        (DayPart::Evening, reminders.evening_deadline, day.and_then(|d| d.evening.as_ref())),
+    ];
+
+    let mut statuses = Vec::with_capacity(slots.len());
+    for (day_part, deadline, reading) in slots {
+        let Some(deadline_hour) = deadline else { continue };
+        let status = if reading.is_some() {
+            SlotStatus::Taken
+        } else if hour >= hour_of_measurement_day(deadline_hour, hours) {
+            SlotStatus::Missed
+        } else {
+            SlotStatus::Pending
+        };
+        statuses.push((day_part, deadline_hour, status));
+    }
+    statuses
+}
+
+
+/// Updates the reminder state and sends reminders for newly missed slots.
+///
+/// Returns the configured check interval.
+async fn check_reminders() -> Duration {
+    let (hours, reminders, display_timezone) = {
+        let config_guard = CONFIG
+            .get().unwrap()
+            .read().await;
+        (config_guard.hours, config_guard.reminders.clone(), config_guard.display_timezone)
+    };
+    let Some(reminders) = reminders else {
+        *REMINDER_STATE.write().await = ReminderState::default();
+        return Duration::minutes(5);
+    };
+    let interval = Duration::minutes(reminders.check_interval_minutes.max(1) as i64);
+
+    let now = in_display_timezone(&Utc::now(), display_timezone);
+    let mut date = now.date_naive();
+    if now.hour() < hours.morning_start {
+        date = date.pred_opt().expect("no previous day?!");
+    }
+    let hour = hour_of_measurement_day(now.hour(), &hours);
+
+    let mut measurements = match get_recent_blood_pressure_measurements(Duration::days(2)).await {
+        Ok(m) => m,
+        Err(e) => {
+            error!("error obtaining recent measurements for reminders: {}", e);
+            return interval;
+        },
+    };
+    measurements.sort_by_key(|m| m.timestamp);
+    let date_string = date.format("%Y-%m-%d").to_string();
+    let days = DailyBloodPressureMeasurements::group_by_day(&measurements, &hours);
+    let day = days.iter()
+        .find(|d| d.date_string == date_string);
+
+    let previous_slots = {
+        let state_guard = REMINDER_STATE
+            .read().await;
+        if state_guard.date == Some(date) {
+            state_guard.slots.clone()
+        } else {
+            Vec::new()
+        }
+    };
+
+    let mut slots = Vec::new();
+    for (day_part, deadline_hour, status) in slot_statuses(&reminders, &hours, day, hour) {
+        let mut reminded_at = previous_slots.iter()
+            .find(|s| s.day_part == day_part)
+            .and_then(|s| s.reminded_at);
+
+        if status == SlotStatus::Missed && reminded_at.is_none() {
+            let reminder = Reminder {
+                date: date_string.clone(),
+                day_part: day_part.as_str(),
+                deadline_hour,
+                message: format!(
+                    "No {} blood pressure reading has been taken on {} by {:02}:00.",
+                    day_part.as_str(), date_string, deadline_hour,
+                ),
+            };
+            for sink in &reminders.sinks {
+                if let Err(e) = deliver(sink, &reminder).await {
+                    error!("error delivering reminder {:?} to {:?}: {}", reminder.message, sink, e);
+                }
+            }
+            // failed deliveries are not retried, so as not to send a flood of reminders later
+            reminded_at = Some(now);
+        }
+
+        slots.push(SlotReminder {
+            day_part,
+            deadline_hour,
+            status,
+            reminded_at,
+        });
+    }
+
+    *REMINDER_STATE.write().await = ReminderState {
+        date: Some(date),
+        slots,
+    };
+    interval
+}
+
+
+/// Periodically checks for missed readings. Runs forever.
+pub(crate) async fn run_reminders() {
+    loop {
+        let interval = check_reminders().await;
+        tokio::time::sleep(interval.to_std().expect("negative reminder interval")).await;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    use crate::model::BloodPressureMeasurement;
+
+    const HOURS: Hours = Hours {
+        morning_start: 5,
+        morning_end: 13,
+        midday_start: 11,
+        midday_end: 20,
+        evening_start: 17,
+    };
+
+    #[test]
+    fn statuses() {
+        let reminders = Reminders {
+            morning_deadline: Some(10),
+            midday_deadline: None,
+            evening_deadline: Some(1),
+            check_interval_minutes: 5,
+            sinks: Vec::new(),
+        };
+        let timestamp = FixedOffset::east_opt(3600).unwrap()
+            .with_ymd_and_hms(2024, 1, 1, 7, 30, 0).unwrap();
+        let mut day = DailyBloodPressureMeasurements::new_empty("2024-01-01".to_owned());
+        day.morning = Some(BloodPressureMeasurement::new(1, timestamp, 120, 80, 60, None));
+
+        let statuses = slot_statuses(&reminders, &HOURS, Some(&day), 23);
+        assert_eq!(statuses, vec![
+            (DayPart::Morning, 10, SlotStatus::Taken),
+            (DayPart::Evening, 1, SlotStatus::Pending),
+        ]);
+
+        // 01:00 on the next calendar day is still part of the measurement day
+        let statuses = slot_statuses(&reminders, &HOURS, None, hour_of_measurement_day(1, &HOURS));
+        assert_eq!(statuses, vec![
+            (DayPart::Morning, 10, SlotStatus::Missed),
+            (DayPart::Evening, 1, SlotStatus::Missed),
+        ]);
+    }
+}
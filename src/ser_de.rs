@@ -1,30 +1,35 @@
-pub(crate) mod serde_datetime_local {
-    use chrono::{DateTime, Local, NaiveDateTime, Utc};
+pub(crate) mod serde_datetime_offset {
+    use chrono::{DateTime, FixedOffset, SecondsFormat};
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
     use serde::de::Error as _;
 
-    const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.fZ";
-
-    pub fn serialize<S: Serializer>(value: &DateTime<Local>, serializer: S) -> Result<S::Ok, S::Error> {
-        let utc = value.with_timezone(&Utc);
-        let string = utc.format(TIME_FORMAT).to_string();
+    pub fn serialize<S: Serializer>(value: &DateTime<FixedOffset>, serializer: S) -> Result<S::Ok, S::Error> {
+        let string = value.to_rfc3339_opts(SecondsFormat::AutoSi, true);
         string.serialize(serializer)
     }
 
-    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Local>, D::Error> {
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<FixedOffset>, D::Error> {
         let string = String::deserialize(deserializer)?;
-        let naive_utc = NaiveDateTime::parse_from_str(&string, TIME_FORMAT)
-            .map_err(|e| D::Error::custom(e))?;
-        let utc = naive_utc.and_utc();
-        Ok(utc.with_timezone(&Local))
+        DateTime::parse_from_rfc3339(&string)
+            .map_err(|e| D::Error::custom(e))
     }
 }
 
-fn rat32_to_string(value: &num_rational::Rational32) -> String {
+pub(crate) mod serde_datetime_offset_opt {
+    use chrono::{DateTime, FixedOffset, SecondsFormat};
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<DateTime<FixedOffset>>, serializer: S) -> Result<S::Ok, S::Error> {
+        let string = value.as_ref().map(|v| v.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+        string.serialize(serializer)
+    }
+}
+
+pub(crate) fn rat32_to_string(value: &num_rational::Rational32) -> String {
     format!("{}/{}", value.numer(), value.denom())
 }
 
-fn string_to_rat32(s: &str) -> Result<num_rational::Rational32, String> {
+pub(crate) fn string_to_rat32(s: &str) -> Result<num_rational::Rational32, String> {
     let (num, denom) = if let Some((num_str, denom_str)) = s.split_once('/') {
         let num = num_str.parse()
             .map_err(|e| format!("failed to parse numerator {:?}: {}", num_str, e))?;
@@ -84,3 +89,26 @@ pub(crate) mod serde_rat32_opt {
     }
 }
 
+
+pub(crate) mod serde_rat32_map {
+    use std::collections::BTreeMap;
+
+    use num_rational::Rational32;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error as _;
+
+    pub fn serialize<S: Serializer>(value: &BTreeMap<String, Rational32>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(value.iter().map(|(k, rat)| (k, super::rat32_to_string(rat))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, Rational32>, D::Error> {
+        let strings: BTreeMap<String, String> = BTreeMap::deserialize(deserializer)?;
+        let mut ret = BTreeMap::new();
+        for (k, s) in strings {
+            let rat = super::string_to_rat32(&s)
+                .map_err(D::Error::custom)?;
+            ret.insert(k, rat);
+        }
+        Ok(ret)
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, Timelike};
+use serde::Serialize;
+
+use crate::config::{DayPart, Hours};
+use crate::model::{BloodPressureMeasurement, DailyBloodPressureMeasurements};
+use crate::units::{Quantity, UnitPreferences};
+
+
+/// A numeric field of a measurement type for which statistics can be calculated.
+pub(crate) struct StatisticsField<T> {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub quantity: Quantity,
+    pub digits: usize,
+    /// Extracts the value of the field in the canonical unit of its quantity.
+    pub extract: fn(&T) -> Option<f64>,
+}
+impl<T> StatisticsField<T> {
+    /// Extracts the value of the field in the preferred unit of its quantity.
+    pub fn extract_in(&self, measurement: &T, units: &UnitPreferences) -> Option<f64> {
+        (self.extract)(measurement)
+            .map(|v| units.convert_from_canonical_f64(self.quantity, v))
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct FieldStatistics {
+    pub key: String,
+    pub label: String,
+    pub unit: String,
+    #[serde(skip)] pub digits: usize,
+    pub count: usize,
+    pub minimum: f64,
+    pub percentile_25: f64,
+    pub median: f64,
+    pub percentile_75: f64,
+    pub maximum: f64,
+    pub mean: f64,
+    pub standard_deviation: Option<f64>,
+    pub coefficient_of_variation: Option<f64>,
+}
+impl FieldStatistics {
+    /// Calculates the statistics of the given values, which are in the preferred unit of the field's
+    /// quantity. Returns `None` if there are no values.
+    pub fn calculate<T>(field: &StatisticsField<T>, values: &[f64], units: &UnitPreferences) -> Option<Self> {
+        Self::from_values(field.key, field.label, units.symbol(field.quantity), field.digits, values)
+    }
+
+    /// Calculates the statistics of the given values of a field that is described at runtime, such
+    /// as a field of a custom metric. Returns `None` if there are no values.
+    pub fn from_values(key: &str, label: &str, unit: &str, digits: usize, values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let mut sorted_values = values.to_vec();
+        sorted_values.sort_unstable_by(|a, b| a.total_cmp(b));
+
+        let count = sorted_values.len();
+        let count_f64 = count as f64;
+        let mean = sorted_values.iter().sum::<f64>() / count_f64;
+
+        // sample standard deviation (Bessel-corrected); undefined for a single value
+        let standard_deviation = if count > 1 {
+            let square_deviation_sum: f64 = sorted_values.iter()
+                .map(|v| (v - mean) * (v - mean))
+                .sum();
+            Some((square_deviation_sum / (count_f64 - 1.0)).sqrt())
+        } else {
+            None
+        };
+        let coefficient_of_variation = standard_deviation
+            .filter(|_| mean != 0.0)
+            .map(|sd| sd / mean.abs());
+
+        Some(Self {
+            key: key.to_owned(),
+            label: label.to_owned(),
+            unit: unit.to_owned(),
+            digits,
+            count,
+            minimum: sorted_values[0],
+            percentile_25: percentile(&sorted_values, 0.25),
+            median: percentile(&sorted_values, 0.5),
+            percentile_75: percentile(&sorted_values, 0.75),
+            maximum: sorted_values[count - 1],
+            mean,
+            standard_deviation,
+            coefficient_of_variation,
+        })
+    }
+}
+
+
+/// Statistics for each field of a set of measurements.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct StatisticsSet {
+    pub count: usize,
+    pub fields: Vec<FieldStatistics>,
+}
+impl StatisticsSet {
+    pub fn calculate<T>(measurements: &[&T], fields: &[StatisticsField<T>], units: &UnitPreferences) -> Self {
+        let mut field_stats = Vec::with_capacity(fields.len());
+        for field in fields {
+            let values: Vec<f64> = measurements.iter()
+                .filter_map(|m| field.extract_in(m, units))
+                .collect();
+            if let Some(fs) = FieldStatistics::calculate(field, &values, units) {
+                field_stats.push(fs);
+            }
+        }
+
+        Self {
+            count: measurements.len(),
+            fields: field_stats,
+        }
+    }
+}
+
+
+/// Statistics for a set of measurements, both overall and broken down by the part of the day
+/// during which each measurement was taken.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct MeasurementStatistics {
+    pub overall: StatisticsSet,
+    pub morning: StatisticsSet,
+    pub midday: StatisticsSet,
+    pub evening: StatisticsSet,
+    pub other: StatisticsSet,
+}
+impl MeasurementStatistics {
+    pub fn calculate<T>(
+        measurements: &[T],
+        fields: &[StatisticsField<T>],
+        hours: &Hours,
+        timestamp: fn(&T) -> DateTime<FixedOffset>,
+        units: &UnitPreferences,
+    ) -> Self {
+        Self::calculate_with(measurements, hours, timestamp, |ms| StatisticsSet::calculate(ms, fields, units))
+    }
+
+    /// Groups the measurements by the part of the day during which they were taken and calculates
+    /// each set of statistics using `calculate_set`.
+    pub fn calculate_with<T, F>(
+        measurements: &[T],
+        hours: &Hours,
+        timestamp: fn(&T) -> DateTime<FixedOffset>,
+        calculate_set: F,
+    ) -> Self
+        where F: Fn(&[&T]) -> StatisticsSet
+    {
+        let mut all = Vec::with_capacity(measurements.len());
+        let mut morning = Vec::new();
+        let mut midday = Vec::new();
+        let mut evening = Vec::new();
+        let mut other = Vec::new();
+        for measurement in measurements {
+            all.push(measurement);
+            let slot = match hours.day_part(timestamp(measurement).hour()) {
+                DayPart::Morning => &mut morning,
+                DayPart::Midday => &mut midday,
+                DayPart::Evening => &mut evening,
+                DayPart::Other => &mut other,
+            };
+            slot.push(measurement);
+        }
+
+        Self {
+            overall: calculate_set(&all),
+            morning: calculate_set(&morning),
+            midday: calculate_set(&midday),
+            evening: calculate_set(&evening),
+            other: calculate_set(&other),
+        }
+    }
+
+    pub fn slots(&self) -> [(&'static str, &StatisticsSet); 4] {
+        [
+            ("morning", &self.morning),
+            ("midday", &self.midday),
+            ("evening", &self.evening),
+            ("other", &self.other),
+        ]
+    }
+}
+
+
+/// The statistics of the fields of a kind of measurement together with what the kind derives beyond
+/// them, such as blood pressure variability.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct StatisticsWithExtras<E> {
+    #[serde(flatten)] pub statistics: MeasurementStatistics,
+    #[serde(flatten)] pub extras: E,
+}
+
+
+const EVENING_TO_MORNING_CHANGE_FIELD: StatisticsField<f64> = StatisticsField {
+    key: "evening_to_morning_change_mmhg",
+    label: "evening-to-morning change",
+    quantity: Quantity::Fixed("mmHg"),
+    digits: 1,
+    extract: |v| Some(*v),
+};
+const MORNING_EVENING_DIFFERENCE_FIELD: StatisticsField<f64> = StatisticsField {
+    key: "morning_evening_difference_mmhg",
+    label: "morning–evening difference",
+    quantity: Quantity::Fixed("mmHg"),
+    digits: 1,
+    extract: |v| Some(*v),
+};
+
+
+/// Measures of blood pressure variability.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct BloodPressureVariability {
+    /// Average real variability of the systolic pressure: the mean absolute difference between
+    /// consecutive readings.
+    pub systolic_arv_mmhg: Option<f64>,
+
+    /// Average real variability of the diastolic pressure.
+    pub diastolic_arv_mmhg: Option<f64>,
+
+    /// Morning systolic pressure minus the systolic pressure of the previous day's evening.
+    ///
+    /// This is only a proxy for the clinical morning surge, which compares the morning pressure to
+    /// the nocturnal or pre-waking pressure; home readings rarely cover the night.
+    pub evening_to_morning_change: Option<FieldStatistics>,
+
+    /// Morning systolic pressure minus the systolic pressure of the same day's evening.
+    pub morning_evening_difference: Option<FieldStatistics>,
+}
+impl BloodPressureVariability {
+    /// Calculates the variability metrics.
+    ///
+    /// `measurements` must be sorted by timestamp; `days` is the same set of measurements grouped by
+    /// day, in any order.
+    pub fn calculate(measurements: &[BloodPressureMeasurement], days: &[DailyBloodPressureMeasurements]) -> Self {
+        let systolics: Vec<f64> = measurements.iter()
+            .map(|m| m.systolic_mmhg.into())
+            .collect();
+        let diastolics: Vec<f64> = measurements.iter()
+            .map(|m| m.diastolic_mmhg.into())
+            .collect();
+
+        let date_to_day: BTreeMap<NaiveDate, &DailyBloodPressureMeasurements> = days.iter()
+            .filter_map(|d| NaiveDate::parse_from_str(&d.date_string, "%Y-%m-%d").ok().map(|date| (date, d)))
+            .collect();
+        let mut evening_to_morning_changes = Vec::new();
+        let mut morning_evening_differences = Vec::new();
+        for (date, day) in &date_to_day {
+            let Some(morning) = &day.morning else { continue };
+
+            if let Some(evening) = &day.evening {
+                morning_evening_differences.push(f64::from(morning.systolic_mmhg - evening.systolic_mmhg));
+            }
+
+            let previous_evening = date.pred_opt()
+                .and_then(|pd| date_to_day.get(&pd))
+                .and_then(|pd| pd.evening.as_ref());
+            if let Some(evening) = previous_evening {
+                evening_to_morning_changes.push(f64::from(morning.systolic_mmhg - evening.systolic_mmhg));
+            }
+        }
+
+        Self {
+            systolic_arv_mmhg: average_real_variability(&systolics),
+            diastolic_arv_mmhg: average_real_variability(&diastolics),
+            evening_to_morning_change: FieldStatistics::calculate(&EVENING_TO_MORNING_CHANGE_FIELD, &evening_to_morning_changes, &UnitPreferences::default()),
+            morning_evening_difference: FieldStatistics::calculate(&MORNING_EVENING_DIFFERENCE_FIELD, &morning_evening_differences, &UnitPreferences::default()),
+        }
+    }
+}
+
+
+/// Calculates the average real variability, i.e. the mean absolute difference between consecutive
+/// values. Returns `None` if there are fewer than two values.
+pub(crate) fn average_real_variability(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let difference_sum: f64 = values.windows(2)
+        .map(|w| (w[1] - w[0]).abs())
+        .sum();
+    Some(difference_sum / (values.len() - 1) as f64)
+}
+
+
+/// Calculates a percentile of the given values, interpolating linearly between the closest ranks.
+///
+/// The values must be sorted in ascending order and must not be empty. `fraction` is the
+/// percentile divided by 100, e.g. 0.25 for the first quartile.
+pub(crate) fn percentile(sorted_values: &[f64], fraction: f64) -> f64 {
+    assert!(!sorted_values.is_empty());
+
+    let rank = (sorted_values.len() - 1) as f64 * fraction;
+    let lower_index = rank.floor() as usize;
+    let upper_index = (lower_index + 1).min(sorted_values.len() - 1);
+    let weight = rank - lower_index as f64;
+
+    sorted_values[lower_index] + weight * (sorted_values[upper_index] - sorted_values[lower_index])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELD: StatisticsField<f64> = StatisticsField {
+        key: "value",
+        label: "value",
+        quantity: Quantity::Fixed(""),
+        digits: 0,
+        extract: |v| Some(*v),
+    };
+
+    #[test]
+    fn percentile_interpolates() {
+        let values = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(percentile(&values, 0.0), 1.0);
+        assert_eq!(percentile(&values, 0.25), 1.75);
+        assert_eq!(percentile(&values, 0.5), 2.5);
+        assert_eq!(percentile(&values, 0.75), 3.25);
+        assert_eq!(percentile(&values, 1.0), 4.0);
+        assert_eq!(percentile(&[42.0], 0.5), 42.0);
+    }
+
+    #[test]
+    fn field_statistics() {
+        let stats = FieldStatistics::calculate(&FIELD, &[4.0, 2.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0], &UnitPreferences::default()).unwrap();
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.minimum, 2.0);
+        assert_eq!(stats.maximum, 9.0);
+        assert_eq!(stats.mean, 5.0);
+        assert_eq!(stats.median, 4.5);
+        let sd = stats.standard_deviation.unwrap();
+        assert!((sd - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert!((stats.coefficient_of_variation.unwrap() - sd / 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn field_statistics_single_and_empty() {
+        let stats = FieldStatistics::calculate(&FIELD, &[3.0], &UnitPreferences::default()).unwrap();
+        assert_eq!(stats.median, 3.0);
+        assert_eq!(stats.standard_deviation, None);
+        assert_eq!(stats.coefficient_of_variation, None);
+
+        assert_eq!(FieldStatistics::calculate(&FIELD, &[], &UnitPreferences::default()), None);
+    }
+
+    #[test]
+    fn arv() {
+        assert_eq!(average_real_variability(&[120.0]), None);
+        assert_eq!(average_real_variability(&[120.0, 130.0, 125.0, 125.0]), Some(5.0));
+    }
+}
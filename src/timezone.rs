@@ -0,0 +1,81 @@
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use chrono_tz::Tz;
+
+use crate::config::CONFIG;
+
+
+/// The largest UTC offset (in either direction) that is accepted for a measurement, in minutes.
+///
+/// Real-world time zones range from UTC-12:00 to UTC+14:00; this leaves some slack.
+pub(crate) const MAX_UTC_OFFSET_MINUTES: i32 = 18 * 60;
+
+
+pub(crate) async fn get_display_timezone() -> Option<Tz> {
+    CONFIG
+        .get().expect("config not set")
+        .read().await
+        .display_timezone
+}
+
+/// Converts a UTC timestamp into the display time zone.
+///
+/// If no display time zone is configured, the server's local time zone is used.
+pub(crate) fn in_display_timezone(utc: &DateTime<Utc>, display_timezone: Option<Tz>) -> DateTime<FixedOffset> {
+    match display_timezone {
+        Some(tz) => utc.with_timezone(&tz).fixed_offset(),
+        None => utc.with_timezone(&Local).fixed_offset(),
+    }
+}
+
+/// Converts a UTC timestamp into the time zone in which the measurement was taken.
+///
+/// Measurements stored without a UTC offset fall back to the display time zone.
+pub(crate) fn localize_timestamp(
+    utc: &DateTime<Utc>,
+    utc_offset_minutes: Option<i32>,
+    display_timezone: Option<Tz>,
+) -> DateTime<FixedOffset> {
+    let offset = utc_offset_minutes
+        .and_then(|m| FixedOffset::east_opt(m * 60));
+    match offset {
+        Some(o) => utc.with_timezone(&o),
+        None => in_display_timezone(utc, display_timezone),
+    }
+}
+
+/// Returns the UTC offset of the given timestamp in minutes, as stored in the database.
+pub(crate) fn utc_offset_minutes(timestamp: &DateTime<FixedOffset>) -> i32 {
+    timestamp.offset().local_minus_utc() / 60
+}
+
+/// Returns the current time, either at the given UTC offset or in the display time zone.
+pub(crate) async fn now_at_offset(utc_offset_minutes: Option<i32>) -> DateTime<FixedOffset> {
+    let display_timezone = get_display_timezone()
+        .await;
+    localize_timestamp(&Utc::now(), utc_offset_minutes, display_timezone)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Timelike};
+
+    #[test]
+    fn recorded_offset_wins() {
+        let utc = Utc.with_ymd_and_hms(2024, 3, 1, 23, 30, 0).unwrap();
+        let localized = localize_timestamp(&utc, Some(9 * 60), Some(chrono_tz::Europe::Vienna));
+        assert_eq!(localized.hour(), 8);
+        assert_eq!(localized.date_naive().to_string(), "2024-03-02");
+        assert_eq!(utc_offset_minutes(&localized), 9 * 60);
+    }
+
+    #[test]
+    fn display_timezone_fallback() {
+        let winter = Utc.with_ymd_and_hms(2024, 1, 15, 6, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2024, 7, 15, 6, 0, 0).unwrap();
+        let vienna = Some(chrono_tz::Europe::Vienna);
+        assert_eq!(utc_offset_minutes(&localize_timestamp(&winter, None, vienna)), 60);
+        assert_eq!(utc_offset_minutes(&localize_timestamp(&summer, None, vienna)), 120);
+    }
+}
@@ -0,0 +1,273 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Serialize;
+
+use crate::statistics::StatisticsField;
+use crate::units::UnitPreferences;
+
+
+/// The two-sided 97.5th percentile of Student's t-distribution for 1 to 30 degrees of freedom.
+const T_975: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
+/// The 97.5th percentile of the standard normal distribution.
+const Z_975: f64 = 1.959964;
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub(crate) struct TrendPoint {
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub timestamp: DateTime<FixedOffset>,
+    pub value: f64,
+}
+
+
+/// A least-squares regression line of a field's values against time.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub(crate) struct LinearRegression {
+    pub count: usize,
+    #[serde(with = "crate::ser_de::serde_datetime_offset")] pub origin: DateTime<FixedOffset>,
+    pub intercept: f64,
+    pub slope_per_day: f64,
+    pub slope_per_day_ci95_lower: Option<f64>,
+    pub slope_per_day_ci95_upper: Option<f64>,
+}
+impl LinearRegression {
+    /// Fits a regression line through the given points. Returns `None` if there are fewer than two
+    /// points or all of them were taken at the same time.
+    ///
+    /// The intercept is the value of the line at `origin`, which is the timestamp of the first point.
+    /// The 95% confidence interval of the slope requires at least three points.
+    pub fn calculate(points: &[TrendPoint]) -> Option<Self> {
+        if points.len() < 2 {
+            return None;
+        }
+
+        let origin = points[0].timestamp;
+        let xs: Vec<f64> = points.iter()
+            .map(|p| days_between(&origin, &p.timestamp))
+            .collect();
+        let count = points.len();
+        let count_f64 = count as f64;
+        let x_mean = xs.iter().sum::<f64>() / count_f64;
+        let y_mean = points.iter().map(|p| p.value).sum::<f64>() / count_f64;
+
+        let mut sxx = 0.0;
+        let mut sxy = 0.0;
+        for (x, p) in xs.iter().zip(points.iter()) {
+            sxx += (x - x_mean) * (x - x_mean);
+            sxy += (x - x_mean) * (p.value - y_mean);
+        }
+        if sxx == 0.0 {
+            return None;
+        }
+
+        let slope = sxy / sxx;
+        let intercept = y_mean - slope * x_mean;
+
+        let (ci_lower, ci_upper) = if count > 2 {
+            let residual_square_sum: f64 = xs.iter().zip(points.iter())
+                .map(|(x, p)| {
+                    let residual = p.value - (intercept + slope * x);
+                    residual * residual
+                })
+                .sum();
+            let degrees_of_freedom = count - 2;
+            let residual_standard_error = (residual_square_sum / degrees_of_freedom as f64).sqrt();
+            let slope_standard_error = residual_standard_error / sxx.sqrt();
+            let margin = t_975(degrees_of_freedom) * slope_standard_error;
+            (Some(slope - margin), Some(slope + margin))
+        } else {
+            (None, None)
+        };
+
+        Some(Self {
+            count,
+            origin,
+            intercept,
+            slope_per_day: slope,
+            slope_per_day_ci95_lower: ci_lower,
+            slope_per_day_ci95_upper: ci_upper,
+        })
+    }
+
+    pub fn slope_per_30_days(&self) -> f64 {
+        self.slope_per_day * 30.0
+    }
+
+    pub fn slope_per_30_days_ci95(&self) -> Option<(f64, f64)> {
+        let lower = self.slope_per_day_ci95_lower?;
+        let upper = self.slope_per_day_ci95_upper?;
+        Some((lower * 30.0, upper * 30.0))
+    }
+
+    /// The value of the regression line at the given time.
+    pub fn value_at(&self, timestamp: &DateTime<FixedOffset>) -> f64 {
+        self.intercept + self.slope_per_day * days_between(&self.origin, timestamp)
+    }
+}
+
+
+/// Rolling means and the regression line of a single field.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct FieldTrend {
+    pub key: String,
+    pub label: String,
+    pub unit: String,
+    #[serde(skip)] pub digits: usize,
+    pub values: Vec<TrendPoint>,
+    pub rolling_mean_7_days: Vec<TrendPoint>,
+    pub rolling_mean_30_days: Vec<TrendPoint>,
+    pub regression: Option<LinearRegression>,
+}
+impl FieldTrend {
+    /// Calculates the trend of each field that has at least one value.
+    ///
+    /// `measurements` must be sorted by timestamp. Values are given in the preferred unit of each
+    /// field's quantity.
+    pub fn calculate_all<T>(
+        measurements: &[T],
+        fields: &[StatisticsField<T>],
+        timestamp: fn(&T) -> DateTime<FixedOffset>,
+        units: &UnitPreferences,
+    ) -> Vec<Self> {
+        fields.iter()
+            .filter_map(|field| {
+                let values: Vec<TrendPoint> = measurements.iter()
+                    .filter_map(|m| field.extract_in(m, units).map(|value| TrendPoint { timestamp: timestamp(m), value }))
+                    .collect();
+                Self::from_points(field.key, field.label, units.symbol(field.quantity), field.digits, values)
+            })
+            .collect()
+    }
+
+    /// Calculates the trend of a field that is described at runtime, such as a field of a custom
+    /// metric. `values` must be sorted by timestamp. Returns `None` if there are no values.
+    pub fn from_points(key: &str, label: &str, unit: &str, digits: usize, values: Vec<TrendPoint>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            key: key.to_owned(),
+            label: label.to_owned(),
+            unit: unit.to_owned(),
+            digits,
+            rolling_mean_7_days: rolling_mean(&values, Duration::days(7)),
+            rolling_mean_30_days: rolling_mean(&values, Duration::days(30)),
+            regression: LinearRegression::calculate(&values),
+            values,
+        })
+    }
+
+    /// The endpoints of the regression line over the time span of the values, for drawing.
+    pub fn regression_line(&self) -> Vec<TrendPoint> {
+        let (Some(regression), Some(first), Some(last)) = (&self.regression, self.values.first(), self.values.last()) else {
+            return Vec::new();
+        };
+        [first, last].iter()
+            .map(|p| TrendPoint { timestamp: p.timestamp, value: regression.value_at(&p.timestamp) })
+            .collect()
+    }
+}
+
+
+/// Calculates the trailing mean at each point over the given window.
+///
+/// The mean at each point covers all points taken within `window` before it, up to and including the
+/// point itself. `points` must be sorted by timestamp.
+pub(crate) fn rolling_mean(points: &[TrendPoint], window: Duration) -> Vec<TrendPoint> {
+    let mut means = Vec::with_capacity(points.len());
+    let mut window_start = 0;
+    let mut window_sum = 0.0;
+    for (i, point) in points.iter().enumerate() {
+        window_sum += point.value;
+        while points[window_start].timestamp <= point.timestamp - window {
+            window_sum -= points[window_start].value;
+            window_start += 1;
+        }
+        let window_count = (i + 1 - window_start) as f64;
+        means.push(TrendPoint {
+            timestamp: point.timestamp,
+            value: window_sum / window_count,
+        });
+    }
+    means
+}
+
+
+fn days_between(start: &DateTime<FixedOffset>, end: &DateTime<FixedOffset>) -> f64 {
+    (*end - *start).num_milliseconds() as f64 / 1000.0 / SECONDS_PER_DAY
+}
+
+
+/// The 97.5th percentile of Student's t-distribution with the given degrees of freedom.
+///
+/// Beyond the table, a Cornish-Fisher expansion around the normal distribution is used.
+fn t_975(degrees_of_freedom: usize) -> f64 {
+    assert!(degrees_of_freedom > 0);
+    if degrees_of_freedom <= T_975.len() {
+        return T_975[degrees_of_freedom - 1];
+    }
+
+    let df = degrees_of_freedom as f64;
+    let z = Z_975;
+    z
+        + (z.powi(3) + z) / (4.0 * df)
+        + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * df * df)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn point(day: u32, value: f64) -> TrendPoint {
+        let timestamp = FixedOffset::east_opt(3600).unwrap()
+            .with_ymd_and_hms(2024, 1, day, 8, 0, 0).unwrap();
+        TrendPoint { timestamp, value }
+    }
+
+    #[test]
+    fn rolling_mean_window() {
+        let points = [point(1, 10.0), point(2, 20.0), point(8, 30.0), point(9, 40.0)];
+        let means: Vec<f64> = rolling_mean(&points, Duration::days(7))
+            .iter()
+            .map(|p| p.value)
+            .collect();
+        // on day 8, day 1 has just left the window
+        assert_eq!(means, vec![10.0, 15.0, 25.0, 35.0]);
+    }
+
+    #[test]
+    fn regression_exact_line() {
+        let points = [point(1, 100.0), point(2, 99.5), point(3, 99.0), point(5, 98.0)];
+        let regression = LinearRegression::calculate(&points).unwrap();
+        assert!((regression.slope_per_day + 0.5).abs() < 1e-9);
+        assert!((regression.intercept - 100.0).abs() < 1e-9);
+        assert!((regression.slope_per_day_ci95_lower.unwrap() + 0.5).abs() < 1e-9);
+        assert!((regression.value_at(&point(11, 0.0).timestamp) - 95.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn regression_confidence_interval() {
+        let points = [point(1, 1.0), point(2, 3.0), point(3, 2.0), point(4, 4.0)];
+        let regression = LinearRegression::calculate(&points).unwrap();
+        // slope 0.8, residual standard error sqrt(0.9), Sxx 5, t(2) = 4.303
+        let margin = 4.303 * (0.9f64).sqrt() / (5.0f64).sqrt();
+        assert!((regression.slope_per_day - 0.8).abs() < 1e-9);
+        assert!((regression.slope_per_day_ci95_upper.unwrap() - (0.8 + margin)).abs() < 1e-9);
+        assert!(LinearRegression::calculate(&points[..1]).is_none());
+    }
+
+    #[test]
+    fn t_distribution() {
+        assert_eq!(t_975(1), 12.706);
+        assert!((t_975(60) - 2.000).abs() < 0.001);
+        assert!((t_975(120) - 1.980).abs() < 0.001);
+    }
+}
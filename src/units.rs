@@ -0,0 +1,402 @@
+use num_rational::Rational32;
+use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::model::{
+    HBA1C_ADDITIVE_DENOM, HBA1C_ADDITIVE_NUMER, HBA1C_MULTIPLICATIVE_DENOM, HBA1C_MULTIPLICATIVE_NUMER,
+    SUGAR_MG_PER_DL_IN_MMOL_PER_L,
+};
+use crate::numerism::{r32_from_scaled_decimal, r32_to_f64};
+use crate::ser_de::{rat32_to_string, string_to_rat32};
+
+
+/// The number of fractional digits to which conversions are rounded if they cannot be represented
+/// exactly as a 32-bit rational number.
+const INEXACT_CONVERSION_SCALE: u32 = 4;
+
+
+/// A physical quantity whose unit can be chosen by the user.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Quantity {
+    Mass,
+    Length,
+    Temperature,
+    BloodSugar,
+    Hba1c,
+    /// A quantity that is always given in the unit with the given symbol.
+    Fixed(&'static str),
+}
+
+
+/// A unit of measurement.
+///
+/// A value `v` in this unit corresponds to `v * scale + offset` in the canonical unit of its quantity,
+/// which is the one in which values are stored.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Unit {
+    pub quantity: Quantity,
+    /// The value of the unit key form field, also used in configuration files.
+    pub key: &'static str,
+    /// The suffix of the JSON keys of values in this unit.
+    pub key_suffix: &'static str,
+    pub symbol: &'static str,
+    scale: Rational32,
+    offset: Rational32,
+}
+impl Unit {
+    /// Converts a value in the canonical unit into this unit.
+    ///
+    /// The conversion is exact unless the result cannot be represented as a 32-bit rational
+    /// number, in which case it is rounded to four fractional digits. Returns `None` if the value is
+    /// out of range even then.
+    pub fn convert_from_canonical(&self, canonical: Rational32) -> Option<Rational32> {
+        let exact = canonical.checked_sub(&self.offset)
+            .and_then(|v| v.checked_div(&self.scale));
+        exact.or_else(|| rounded(self.convert_from_canonical_f64(r32_to_f64(&canonical))))
+    }
+
+    /// Converts a value in this unit into the canonical unit, with the same rounding rules as
+    /// [`Unit::convert_from_canonical`].
+    pub fn to_canonical(&self, value: Rational32) -> Option<Rational32> {
+        let exact = value.checked_mul(&self.scale)
+            .and_then(|v| v.checked_add(&self.offset));
+        exact.or_else(|| rounded(self.to_canonical_f64(r32_to_f64(&value))))
+    }
+
+    pub fn convert_from_canonical_f64(&self, canonical: f64) -> f64 {
+        (canonical - r32_to_f64(&self.offset)) / r32_to_f64(&self.scale)
+    }
+
+    pub fn to_canonical_f64(&self, value: f64) -> f64 {
+        value * r32_to_f64(&self.scale) + r32_to_f64(&self.offset)
+    }
+
+    /// Returns the unit of the given quantity with the given key.
+    pub fn by_key(quantity: Quantity, key: &str) -> Option<&'static Unit> {
+        UNITS.iter()
+            .find(|u| u.quantity == quantity && u.key == key)
+    }
+
+    /// Returns the keys of all units of the given quantity.
+    pub fn keys(quantity: Quantity) -> Vec<String> {
+        UNITS.iter()
+            .filter(|u| u.quantity == quantity)
+            .map(|u| u.key.to_owned())
+            .collect()
+    }
+}
+
+/// Returns `numer / denom` in lowest terms; usable in constants. `denom` must be positive.
+const fn reduced(numer: i32, denom: i32) -> Rational32 {
+    let (mut a, mut b) = (numer.unsigned_abs(), denom.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    Rational32::new_raw(numer / a as i32, denom / a as i32)
+}
+
+fn rounded(value: f64) -> Option<Rational32> {
+    let scaled = (value * 10f64.powi(INEXACT_CONVERSION_SCALE as i32)).round();
+    if !scaled.is_finite() {
+        return None;
+    }
+    r32_from_scaled_decimal(scaled as i128, INEXACT_CONVERSION_SCALE).ok()
+}
+
+
+pub(crate) const KILOGRAM: Unit = Unit {
+    quantity: Quantity::Mass, key: "kg", key_suffix: "kg", symbol: "kg",
+    scale: Rational32::new_raw(1, 1), offset: Rational32::new_raw(0, 1),
+};
+/// The international avoirdupois pound of exactly 0.45359237 kg.
+pub(crate) const POUND: Unit = Unit {
+    quantity: Quantity::Mass, key: "lb", key_suffix: "lb", symbol: "lb",
+    scale: Rational32::new_raw(45_359_237, 100_000_000), offset: Rational32::new_raw(0, 1),
+};
+/// The stone of 14 pounds.
+pub(crate) const STONE: Unit = Unit {
+    quantity: Quantity::Mass, key: "st", key_suffix: "st", symbol: "st",
+    scale: Rational32::new_raw(317_514_659, 50_000_000), offset: Rational32::new_raw(0, 1),
+};
+pub(crate) const CENTIMETRE: Unit = Unit {
+    quantity: Quantity::Length, key: "cm", key_suffix: "cm", symbol: "cm",
+    scale: Rational32::new_raw(1, 1), offset: Rational32::new_raw(0, 1),
+};
+pub(crate) const INCH: Unit = Unit {
+    quantity: Quantity::Length, key: "in", key_suffix: "in", symbol: "in",
+    scale: Rational32::new_raw(127, 50), offset: Rational32::new_raw(0, 1),
+};
+pub(crate) const CELSIUS: Unit = Unit {
+    quantity: Quantity::Temperature, key: "celsius", key_suffix: "celsius", symbol: "°C",
+    scale: Rational32::new_raw(1, 1), offset: Rational32::new_raw(0, 1),
+};
+pub(crate) const FAHRENHEIT: Unit = Unit {
+    quantity: Quantity::Temperature, key: "fahrenheit", key_suffix: "fahrenheit", symbol: "°F",
+    scale: Rational32::new_raw(5, 9), offset: Rational32::new_raw(-160, 9),
+};
+pub(crate) const MMOL_PER_L: Unit = Unit {
+    quantity: Quantity::BloodSugar, key: "mmol-per-l", key_suffix: "mmol_per_l", symbol: "mmol/l",
+    scale: Rational32::new_raw(1, 1), offset: Rational32::new_raw(0, 1),
+};
+/// Milligrams of glucose per decilitre, with the conversion factor of `SUGAR_MG_PER_DL_IN_MMOL_PER_L`.
+pub(crate) const MG_PER_DL: Unit = Unit {
+    quantity: Quantity::BloodSugar, key: "mg-per-dl", key_suffix: "mg_per_dl", symbol: "mg/dl",
+    scale: reduced(1, SUGAR_MG_PER_DL_IN_MMOL_PER_L), offset: Rational32::new_raw(0, 1),
+};
+pub(crate) const MMOL_PER_MOL: Unit = Unit {
+    quantity: Quantity::Hba1c, key: "mmol-per-mol", key_suffix: "mmol_per_mol", symbol: "mmol/mol",
+    scale: Rational32::new_raw(1, 1), offset: Rational32::new_raw(0, 1),
+};
+/// The DCCT percentage, with the conversion factors of `HBA1C_*_NUMER` and `HBA1C_*_DENOM`:
+/// `(percent - additive) * multiplicative`, i.e. an offset of `-additive * multiplicative`.
+pub(crate) const DCCT_PERCENT: Unit = Unit {
+    quantity: Quantity::Hba1c, key: "dcct-percent", key_suffix: "dcct_percent", symbol: "% DCCT",
+    scale: reduced(HBA1C_MULTIPLICATIVE_NUMER, HBA1C_MULTIPLICATIVE_DENOM),
+    offset: reduced(-HBA1C_ADDITIVE_NUMER * HBA1C_MULTIPLICATIVE_NUMER, HBA1C_ADDITIVE_DENOM * HBA1C_MULTIPLICATIVE_DENOM),
+};
+
+pub(crate) const UNITS: &[Unit] = &[
+    KILOGRAM, POUND, STONE,
+    CENTIMETRE, INCH,
+    CELSIUS, FAHRENHEIT,
+    MMOL_PER_L, MG_PER_DL,
+    MMOL_PER_MOL, DCCT_PERCENT,
+];
+
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) enum MassUnit {
+    #[default] #[serde(rename = "kg")] Kilogram,
+    #[serde(rename = "lb")] Pound,
+    #[serde(rename = "st")] Stone,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) enum LengthUnit {
+    #[default] #[serde(rename = "cm")] Centimetre,
+    #[serde(rename = "in")] Inch,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) enum TemperatureUnit {
+    #[default] #[serde(rename = "celsius")] Celsius,
+    #[serde(rename = "fahrenheit")] Fahrenheit,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) enum BloodSugarUnit {
+    #[default] #[serde(rename = "mmol-per-l")] MmolPerL,
+    #[serde(rename = "mg-per-dl")] MgPerDl,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) enum Hba1cUnit {
+    #[default] #[serde(rename = "mmol-per-mol")] MmolPerMol,
+    #[serde(rename = "dcct-percent")] DcctPercent,
+}
+
+
+/// The units in which values are shown and entered.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct UnitPreferences {
+    #[serde(default)] pub mass: MassUnit,
+    #[serde(default)] pub length: LengthUnit,
+    #[serde(default)] pub temperature: TemperatureUnit,
+    #[serde(default)] pub blood_sugar: BloodSugarUnit,
+    #[serde(default)] pub hba1c: Hba1cUnit,
+}
+impl UnitPreferences {
+    /// Returns the preferred unit of the given quantity, or `None` if its unit cannot be chosen.
+    pub fn unit(&self, quantity: Quantity) -> Option<&'static Unit> {
+        let unit = match quantity {
+            Quantity::Mass => match self.mass {
+                MassUnit::Kilogram => &KILOGRAM,
+                MassUnit::Pound => &POUND,
+                MassUnit::Stone => &STONE,
+            },
+            Quantity::Length => match self.length {
+                LengthUnit::Centimetre => &CENTIMETRE,
+                LengthUnit::Inch => &INCH,
+            },
+            Quantity::Temperature => match self.temperature {
+                TemperatureUnit::Celsius => &CELSIUS,
+                TemperatureUnit::Fahrenheit => &FAHRENHEIT,
+            },
+            Quantity::BloodSugar => match self.blood_sugar {
+                BloodSugarUnit::MmolPerL => &MMOL_PER_L,
+                BloodSugarUnit::MgPerDl => &MG_PER_DL,
+            },
+            Quantity::Hba1c => match self.hba1c {
+                Hba1cUnit::MmolPerMol => &MMOL_PER_MOL,
+                Hba1cUnit::DcctPercent => &DCCT_PERCENT,
+            },
+            Quantity::Fixed(_) => return None,
+        };
+        Some(unit)
+    }
+
+    pub fn mass(&self) -> &'static Unit {
+        self.unit(Quantity::Mass).expect("mass has a unit")
+    }
+
+    pub fn length(&self) -> &'static Unit {
+        self.unit(Quantity::Length).expect("length has a unit")
+    }
+
+    pub fn temperature(&self) -> &'static Unit {
+        self.unit(Quantity::Temperature).expect("temperature has a unit")
+    }
+
+    pub fn blood_sugar(&self) -> &'static Unit {
+        self.unit(Quantity::BloodSugar).expect("blood sugar has a unit")
+    }
+
+    pub fn hba1c(&self) -> &'static Unit {
+        self.unit(Quantity::Hba1c).expect("HBA1c has a unit")
+    }
+
+    /// Returns the symbol of the unit in which values of the given quantity are shown.
+    pub fn symbol(&self, quantity: Quantity) -> &'static str {
+        match quantity {
+            Quantity::Fixed(symbol) => symbol,
+            other => self.unit(other).map(|u| u.symbol).unwrap_or(""),
+        }
+    }
+
+    /// Converts a value of the given quantity from the canonical unit into the preferred one.
+    pub fn convert_from_canonical_f64(&self, quantity: Quantity, canonical: f64) -> f64 {
+        match self.unit(quantity) {
+            Some(unit) => unit.convert_from_canonical_f64(canonical),
+            None => canonical,
+        }
+    }
+}
+
+
+/// Returns the quantity of the value with the given JSON key if the key ends in the suffix of a
+/// canonical unit, along with the part of the key before the suffix.
+///
+/// Other substances are measured in mmol/l as well (e.g. ketones or cholesterol), but convert to
+/// mg/dl with different factors; only keys naming blood sugar are recognized as such.
+pub(crate) fn canonical_json_key(key: &str) -> Option<(&str, Quantity)> {
+    let canonical_units = UnitPreferences::default();
+    [Quantity::Mass, Quantity::Length, Quantity::Temperature, Quantity::BloodSugar, Quantity::Hba1c]
+        .into_iter()
+        .find_map(|quantity| {
+            let suffix = canonical_units.unit(quantity)?.key_suffix;
+            let base = key.strip_suffix(suffix)?.strip_suffix('_')?;
+            if quantity == Quantity::BloodSugar && !(base.ends_with("sugar") || base == "eag") {
+                return None;
+            }
+            Some((base, quantity))
+        })
+}
+
+/// Converts the values in a serialized API response into the preferred units.
+///
+/// Values are recognized by the unit suffix of their keys (e.g. `mass_kg`), which is replaced by
+/// the suffix of the preferred unit (e.g. `mass_lb`). Rationals (`"numerator/denominator"`) stay
+/// rationals and numbers stay numbers. Nested objects and arrays are converted as well.
+pub(crate) fn convert_json(value: &mut Value, units: &UnitPreferences) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                convert_json(item, units);
+            }
+        },
+        Value::Object(map) => {
+            let entries = std::mem::take(map);
+            for (key, mut entry) in entries {
+                let converted = canonical_json_key(&key)
+                    .and_then(|(base, quantity)| Some((base, units.unit(quantity)?)));
+                match converted {
+                    Some((base, unit)) if !entry.is_object() && !entry.is_array() => {
+                        let new_key = format!("{}_{}", base, unit.key_suffix);
+                        map.insert(new_key, convert_json_scalar(entry, unit));
+                    },
+                    _ => {
+                        convert_json(&mut entry, units);
+                        map.insert(key, entry);
+                    },
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+fn convert_json_scalar(value: Value, unit: &Unit) -> Value {
+    match &value {
+        Value::String(s) => {
+            let Ok(canonical) = string_to_rat32(s) else { return value };
+            match unit.convert_from_canonical(canonical) {
+                Some(converted) => Value::String(rat32_to_string(&converted)),
+                None => Value::Null,
+            }
+        },
+        Value::Number(n) => {
+            let Some(canonical) = n.as_f64() else { return value };
+            serde_json::Number::from_f64(unit.convert_from_canonical_f64(canonical))
+                .map(Value::Number)
+                .unwrap_or(Value::Null)
+        },
+        _ => value,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        // exact where possible
+        assert_eq!(FAHRENHEIT.convert_from_canonical(Rational32::new(37, 1)), Some(Rational32::new(493, 5)));
+        assert_eq!(FAHRENHEIT.to_canonical(Rational32::new(493, 5)), Some(Rational32::new(37, 1)));
+        assert_eq!(INCH.convert_from_canonical(Rational32::new(254, 10)), Some(Rational32::new(10, 1)));
+        assert_eq!(MG_PER_DL.to_canonical(Rational32::new(100, 1)), Some(Rational32::new(50, 9)));
+        assert_eq!(DCCT_PERCENT.to_canonical(Rational32::new(65, 10)), Some(Rational32::new(1_191_261, 25_000)));
+        assert_eq!(POUND.to_canonical(Rational32::new(100, 1)), Some(Rational32::new(45_359_237, 1_000_000)));
+        assert_eq!(STONE.to_canonical(Rational32::new(25, 2)), Some(Rational32::new(317_514_659, 4_000_000)));
+
+        // rounded otherwise
+        assert_eq!(POUND.convert_from_canonical(Rational32::new(817, 10)), Some(Rational32::new(105_549, 586)));
+        assert_eq!(STONE.to_canonical(Rational32::new(133, 10)), Some(Rational32::new(12_331, 146)));
+
+        assert!((STONE.convert_from_canonical_f64(63.5029318) - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn blood_sugar_units_match_the_model() {
+        use chrono::DateTime;
+        use crate::model::{BloodSugarMeasurement, LongTermBloodSugarMeasurement};
+
+        assert_eq!(DCCT_PERCENT.scale, Rational32::new(10_929, 1_000));
+        assert_eq!(DCCT_PERCENT.offset, Rational32::new(-1_169_403, 50_000));
+
+        let timestamp = DateTime::parse_from_rfc3339("2024-03-01T07:00:00+01:00").unwrap();
+        for value in [Rational32::new(65, 10), Rational32::new(263, 40), Rational32::new(100, 1), Rational32::new(1, 3)] {
+            let sugar = BloodSugarMeasurement::new_mg_per_dl(-1, timestamp, value).unwrap();
+            assert_eq!(MG_PER_DL.to_canonical(value), Some(sugar.sugar_mmol_per_l));
+            assert!((MG_PER_DL.convert_from_canonical_f64(r32_to_f64(&sugar.sugar_mmol_per_l)) - sugar.sugar_mg_per_dl()).abs() < 1e-9);
+
+            let hba1c = LongTermBloodSugarMeasurement::new_dcct_percent(-1, timestamp, value).unwrap();
+            assert_eq!(DCCT_PERCENT.to_canonical(value), Some(hba1c.hba1c_mmol_per_mol));
+            assert!((DCCT_PERCENT.convert_from_canonical_f64(r32_to_f64(&hba1c.hba1c_mmol_per_mol)) - hba1c.hba1c_dcct_percent()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn json_keys() {
+        assert_eq!(canonical_json_key("waist_circum_cm"), Some(("waist_circum", Quantity::Length)));
+        assert_eq!(canonical_json_key("core_temperature_celsius"), Some(("core_temperature", Quantity::Temperature)));
+        assert_eq!(canonical_json_key("hba1c_mmol_per_mol"), Some(("hba1c", Quantity::Hba1c)));
+        assert_eq!(canonical_json_key("sugar_mmol_per_l"), Some(("sugar", Quantity::BloodSugar)));
+        assert_eq!(canonical_json_key("mean_sugar_mmol_per_l"), Some(("mean_sugar", Quantity::BloodSugar)));
+        assert_eq!(canonical_json_key("ldl_mmol_per_l"), None);
+        assert_eq!(canonical_json_key("systolic_mmhg"), None);
+        assert_eq!(canonical_json_key("kg"), None);
+    }
+}
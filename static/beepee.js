@@ -10,6 +10,8 @@ var BeePee;
     BeePee.todToDiastolic = [];
     BeePee.todToPulse = [];
     BeePee.todToSpo2 = [];
+    BeePee.trends = [];
+    const trendColors = ["#f00", "#00f", "#0f0", "#fc0", "#f0f", "#0cc"];
     function comparePoints(p1, p2) {
         let diff = p1.x - p2.x;
         if (diff != 0.0) {
@@ -23,6 +25,43 @@ var BeePee;
         newPoints.sort(comparePoints);
         return newPoints;
     }
+    function trendOverlayDatasets(key, label, color) {
+        let trend = BeePee.trends.find(t => t.key === key);
+        if (trend === undefined) {
+            return [];
+        }
+        return [
+            {
+                label: `${label} (7-day mean)`,
+                data: trend.rollingMean7Days,
+                parsing: false,
+                borderColor: color,
+                borderWidth: 1,
+                pointRadius: 0,
+                showLine: true,
+            },
+            {
+                label: `${label} (30-day mean)`,
+                data: trend.rollingMean30Days,
+                parsing: false,
+                borderColor: color,
+                borderWidth: 2,
+                borderDash: [6, 3],
+                pointRadius: 0,
+                showLine: true,
+            },
+            {
+                label: `${label} (linear trend)`,
+                data: trend.regressionLine,
+                parsing: false,
+                borderColor: color,
+                borderWidth: 1,
+                borderDash: [2, 2],
+                pointRadius: 0,
+                showLine: true,
+            },
+        ];
+    }
     function createCharts() {
         let tsChartCanvas = document.getElementById('ts-chart-canvas');
         let tsChartContext = tsChartCanvas.getContext("2d");
@@ -58,6 +97,9 @@ var BeePee;
                         parsing: false,
                         borderColor: "#fc0",
                     },
+                    ...trendOverlayDatasets("systolic_mmhg", "systolic", "#f00"),
+                    ...trendOverlayDatasets("diastolic_mmhg", "diastolic", "#00f"),
+                    ...trendOverlayDatasets("pulse_bpm", "pulse", "#0f0"),
                 ],
             },
             options: {
@@ -115,9 +157,69 @@ var BeePee;
             },
         });
     }
+    function createTrendCharts(keys) {
+        let container = document.getElementById("trend-charts-container");
+        if (container === null) {
+            console.error("trend chart container not found");
+            return;
+        }
+        keys.forEach((key, index) => {
+            let trend = BeePee.trends.find(t => t.key === key);
+            if (trend === undefined) {
+                return;
+            }
+            let canvas = document.createElement("canvas");
+            canvas.classList.add("trend-chart-canvas", key);
+            container.appendChild(canvas);
+            let context = canvas.getContext("2d");
+            if (context === null) {
+                console.error(`failed to create ${key} trend canvas context`);
+                return;
+            }
+            let color = trendColors[index % trendColors.length];
+            new Chart(context, {
+                type: "scatter",
+                data: {
+                    datasets: [
+                        {
+                            label: trend.label,
+                            data: sortedClonePoints(trend.values),
+                            parsing: false,
+                            borderColor: color,
+                        },
+                        ...trendOverlayDatasets(key, trend.label, color),
+                    ],
+                },
+                options: {
+                    animations: false,
+                    scales: {
+                        xAxis: {
+                            type: "time",
+                        },
+                    },
+                },
+            });
+        });
+    }
+    function fillUtcOffsets() {
+        // getTimezoneOffset returns UTC minus local time; we store local time minus UTC
+        let utcOffsetMinutes = -(new Date().getTimezoneOffset());
+        let offsetInputs = document.querySelectorAll("input.utc-offset");
+        offsetInputs.forEach(input => {
+            input.value = `${utcOffsetMinutes}`;
+        });
+    }
     function setUp() {
         document.addEventListener("DOMContentLoaded", createCharts);
     }
     BeePee.setUp = setUp;
+    function setUpTrendCharts(keys) {
+        document.addEventListener("DOMContentLoaded", () => createTrendCharts(keys));
+    }
+    BeePee.setUpTrendCharts = setUpTrendCharts;
+    function setUpForms() {
+        document.addEventListener("DOMContentLoaded", fillUtcOffsets);
+    }
+    BeePee.setUpForms = setUpForms;
 })(BeePee || (BeePee = {}));
 //# sourceMappingURL=beepee.js.map
\ No newline at end of file
@@ -18,6 +18,19 @@ module BeePee {
     export let todToPulse: Point[] = [];
     export let todToSpo2: Point[] = [];
 
+    interface Trend {
+        key: string;
+        label: string;
+        values: Point[];
+        rollingMean7Days: Point[];
+        rollingMean30Days: Point[];
+        regressionLine: Point[];
+    }
+
+    export let trends: Trend[] = [];
+
+    const trendColors: string[] = ["#f00", "#00f", "#0f0", "#fc0", "#f0f", "#0cc"];
+
     function comparePoints(p1: Point, p2: Point): number {
         let diff = p1.x - p2.x;
         if (diff != 0.0) {
@@ -33,6 +46,45 @@ module BeePee {
         return newPoints;
     }
 
+    function trendOverlayDatasets(key: string, label: string, color: string): any[] {
+        let trend = trends.find(t => t.key === key);
+        if (trend === undefined) {
+            return [];
+        }
+
+        return [
+            {
+                label: `${label} (7-day mean)`,
+                data: trend.rollingMean7Days,
+                parsing: false,
+                borderColor: color,
+                borderWidth: 1,
+                pointRadius: 0,
+                showLine: true,
+            },
+            {
+                label: `${label} (30-day mean)`,
+                data: trend.rollingMean30Days,
+                parsing: false,
+                borderColor: color,
+                borderWidth: 2,
+                borderDash: [6, 3],
+                pointRadius: 0,
+                showLine: true,
+            },
+            {
+                label: `${label} (linear trend)`,
+                data: trend.regressionLine,
+                parsing: false,
+                borderColor: color,
+                borderWidth: 1,
+                borderDash: [2, 2],
+                pointRadius: 0,
+                showLine: true,
+            },
+        ];
+    }
+
     function createCharts() {
         let tsChartCanvas = <HTMLCanvasElement>document.getElementById('ts-chart-canvas');
         let tsChartContext = tsChartCanvas.getContext("2d");
@@ -70,6 +122,9 @@ module BeePee {
                         parsing: false,
                         borderColor: "#fc0",
                     },
+                    ...trendOverlayDatasets("systolic_mmhg", "systolic", "#f00"),
+                    ...trendOverlayDatasets("diastolic_mmhg", "diastolic", "#00f"),
+                    ...trendOverlayDatasets("pulse_bpm", "pulse", "#0f0"),
                 ],
             },
             options: {
@@ -131,7 +186,72 @@ module BeePee {
         });
     }
 
+    function createTrendCharts(keys: string[]) {
+        let container = document.getElementById("trend-charts-container");
+        if (container === null) {
+            console.error("trend chart container not found");
+            return;
+        }
+
+        keys.forEach((key, index) => {
+            let trend = trends.find(t => t.key === key);
+            if (trend === undefined) {
+                return;
+            }
+
+            let canvas = document.createElement("canvas");
+            canvas.classList.add("trend-chart-canvas", key);
+            container!.appendChild(canvas);
+            let context = canvas.getContext("2d");
+            if (context === null) {
+                console.error(`failed to create ${key} trend canvas context`);
+                return;
+            }
+
+            let color = trendColors[index % trendColors.length];
+            new Chart(context, {
+                type: "scatter",
+                data: {
+                    datasets: [
+                        {
+                            label: trend.label,
+                            data: sortedClonePoints(trend.values),
+                            parsing: false,
+                            borderColor: color,
+                        },
+                        ...trendOverlayDatasets(key, trend.label, color),
+                    ],
+                },
+                options: {
+                    animations: false,
+                    scales: {
+                        xAxis: {
+                            type: "time",
+                        },
+                    },
+                },
+            });
+        });
+    }
+
+    function fillUtcOffsets() {
+        // getTimezoneOffset returns UTC minus local time; we store local time minus UTC
+        let utcOffsetMinutes = -(new Date().getTimezoneOffset());
+        let offsetInputs = document.querySelectorAll<HTMLInputElement>("input.utc-offset");
+        offsetInputs.forEach(input => {
+            input.value = `${utcOffsetMinutes}`;
+        });
+    }
+
     export function setUp() {
         document.addEventListener("DOMContentLoaded", createCharts);
     }
+
+    export function setUpTrendCharts(keys: string[]) {
+        document.addEventListener("DOMContentLoaded", () => createTrendCharts(keys));
+    }
+
+    export function setUpForms() {
+        document.addEventListener("DOMContentLoaded", fillUtcOffsets);
+    }
 }